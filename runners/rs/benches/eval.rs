@@ -0,0 +1,270 @@
+//! Evaluation throughput benchmarks over generated 10k-record payloads.
+//!
+//! To check a change to evaluation code or a `policy-rs` bump for
+//! regressions, record a baseline first and compare against it afterwards:
+//!
+//! ```text
+//! cargo bench --bench eval -- --save-baseline before
+//! # ...apply the change...
+//! cargo bench --bench eval -- --baseline before
+//! ```
+//!
+//! Baselines live under `target/criterion/`, so they only compare runs on the
+//! same machine.
+
+mod fixtures;
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use policy_rs::{EvaluateResult, PolicyEngine, PolicySnapshot};
+use runner_rs::{EvalOptions, eval, evaluate_logs_data, evaluate_traces_data, otel};
+use serde_json::json;
+
+const RECORDS: usize = 10_000;
+
+fn kept(result: EvaluateResult) -> bool {
+    match result {
+        EvaluateResult::Drop { .. } => false,
+        EvaluateResult::Sample { keep, .. } => keep,
+        EvaluateResult::RateLimit { allowed, .. } => allowed,
+        _ => true,
+    }
+}
+
+fn filter_logs(engine: &PolicyEngine, snapshot: &PolicySnapshot, data: &mut otel::LogsData) {
+    for rl in &mut data.resource_logs {
+        let mut resource_index = eval::AttrIndex::new(
+            rl.resource
+                .as_ref()
+                .map_or(&[][..], |r| r.attributes.as_slice()),
+        );
+        for sl in &mut rl.scope_logs {
+            let mut scope_index =
+                eval::AttrIndex::new(sl.scope.as_ref().map_or(&[][..], |s| &s.attributes));
+            sl.log_records.retain_mut(|rec| {
+                rec.prepare();
+                let mut ctx = eval::MutLogContext {
+                    record: rec,
+                    resource: rl.resource.as_mut(),
+                    scope: sl.scope.as_mut(),
+                    resource_index: &mut resource_index,
+                    scope_index: &mut scope_index,
+                    resource_schema_url: &rl.schema_url,
+                    scope_schema_url: &sl.schema_url,
+                    aliasing: None,
+                    severity: None,
+                };
+                kept(engine.evaluate_and_transform(snapshot, &mut ctx).unwrap())
+            });
+        }
+    }
+}
+
+fn filter_traces(engine: &PolicyEngine, snapshot: &PolicySnapshot, data: &mut otel::TracesData) {
+    for rs in &mut data.resource_spans {
+        let resource_index = eval::AttrIndex::new(
+            rs.resource
+                .as_ref()
+                .map_or(&[][..], |r| r.attributes.as_slice()),
+        );
+        for ss in &mut rs.scope_spans {
+            let scope_index =
+                eval::AttrIndex::new(ss.scope.as_ref().map_or(&[][..], |s| &s.attributes));
+            let mut scratch = String::new();
+            ss.spans.retain_mut(|span| {
+                span.prepare();
+                let mut ctx = eval::MutTraceContext {
+                    span,
+                    resource: rs.resource.as_ref(),
+                    scope: ss.scope.as_ref(),
+                    resource_index: &resource_index,
+                    scope_index: &scope_index,
+                    resource_schema_url: &rs.schema_url,
+                    scope_schema_url: &ss.schema_url,
+                    aliasing: None,
+                    scratch: &mut scratch,
+                };
+                kept(engine.evaluate_trace(snapshot, &mut ctx).unwrap())
+            });
+        }
+    }
+}
+
+fn bench_logs(c: &mut Criterion) {
+    let engine = PolicyEngine::new();
+    let logs = fixtures::generate_logs(RECORDS);
+    let mut group = c.benchmark_group("logs");
+    group.throughput(Throughput::Elements(RECORDS as u64));
+
+    let equality = fixtures::snapshot(
+        "log-equality",
+        json!({ "policies": [{
+            "id": "drop-health-check",
+            "name": "Drop health check logs",
+            "log": {
+                "match": [{ "log_field": "body", "exact": "health check" }],
+                "keep": "none",
+            },
+        }]}),
+    );
+    group.bench_function("flat_equality", |b| {
+        b.iter_batched(
+            || logs.clone(),
+            |mut data| filter_logs(&engine, &equality, &mut data),
+            BatchSize::LargeInput,
+        )
+    });
+
+    let redaction = fixtures::snapshot(
+        "log-redaction",
+        json!({ "policies": [{
+            "id": "redact-api-key",
+            "name": "Redact api_key on login logs",
+            "log": {
+                "match": [{ "log_field": "body", "contains": "login" }],
+                "keep": "all",
+                "transform": {
+                    "redact": [{ "log_attribute": "api_key", "replacement": "[REDACTED]" }],
+                    "add": [{ "log_attribute": "redacted", "value": "true", "upsert": true }],
+                },
+            },
+        }]}),
+    );
+    group.bench_function("transform_redaction", |b| {
+        b.iter_batched(
+            || logs.clone(),
+            |mut data| filter_logs(&engine, &redaction, &mut data),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn bench_traces(c: &mut Criterion) {
+    let engine = PolicyEngine::new();
+    let traces = fixtures::generate_traces(RECORDS);
+    let mut group = c.benchmark_group("traces");
+    group.throughput(Throughput::Elements(RECORDS as u64));
+
+    let nested = fixtures::snapshot(
+        "trace-nested",
+        json!({ "policies": [{
+            "id": "drop-selects",
+            "name": "Drop nested SELECT statements",
+            "trace": {
+                "match": [{ "span_attribute": ["db", "query", "statement"], "exact": "SELECT" }],
+                "keep": { "percentage": 0 },
+            },
+        }]}),
+    );
+    group.bench_function("nested_attributes", |b| {
+        b.iter_batched(
+            || traces.clone(),
+            |mut data| filter_traces(&engine, &nested, &mut data),
+            BatchSize::LargeInput,
+        )
+    });
+
+    // Every span is sampled, so each one goes through the ot tracestate merge.
+    let sampling = fixtures::snapshot(
+        "trace-sampling",
+        json!({ "policies": [{
+            "id": "sample-api",
+            "name": "Sample api-server spans",
+            "trace": {
+                "match": [{ "resource_attribute": "service.name", "exact": "api-server" }],
+                "keep": { "percentage": 50.0 },
+            },
+        }]}),
+    );
+    group.bench_function("tracestate_merge", |b| {
+        b.iter_batched(
+            || traces.clone(),
+            |mut data| filter_traces(&engine, &sampling, &mut data),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// A snapshot with no log policies, walked through the engine and through
+/// the `skip_engine` fast path the runner takes for such snapshots.
+fn bench_uncovered(c: &mut Criterion) {
+    let engine = PolicyEngine::new();
+    let logs = fixtures::generate_logs(RECORDS);
+    let mut group = c.benchmark_group("uncovered");
+    group.throughput(Throughput::Elements(RECORDS as u64));
+
+    let traces_only = fixtures::snapshot(
+        "traces-only",
+        json!({ "policies": [{
+            "id": "drop-selects",
+            "name": "Drop SELECT spans",
+            "trace": {
+                "match": [{ "span_attribute": "db.statement", "exact": "SELECT" }],
+                "keep": "none",
+            },
+        }]}),
+    );
+    for (name, skip_engine) in [("engine", false), ("fast_path", true)] {
+        let opts = EvalOptions {
+            skip_engine,
+            ..EvalOptions::default()
+        };
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || logs.clone(),
+                |mut data| evaluate_logs_data(&engine, &traces_only, &mut data, &opts).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// Resource-routing policies over 5k spans under one resource, evaluated per
+/// span and with the first span's decision reused for the rest of its scope.
+fn bench_scope_reuse(c: &mut Criterion) {
+    const SPANS: usize = 5_000;
+    let engine = PolicyEngine::new();
+    let traces = fixtures::generate_traces(SPANS);
+    let mut group = c.benchmark_group("scope_reuse");
+    group.throughput(Throughput::Elements(SPANS as u64));
+
+    let policies: Vec<_> = ["batch-worker", "cron", "legacy-gateway", "api-server"]
+        .iter()
+        .map(|service| {
+            json!({
+                "id": format!("drop-{service}"),
+                "name": format!("Drop {service} spans"),
+                "trace": {
+                    "match": [{ "resource_attribute": "service.name", "exact": service }],
+                    "keep": { "percentage": 0 },
+                },
+            })
+        })
+        .collect();
+    let routing = fixtures::snapshot("trace-routing", json!({ "policies": policies }));
+    for (name, reuse_scope_decisions) in [("per_span", false), ("reused", true)] {
+        let opts = EvalOptions {
+            reuse_scope_decisions,
+            ..EvalOptions::default()
+        };
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || traces.clone(),
+                |mut data| evaluate_traces_data(&engine, &routing, &mut data, &opts).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_logs,
+    bench_traces,
+    bench_uncovered,
+    bench_scope_reuse
+);
+criterion_main!(benches);
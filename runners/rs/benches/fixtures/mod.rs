@@ -0,0 +1,88 @@
+//! Generated OTLP payloads and policy snapshots shared by the benchmarks.
+
+use std::fs;
+use std::path::PathBuf;
+
+use policy_rs::{FileProvider, PolicyRegistry, PolicySnapshot};
+use serde_json::json;
+
+use crate::otel;
+
+/// Load a policies document into a fresh registry via a scratch file, the
+/// same path `--policies` takes.
+pub fn snapshot(name: &str, policies: serde_json::Value) -> PolicySnapshot {
+    let path: PathBuf = std::env::temp_dir().join(format!("runner-rs-bench-{name}.json"));
+    fs::write(&path, policies.to_string()).expect("write bench policies");
+    let registry = PolicyRegistry::new();
+    registry
+        .subscribe(&FileProvider::new(&path))
+        .expect("load bench policies");
+    registry.snapshot()
+}
+
+fn string_attr(key: &str, value: &str) -> serde_json::Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+/// `count` log records under one resource; every tenth is a health check and
+/// every third carries an `api_key` and a login body.
+pub fn generate_logs(count: usize) -> otel::LogsData {
+    let records: Vec<_> = (0..count)
+        .map(|i| {
+            let body = match i {
+                i if i % 10 == 0 => "health check".to_string(),
+                i if i % 3 == 0 => format!("user {i} login ok"),
+                _ => format!("request {i} served"),
+            };
+            let mut attributes = vec![string_attr("http.route", "/api/users")];
+            if i % 3 == 0 {
+                attributes.push(string_attr("api_key", "sk-0123456789abcdef"));
+            }
+            json!({
+                "severityText": "INFO",
+                "body": { "stringValue": body },
+                "attributes": attributes,
+            })
+        })
+        .collect();
+    serde_json::from_value(json!({
+        "resourceLogs": [{
+            "resource": { "attributes": [string_attr("service.name", "api-server")] },
+            "scopeLogs": [{ "scope": { "name": "bench" }, "logRecords": records }],
+        }]
+    }))
+    .expect("generated logs parse")
+}
+
+/// `count` spans under one resource, each with a three-level nested `db`
+/// kvlist attribute and an existing tracestate to merge thresholds into.
+pub fn generate_traces(count: usize) -> otel::TracesData {
+    let spans: Vec<_> = (0..count)
+        .map(|i| {
+            let statement = if i % 2 == 0 { "SELECT" } else { "INSERT" };
+            json!({
+                "traceId": format!("{:032x}", i as u128 * 0x9e37_79b9_7f4a_7c15),
+                "spanId": format!("{:016x}", i as u64 + 1),
+                "traceState": "ot=p:8;rv:aabbccddeeff00,vendor=x",
+                "name": format!("query {i}"),
+                "kind": "SPAN_KIND_CLIENT",
+                "attributes": [{
+                    "key": "db",
+                    "value": { "kvlistValue": { "values": [{
+                        "key": "query",
+                        "value": { "kvlistValue": { "values": [
+                            string_attr("statement", statement),
+                        ]}},
+                    }]}},
+                }],
+            })
+        })
+        .collect();
+    serde_json::from_value(json!({
+        "resourceSpans": [{
+            "resource": { "attributes": [string_attr("service.name", "api-server")] },
+            "scopeSpans": [{ "scope": { "name": "bench" }, "spans": spans }],
+        }]
+    }))
+    .expect("generated traces parse")
+}
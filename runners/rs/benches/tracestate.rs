@@ -0,0 +1,75 @@
+//! Per-call cost of the ot tracestate merge behind threshold injection.
+//!
+//! Before timing, each input is merged into a warmed buffer under a counting
+//! allocator, and the allocations per call are printed. The empty case, the
+//! usual one for head-sampled traffic, must allocate nothing:
+//!
+//! ```text
+//! cargo bench --bench tracestate
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use runner_rs::eval::merge_ot_tracestate;
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const CALLS: usize = 10_000;
+
+/// Allocations per merge of `tracestate` into a buffer already grown by one.
+fn allocations_per_call(tracestate: &str) -> f64 {
+    let mut out = String::new();
+    merge_ot_tracestate(&mut out, tracestate, "th", "8");
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..CALLS {
+        merge_ot_tracestate(&mut out, black_box(tracestate), "th", black_box("8"));
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / CALLS as f64
+}
+
+fn bench_merge(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tracestate_merge");
+    for (name, tracestate) in [
+        ("empty", ""),
+        ("vendors", "congo=t61rcWkgMzE,rojo=00f067aa0ba902b7"),
+        ("existing_ot", "ot=th:c;rv:9b8233f7e3a151,congo=t61rcWkgMzE"),
+    ] {
+        let allocations = allocations_per_call(tracestate);
+        println!("tracestate_merge/{name}: {allocations} allocation(s) per call");
+        if tracestate.is_empty() {
+            assert_eq!(allocations, 0.0, "merging an empty tracestate allocated");
+        }
+        let mut out = String::new();
+        group.bench_function(name, |b| {
+            b.iter(|| merge_ot_tracestate(&mut out, black_box(tracestate), "th", "8"))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_merge);
+criterion_main!(benches);
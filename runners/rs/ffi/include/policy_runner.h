@@ -0,0 +1,87 @@
+#ifndef POLICY_RUNNER_H
+#define POLICY_RUNNER_H
+
+/* Generated by cbindgen from src/lib.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define PR_OK 0
+
+// A null pointer, non-UTF-8 signal name, or unknown signal.
+#define PR_ERR_ARGUMENT 1
+
+// The input is not a valid OTLP JSON payload for the signal.
+#define PR_ERR_INPUT 2
+
+// Policy evaluation failed.
+#define PR_ERR_EVALUATION 3
+
+#define PR_ERR_PANIC 4
+
+// Loaded policies, ready to evaluate against.
+typedef struct pr_handle pr_handle;
+
+// A byte buffer allocated by this library.
+typedef struct pr_buf {
+  uint8_t *ptr;
+  size_t len;
+} pr_buf;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Parse a policy document (the JSON `--policies` files hold) into a handle.
+//
+// Returns null on failure, with a message in `*error` when `error` is not
+// null.
+//
+// # Safety
+//
+// `policies` must point to `len` readable bytes. `error` must be null or
+// point to a writable `pr_buf`.
+struct pr_handle *pr_load_policies(const uint8_t *policies, size_t len, struct pr_buf *error);
+
+// Release a handle from [`pr_load_policies`]. Null is ignored.
+//
+// # Safety
+//
+// `handle` must be null or a handle not yet freed, with no other call on it
+// in flight.
+void pr_free(struct pr_handle *handle);
+
+// Release a buffer this library filled in. An empty buffer is ignored.
+//
+// # Safety
+//
+// `buf` must have come from this library and not been freed already.
+void pr_free_buf(struct pr_buf buf);
+
+// Evaluate one OTLP JSON payload. `signal` is `log`, `metric`, `trace` or
+// `profile`.
+//
+// On [`PR_OK`], `*out` holds `{"output": <filtered payload>, "results":
+// [...]}` with one entry per record giving its `resource`/`scope`/`record`
+// indexes in the input, the `decision`, the deciding `policy_id`, whether
+// it was `kept` and whether that policy's transforms changed it
+// (`transformed`). Any other status leaves an error message in `*out`.
+//
+// # Safety
+//
+// `handle` must be a live handle, `signal` a NUL-terminated string, `input`
+// must point to `len` readable bytes, and `out` to a writable `pr_buf`.
+int32_t pr_evaluate(const struct pr_handle *handle,
+                    const char *signal,
+                    const uint8_t *input,
+                    size_t len,
+                    struct pr_buf *out);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* POLICY_RUNNER_H */
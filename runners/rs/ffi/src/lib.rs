@@ -0,0 +1,323 @@
+//! C ABI over the runner's evaluation core, for embedding the evaluator in
+//! the collector's cgo processor instead of running the binary per batch.
+//!
+//! # Ownership
+//!
+//! - [`pr_load_policies`] returns a handle the caller owns until it passes it
+//!   to [`pr_free`].
+//! - Every [`PrBuf`] this library fills in belongs to the caller, on success
+//!   and on error alike, and is released with [`pr_free_buf`]. Buffers are
+//!   not NUL-terminated; use `len`.
+//!
+//! # Threads
+//!
+//! A handle is immutable once loaded, so [`pr_evaluate`] may be called on the
+//! same handle from any number of threads at once. Rate-limited policies
+//! share their token buckets across those calls. [`pr_free`] must not race
+//! with any other call on the handle it frees.
+//!
+//! # Errors
+//!
+//! Functions report failure through a status code (or a null handle) and put
+//! a UTF-8 message in the caller's buffer. Panics are caught at the boundary
+//! and reported as [`PR_ERR_PANIC`]; they never unwind into C.
+
+use std::ffi::{CStr, c_char};
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+use policy_rs::{
+    EvaluateResult, FileProvider, Policy, PolicyCallback, PolicyEngine, PolicyError,
+    PolicyProvider, PolicyRegistry, PolicySnapshot,
+};
+use runner_rs::{EvalOptions, SignalData, evaluate_signal, otel};
+use serde::Serialize;
+
+pub const PR_OK: i32 = 0;
+/// A null pointer, non-UTF-8 signal name, or unknown signal.
+pub const PR_ERR_ARGUMENT: i32 = 1;
+/// The input is not a valid OTLP JSON payload for the signal.
+pub const PR_ERR_INPUT: i32 = 2;
+/// Policy evaluation failed.
+pub const PR_ERR_EVALUATION: i32 = 3;
+pub const PR_ERR_PANIC: i32 = 4;
+
+/// Loaded policies, ready to evaluate against.
+pub struct PrHandle {
+    engine: PolicyEngine,
+    snapshot: PolicySnapshot,
+}
+
+/// A byte buffer allocated by this library.
+#[repr(C)]
+pub struct PrBuf {
+    pub ptr: *mut u8,
+    pub len: usize,
+}
+
+impl PrBuf {
+    const EMPTY: PrBuf = PrBuf {
+        ptr: ptr::null_mut(),
+        len: 0,
+    };
+
+    fn from_vec(bytes: Vec<u8>) -> PrBuf {
+        let bytes = Box::into_raw(bytes.into_boxed_slice());
+        PrBuf {
+            ptr: bytes.cast(),
+            len: bytes.len(),
+        }
+    }
+}
+
+/// Put a failure message in `buf`, if the caller passed one.
+///
+/// # Safety
+///
+/// `buf` must be null or point to a writable `pr_buf`.
+unsafe fn report(buf: *mut PrBuf, message: String) {
+    if let Some(buf) = unsafe { buf.as_mut() } {
+        *buf = PrBuf::from_vec(message.into_bytes());
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    let detail = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic");
+    format!("panic: {detail}")
+}
+
+// ─── Loading ─────────────────────────────────────────────────────────
+
+/// Hands an already-parsed policy set to the registry.
+struct Loaded(Vec<Policy>);
+
+impl PolicyProvider for Loaded {
+    fn subscribe(&self, callback: PolicyCallback) -> Result<(), PolicyError> {
+        callback(self.0.clone());
+        Ok(())
+    }
+}
+
+fn load(document: &[u8]) -> Result<PrHandle, String> {
+    // Policy documents are only parsed from files, so stage the bytes in one
+    // for FileProvider.
+    let mut staged = tempfile::NamedTempFile::new().map_err(|e| e.to_string())?;
+    staged.write_all(document).map_err(|e| e.to_string())?;
+    let policies = FileProvider::new(staged.path())
+        .load()
+        .map_err(|e| e.to_string())?;
+    let registry = PolicyRegistry::new();
+    registry
+        .subscribe(&Loaded(policies))
+        .map_err(|e| e.to_string())?;
+    Ok(PrHandle {
+        engine: PolicyEngine::new(),
+        snapshot: registry.snapshot(),
+    })
+}
+
+/// Parse a policy document (the JSON `--policies` files hold) into a handle.
+///
+/// Returns null on failure, with a message in `*error` when `error` is not
+/// null.
+///
+/// # Safety
+///
+/// `policies` must point to `len` readable bytes. `error` must be null or
+/// point to a writable `pr_buf`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pr_load_policies(
+    policies: *const u8,
+    len: usize,
+    error: *mut PrBuf,
+) -> *mut PrHandle {
+    if policies.is_null() {
+        unsafe { report(error, "policies is null".to_string()) };
+        return ptr::null_mut();
+    }
+    let document = unsafe { slice::from_raw_parts(policies, len) };
+    match panic::catch_unwind(|| load(document)) {
+        Ok(Ok(handle)) => Box::into_raw(Box::new(handle)),
+        Ok(Err(message)) => {
+            unsafe { report(error, message) };
+            ptr::null_mut()
+        }
+        Err(payload) => {
+            unsafe { report(error, panic_message(&*payload)) };
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Release a handle from [`pr_load_policies`]. Null is ignored.
+///
+/// # Safety
+///
+/// `handle` must be null or a handle not yet freed, with no other call on it
+/// in flight.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pr_free(handle: *mut PrHandle) {
+    if !handle.is_null() {
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/// Release a buffer this library filled in. An empty buffer is ignored.
+///
+/// # Safety
+///
+/// `buf` must have come from this library and not been freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pr_free_buf(buf: PrBuf) {
+    if !buf.ptr.is_null() {
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(buf.ptr, buf.len)) });
+    }
+}
+
+// ─── Evaluation ──────────────────────────────────────────────────────
+
+#[derive(Serialize)]
+struct Evaluation<'a, D> {
+    /// The input with dropped records removed, as the runner would write it.
+    output: &'a D,
+    results: Vec<Decision>,
+}
+
+#[derive(Serialize)]
+struct Decision {
+    resource: usize,
+    scope: usize,
+    record: usize,
+    decision: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    policy_id: Option<String>,
+    kept: bool,
+    /// Whether the deciding policy's transforms changed the record, which
+    /// tells a redact-and-keep apart from a plain keep.
+    transformed: bool,
+}
+
+fn transformed(result: &EvaluateResult) -> bool {
+    match result {
+        EvaluateResult::Keep { transformed, .. }
+        | EvaluateResult::Sample { transformed, .. }
+        | EvaluateResult::RateLimit { transformed, .. } => *transformed,
+        EvaluateResult::NoMatch | EvaluateResult::Drop { .. } => false,
+    }
+}
+
+fn policy_id(result: EvaluateResult) -> Option<String> {
+    match result {
+        EvaluateResult::NoMatch => None,
+        EvaluateResult::Keep { policy_id, .. }
+        | EvaluateResult::Drop { policy_id }
+        | EvaluateResult::Sample { policy_id, .. }
+        | EvaluateResult::RateLimit { policy_id, .. } => Some(policy_id),
+    }
+}
+
+fn evaluate<D: SignalData>(handle: &PrHandle, input: &[u8]) -> Result<Vec<u8>, (i32, String)> {
+    let mut data: D = serde_json::from_slice(input)
+        .map_err(|e| (PR_ERR_INPUT, format!("failed to parse {}: {e}", D::NAME)))?;
+    let results = evaluate_signal(
+        &handle.engine,
+        &handle.snapshot,
+        &mut data,
+        &EvalOptions::default(),
+    )
+    .map_err(|e| (PR_ERR_EVALUATION, format!("evaluation error: {e}")))?;
+    let results = results
+        .into_iter()
+        .map(|r| {
+            let kept = runner_rs::keeps(&r.result);
+            let decision = runner_rs::decision_name(&r.result);
+            let transformed = transformed(&r.result);
+            Decision {
+                resource: r.resource,
+                scope: r.scope,
+                record: r.record,
+                decision,
+                policy_id: policy_id(r.result),
+                kept,
+                transformed,
+            }
+        })
+        .collect();
+    serde_json::to_vec(&Evaluation {
+        output: &data,
+        results,
+    })
+    .map_err(|e| {
+        (
+            PR_ERR_INPUT,
+            format!("failed to serialize {}: {e}", D::NAME),
+        )
+    })
+}
+
+/// Evaluate one OTLP JSON payload. `signal` is `log`, `metric`, `trace` or
+/// `profile`.
+///
+/// On [`PR_OK`], `*out` holds `{"output": <filtered payload>, "results":
+/// [...]}` with one entry per record giving its `resource`/`scope`/`record`
+/// indexes in the input, the `decision`, the deciding `policy_id`, whether
+/// it was `kept` and whether that policy's transforms changed it
+/// (`transformed`). Any other status leaves an error message in `*out`.
+///
+/// # Safety
+///
+/// `handle` must be a live handle, `signal` a NUL-terminated string, `input`
+/// must point to `len` readable bytes, and `out` to a writable `pr_buf`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn pr_evaluate(
+    handle: *const PrHandle,
+    signal: *const c_char,
+    input: *const u8,
+    len: usize,
+    out: *mut PrBuf,
+) -> i32 {
+    if out.is_null() {
+        return PR_ERR_ARGUMENT;
+    }
+    unsafe { *out = PrBuf::EMPTY };
+    let (Some(handle), false, false) = (
+        unsafe { handle.as_ref() },
+        signal.is_null(),
+        input.is_null(),
+    ) else {
+        unsafe { report(out, "handle, signal and input must not be null".to_string()) };
+        return PR_ERR_ARGUMENT;
+    };
+    let signal = unsafe { CStr::from_ptr(signal) }.to_str().unwrap_or("");
+    let input = unsafe { slice::from_raw_parts(input, len) };
+    let process = match signal {
+        "log" => evaluate::<otel::LogsData>,
+        "metric" => evaluate::<otel::MetricsData>,
+        "trace" => evaluate::<otel::TracesData>,
+        "profile" => evaluate::<otel::ProfilesData>,
+        other => {
+            unsafe { report(out, format!("unknown signal: {other}")) };
+            return PR_ERR_ARGUMENT;
+        }
+    };
+    // The handle is only read, so a panic part-way can't leave it torn.
+    match panic::catch_unwind(AssertUnwindSafe(|| process(handle, input))) {
+        Ok(Ok(json)) => {
+            unsafe { *out = PrBuf::from_vec(json) };
+            PR_OK
+        }
+        Ok(Err((status, message))) => {
+            unsafe { report(out, message) };
+            status
+        }
+        Err(payload) => {
+            unsafe { report(out, panic_message(&*payload)) };
+            PR_ERR_PANIC
+        }
+    }
+}
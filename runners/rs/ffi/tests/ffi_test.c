@@ -0,0 +1,124 @@
+/* Exercises the C ABI against the logs_exact_drop conformance case.
+ *
+ *   ffi_test <policies.json> <input.json>
+ *
+ * Exits non-zero on the first failed check. */
+
+#include <pthread.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "policy_runner.h"
+
+#define THREADS 8
+
+static int failures = 0;
+
+#define CHECK(cond, ...)                                   \
+  do {                                                     \
+    if (!(cond)) {                                         \
+      fprintf(stderr, "FAIL %s:%d: ", __FILE__, __LINE__); \
+      fprintf(stderr, __VA_ARGS__);                        \
+      fprintf(stderr, "\n");                               \
+      failures++;                                          \
+    }                                                      \
+  } while (0)
+
+static char *read_file(const char *path, size_t *len) {
+  FILE *f = fopen(path, "rb");
+  if (!f) {
+    perror(path);
+    exit(2);
+  }
+  fseek(f, 0, SEEK_END);
+  *len = (size_t)ftell(f);
+  fseek(f, 0, SEEK_SET);
+  char *data = malloc(*len);
+  if (fread(data, 1, *len, f) != *len) {
+    perror(path);
+    exit(2);
+  }
+  fclose(f);
+  return data;
+}
+
+/* Whether the (not NUL-terminated) buffer contains `needle`. */
+static int contains(pr_buf buf, const char *needle) {
+  size_t n = strlen(needle);
+  for (size_t i = 0; i + n <= buf.len; i++) {
+    if (memcmp(buf.ptr + i, needle, n) == 0) return 1;
+  }
+  return 0;
+}
+
+struct job {
+  const pr_handle *handle;
+  const char *input;
+  size_t len;
+  int ok;
+};
+
+static void *evaluate_drop_case(void *arg) {
+  struct job *job = arg;
+  pr_buf out;
+  int32_t status = pr_evaluate(job->handle, "log", (const uint8_t *)job->input, job->len, &out);
+  job->ok = status == PR_OK && contains(out, "\"decision\":\"drop\"") &&
+            contains(out, "\"transformed\":false") &&
+            contains(out, "\"policy_id\":\"drop-health-check\"") &&
+            !contains(out, "\"stringValue\":\"health check\"") &&
+            contains(out, "\"stringValue\":\"user login\"");
+  pr_free_buf(out);
+  return NULL;
+}
+
+int main(int argc, char **argv) {
+  if (argc != 3) {
+    fprintf(stderr, "usage: %s <policies.json> <input.json>\n", argv[0]);
+    return 2;
+  }
+  size_t policies_len, input_len;
+  char *policies = read_file(argv[1], &policies_len);
+  char *input = read_file(argv[2], &input_len);
+
+  /* Bad policies: null handle and a message. */
+  pr_buf error = {0};
+  pr_handle *bad = pr_load_policies((const uint8_t *)"{", 1, &error);
+  CHECK(bad == NULL, "malformed policies loaded");
+  CHECK(error.len > 0, "no error message for malformed policies");
+  pr_free_buf(error);
+
+  pr_handle *handle = pr_load_policies((const uint8_t *)policies, policies_len, &error);
+  CHECK(handle != NULL, "load failed: %.*s", (int)error.len, (const char *)error.ptr);
+  if (!handle) return 1;
+
+  /* Unknown signal and bad input are errors with a message. */
+  pr_buf out;
+  CHECK(pr_evaluate(handle, "logs", (const uint8_t *)input, input_len, &out) == PR_ERR_ARGUMENT,
+        "unknown signal accepted");
+  CHECK(contains(out, "unknown signal"), "unknown signal message: %.*s", (int)out.len,
+        (const char *)out.ptr);
+  pr_free_buf(out);
+  CHECK(pr_evaluate(handle, "log", (const uint8_t *)"[", 1, &out) == PR_ERR_INPUT,
+        "malformed input accepted");
+  pr_free_buf(out);
+
+  /* The same handle from several threads at once. */
+  pthread_t threads[THREADS];
+  struct job jobs[THREADS];
+  for (int i = 0; i < THREADS; i++) {
+    jobs[i] = (struct job){handle, input, input_len, 0};
+    pthread_create(&threads[i], NULL, evaluate_drop_case, &jobs[i]);
+  }
+  for (int i = 0; i < THREADS; i++) {
+    pthread_join(threads[i], NULL);
+    CHECK(jobs[i].ok, "thread %d: wrong evaluation result", i);
+  }
+
+  pr_free(handle);
+  pr_free(NULL);
+  free(policies);
+  free(input);
+  if (failures == 0) printf("ffi: all checks passed\n");
+  return failures == 0 ? 0 : 1;
+}
@@ -0,0 +1,151 @@
+//! Arbitrary bytes through the OTLP JSON parse and evaluation path, against a
+//! small fixed policy set touching nested attributes, transforms and trace
+//! sampling. Anything that parses must evaluate and re-serialize without
+//! panicking.
+
+#![no_main]
+#![allow(dead_code)]
+
+#[path = "../../src/eval.rs"]
+mod eval;
+#[path = "../../src/otel.rs"]
+mod otel;
+#[path = "../../src/semconv.rs"]
+mod semconv;
+#[path = "../../src/severity.rs"]
+mod severity;
+#[path = "../../src/tracestate.rs"]
+mod tracestate;
+
+use std::sync::OnceLock;
+
+use libfuzzer_sys::fuzz_target;
+use policy_rs::{FileProvider, PolicyEngine, PolicyRegistry, PolicySnapshot};
+
+const POLICIES: &str = r#"{
+  "policies": [
+    {
+      "id": "drop-nested-region",
+      "name": "Drop nested cloud.region",
+      "log": {
+        "match": [{ "resource_attribute": ["cloud", "region"], "exact": "us-east-1" }],
+        "keep": "none"
+      }
+    },
+    {
+      "id": "redact-login",
+      "name": "Redact api_key on login",
+      "log": {
+        "match": [{ "log_field": "body", "regex": "log[io]n" }],
+        "keep": "all",
+        "transform": {
+          "redact": [{ "log_attribute": "api_key", "replacement": "[REDACTED]" }],
+          "rename": [{ "from": { "log_attribute": "user" }, "to": "user.id", "upsert": true }]
+        }
+      }
+    },
+    {
+      "id": "sample-spans",
+      "name": "Sample nested db spans",
+      "trace": {
+        "match": [{ "span_attribute": ["db", "statement"], "exists": true }],
+        "keep": { "percentage": 50.0 }
+      }
+    }
+  ]
+}"#;
+
+fn snapshot() -> &'static PolicySnapshot {
+    static SNAPSHOT: OnceLock<PolicySnapshot> = OnceLock::new();
+    SNAPSHOT.get_or_init(|| {
+        let path = std::env::temp_dir().join("runner-rs-fuzz-policies.json");
+        std::fs::write(&path, POLICIES).expect("write fuzz policies");
+        let registry = PolicyRegistry::new();
+        registry
+            .subscribe(&FileProvider::new(&path))
+            .expect("load fuzz policies");
+        registry.snapshot()
+    })
+}
+
+fn attrs(resource: Option<&otel::Resource>) -> &[otel::KeyValue] {
+    resource.map_or(&[], |r| &r.attributes)
+}
+
+fn scope_attrs(scope: Option<&otel::InstrumentationScope>) -> &[otel::KeyValue] {
+    scope.map_or(&[], |s| &s.attributes)
+}
+
+fn logs(engine: &PolicyEngine, snapshot: &PolicySnapshot, mut data: otel::LogsData) {
+    for rl in &mut data.resource_logs {
+        if let Some(r) = rl.resource.as_mut() {
+            otel::prepare_attributes(&mut r.attributes);
+        }
+        let mut resource_index = eval::AttrIndex::new(attrs(rl.resource.as_ref()));
+        for sl in &mut rl.scope_logs {
+            if let Some(s) = sl.scope.as_mut() {
+                otel::prepare_attributes(&mut s.attributes);
+            }
+            let mut scope_index = eval::AttrIndex::new(scope_attrs(sl.scope.as_ref()));
+            for rec in &mut sl.log_records {
+                rec.prepare();
+                let mut ctx = eval::MutLogContext {
+                    record: rec,
+                    resource: rl.resource.as_mut(),
+                    scope: sl.scope.as_mut(),
+                    resource_index: &mut resource_index,
+                    scope_index: &mut scope_index,
+                    resource_schema_url: &rl.schema_url,
+                    scope_schema_url: &sl.schema_url,
+                    aliasing: None,
+                    severity: None,
+                };
+                let _ = engine.evaluate_and_transform(snapshot, &mut ctx);
+            }
+        }
+    }
+    serde_json::to_vec(&data).expect("filtered logs serialize");
+}
+
+fn traces(engine: &PolicyEngine, snapshot: &PolicySnapshot, mut data: otel::TracesData) {
+    for rs in &mut data.resource_spans {
+        if let Some(r) = rs.resource.as_mut() {
+            otel::prepare_attributes(&mut r.attributes);
+        }
+        let resource_index = eval::AttrIndex::new(attrs(rs.resource.as_ref()));
+        for ss in &mut rs.scope_spans {
+            if let Some(s) = ss.scope.as_mut() {
+                otel::prepare_attributes(&mut s.attributes);
+            }
+            let scope_index = eval::AttrIndex::new(scope_attrs(ss.scope.as_ref()));
+            let mut scratch = String::new();
+            for span in &mut ss.spans {
+                span.prepare();
+                let mut ctx = eval::MutTraceContext {
+                    span,
+                    resource: rs.resource.as_ref(),
+                    scope: ss.scope.as_ref(),
+                    resource_index: &resource_index,
+                    scope_index: &scope_index,
+                    resource_schema_url: &rs.schema_url,
+                    scope_schema_url: &ss.schema_url,
+                    aliasing: None,
+                    scratch: &mut scratch,
+                };
+                let _ = engine.evaluate_trace(snapshot, &mut ctx);
+            }
+        }
+    }
+    serde_json::to_vec(&data).expect("filtered traces serialize");
+}
+
+fuzz_target!(|data: &[u8]| {
+    let engine = PolicyEngine::new();
+    let snapshot = snapshot();
+    if let Ok(parsed) = serde_json::from_slice::<otel::LogsData>(data) {
+        logs(&engine, snapshot, parsed);
+    }
+    if let Ok(parsed) = serde_json::from_slice::<otel::TracesData>(data) {
+        traces(&engine, snapshot, parsed);
+    }
+});
@@ -0,0 +1,61 @@
+//! Arbitrary tracestates through the ot threshold merge. The merged string
+//! must always read back as a tracestate whose first member is `ot=` carrying
+//! exactly the threshold that was written, stay within the W3C member limit,
+//! and come back unchanged when merged again.
+
+#![no_main]
+#![allow(dead_code)]
+
+#[path = "../../src/eval.rs"]
+mod eval;
+#[path = "../../src/otel.rs"]
+mod otel;
+#[path = "../../src/semconv.rs"]
+mod semconv;
+#[path = "../../src/severity.rs"]
+mod severity;
+#[path = "../../src/tracestate.rs"]
+mod tracestate;
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|tracestate: &str| {
+    // Thresholds are hex digits written by the engine, never separators; vary
+    // them with the input so seeds stay plain tracestate strings.
+    let value = format!("{:x}", tracestate.len());
+
+    let mut merged = String::new();
+    eval::merge_ot_tracestate(&mut merged, tracestate, "th", &value);
+
+    let mut members = merged.split(',');
+    let ot = members
+        .next()
+        .and_then(|m| m.strip_prefix("ot="))
+        .expect("merged tracestate starts with ot=");
+    let thresholds: Vec<_> = ot
+        .split(';')
+        .filter_map(|part| part.strip_prefix("th:"))
+        .collect();
+    assert_eq!(
+        thresholds,
+        [value.as_str()],
+        "exactly one th sub-key: {tracestate:?} -> {merged}"
+    );
+    let mut count = 1;
+    for member in members {
+        count += 1;
+        assert!(
+            !member.is_empty(),
+            "empty member: {tracestate:?} -> {merged}"
+        );
+        assert!(
+            !member.starts_with("ot="),
+            "second ot member: {tracestate:?} -> {merged}"
+        );
+    }
+    assert!(count <= 32, "{count} members: {tracestate:?} -> {merged}");
+
+    let mut remerged = String::new();
+    eval::merge_ot_tracestate(&mut remerged, &merged, "th", &value);
+    assert_eq!(remerged, merged, "merge not idempotent: {tracestate:?}");
+});
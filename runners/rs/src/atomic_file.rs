@@ -0,0 +1,85 @@
+//! Writing files so that a failed run never leaves a partial one behind.
+//!
+//! Every file the runner writes goes first to `<path>.tmp` and is renamed
+//! over `<path>` only once complete. A rename within a directory is atomic,
+//! so readers see the old file or the new one, never part of either. The
+//! temp file is removed when writing fails, and when a failure or a panic
+//! returns through the write, abandoning the run partway; whatever was at
+//! `<path>` before is left as it was.
+//!
+//! JSON lines (`--input-lines` output, `--trace-engine`) are written the
+//! same way, so a file that exists is complete and carries no end marker.
+
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Files written so far, in order, for `--status-file`.
+static WRITTEN: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+fn written_mut() -> MutexGuard<'static, Vec<PathBuf>> {
+    WRITTEN.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A file being written beside its destination.
+pub struct Staged {
+    out: BufWriter<fs::File>,
+    tmp: PathBuf,
+    path: PathBuf,
+    committed: bool,
+}
+
+impl Staged {
+    pub fn create(path: &Path) -> io::Result<Staged> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let file = fs::File::create(&tmp)?;
+        Ok(Staged {
+            out: BufWriter::new(file),
+            tmp,
+            path: path.to_path_buf(),
+            committed: false,
+        })
+    }
+
+    /// Replace the destination with what was written.
+    pub fn commit(mut self) -> io::Result<()> {
+        self.out.flush()?;
+        fs::rename(&self.tmp, &self.path)?;
+        self.committed = true;
+        written_mut().push(self.path.clone());
+        Ok(())
+    }
+}
+
+impl Write for Staged {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.out.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl Drop for Staged {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+/// Write `data` to `path` in one go.
+pub fn write(path: &Path, data: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut staged = Staged::create(path)?;
+    staged.write_all(data.as_ref())?;
+    staged.commit()
+}
+
+/// The files written so far, in the order they were completed.
+pub fn written() -> Vec<PathBuf> {
+    written_mut().clone()
+}
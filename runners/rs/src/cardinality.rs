@@ -0,0 +1,213 @@
+//! `--profile-attributes`: what values each attribute key takes across the
+//! input, to write matchers from.
+//!
+//! Resource, scope and record attributes are tallied separately. Record
+//! attributes are log record, span and datapoint attributes; profiles only
+//! contribute resource and scope attributes. For each key the report gives
+//! the share of the level's resources, scopes or records carrying it, how
+//! many distinct values it takes and the most common ones:
+//!
+//! ```json
+//! { "record": { "entities": 40, "attributes": { "http.route": {
+//!     "presence_pct": 75.0, "distinct": 3, "overflow": false,
+//!     "top": [{ "value": "/api/users", "count": 20 }] } } } }
+//! ```
+//!
+//! Values are compared by [`otel::AnyValue::canonical_string`]. Only the
+//! first [`DISTINCT_CAP`] distinct values of a key are counted; past that
+//! `overflow` is set and `distinct` is a lower bound. Every input record is
+//! tallied, whether or not `--sample-input` evaluates it.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use runner_rs::otel::{self, KeyValue};
+use serde::Serialize;
+
+use crate::status::{self, Failure};
+
+/// Distinct values tracked per key.
+pub const DISTINCT_CAP: usize = 1000;
+/// Values listed per key.
+pub const TOP_K: usize = 10;
+
+#[derive(Default)]
+struct KeyTally {
+    present: usize,
+    values: HashMap<String, usize>,
+    overflow: bool,
+}
+
+#[derive(Default)]
+struct Level {
+    entities: usize,
+    keys: BTreeMap<String, KeyTally>,
+}
+
+impl Level {
+    /// Tally one resource, scope or record's attributes.
+    fn observe(&mut self, attrs: &[KeyValue]) {
+        self.entities += 1;
+        for kv in attrs {
+            let tally = self.keys.entry(kv.key.clone()).or_default();
+            tally.present += 1;
+            let value = kv
+                .value
+                .as_ref()
+                .map_or_else(String::new, |v| v.canonical_string().into_owned());
+            if let Some(count) = tally.values.get_mut(&value) {
+                *count += 1;
+            } else if tally.values.len() < DISTINCT_CAP {
+                tally.values.insert(value, 1);
+            } else {
+                tally.overflow = true;
+            }
+        }
+    }
+
+    fn report(&self) -> LevelReport<'_> {
+        let attributes = self
+            .keys
+            .iter()
+            .map(|(key, tally)| {
+                let mut top: Vec<ValueCount<'_>> = tally
+                    .values
+                    .iter()
+                    .map(|(value, &count)| ValueCount { value, count })
+                    .collect();
+                top.sort_by(|a, b| b.count.cmp(&a.count).then(a.value.cmp(b.value)));
+                top.truncate(TOP_K);
+                let report = KeyReport {
+                    presence_pct: tally.present as f64 * 100.0 / self.entities as f64,
+                    distinct: tally.values.len(),
+                    overflow: tally.overflow,
+                    top,
+                };
+                (key.as_str(), report)
+            })
+            .collect();
+        LevelReport {
+            entities: self.entities,
+            attributes,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ValueCount<'a> {
+    value: &'a str,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct KeyReport<'a> {
+    presence_pct: f64,
+    distinct: usize,
+    overflow: bool,
+    top: Vec<ValueCount<'a>>,
+}
+
+#[derive(Serialize)]
+struct LevelReport<'a> {
+    entities: usize,
+    attributes: BTreeMap<&'a str, KeyReport<'a>>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    resource: LevelReport<'a>,
+    scope: LevelReport<'a>,
+    record: LevelReport<'a>,
+}
+
+/// Attribute tallies across every payload observed.
+#[derive(Default)]
+pub struct Profile {
+    resource: Level,
+    scope: Level,
+    record: Level,
+}
+
+impl Profile {
+    fn resource(&mut self, resource: Option<&otel::Resource>) {
+        self.resource
+            .observe(resource.map_or(&[], |r| r.attributes.as_slice()));
+    }
+
+    fn scope(&mut self, scope: Option<&otel::InstrumentationScope>) {
+        self.scope
+            .observe(scope.map_or(&[], |s| s.attributes.as_slice()));
+    }
+
+    pub fn write(&self, path: &Path) -> status::Result {
+        let report = Report {
+            resource: self.resource.report(),
+            scope: self.scope.report(),
+            record: self.record.report(),
+        };
+        let data = serde_json::to_string_pretty(&report)
+            .map_err(|e| Failure(format!("failed to serialize attribute profile: {e}")))?;
+        crate::atomic_file::write(path, data)
+            .map_err(|e| Failure(format!("failed to write attribute profile: {e}")))
+    }
+}
+
+/// Payloads whose attributes can be tallied.
+pub trait Profiled {
+    fn profile(&self, profile: &mut Profile);
+}
+
+impl Profiled for otel::LogsData {
+    fn profile(&self, profile: &mut Profile) {
+        for rl in &self.resource_logs {
+            profile.resource(rl.resource.as_ref());
+            for sl in &rl.scope_logs {
+                profile.scope(sl.scope.as_ref());
+                for rec in &sl.log_records {
+                    profile.record.observe(&rec.attributes);
+                }
+            }
+        }
+    }
+}
+
+impl Profiled for otel::MetricsData {
+    fn profile(&self, profile: &mut Profile) {
+        for rm in &self.resource_metrics {
+            profile.resource(rm.resource.as_ref());
+            for sm in &rm.scope_metrics {
+                profile.scope(sm.scope.as_ref());
+                for data in sm.metrics.iter().filter_map(|m| m.data.as_ref()) {
+                    for attrs in data.datapoint_attributes() {
+                        profile.record.observe(&attrs);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Profiled for otel::TracesData {
+    fn profile(&self, profile: &mut Profile) {
+        for rs in &self.resource_spans {
+            profile.resource(rs.resource.as_ref());
+            for ss in &rs.scope_spans {
+                profile.scope(ss.scope.as_ref());
+                for span in &ss.spans {
+                    profile.record.observe(&span.attributes);
+                }
+            }
+        }
+    }
+}
+
+impl Profiled for otel::ProfilesData {
+    fn profile(&self, profile: &mut Profile) {
+        for rp in &self.resource_profiles {
+            profile.resource(rp.resource.as_ref());
+            for sp in &rp.scope_profiles {
+                profile.scope(sp.scope.as_ref());
+            }
+        }
+    }
+}
@@ -0,0 +1,518 @@
+//! OTLP semantic checks on input payloads, ahead of evaluation.
+//!
+//! Parsing only establishes that a payload has the right shape. These rules
+//! catch values that parse but can't be real telemetry (a 15-byte trace id,
+//! a log with no observed time, a metric with no data points) and would
+//! otherwise show up as a run of puzzling `no_match` decisions.
+//!
+//! Each [`Violation`] names the offending field by its JSON path, in the same
+//! form `--strict-input` reports unknown keys, e.g.
+//! `resourceSpans[0].scopeSpans[0].spans[2].traceId`.
+
+use base64::Engine as _;
+
+use crate::{eval, otel, tracestate};
+
+/// One broken rule on one field.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    /// JSON path of the field, or of the record for whole-record rules.
+    pub path: String,
+    /// Short rule name, e.g. `id-length`.
+    pub rule: &'static str,
+    pub message: String,
+}
+
+/// Payload types [`check`] knows the rules for.
+pub trait CheckInput {
+    fn violations(&self) -> Vec<Violation>;
+}
+
+/// Every rule violation in `data`, in input order.
+pub fn check<D: CheckInput>(data: &D) -> Vec<Violation> {
+    data.violations()
+}
+
+struct Checker {
+    out: Vec<Violation>,
+}
+
+impl Checker {
+    fn report(&mut self, path: String, rule: &'static str, message: String) {
+        self.out.push(Violation {
+            path,
+            rule,
+            message,
+        });
+    }
+
+    /// A hex identifier that must decode to `len` bytes, or be empty when
+    /// `required` is false.
+    fn id(&mut self, path: String, value: &str, len: usize, required: bool) {
+        if value.is_empty() {
+            if required {
+                self.report(path, "required", "missing".to_string());
+            }
+            return;
+        }
+        let valid_hex =
+            value.len().is_multiple_of(2) && value.bytes().all(|b| b.is_ascii_hexdigit());
+        if !valid_hex {
+            // Protobuf's canonical JSON mapping writes bytes as base64, and
+            // some exporters follow it for ids too; OTLP/JSON doesn't.
+            let hint = match base64::engine::general_purpose::STANDARD.decode(value) {
+                Ok(bytes) if bytes.len() == len => " (it is base64; OTLP/JSON ids are hex)",
+                _ => "",
+            };
+            self.report(path, "id-encoding", format!("{value:?} is not hex{hint}"));
+        } else if value.len() / 2 != len {
+            self.report(
+                path,
+                "id-length",
+                format!("{} bytes, expected {len}", value.len() / 2),
+            );
+        } else if value.bytes().all(|b| b == b'0') {
+            self.report(path, "id-zero", "all-zero id".to_string());
+        }
+    }
+
+    /// A timestamp that must be present and non-zero. Returns its value.
+    fn time(&mut self, path: String, value: &serde_json::Value) -> Option<u64> {
+        match otel::unix_nanos(value) {
+            Some(0) => self.report(path, "required", "zero".to_string()),
+            Some(n) => return Some(n),
+            None if value.is_null() => self.report(path, "required", "missing".to_string()),
+            None => self.report(path, "timestamp", format!("{value} is not a u64")),
+        }
+        None
+    }
+
+    fn one_of(&mut self, path: String, value: &str, allowed: &[&str]) {
+        if !value.is_empty() && !allowed.contains(&value) {
+            self.report(path, "enum", format!("{value:?} is not a known value"));
+        }
+    }
+}
+
+/// A numeric or named enum value, accepted when it is one of `names` or a
+/// number below `names.len()`.
+fn enum_value(value: &serde_json::Value, names: &[&str]) -> bool {
+    match value {
+        serde_json::Value::Null => true,
+        serde_json::Value::Number(n) => n.as_u64().is_some_and(|n| n < names.len() as u64),
+        serde_json::Value::String(s) => names.contains(&s.as_str()),
+        _ => false,
+    }
+}
+
+const SEVERITY_NUMBERS: &[&str] = &[
+    "SEVERITY_NUMBER_UNSPECIFIED",
+    "SEVERITY_NUMBER_TRACE",
+    "SEVERITY_NUMBER_TRACE2",
+    "SEVERITY_NUMBER_TRACE3",
+    "SEVERITY_NUMBER_TRACE4",
+    "SEVERITY_NUMBER_DEBUG",
+    "SEVERITY_NUMBER_DEBUG2",
+    "SEVERITY_NUMBER_DEBUG3",
+    "SEVERITY_NUMBER_DEBUG4",
+    "SEVERITY_NUMBER_INFO",
+    "SEVERITY_NUMBER_INFO2",
+    "SEVERITY_NUMBER_INFO3",
+    "SEVERITY_NUMBER_INFO4",
+    "SEVERITY_NUMBER_WARN",
+    "SEVERITY_NUMBER_WARN2",
+    "SEVERITY_NUMBER_WARN3",
+    "SEVERITY_NUMBER_WARN4",
+    "SEVERITY_NUMBER_ERROR",
+    "SEVERITY_NUMBER_ERROR2",
+    "SEVERITY_NUMBER_ERROR3",
+    "SEVERITY_NUMBER_ERROR4",
+    "SEVERITY_NUMBER_FATAL",
+    "SEVERITY_NUMBER_FATAL2",
+    "SEVERITY_NUMBER_FATAL3",
+    "SEVERITY_NUMBER_FATAL4",
+];
+
+const SPAN_KINDS: &[&str] = &[
+    "SPAN_KIND_UNSPECIFIED",
+    "SPAN_KIND_INTERNAL",
+    "SPAN_KIND_SERVER",
+    "SPAN_KIND_CLIENT",
+    "SPAN_KIND_PRODUCER",
+    "SPAN_KIND_CONSUMER",
+];
+
+const STATUS_CODES: &[&str] = &["STATUS_CODE_UNSET", "STATUS_CODE_OK", "STATUS_CODE_ERROR"];
+
+const AGGREGATION_TEMPORALITIES: &[&str] = &[
+    "AGGREGATION_TEMPORALITY_UNSPECIFIED",
+    "AGGREGATION_TEMPORALITY_DELTA",
+    "AGGREGATION_TEMPORALITY_CUMULATIVE",
+];
+
+// ─── Logs ────────────────────────────────────────────────────────────
+
+impl CheckInput for otel::LogsData {
+    fn violations(&self) -> Vec<Violation> {
+        let mut c = Checker { out: Vec::new() };
+        for (i, rl) in self.resource_logs.iter().enumerate() {
+            for (j, sl) in rl.scope_logs.iter().enumerate() {
+                for (k, log) in sl.log_records.iter().enumerate() {
+                    let path = format!("resourceLogs[{i}].scopeLogs[{j}].logRecords[{k}]");
+                    c.id(format!("{path}.traceId"), &log.trace_id, 16, false);
+                    c.id(format!("{path}.spanId"), &log.span_id, 8, false);
+                    if !log.span_id.is_empty() && log.trace_id.is_empty() {
+                        c.report(
+                            format!("{path}.traceId"),
+                            "required",
+                            "spanId is set without a traceId".to_string(),
+                        );
+                    }
+                    c.time(
+                        format!("{path}.observedTimeUnixNano"),
+                        &log.observed_time_unix_nano,
+                    );
+                    c.one_of(
+                        format!("{path}.severityNumber"),
+                        &log.severity_number,
+                        SEVERITY_NUMBERS,
+                    );
+                }
+            }
+        }
+        c.out
+    }
+}
+
+// ─── Metrics ─────────────────────────────────────────────────────────
+
+fn check_temporality(c: &mut Checker, path: &str, value: &serde_json::Value) {
+    if !enum_value(value, AGGREGATION_TEMPORALITIES) {
+        c.report(
+            format!("{path}.aggregationTemporality"),
+            "enum",
+            format!("{value} is not a known value"),
+        );
+    }
+}
+
+impl CheckInput for otel::MetricsData {
+    fn violations(&self) -> Vec<Violation> {
+        let mut c = Checker { out: Vec::new() };
+        for (i, rm) in self.resource_metrics.iter().enumerate() {
+            for (j, sm) in rm.scope_metrics.iter().enumerate() {
+                for (k, metric) in sm.metrics.iter().enumerate() {
+                    let path = format!("resourceMetrics[{i}].scopeMetrics[{j}].metrics[{k}]");
+                    if metric.name.is_empty() {
+                        c.report(format!("{path}.name"), "required", "missing".to_string());
+                    }
+                    let Some(data) = &metric.data else {
+                        c.report(
+                            path,
+                            "required",
+                            "no gauge, sum, histogram, exponentialHistogram or summary".to_string(),
+                        );
+                        continue;
+                    };
+                    let points = match data {
+                        otel::MetricData::Gauge(g) => g.data_points.len(),
+                        otel::MetricData::Sum(s) => {
+                            check_temporality(&mut c, &path, &s.aggregation_temporality);
+                            s.data_points.len()
+                        }
+                        otel::MetricData::Histogram(h) => {
+                            check_temporality(&mut c, &path, &h.aggregation_temporality);
+                            for (p, dp) in h.data_points.iter().enumerate() {
+                                if !dp.bucket_counts.is_empty()
+                                    && dp.bucket_counts.len() != dp.explicit_bounds.len() + 1
+                                {
+                                    c.report(
+                                        format!("{path}.histogram.dataPoints[{p}].bucketCounts"),
+                                        "bucket-count",
+                                        format!(
+                                            "{} buckets for {} bounds",
+                                            dp.bucket_counts.len(),
+                                            dp.explicit_bounds.len()
+                                        ),
+                                    );
+                                }
+                            }
+                            h.data_points.len()
+                        }
+                        otel::MetricData::ExponentialHistogram(eh) => {
+                            check_temporality(&mut c, &path, &eh.aggregation_temporality);
+                            eh.data_points.len()
+                        }
+                        otel::MetricData::Summary(s) => s.data_points.len(),
+                    };
+                    if points == 0 {
+                        c.report(path, "no-data", "no data points".to_string());
+                    }
+                }
+            }
+        }
+        c.out
+    }
+}
+
+// ─── Traces ──────────────────────────────────────────────────────────
+
+impl CheckInput for otel::TracesData {
+    fn violations(&self) -> Vec<Violation> {
+        let mut c = Checker { out: Vec::new() };
+        for (i, rs) in self.resource_spans.iter().enumerate() {
+            for (j, ss) in rs.scope_spans.iter().enumerate() {
+                for (k, span) in ss.spans.iter().enumerate() {
+                    let path = format!("resourceSpans[{i}].scopeSpans[{j}].spans[{k}]");
+                    c.id(format!("{path}.traceId"), &span.trace_id, 16, true);
+                    c.id(format!("{path}.spanId"), &span.span_id, 8, true);
+                    c.id(
+                        format!("{path}.parentSpanId"),
+                        &span.parent_span_id,
+                        8,
+                        false,
+                    );
+                    if span.name.is_empty() {
+                        c.report(format!("{path}.name"), "required", "missing".to_string());
+                    }
+                    let kind = eval::normalize_span_kind(&span.kind);
+                    if kind.is_some_and(|k| !SPAN_KINDS.contains(&k.as_ref())) {
+                        c.report(
+                            format!("{path}.kind"),
+                            "enum",
+                            format!("{} is not a known value", span.kind),
+                        );
+                    }
+                    let start = c.time(
+                        format!("{path}.startTimeUnixNano"),
+                        &span.start_time_unix_nano,
+                    );
+                    let end = c.time(format!("{path}.endTimeUnixNano"), &span.end_time_unix_nano);
+                    if let (Some(start), Some(end)) = (start, end)
+                        && end < start
+                    {
+                        c.report(
+                            format!("{path}.endTimeUnixNano"),
+                            "time-order",
+                            "ends before it starts".to_string(),
+                        );
+                    }
+                    if let Some(status) = &span.status {
+                        c.one_of(format!("{path}.status.code"), &status.code, STATUS_CODES);
+                    }
+                    for problem in tracestate::validate(&span.trace_state).problems {
+                        c.report(format!("{path}.traceState"), problem.rule, problem.message);
+                    }
+                }
+            }
+        }
+        c.out
+    }
+}
+
+// ─── Profiles ────────────────────────────────────────────────────────
+
+/// Profile bodies are carried as raw JSON, so there is nothing typed to
+/// check.
+impl CheckInput for otel::ProfilesData {
+    fn violations(&self) -> Vec<Violation> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    const TRACE_ID: &str = "5b8efff798038103d269b633813fc60c";
+    const SPAN_ID: &str = "eee19b7ec3c1b174";
+
+    /// `(path, rule)` for each violation, the path relative to the record.
+    fn rules<D: CheckInput + serde::de::DeserializeOwned>(
+        payload: Value,
+        record_path: &str,
+    ) -> Vec<(String, &'static str)> {
+        let data: D = serde_json::from_value(payload).unwrap();
+        check(&data)
+            .into_iter()
+            .map(|v| {
+                let path = v.path.strip_prefix(record_path).unwrap_or(&v.path);
+                (path.to_string(), v.rule)
+            })
+            .collect()
+    }
+
+    fn log_rules(overrides: Value) -> Vec<(String, &'static str)> {
+        let mut record =
+            json!({ "observedTimeUnixNano": 1, "severityNumber": "SEVERITY_NUMBER_INFO" });
+        merge(&mut record, overrides);
+        let payload = json!({ "resourceLogs": [{ "scopeLogs": [{ "logRecords": [record] }] }] });
+        rules::<otel::LogsData>(payload, "resourceLogs[0].scopeLogs[0].logRecords[0]")
+    }
+
+    fn span_rules(overrides: Value) -> Vec<(String, &'static str)> {
+        let mut span = json!({
+            "traceId": TRACE_ID,
+            "spanId": SPAN_ID,
+            "name": "GET /",
+            "kind": "SPAN_KIND_SERVER",
+            "startTimeUnixNano": 1,
+            "endTimeUnixNano": 2,
+        });
+        merge(&mut span, overrides);
+        let payload = json!({ "resourceSpans": [{ "scopeSpans": [{ "spans": [span] }] }] });
+        rules::<otel::TracesData>(payload, "resourceSpans[0].scopeSpans[0].spans[0]")
+    }
+
+    fn metric_rules(metric: Value) -> Vec<(String, &'static str)> {
+        let payload = json!({ "resourceMetrics": [{ "scopeMetrics": [{ "metrics": [metric] }] }] });
+        rules::<otel::MetricsData>(payload, "resourceMetrics[0].scopeMetrics[0].metrics[0]")
+    }
+
+    fn merge(target: &mut Value, overrides: Value) {
+        if let (Value::Object(target), Value::Object(overrides)) = (target, overrides) {
+            target.extend(overrides);
+        }
+    }
+
+    fn found(expected: &[(&str, &'static str)]) -> Vec<(String, &'static str)> {
+        expected
+            .iter()
+            .map(|&(path, rule)| (path.to_string(), rule))
+            .collect()
+    }
+
+    #[test]
+    fn valid_records_pass() {
+        assert_eq!(
+            log_rules(json!({ "traceId": TRACE_ID, "spanId": SPAN_ID })),
+            []
+        );
+        assert_eq!(span_rules(json!({ "parentSpanId": SPAN_ID })), []);
+        let gauge = json!({ "name": "m", "gauge": { "dataPoints": [{ "asInt": 1 }] } });
+        assert_eq!(metric_rules(gauge), []);
+    }
+
+    #[test]
+    fn ids_must_have_their_length() {
+        let short_trace = &TRACE_ID[2..];
+        let long_span = format!("{SPAN_ID}00");
+        assert_eq!(
+            span_rules(
+                json!({ "traceId": short_trace, "spanId": long_span, "parentSpanId": "00" })
+            ),
+            found(&[
+                (".traceId", "id-length"),
+                (".spanId", "id-length"),
+                (".parentSpanId", "id-length"),
+            ])
+        );
+        let logs: otel::LogsData = serde_json::from_value(json!({
+            "resourceLogs": [{ "scopeLogs": [{ "logRecords": [{
+                "observedTimeUnixNano": 1,
+                "traceId": short_trace,
+            }] }] }],
+        }))
+        .unwrap();
+        assert_eq!(check(&logs)[0].message, "15 bytes, expected 16");
+    }
+
+    #[test]
+    fn ids_must_be_nonzero_hex() {
+        let base64_span = base64::engine::general_purpose::STANDARD.encode([7u8; 8]);
+        assert_eq!(
+            span_rules(json!({ "traceId": "0".repeat(32), "spanId": base64_span })),
+            found(&[(".traceId", "id-zero"), (".spanId", "id-encoding")])
+        );
+        assert_eq!(
+            log_rules(json!({ "spanId": SPAN_ID })),
+            found(&[(".traceId", "required")])
+        );
+        assert_eq!(
+            span_rules(json!({ "traceId": "", "spanId": "" })),
+            found(&[(".traceId", "required"), (".spanId", "required")])
+        );
+    }
+
+    #[test]
+    fn observed_time_must_be_nonzero() {
+        for time in [json!(0), json!("0"), json!(null)] {
+            assert_eq!(
+                log_rules(json!({ "observedTimeUnixNano": time })),
+                found(&[(".observedTimeUnixNano", "required")]),
+                "{time}"
+            );
+        }
+        assert_eq!(
+            log_rules(json!({ "observedTimeUnixNano": "soon" })),
+            found(&[(".observedTimeUnixNano", "timestamp")])
+        );
+        assert_eq!(
+            span_rules(json!({ "startTimeUnixNano": 5, "endTimeUnixNano": 4 })),
+            found(&[(".endTimeUnixNano", "time-order")])
+        );
+    }
+
+    #[test]
+    fn metrics_must_have_data() {
+        assert_eq!(
+            metric_rules(json!({ "name": "m" })),
+            found(&[("", "required")])
+        );
+        assert_eq!(
+            metric_rules(
+                json!({ "name": "m", "sum": { "dataPoints": [], "aggregationTemporality": 1 } })
+            ),
+            found(&[("", "no-data")])
+        );
+        assert_eq!(
+            metric_rules(json!({ "gauge": { "dataPoints": [{ "asInt": 1 }] } })),
+            found(&[(".name", "required")])
+        );
+        let histogram = json!({
+            "name": "m",
+            "histogram": {
+                "dataPoints": [{ "bucketCounts": [1, 2], "explicitBounds": [1.0, 2.0] }],
+                "aggregationTemporality": "AGGREGATION_TEMPORALITY_DELTA",
+            },
+        });
+        assert_eq!(
+            metric_rules(histogram),
+            found(&[(".histogram.dataPoints[0].bucketCounts", "bucket-count")])
+        );
+    }
+
+    #[test]
+    fn enums_must_be_in_range() {
+        assert_eq!(
+            log_rules(json!({ "severityNumber": "SEVERITY_NUMBER_LOUD" })),
+            found(&[(".severityNumber", "enum")])
+        );
+        assert_eq!(
+            span_rules(json!({ "kind": 6, "status": { "code": "STATUS_CODE_MAYBE" } })),
+            found(&[(".kind", "enum"), (".status.code", "enum")])
+        );
+        assert_eq!(
+            span_rules(json!({ "kind": "bogus" })),
+            found(&[(".kind", "enum")])
+        );
+        for temporality in [json!(3), json!(-1), json!("DELTA"), json!(true)] {
+            let sum = json!({
+                "name": "m",
+                "sum": { "dataPoints": [{ "asInt": 1 }], "aggregationTemporality": temporality },
+            });
+            assert_eq!(
+                metric_rules(sum),
+                found(&[(".aggregationTemporality", "enum")]),
+                "{temporality}"
+            );
+        }
+        // Every known value, named or numbered, is accepted.
+        for (i, kind) in SPAN_KINDS.iter().enumerate() {
+            assert_eq!(span_rules(json!({ "kind": kind })), []);
+            assert_eq!(span_rules(json!({ "kind": i })), []);
+        }
+    }
+}
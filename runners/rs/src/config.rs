@@ -0,0 +1,254 @@
+//! `--config runner.toml`: run-mode flags from a file.
+//!
+//! Keys are long flag names (`strict-input` or `strict_input`); values are
+//! strings, numbers, `true` for a bare flag, or arrays for repeatable flags:
+//!
+//! ```toml
+//! policies = ["base.json", "overrides/"]
+//! merge-strategy = "override"
+//! strict-input = true
+//! ```
+//!
+//! Precedence, highest first: command-line flags, `POLICY_RUNNER_<FLAG>`
+//! environment variables, the `--profile` (see [`profiles`]), the config
+//! file, built-in defaults. A file may name its own default `profile`. `--policies`
+//! has no variable of its own, since `POLICY_RUNNER_POLICIES` already holds
+//! policy JSON for `--policies env:`.
+//!
+//! The file is folded in by appending its flags to the command line for any
+//! option that neither the command line nor the environment set, so values go
+//! through exactly the same parsing as typed flags.
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::CommandFactory;
+use clap::parser::ValueSource;
+
+use crate::status::{self, Failure};
+use crate::{Cli, profiles};
+
+/// The config file at `path`, parsed.
+pub fn read(path: &Path) -> status::Result<toml::Table> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| Failure(format!("failed to read config {}: {e}", path.display())))?;
+    text.parse()
+        .map_err(|e| Failure(format!("failed to parse config {}: {e}", path.display())))
+}
+
+/// `argv` with flags from `--profile` and `--config` appended. Subcommands
+/// take neither.
+pub fn apply(mut argv: Vec<OsString>) -> status::Result<Vec<OsString>> {
+    let command = Cli::command();
+    // Appending flags can't mend a command line clap rejects, so it's passed
+    // through for `run`'s own parse to report.
+    let Ok(matches) = command.clone().try_get_matches_from(&argv) else {
+        return Ok(argv);
+    };
+    if matches.subcommand().is_some() {
+        return Ok(argv);
+    }
+    let path = matches.get_one::<PathBuf>("config");
+    let table = path.map(|path| read(path)).transpose()?.unwrap_or_default();
+    // Ids of the options set so far, which nothing later may set again.
+    let mut set: HashSet<String> = command
+        .get_arguments()
+        .map(|arg| arg.get_id().as_str())
+        .filter(|id| {
+            matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        })
+        .map(str::to_string)
+        .collect();
+    let name = matches
+        .get_one::<String>("profile")
+        .map(String::as_str)
+        .or_else(|| table.get("profile").and_then(|v| v.as_str()));
+    if let Some(name) = name {
+        let source = path.map(|p| p.display().to_string()).unwrap_or_default();
+        let profiles = profiles::all(&table, &source).map_err(Failure)?;
+        let Some(profile) = profiles.iter().find(|p| p.name == name) else {
+            return Err(Failure(format!(
+                "unknown profile: {name} (see `runner-rs profiles`)"
+            )));
+        };
+        let what = format!("profile {name}");
+        append(&command, &mut argv, &profile.flags, &mut set, &what)?;
+    }
+    append(&command, &mut argv, &table, &mut set, "config")?;
+    Ok(argv)
+}
+
+/// Append `table`'s flags to `argv`, except those `set` already holds or
+/// that conflict with one it holds, and add them to it. `what` names the
+/// table in errors.
+fn append(
+    command: &clap::Command,
+    argv: &mut Vec<OsString>,
+    table: &toml::Table,
+    set: &mut HashSet<String>,
+    what: &str,
+) -> status::Result {
+    let mut added = Vec::new();
+    for (key, value) in table {
+        let id = key.replace('-', "_");
+        if id == "profiles" || (id == "profile" && what == "config") {
+            continue;
+        }
+        let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id.as_str() && id != "config" && id != "profile")
+        else {
+            return Err(Failure(format!("unknown {what} key: {key}")));
+        };
+        let Some(long) = arg.get_long() else {
+            return Err(Failure(format!("unknown {what} key: {key}")));
+        };
+        if set.contains(&id) || conflicts(command, arg, set) {
+            continue;
+        }
+        let values = match value {
+            toml::Value::Array(items) => items.iter().collect(),
+            _ => vec![value],
+        };
+        for value in values {
+            match value {
+                toml::Value::Boolean(true) => argv.push(format!("--{long}").into()),
+                toml::Value::Boolean(false) => {}
+                toml::Value::String(s) => argv.push(format!("--{long}={s}").into()),
+                toml::Value::Integer(n) => argv.push(format!("--{long}={n}").into()),
+                toml::Value::Float(n) => argv.push(format!("--{long}={n}").into()),
+                _ => {
+                    return Err(Failure(format!(
+                        "{what} key {key}: unsupported value {value}"
+                    )));
+                }
+            }
+        }
+        added.push(id);
+    }
+    set.extend(added);
+    Ok(())
+}
+
+/// Whether `arg` conflicts with an option in `set`, whichever of the two
+/// declares it.
+fn conflicts(command: &clap::Command, arg: &clap::Arg, set: &HashSet<String>) -> bool {
+    let declares = |a: &clap::Arg, b: &clap::Arg| {
+        command
+            .get_arg_conflicts_with(a)
+            .iter()
+            .any(|c| c.get_id() == b.get_id())
+    };
+    command
+        .get_arguments()
+        .filter(|other| set.contains(other.get_id().as_str()))
+        .any(|other| declares(arg, other) || declares(other, arg))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use clap::Parser;
+
+    use super::*;
+
+    /// Held while a test sets `POLICY_RUNNER_*` variables, which every
+    /// parse in the process reads. Only variables no other test's flags
+    /// depend on are set.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// The run arguments for `flags`, with `config` as the config file and
+    /// `env` set for the duration.
+    fn parse(flags: &[&str], config: &str, env: &[(&str, &str)]) -> status::Result<crate::Args> {
+        let _guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.path(), config).unwrap();
+        let mut argv: Vec<OsString> = vec!["runner-rs".into(), "--config".into()];
+        argv.push(file.path().into());
+        argv.extend(flags.iter().map(OsString::from));
+        // SAFETY: `ENV` serializes the tests that set variables.
+        env.iter()
+            .for_each(|(k, v)| unsafe { std::env::set_var(k, v) });
+        let parsed = apply(argv).map(|argv| Cli::try_parse_from(argv).unwrap().args);
+        env.iter()
+            .for_each(|(k, _)| unsafe { std::env::remove_var(k) });
+        parsed
+    }
+
+    fn args(flags: &[&str], config: &str, env: &[(&str, &str)]) -> crate::Args {
+        parse(flags, config, env).unwrap_or_else(|Failure(e)| panic!("{e}"))
+    }
+
+    const MAX_BYTES_VAR: &str = "POLICY_RUNNER_MAX_INPUT_BYTES";
+    const STRIP_VAR: &str = "POLICY_RUNNER_STRIP_ATTRIBUTES";
+
+    #[test]
+    fn command_line_beats_environment_beats_config() {
+        let config = "max-input-bytes = 1";
+        let parsed = args(&[], config, &[]);
+        assert_eq!(parsed.max_input_bytes, Some(1));
+        let parsed = args(&[], config, &[(MAX_BYTES_VAR, "2")]);
+        assert_eq!(parsed.max_input_bytes, Some(2));
+        let flags = ["--max-input-bytes", "3"];
+        let parsed = args(&flags, config, &[(MAX_BYTES_VAR, "2")]);
+        assert_eq!(parsed.max_input_bytes, Some(3));
+    }
+
+    #[test]
+    fn repeatable_flags_take_arrays_and_comma_lists() {
+        let config = r#"strip_attributes = ["a.*", "b"]"#;
+        let parsed = args(&[], config, &[]);
+        assert_eq!(parsed.strip_attributes, ["a.*", "b"]);
+        // A variable replaces the config's list rather than adding to it.
+        let parsed = args(&[], config, &[(STRIP_VAR, "x,y.*")]);
+        assert_eq!(parsed.strip_attributes, ["x", "y.*"]);
+        let flags = ["--strip-attributes", "c", "--strip-attributes", "d,e"];
+        let parsed = args(&flags, config, &[(STRIP_VAR, "x")]);
+        assert_eq!(parsed.strip_attributes, ["c", "d", "e"]);
+    }
+
+    #[test]
+    fn bare_flags_take_booleans() {
+        let parsed = args(&[], "strict-input = true\nquiet = false", &[]);
+        assert!(parsed.strict_input);
+        assert!(!parsed.quiet);
+    }
+
+    #[test]
+    fn unknown_and_unsupported_keys_are_errors() {
+        let error = |config: &str| match parse(&[], config, &[]) {
+            Err(Failure(message)) => message,
+            Ok(_) => panic!("{config:?} should be rejected"),
+        };
+        assert_eq!(
+            error("no-such-flag = 1"),
+            "unknown config key: no-such-flag"
+        );
+        assert_eq!(
+            error("config = \"other.toml\""),
+            "unknown config key: config"
+        );
+        assert_eq!(
+            error("quiet = { nested = true }"),
+            "config key quiet: unsupported value { nested = true }"
+        );
+    }
+
+    #[test]
+    fn unreadable_config_names_the_file() {
+        let argv = ["runner-rs", "--config", "/nonexistent/runner.toml"];
+        let Err(Failure(message)) = apply(argv.iter().map(OsString::from).collect()) else {
+            panic!("a missing config file should be an error");
+        };
+        assert!(
+            message.starts_with("failed to read config /nonexistent/runner.toml: "),
+            "{message}"
+        );
+    }
+}
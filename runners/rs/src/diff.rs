@@ -0,0 +1,252 @@
+//! Field-level differences between two serialized records, for showing what
+//! a policy's transforms did to a record.
+//!
+//! Records are compared in their serialized JSON form. Object keys come out
+//! sorted, so the changes for a given pair of records are always listed in
+//! the same order.
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Longest string kept in a [`Change`] before it is cut short.
+const MAX_VALUE_CHARS: usize = 256;
+
+/// One path whose value differs. `before` is absent for an added path and
+/// `after` for a removed one, or for one whose new value is a redaction
+/// replacement, which is marked `redacted` instead.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Change {
+    /// Dotted JSON path, with `[i]` for array elements, e.g.
+    /// `attributes[2].value.stringValue`.
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub redacted: bool,
+}
+
+/// The changes that turn `before` into `after`. Objects are compared key by
+/// key and arrays element by element; any other differing value is one
+/// change. A new value equal to one of `redactions` is left out, so the diff
+/// doesn't repeat the replacement on every redacted path.
+pub fn diff(before: &Value, after: &Value, redactions: &[String]) -> Vec<Change> {
+    let mut differ = Differ {
+        redactions,
+        changes: Vec::new(),
+    };
+    differ.walk(String::new(), Some(before), Some(after));
+    differ.changes
+}
+
+struct Differ<'a> {
+    redactions: &'a [String],
+    changes: Vec<Change>,
+}
+
+impl Differ<'_> {
+    fn walk(&mut self, path: String, before: Option<&Value>, after: Option<&Value>) {
+        match (before, after) {
+            (Some(Value::Object(b)), Some(Value::Object(a))) => {
+                let mut keys: Vec<&String> = b.keys().chain(a.keys()).collect();
+                keys.sort();
+                keys.dedup();
+                for key in keys {
+                    let child = if path.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{path}.{key}")
+                    };
+                    self.walk(child, b.get(key), a.get(key));
+                }
+            }
+            (Some(Value::Array(b)), Some(Value::Array(a))) => {
+                for i in 0..b.len().max(a.len()) {
+                    self.walk(format!("{path}[{i}]"), b.get(i), a.get(i));
+                }
+            }
+            (b, a) if b == a => {}
+            (b, a) => {
+                let redacted = a
+                    .and_then(Value::as_str)
+                    .is_some_and(|a| self.redactions.iter().any(|r| r == a));
+                self.changes.push(Change {
+                    path,
+                    before: b.map(capped),
+                    after: if redacted { None } else { a.map(capped) },
+                    redacted,
+                });
+            }
+        }
+    }
+}
+
+/// `value`, with long strings (or long serialized arrays and objects) cut to
+/// [`MAX_VALUE_CHARS`] and marked with a trailing `…`.
+fn capped(value: &Value) -> Value {
+    let text = match value {
+        Value::String(s) => s.clone(),
+        Value::Array(_) | Value::Object(_) => value.to_string(),
+        _ => return value.clone(),
+    };
+    if text.chars().count() <= MAX_VALUE_CHARS {
+        return value.clone();
+    }
+    let mut cut: String = text.chars().take(MAX_VALUE_CHARS).collect();
+    cut.push('…');
+    Value::String(cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn change(path: &str, before: Option<Value>, after: Option<Value>) -> Change {
+        Change {
+            path: path.to_string(),
+            before,
+            after,
+            redacted: false,
+        }
+    }
+
+    #[test]
+    fn identical_records_have_no_changes() {
+        let record = json!({ "body": { "stringValue": "hi" }, "attributes": [] });
+        assert_eq!(diff(&record, &record, &[]), []);
+    }
+
+    #[test]
+    fn added_removed_and_changed_paths_in_key_order() {
+        let before = json!({
+            "severityText": "INFO",
+            "body": { "stringValue": "hi" },
+            "attributes": [{ "key": "a", "value": { "intValue": "1" } }],
+        });
+        let after = json!({
+            "severityText": "WARN",
+            "attributes": [
+                { "key": "a", "value": { "intValue": "2" } },
+                { "key": "b", "value": { "boolValue": true } },
+            ],
+        });
+        assert_eq!(
+            diff(&before, &after, &[]),
+            [
+                change(
+                    "attributes[0].value.intValue",
+                    Some(json!("1")),
+                    Some(json!("2"))
+                ),
+                change(
+                    "attributes[1]",
+                    None,
+                    Some(json!({ "key": "b", "value": { "boolValue": true } }))
+                ),
+                change("body", Some(json!({ "stringValue": "hi" })), None),
+                change("severityText", Some(json!("INFO")), Some(json!("WARN"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_changed_type_is_one_change() {
+        let before = json!({ "body": { "stringValue": "1" } });
+        let after = json!({ "body": [1] });
+        assert_eq!(
+            diff(&before, &after, &[]),
+            [change(
+                "body",
+                Some(json!({ "stringValue": "1" })),
+                Some(json!([1]))
+            )]
+        );
+    }
+
+    #[test]
+    fn redacted_values_are_elided() {
+        let before = json!({ "attributes": [
+            { "key": "token", "value": { "stringValue": "s3cret" } },
+            { "key": "user", "value": { "stringValue": "ann" } },
+        ] });
+        let after = json!({ "attributes": [
+            { "key": "token", "value": { "stringValue": "[REDACTED]" } },
+            { "key": "user", "value": { "stringValue": "bob" } },
+        ] });
+        let changes = diff(&before, &after, &["[REDACTED]".to_string()]);
+        assert_eq!(
+            changes,
+            [
+                Change {
+                    redacted: true,
+                    ..change(
+                        "attributes[0].value.stringValue",
+                        Some(json!("s3cret")),
+                        None
+                    )
+                },
+                change(
+                    "attributes[1].value.stringValue",
+                    Some(json!("ann")),
+                    Some(json!("bob"))
+                ),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&changes[0]).unwrap(),
+            json!({
+                "path": "attributes[0].value.stringValue",
+                "before": "s3cret",
+                "redacted": true,
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(&changes[1]).unwrap(),
+            json!({
+                "path": "attributes[1].value.stringValue",
+                "before": "ann",
+                "after": "bob",
+            })
+        );
+    }
+
+    #[test]
+    fn values_are_capped() {
+        let long = "é".repeat(MAX_VALUE_CHARS + 1);
+        let before = json!({ "body": long, "list": [long.clone()], "short": "x" });
+        let after = json!({ "list": [], "short": "y".repeat(MAX_VALUE_CHARS) });
+        let changes = diff(&before, &after, &[]);
+        let cut = format!("{}…", "é".repeat(MAX_VALUE_CHARS));
+        assert_eq!(changes[0], change("body", Some(json!(cut)), None));
+        assert_eq!(changes[1], change("list[0]", Some(json!(cut)), None));
+        assert_eq!(
+            changes[2],
+            change(
+                "short",
+                Some(json!("x")),
+                Some(json!("y".repeat(MAX_VALUE_CHARS)))
+            )
+        );
+    }
+
+    #[test]
+    fn composite_values_are_capped_as_json() {
+        let before = json!({});
+        let after = json!({ "list": vec![1; MAX_VALUE_CHARS] });
+        let [
+            Change {
+                after: Some(Value::String(cut)),
+                ..
+            },
+        ] = &diff(&before, &after, &[])[..]
+        else {
+            panic!("expected one capped addition");
+        };
+        assert_eq!(cut.chars().count(), MAX_VALUE_CHARS + 1);
+        assert!(cut.starts_with("[1,1,"));
+        assert!(cut.ends_with('…'));
+    }
+}
@@ -0,0 +1,81 @@
+//! `--drops-sidecar`: which records evaluation removed, written beside the
+//! output as `<output>.drops.json`.
+//!
+//! One entry per array records were removed from, in input order, giving its
+//! JSON path in the input, how many went and their indexes in it:
+//!
+//! ```json
+//! [{ "path": "resourceLogs[0].scopeLogs[1].logRecords", "dropped": 2, "indexes": [0, 3] }]
+//! ```
+//!
+//! Under `--metric-granularity datapoint` the arrays are each metric's
+//! datapoints (`...metrics[3].dataPoints`), so counts are datapoints, as
+//! OTLP's `rejected_data_points` counts them. Records `--sample-input` left
+//! unevaluated aren't listed.
+
+use std::path::{Path, PathBuf};
+
+use policy_rs::EvaluateResult;
+use runner_rs::{RecordResult, keeps};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct Dropped {
+    path: String,
+    dropped: usize,
+    indexes: Vec<usize>,
+}
+
+/// `out.json` becomes `out.json.drops.json`.
+pub fn sidecar_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".drops.json");
+    PathBuf::from(path)
+}
+
+/// The dropped records among `results` for a `signal` payload, grouped by
+/// the array they were removed from.
+pub fn dropped(signal: &str, results: &[RecordResult]) -> Vec<Dropped> {
+    let (resources, scopes, records) = match signal {
+        "log" => ("resourceLogs", "scopeLogs", "logRecords"),
+        "metric" => ("resourceMetrics", "scopeMetrics", "metrics"),
+        "trace" => ("resourceSpans", "scopeSpans", "spans"),
+        _ => ("resourceProfiles", "scopeProfiles", "profiles"),
+    };
+    // Only drop decisions remove a metric; sampling doesn't apply to them.
+    let removed = |result: &EvaluateResult| match signal {
+        "metric" => matches!(result, EvaluateResult::Drop { .. }),
+        _ => !keeps(result),
+    };
+    let mut out: Vec<Dropped> = Vec::new();
+    for r in results.iter().filter(|r| removed(&r.result)) {
+        let (path, index) = match r.datapoint {
+            Some(datapoint) => (
+                format!(
+                    "{resources}[{}].{scopes}[{}].{records}[{}].dataPoints",
+                    r.resource, r.scope, r.record
+                ),
+                datapoint,
+            ),
+            None => (
+                format!(
+                    "{resources}[{}].{scopes}[{}].{records}",
+                    r.resource, r.scope
+                ),
+                r.record,
+            ),
+        };
+        match out.last_mut() {
+            Some(last) if last.path == path => {
+                last.dropped += 1;
+                last.indexes.push(index);
+            }
+            _ => out.push(Dropped {
+                path,
+                dropped: 1,
+                indexes: vec![index],
+            }),
+        }
+    }
+    out
+}
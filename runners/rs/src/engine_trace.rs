@@ -0,0 +1,145 @@
+//! What the engine asks of each record, for finding where two `policy_rs`
+//! versions part ways.
+//!
+//! [`Traced`] wraps a record context and logs every lookup and write the
+//! engine makes through it, with the answer, in order. Requests are written
+//! compactly as `[call, selector, answer]`, the selector as
+//! [`selector_display`](crate::selector_display) renders it:
+//!
+//! ```json
+//! ["get_field", "log_field:severity_text", "DEBUG"]
+//! ["field_exists", "log_attribute[\"user\"]", false]
+//! ["get_typed_value", "log_attribute[\"status\"]", {"int": 500}]
+//! ```
+//!
+//! Absent fields answer `null`. Writes log what was written: `set_field`
+//! the value, `delete_field` whether anything was there, and `move_field`
+//! the destination selector.
+//!
+//! [`Traced`] also counts lookups into [`selector_stats`](crate::selector_stats)
+//! when given somewhere to count them.
+
+use std::borrow::Cow;
+use std::sync::{Mutex, PoisonError};
+
+use policy_rs::engine::{Signal, TypedValue};
+use policy_rs::{Matchable, Transformable};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::selector_display::Selector;
+use crate::selector_stats::{self, Timer};
+
+/// One request and its answer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Request(pub String, pub String, pub Value);
+
+/// Requests made while evaluating the current record.
+pub type Log = Mutex<Vec<Request>>;
+
+/// Take the requests logged so far.
+pub fn take(log: &Log) -> Vec<Request> {
+    std::mem::take(&mut *log.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// A record context whose requests are logged to `log`, and whose lookups
+/// are counted in `stats`, when there are those.
+pub struct Traced<'t, T> {
+    pub inner: T,
+    pub log: Option<&'t Log>,
+    pub stats: Option<&'t selector_stats::Stats>,
+}
+
+impl<T> Traced<'_, T> {
+    fn timer(&self) -> Option<Timer> {
+        self.stats.map(|_| Timer::start())
+    }
+
+    fn count(&self, field: &impl Selector, found: bool, timer: Option<Timer>) {
+        if let Some(stats) = self.stats {
+            stats.record(field, found, timer.and_then(|t| t.elapsed()));
+        }
+    }
+
+    fn push(&self, call: &str, field: &impl Selector, answer: impl FnOnce() -> Value) {
+        if let Some(log) = self.log {
+            let request = Request(call.to_string(), field.render(), answer());
+            log.lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(request);
+        }
+    }
+}
+
+fn typed(value: &TypedValue<'_>) -> Value {
+    match value {
+        TypedValue::String(s) => json!({ "string": s }),
+        TypedValue::Bool(b) => json!({ "bool": b }),
+        TypedValue::Int(i) => json!({ "int": i }),
+        TypedValue::Double(d) => json!({ "double": d.to_string() }),
+        TypedValue::Bytes(b) => json!({ "bytes": hex(b) }),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+impl<T: Matchable> Matchable for Traced<'_, T>
+where
+    <T::Signal as Signal>::FieldSelector: Selector,
+{
+    type Signal = T::Signal;
+
+    fn get_field(&self, field: &<T::Signal as Signal>::FieldSelector) -> Option<Cow<'_, str>> {
+        let timer = self.timer();
+        let value = self.inner.get_field(field);
+        self.count(field, value.is_some(), timer);
+        self.push("get_field", field, || json!(value.as_deref()));
+        value
+    }
+
+    fn field_exists(&self, field: &<T::Signal as Signal>::FieldSelector) -> bool {
+        let exists = self.inner.field_exists(field);
+        self.count(field, exists, None);
+        self.push("field_exists", field, || json!(exists));
+        exists
+    }
+
+    fn get_typed_value(
+        &self,
+        field: &<T::Signal as Signal>::FieldSelector,
+    ) -> Option<TypedValue<'_>> {
+        let value = self.inner.get_typed_value(field);
+        self.count(field, value.is_some(), None);
+        self.push("get_typed_value", field, || {
+            value.as_ref().map_or(Value::Null, typed)
+        });
+        value
+    }
+}
+
+impl<T: Transformable> Transformable for Traced<'_, T>
+where
+    <T::Signal as Signal>::FieldSelector: Selector,
+{
+    fn set_field(&mut self, field: &<T::Signal as Signal>::FieldSelector, value: &str) {
+        self.push("set_field", field, || json!(value));
+        self.inner.set_field(field, value);
+    }
+
+    fn delete_field(&mut self, field: &<T::Signal as Signal>::FieldSelector) -> bool {
+        let deleted = self.inner.delete_field(field);
+        self.push("delete_field", field, || json!(deleted));
+        deleted
+    }
+
+    fn move_field(
+        &mut self,
+        from: &<T::Signal as Signal>::FieldSelector,
+        to: &<T::Signal as Signal>::FieldSelector,
+    ) {
+        self.push("move_field", from, || json!(to.render()));
+        self.inner.move_field(from, to);
+    }
+}
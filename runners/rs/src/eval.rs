@@ -1,14 +1,16 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 
-use policy_rs::engine::TypedValue;
-use policy_rs::proto::tero::policy::v1::{LogField, MetricField, TraceField};
+use policy_rs::engine::{Signal, TypedValue};
+use policy_rs::proto::tero::policy::v1::{LogField, MetricField, SpanKind, TraceField};
 use policy_rs::{
     LogFieldSelector, LogSignal, Matchable, MetricFieldSelector, MetricSignal, TraceFieldSelector,
     TraceSignal, Transformable,
 };
-use serde::Deserialize;
 
 use crate::otel;
+use crate::semconv::{self, record_schema};
+use crate::severity;
 
 // ─── Context types ───────────────────────────────────────────────────
 
@@ -17,8 +19,12 @@ pub struct MetricContext<'a> {
     pub datapoint_attributes: &'a [otel::KeyValue],
     pub resource: Option<&'a otel::Resource>,
     pub scope: Option<&'a otel::InstrumentationScope>,
+    pub resource_index: &'a AttrIndex,
+    pub scope_index: &'a AttrIndex,
     pub resource_schema_url: &'a str,
     pub scope_schema_url: &'a str,
+    /// Attribute aliases to fall back on, when they're in force.
+    pub aliasing: Option<&'a semconv::Aliasing>,
 }
 
 // ─── Attribute helpers ───────────────────────────────────────────────
@@ -59,85 +65,119 @@ fn log_body_present(val: Option<&otel::AnyValue>) -> bool {
         || v.bytes_value.is_some()
 }
 
-fn find_attribute_path<'a>(attrs: &'a [otel::KeyValue], path: &[String]) -> Option<Cow<'a, str>> {
-    if path.is_empty() {
-        return None;
+/// Position of the first attribute for each key in a resource or scope
+/// attribute vector. Built once per envelope so every record evaluated under
+/// it resolves resource/scope selectors without rescanning the vector.
+/// Anything that mutates the indexed vector must call [`AttrIndex::rebuild`].
+#[derive(Default)]
+pub struct AttrIndex {
+    positions: HashMap<String, usize>,
+}
+
+impl AttrIndex {
+    pub fn new(attrs: &[otel::KeyValue]) -> Self {
+        let mut index = AttrIndex::default();
+        index.rebuild(attrs);
+        index
     }
-    for kv in attrs {
-        if kv.key != path[0] {
-            continue;
-        }
-        if path.len() == 1 {
-            return any_value_string(kv.value.as_ref());
-        }
-        // Traverse into nested kvlist
-        if let Some(ref val) = kv.value {
-            if let Some(ref kvlist) = val.kvlist_value {
-                if let Ok(nested) = serde_json::from_value::<KvlistValues>(kvlist.clone()) {
-                    return find_attribute_path_owned(&nested.values, &path[1..]);
-                }
-            }
+
+    pub fn rebuild(&mut self, attrs: &[otel::KeyValue]) {
+        self.positions.clear();
+        for (i, kv) in attrs.iter().enumerate() {
+            self.positions.entry(kv.key.clone()).or_insert(i);
         }
-        return None;
     }
-    None
 }
 
-#[derive(Deserialize)]
-struct KvlistValues {
-    values: Vec<otel::KeyValue>,
+/// The attribute a path's first segment names — via the envelope index when
+/// one is supplied, otherwise by scanning. When the name is absent, any
+/// [`semconv`] alias it has under `schema_url` is tried in its place.
+fn first_attr<'a>(
+    attrs: &'a [otel::KeyValue],
+    index: Option<&AttrIndex>,
+    aliasing: Option<&semconv::Aliasing>,
+    schema_url: &str,
+    path: &[String],
+) -> Option<&'a otel::KeyValue> {
+    let key = path.first()?;
+    let lookup = |key: &str| match index {
+        Some(index) => index.positions.get(key).and_then(|&i| attrs.get(i)),
+        None => attrs.iter().find(|kv| kv.key == key),
+    };
+    lookup(key).or_else(|| aliasing?.find(schema_url, key, lookup))
 }
 
-fn find_attribute_path_owned(attrs: &[otel::KeyValue], path: &[String]) -> Option<Cow<'static, str>> {
-    if path.is_empty() {
-        return None;
+fn find_attribute_path<'a>(
+    attrs: &'a [otel::KeyValue],
+    index: Option<&AttrIndex>,
+    aliasing: Option<&semconv::Aliasing>,
+    schema_url: &str,
+    path: &[String],
+) -> Option<Cow<'a, str>> {
+    let kv = first_attr(attrs, index, aliasing, schema_url, path)?;
+    if path.len() == 1 {
+        return any_value_string(kv.value.as_ref());
     }
-    for kv in attrs {
-        if kv.key != path[0] {
-            continue;
-        }
-        if path.len() == 1 {
-            if let Some(ref val) = kv.value {
-                if let Some(ref s) = val.string_value {
-                    if !s.is_empty() {
-                        return Some(Cow::Owned(s.clone()));
-                    }
-                }
-            }
-            return None;
-        }
-        if let Some(ref val) = kv.value {
-            if let Some(ref kvlist) = val.kvlist_value {
-                if let Ok(nested) = serde_json::from_value::<KvlistValues>(kvlist.clone()) {
-                    return find_attribute_path_owned(&nested.values, &path[1..]);
-                }
-            }
-        }
-        return None;
+    kvlist_path(kv.value.as_ref()?.kvlist_value.as_ref()?, &path[1..])
+        .and_then(|v| v.get("stringValue")?.as_str())
+        .filter(|s| !s.is_empty())
+        .map(Cow::Borrowed)
+}
+
+/// Walk a raw `kvlistValue` (`{"values": [{"key", "value"}, ...]}`) down
+/// `path`, returning the AnyValue JSON at the end. Borrows straight out of the
+/// parsed input, so nested lookups cost no allocation.
+fn kvlist_path<'a>(
+    kvlist: &'a serde_json::Value,
+    path: &[String],
+) -> Option<&'a serde_json::Value> {
+    let (key, rest) = path.split_first()?;
+    let value = kvlist
+        .get("values")?
+        .as_array()?
+        .iter()
+        .find(|kv| kv.get("key").and_then(|k| k.as_str()) == Some(key.as_str()))?
+        .get("value")?;
+    if rest.is_empty() {
+        return Some(value);
     }
-    None
+    kvlist_path(value.get("kvlistValue")?, rest)
+}
+
+/// JSON counterpart of [`any_value_present`] for values reached through a
+/// nested kvlist.
+fn json_any_value_present(v: &serde_json::Value) -> bool {
+    const VARIANTS: [&str; 7] = [
+        "stringValue",
+        "boolValue",
+        "intValue",
+        "doubleValue",
+        "arrayValue",
+        "kvlistValue",
+        "bytesValue",
+    ];
+    VARIANTS
+        .iter()
+        .any(|k| v.get(k).is_some_and(|x| !x.is_null()))
 }
 
 /// Resolve an attribute path to its raw AnyValue, preserving the value's
 /// native type for typed (equals/gt/gte/lt/lte) matching. Only flat paths are
-/// supported — nested kvlist values are stored as raw JSON and aren't borrowed.
+/// supported — nested kvlist values are raw JSON with no typed AnyValue to
+/// hand back.
 fn find_attribute_value<'a>(
     attrs: &'a [otel::KeyValue],
+    index: Option<&AttrIndex>,
+    aliasing: Option<&semconv::Aliasing>,
+    schema_url: &str,
     path: &[String],
 ) -> Option<&'a otel::AnyValue> {
-    if path.is_empty() {
-        return None;
-    }
-    for kv in attrs {
-        if kv.key != path[0] {
-            continue;
-        }
-        if path.len() == 1 {
-            return kv.value.as_ref();
-        }
+    if path.len() != 1 {
         return None;
     }
-    None
+    first_attr(attrs, index, aliasing, schema_url, path)?
+        .value
+        .as_ref()
 }
 
 /// Map an OTLP AnyValue to the engine's TypedValue so non-string matchers see
@@ -179,26 +219,24 @@ fn scope_attrs(scope: Option<&otel::InstrumentationScope>) -> &[otel::KeyValue]
 /// regardless of whether that value can be expressed as a string. This is
 /// the primitive used to power `exists: true` matchers, in contrast to
 /// `find_attribute_path` which only returns Some for string-typed values.
-fn attribute_exists_path(attrs: &[otel::KeyValue], path: &[String]) -> bool {
-    if path.is_empty() {
-        return false;
-    }
-    for kv in attrs {
-        if kv.key != path[0] {
-            continue;
-        }
-        if path.len() == 1 {
-            return any_value_present(kv.value.as_ref());
-        }
-        if let Some(ref val) = kv.value
-            && let Some(ref kvlist) = val.kvlist_value
-            && let Ok(nested) = serde_json::from_value::<KvlistValues>(kvlist.clone())
-        {
-            return attribute_exists_path(&nested.values, &path[1..]);
-        }
+fn attribute_exists_path(
+    attrs: &[otel::KeyValue],
+    index: Option<&AttrIndex>,
+    aliasing: Option<&semconv::Aliasing>,
+    schema_url: &str,
+    path: &[String],
+) -> bool {
+    let Some(kv) = first_attr(attrs, index, aliasing, schema_url, path) else {
         return false;
+    };
+    if path.len() == 1 {
+        return any_value_present(kv.value.as_ref());
     }
-    false
+    kv.value
+        .as_ref()
+        .and_then(|v| v.kvlist_value.as_ref())
+        .and_then(|kvlist| kvlist_path(kvlist, &path[1..]))
+        .is_some_and(json_any_value_present)
 }
 
 /// Remove and return the first KeyValue matching `path[0]`. Only operates on
@@ -228,8 +266,14 @@ pub struct MutLogContext<'a> {
     pub record: &'a mut otel::LogRecord,
     pub resource: Option<&'a mut otel::Resource>,
     pub scope: Option<&'a mut otel::InstrumentationScope>,
+    pub resource_index: &'a mut AttrIndex,
+    pub scope_index: &'a mut AttrIndex,
     pub resource_schema_url: &'a str,
     pub scope_schema_url: &'a str,
+    /// Attribute aliases to fall back on, when they're in force.
+    pub aliasing: Option<&'a semconv::Aliasing>,
+    /// The table `severity_text` matches through, when it's normalized.
+    pub severity: Option<&'a severity::Table>,
 }
 
 impl Matchable for MutLogContext<'_> {
@@ -239,7 +283,10 @@ impl Matchable for MutLogContext<'_> {
         match field {
             LogFieldSelector::Simple(f) => match f {
                 LogField::Body => any_value_string(self.record.body.as_ref()),
-                LogField::SeverityText => non_empty(&self.record.severity_text),
+                LogField::SeverityText => non_empty(severity::normalize(
+                    self.severity,
+                    &self.record.severity_text,
+                )),
                 LogField::TraceId => non_empty(&self.record.trace_id),
                 LogField::SpanId => non_empty(&self.record.span_id),
                 LogField::EventName => non_empty(&self.record.event_name),
@@ -247,27 +294,33 @@ impl Matchable for MutLogContext<'_> {
                 LogField::ScopeSchemaUrl => non_empty(self.scope_schema_url),
                 _ => None,
             },
-            LogFieldSelector::LogAttribute(path) => {
-                find_attribute_path(&self.record.attributes, path)
-            }
-            LogFieldSelector::ResourceAttribute(path) => {
-                find_attribute_path(
-                    self.resource
-                        .as_ref()
-                        .map(|r| r.attributes.as_slice())
-                        .unwrap_or(&[]),
-                    path,
-                )
-            }
-            LogFieldSelector::ScopeAttribute(path) => {
-                find_attribute_path(
-                    self.scope
-                        .as_ref()
-                        .map(|s| s.attributes.as_slice())
-                        .unwrap_or(&[]),
-                    path,
-                )
-            }
+            LogFieldSelector::LogAttribute(path) => find_attribute_path(
+                &self.record.attributes,
+                None,
+                self.aliasing,
+                record_schema(self.scope_schema_url, self.resource_schema_url),
+                path,
+            ),
+            LogFieldSelector::ResourceAttribute(path) => find_attribute_path(
+                self.resource
+                    .as_ref()
+                    .map(|r| r.attributes.as_slice())
+                    .unwrap_or(&[]),
+                Some(self.resource_index),
+                self.aliasing,
+                self.resource_schema_url,
+                path,
+            ),
+            LogFieldSelector::ScopeAttribute(path) => find_attribute_path(
+                self.scope
+                    .as_ref()
+                    .map(|s| s.attributes.as_slice())
+                    .unwrap_or(&[]),
+                Some(self.scope_index),
+                self.aliasing,
+                self.scope_schema_url,
+                path,
+            ),
         }
     }
 
@@ -283,14 +336,21 @@ impl Matchable for MutLogContext<'_> {
                 LogField::ScopeSchemaUrl => !self.scope_schema_url.is_empty(),
                 _ => false,
             },
-            LogFieldSelector::LogAttribute(path) => {
-                attribute_exists_path(&self.record.attributes, path)
-            }
+            LogFieldSelector::LogAttribute(path) => attribute_exists_path(
+                &self.record.attributes,
+                None,
+                self.aliasing,
+                record_schema(self.scope_schema_url, self.resource_schema_url),
+                path,
+            ),
             LogFieldSelector::ResourceAttribute(path) => attribute_exists_path(
                 self.resource
                     .as_ref()
                     .map(|r| r.attributes.as_slice())
                     .unwrap_or(&[]),
+                Some(self.resource_index),
+                self.aliasing,
+                self.resource_schema_url,
                 path,
             ),
             LogFieldSelector::ScopeAttribute(path) => attribute_exists_path(
@@ -298,6 +358,9 @@ impl Matchable for MutLogContext<'_> {
                     .as_ref()
                     .map(|s| s.attributes.as_slice())
                     .unwrap_or(&[]),
+                Some(self.scope_index),
+                self.aliasing,
+                self.scope_schema_url,
                 path,
             ),
         }
@@ -319,9 +382,11 @@ impl Matchable for MutLogContext<'_> {
                     .as_deref()
                     .map(TypedValue::Bytes)
                     .or_else(|| non_empty(&self.record.span_id).map(TypedValue::String)),
-                LogField::SeverityText => {
-                    non_empty(&self.record.severity_text).map(TypedValue::String)
-                }
+                LogField::SeverityText => non_empty(severity::normalize(
+                    self.severity,
+                    &self.record.severity_text,
+                ))
+                .map(TypedValue::String),
                 LogField::EventName => non_empty(&self.record.event_name).map(TypedValue::String),
                 LogField::ResourceSchemaUrl => {
                     non_empty(self.resource_schema_url).map(TypedValue::String)
@@ -331,14 +396,22 @@ impl Matchable for MutLogContext<'_> {
                 }
                 _ => None,
             },
-            LogFieldSelector::LogAttribute(path) => {
-                find_attribute_value(&self.record.attributes, path).and_then(any_value_typed)
-            }
+            LogFieldSelector::LogAttribute(path) => find_attribute_value(
+                &self.record.attributes,
+                None,
+                self.aliasing,
+                record_schema(self.scope_schema_url, self.resource_schema_url),
+                path,
+            )
+            .and_then(any_value_typed),
             LogFieldSelector::ResourceAttribute(path) => find_attribute_value(
                 self.resource
                     .as_ref()
                     .map(|r| r.attributes.as_slice())
                     .unwrap_or(&[]),
+                Some(self.resource_index),
+                self.aliasing,
+                self.resource_schema_url,
                 path,
             )
             .and_then(any_value_typed),
@@ -347,6 +420,9 @@ impl Matchable for MutLogContext<'_> {
                     .as_ref()
                     .map(|s| s.attributes.as_slice())
                     .unwrap_or(&[]),
+                Some(self.scope_index),
+                self.aliasing,
+                self.scope_schema_url,
                 path,
             )
             .and_then(any_value_typed),
@@ -354,8 +430,20 @@ impl Matchable for MutLogContext<'_> {
     }
 }
 
+impl MutLogContext<'_> {
+    fn reindex(&mut self) {
+        if let Some(r) = self.resource.as_deref() {
+            self.resource_index.rebuild(&r.attributes);
+        }
+        if let Some(s) = self.scope.as_deref() {
+            self.scope_index.rebuild(&s.attributes);
+        }
+    }
+}
+
 impl Transformable for MutLogContext<'_> {
     fn set_field(&mut self, field: &LogFieldSelector, value: &str) {
+        tracing::trace!(?field, value, "transform: set");
         match field {
             LogFieldSelector::Simple(f) => match f {
                 LogField::Body => {
@@ -376,17 +464,20 @@ impl Transformable for MutLogContext<'_> {
             LogFieldSelector::ResourceAttribute(path) => {
                 if let Some(ref mut r) = self.resource {
                     set_string_attr(&mut r.attributes, path, value);
+                    self.resource_index.rebuild(&r.attributes);
                 }
             }
             LogFieldSelector::ScopeAttribute(path) => {
                 if let Some(ref mut s) = self.scope {
                     set_string_attr(&mut s.attributes, path, value);
+                    self.scope_index.rebuild(&s.attributes);
                 }
             }
         }
     }
 
     fn delete_field(&mut self, field: &LogFieldSelector) -> bool {
+        tracing::trace!(?field, "transform: delete");
         match field {
             LogFieldSelector::Simple(f) => match f {
                 LogField::Body => {
@@ -417,20 +508,33 @@ impl Transformable for MutLogContext<'_> {
                 _ => false,
             },
             LogFieldSelector::LogAttribute(path) => remove_attr(&mut self.record.attributes, path),
-            LogFieldSelector::ResourceAttribute(path) => self
-                .resource
-                .as_deref_mut()
-                .map(|r| remove_attr(&mut r.attributes, path))
-                .unwrap_or(false),
-            LogFieldSelector::ScopeAttribute(path) => self
-                .scope
-                .as_deref_mut()
-                .map(|s| remove_attr(&mut s.attributes, path))
-                .unwrap_or(false),
+            LogFieldSelector::ResourceAttribute(path) => {
+                let index = &mut *self.resource_index;
+                self.resource
+                    .as_deref_mut()
+                    .map(|r| {
+                        let hit = remove_attr(&mut r.attributes, path);
+                        index.rebuild(&r.attributes);
+                        hit
+                    })
+                    .unwrap_or(false)
+            }
+            LogFieldSelector::ScopeAttribute(path) => {
+                let index = &mut *self.scope_index;
+                self.scope
+                    .as_deref_mut()
+                    .map(|s| {
+                        let hit = remove_attr(&mut s.attributes, path);
+                        index.rebuild(&s.attributes);
+                        hit
+                    })
+                    .unwrap_or(false)
+            }
         }
     }
 
     fn move_field(&mut self, from: &LogFieldSelector, to: &LogFieldSelector) {
+        tracing::trace!(?from, ?to, "transform: move");
         // Engine guarantees `from` exists and that upsert preconditions on
         // `to` are satisfied. Remove the underlying KeyValue (preserving the
         // OTel value type), then re-insert it under `to`'s key in `to`'s
@@ -450,6 +554,9 @@ impl Transformable for MutLogContext<'_> {
                 .and_then(|s| remove_attr_kv(&mut s.attributes, path)),
             _ => None,
         };
+        // Removal shifts positions, so both envelope indexes are refreshed
+        // before `to` is resolved.
+        self.reindex();
         let Some(mut kv) = source_kv else {
             return;
         };
@@ -472,12 +579,14 @@ impl Transformable for MutLogContext<'_> {
                 if let Some(ref mut r) = self.resource {
                     r.attributes.retain(|x| x.key != key);
                     r.attributes.push(kv);
+                    self.resource_index.rebuild(&r.attributes);
                 }
             }
             LogFieldSelector::ScopeAttribute(_) => {
                 if let Some(ref mut s) = self.scope {
                     s.attributes.retain(|x| x.key != key);
                     s.attributes.push(kv);
+                    self.scope_index.rebuild(&s.attributes);
                 }
             }
             _ => {}
@@ -498,9 +607,14 @@ fn remove_attr(attrs: &mut Vec<otel::KeyValue>, path: &[String]) -> bool {
 /// Set or overwrite an attribute value as a string. Used by the engine for
 /// add/redact dispatch — both paths land in a string-typed value.
 fn set_string_attr(attrs: &mut Vec<otel::KeyValue>, path: &[String], value: &str) {
-    let Some(key) = attr_path(path) else {
-        return;
-    };
+    if let Some(key) = attr_path(path) {
+        set_attr(attrs, key, value);
+    }
+}
+
+/// Set `key` to the string `value`: its first occurrence is overwritten, or
+/// it is added.
+pub fn set_attr(attrs: &mut Vec<otel::KeyValue>, key: &str, value: &str) {
     if let Some(kv) = attrs.iter_mut().find(|kv| kv.key == key) {
         kv.value = Some(otel::AnyValue {
             string_value: Some(value.to_string()),
@@ -528,9 +642,7 @@ impl Matchable for MetricContext<'_> {
                 MetricField::Name => non_empty(&self.metric.name),
                 MetricField::Description => non_empty(&self.metric.description),
                 MetricField::Unit => non_empty(&self.metric.unit),
-                MetricField::ScopeName => {
-                    self.scope.as_ref().and_then(|s| non_empty(&s.name))
-                }
+                MetricField::ScopeName => self.scope.as_ref().and_then(|s| non_empty(&s.name)),
                 MetricField::ScopeVersion => {
                     self.scope.as_ref().and_then(|s| non_empty(&s.version))
                 }
@@ -538,15 +650,27 @@ impl Matchable for MetricContext<'_> {
                 MetricField::ScopeSchemaUrl => non_empty(self.scope_schema_url),
                 _ => None,
             },
-            MetricFieldSelector::DatapointAttribute(path) => {
-                find_attribute_path(self.datapoint_attributes, path)
-            }
-            MetricFieldSelector::ResourceAttribute(path) => {
-                find_attribute_path(resource_attrs(self.resource), path)
-            }
-            MetricFieldSelector::ScopeAttribute(path) => {
-                find_attribute_path(scope_attrs(self.scope), path)
-            }
+            MetricFieldSelector::DatapointAttribute(path) => find_attribute_path(
+                self.datapoint_attributes,
+                None,
+                self.aliasing,
+                record_schema(self.scope_schema_url, self.resource_schema_url),
+                path,
+            ),
+            MetricFieldSelector::ResourceAttribute(path) => find_attribute_path(
+                resource_attrs(self.resource),
+                Some(self.resource_index),
+                self.aliasing,
+                self.resource_schema_url,
+                path,
+            ),
+            MetricFieldSelector::ScopeAttribute(path) => find_attribute_path(
+                scope_attrs(self.scope),
+                Some(self.scope_index),
+                self.aliasing,
+                self.scope_schema_url,
+                path,
+            ),
             MetricFieldSelector::Type => {
                 let data = self.metric.data.as_ref()?;
                 Some(Cow::Borrowed(data.metric_type()))
@@ -560,15 +684,27 @@ impl Matchable for MetricContext<'_> {
 
     fn field_exists(&self, field: &MetricFieldSelector) -> bool {
         match field {
-            MetricFieldSelector::DatapointAttribute(path) => {
-                attribute_exists_path(self.datapoint_attributes, path)
-            }
-            MetricFieldSelector::ResourceAttribute(path) => {
-                attribute_exists_path(resource_attrs(self.resource), path)
-            }
-            MetricFieldSelector::ScopeAttribute(path) => {
-                attribute_exists_path(scope_attrs(self.scope), path)
-            }
+            MetricFieldSelector::DatapointAttribute(path) => attribute_exists_path(
+                self.datapoint_attributes,
+                None,
+                self.aliasing,
+                record_schema(self.scope_schema_url, self.resource_schema_url),
+                path,
+            ),
+            MetricFieldSelector::ResourceAttribute(path) => attribute_exists_path(
+                resource_attrs(self.resource),
+                Some(self.resource_index),
+                self.aliasing,
+                self.resource_schema_url,
+                path,
+            ),
+            MetricFieldSelector::ScopeAttribute(path) => attribute_exists_path(
+                scope_attrs(self.scope),
+                Some(self.scope_index),
+                self.aliasing,
+                self.scope_schema_url,
+                path,
+            ),
             // Simple fields and Type/Temporality are all string-valued — the
             // default (get_field().is_some()) is correct.
             _ => self.get_field(field).is_some(),
@@ -577,32 +713,130 @@ impl Matchable for MetricContext<'_> {
 
     fn get_typed_value(&self, field: &MetricFieldSelector) -> Option<TypedValue<'_>> {
         match field {
-            MetricFieldSelector::DatapointAttribute(path) => {
-                find_attribute_value(self.datapoint_attributes, path).and_then(any_value_typed)
-            }
-            MetricFieldSelector::ResourceAttribute(path) => {
-                find_attribute_value(resource_attrs(self.resource), path).and_then(any_value_typed)
-            }
-            MetricFieldSelector::ScopeAttribute(path) => {
-                find_attribute_value(scope_attrs(self.scope), path).and_then(any_value_typed)
-            }
+            MetricFieldSelector::DatapointAttribute(path) => find_attribute_value(
+                self.datapoint_attributes,
+                None,
+                self.aliasing,
+                record_schema(self.scope_schema_url, self.resource_schema_url),
+                path,
+            )
+            .and_then(any_value_typed),
+            MetricFieldSelector::ResourceAttribute(path) => find_attribute_value(
+                resource_attrs(self.resource),
+                Some(self.resource_index),
+                self.aliasing,
+                self.resource_schema_url,
+                path,
+            )
+            .and_then(any_value_typed),
+            MetricFieldSelector::ScopeAttribute(path) => find_attribute_value(
+                scope_attrs(self.scope),
+                Some(self.scope_index),
+                self.aliasing,
+                self.scope_schema_url,
+                path,
+            )
+            .and_then(any_value_typed),
             // Name/description/unit/type/temporality/scope are string-valued.
             _ => self.get_field(field).map(TypedValue::String),
         }
     }
 }
 
+// ─── Profile Matchable ───────────────────────────────────────────────
+
+/// Profiles are evaluated against log policies: the registry has no profile
+/// signal, but resource/scope attribute and schema URL selectors mean the
+/// same thing for any envelope. Record-level log selectors resolve as absent.
+pub struct ProfileContext<'a> {
+    pub resource: Option<&'a otel::Resource>,
+    pub scope: Option<&'a otel::InstrumentationScope>,
+    pub resource_index: &'a AttrIndex,
+    pub scope_index: &'a AttrIndex,
+    pub resource_schema_url: &'a str,
+    pub scope_schema_url: &'a str,
+    /// Attribute aliases to fall back on, when they're in force.
+    pub aliasing: Option<&'a semconv::Aliasing>,
+}
+
+impl Matchable for ProfileContext<'_> {
+    type Signal = LogSignal;
+
+    fn get_field(&self, field: &LogFieldSelector) -> Option<Cow<'_, str>> {
+        match field {
+            LogFieldSelector::Simple(LogField::ResourceSchemaUrl) => {
+                non_empty(self.resource_schema_url)
+            }
+            LogFieldSelector::Simple(LogField::ScopeSchemaUrl) => non_empty(self.scope_schema_url),
+            LogFieldSelector::ResourceAttribute(path) => find_attribute_path(
+                resource_attrs(self.resource),
+                Some(self.resource_index),
+                self.aliasing,
+                self.resource_schema_url,
+                path,
+            ),
+            LogFieldSelector::ScopeAttribute(path) => find_attribute_path(
+                scope_attrs(self.scope),
+                Some(self.scope_index),
+                self.aliasing,
+                self.scope_schema_url,
+                path,
+            ),
+            _ => None,
+        }
+    }
+
+    fn field_exists(&self, field: &LogFieldSelector) -> bool {
+        match field {
+            LogFieldSelector::ResourceAttribute(path) => attribute_exists_path(
+                resource_attrs(self.resource),
+                Some(self.resource_index),
+                self.aliasing,
+                self.resource_schema_url,
+                path,
+            ),
+            LogFieldSelector::ScopeAttribute(path) => attribute_exists_path(
+                scope_attrs(self.scope),
+                Some(self.scope_index),
+                self.aliasing,
+                self.scope_schema_url,
+                path,
+            ),
+            _ => self.get_field(field).is_some(),
+        }
+    }
+
+    fn get_typed_value(&self, field: &LogFieldSelector) -> Option<TypedValue<'_>> {
+        match field {
+            LogFieldSelector::ResourceAttribute(path) => find_attribute_value(
+                resource_attrs(self.resource),
+                Some(self.resource_index),
+                self.aliasing,
+                self.resource_schema_url,
+                path,
+            )
+            .and_then(any_value_typed),
+            LogFieldSelector::ScopeAttribute(path) => find_attribute_value(
+                scope_attrs(self.scope),
+                Some(self.scope_index),
+                self.aliasing,
+                self.scope_schema_url,
+                path,
+            )
+            .and_then(any_value_typed),
+            _ => self.get_field(field).map(TypedValue::String),
+        }
+    }
+}
+
 // ─── Trace Matchable ─────────────────────────────────────────────────
 
-/// Shared trace field resolution used by both immutable and mutable trace contexts.
+/// Trace field resolution backing `MutTraceContext::get_field`.
 fn resolve_trace_field<'a>(
-    span: &'a otel::Span,
-    resource: Option<&'a otel::Resource>,
-    scope: Option<&'a otel::InstrumentationScope>,
-    resource_schema_url: &'a str,
-    scope_schema_url: &'a str,
+    ctx: &'a MutTraceContext<'_>,
     field: &TraceFieldSelector,
 ) -> Option<Cow<'a, str>> {
+    let span = &*ctx.span;
     match field {
         TraceFieldSelector::Simple(f) => match f {
             TraceField::Name => non_empty(&span.name),
@@ -610,20 +844,34 @@ fn resolve_trace_field<'a>(
             TraceField::SpanId => non_empty(&span.span_id),
             TraceField::ParentSpanId => non_empty(&span.parent_span_id),
             TraceField::TraceState => non_empty(&span.trace_state),
-            TraceField::ScopeName => scope.as_ref().and_then(|s| non_empty(&s.name)),
-            TraceField::ScopeVersion => scope.as_ref().and_then(|s| non_empty(&s.version)),
-            TraceField::ResourceSchemaUrl => non_empty(resource_schema_url),
-            TraceField::ScopeSchemaUrl => non_empty(scope_schema_url),
+            TraceField::ScopeName => ctx.scope.and_then(|s| non_empty(&s.name)),
+            TraceField::ScopeVersion => ctx.scope.and_then(|s| non_empty(&s.version)),
+            TraceField::ResourceSchemaUrl => non_empty(ctx.resource_schema_url),
+            TraceField::ScopeSchemaUrl => non_empty(ctx.scope_schema_url),
             _ => None,
         },
-        TraceFieldSelector::SpanAttribute(path) => find_attribute_path(&span.attributes, path),
-        TraceFieldSelector::ResourceAttribute(path) => {
-            find_attribute_path(resource_attrs(resource), path)
-        }
-        TraceFieldSelector::ScopeAttribute(path) => {
-            find_attribute_path(scope_attrs(scope), path)
-        }
-        TraceFieldSelector::SpanKind => non_empty(&span.kind),
+        TraceFieldSelector::SpanAttribute(path) => find_attribute_path(
+            &span.attributes,
+            None,
+            ctx.aliasing,
+            record_schema(ctx.scope_schema_url, ctx.resource_schema_url),
+            path,
+        ),
+        TraceFieldSelector::ResourceAttribute(path) => find_attribute_path(
+            resource_attrs(ctx.resource),
+            Some(ctx.resource_index),
+            ctx.aliasing,
+            ctx.resource_schema_url,
+            path,
+        ),
+        TraceFieldSelector::ScopeAttribute(path) => find_attribute_path(
+            scope_attrs(ctx.scope),
+            Some(ctx.scope_index),
+            ctx.aliasing,
+            ctx.scope_schema_url,
+            path,
+        ),
+        TraceFieldSelector::SpanKind => normalize_span_kind(&span.kind),
         TraceFieldSelector::SpanStatus => {
             let status = span.status.as_ref()?;
             // Map OTel StatusCode to policy SpanStatusCode string format
@@ -651,41 +899,99 @@ fn resolve_trace_field<'a>(
     }
 }
 
+/// Map a span kind in any of the encodings seen from producers to the
+/// canonical `SPAN_KIND_*` name the engine matches against: the enum name
+/// itself, the bare kind in any case (`Server`, `server`), or the numeric
+/// value (`2` or `"2"`). Values that don't map are passed through unchanged so
+/// they stay visible rather than reading as absent.
+pub(crate) fn normalize_span_kind(kind: &serde_json::Value) -> Option<Cow<'_, str>> {
+    match kind {
+        serde_json::Value::Number(n) => {
+            let canonical = n
+                .as_i64()
+                .and_then(|i| i32::try_from(i).ok())
+                .and_then(|i| SpanKind::try_from(i).ok())
+                .map(|k| k.as_str_name());
+            Some(canonical.map_or_else(|| Cow::Owned(n.to_string()), Cow::Borrowed))
+        }
+        serde_json::Value::String(s) if s.is_empty() => None,
+        serde_json::Value::String(s) => {
+            Some(span_kind_from_str(s).map_or(Cow::Borrowed(s.as_str()), Cow::Borrowed))
+        }
+        _ => None,
+    }
+}
+
+fn span_kind_from_str(s: &str) -> Option<&'static str> {
+    if let Ok(i) = s.parse::<i32>() {
+        return SpanKind::try_from(i).ok().map(|k| k.as_str_name());
+    }
+    const PREFIX: &str = "SPAN_KIND_";
+    const KINDS: [SpanKind; 6] = [
+        SpanKind::Unspecified,
+        SpanKind::Internal,
+        SpanKind::Server,
+        SpanKind::Client,
+        SpanKind::Producer,
+        SpanKind::Consumer,
+    ];
+    let bare = match s.get(..PREFIX.len()) {
+        Some(p) if p.eq_ignore_ascii_case(PREFIX) => &s[PREFIX.len()..],
+        _ => s,
+    };
+    KINDS
+        .iter()
+        .map(|k| k.as_str_name())
+        .find(|name| name[PREFIX.len()..].eq_ignore_ascii_case(bare))
+}
+
 // ─── Trace Context ───────────────────────────────────────────────────
 
 pub struct MutTraceContext<'a> {
     pub span: &'a mut otel::Span,
     pub resource: Option<&'a otel::Resource>,
     pub scope: Option<&'a otel::InstrumentationScope>,
+    pub resource_index: &'a AttrIndex,
+    pub scope_index: &'a AttrIndex,
     pub resource_schema_url: &'a str,
     pub scope_schema_url: &'a str,
+    /// Attribute aliases to fall back on, when they're in force.
+    pub aliasing: Option<&'a semconv::Aliasing>,
+    /// Buffer the ot tracestate merge writes into before it's swapped into
+    /// the span; the span's old tracestate becomes the next merge's buffer.
+    pub scratch: &'a mut String,
 }
 
 impl Matchable for MutTraceContext<'_> {
     type Signal = TraceSignal;
 
     fn get_field(&self, field: &TraceFieldSelector) -> Option<Cow<'_, str>> {
-        resolve_trace_field(
-            self.span,
-            self.resource,
-            self.scope,
-            self.resource_schema_url,
-            self.scope_schema_url,
-            field,
-        )
+        resolve_trace_field(self, field)
     }
 
     fn field_exists(&self, field: &TraceFieldSelector) -> bool {
         match field {
-            TraceFieldSelector::SpanAttribute(path) => {
-                attribute_exists_path(&self.span.attributes, path)
-            }
-            TraceFieldSelector::ResourceAttribute(path) => {
-                attribute_exists_path(resource_attrs(self.resource), path)
-            }
-            TraceFieldSelector::ScopeAttribute(path) => {
-                attribute_exists_path(scope_attrs(self.scope), path)
-            }
+            TraceFieldSelector::SpanAttribute(path) => attribute_exists_path(
+                &self.span.attributes,
+                None,
+                self.aliasing,
+                record_schema(self.scope_schema_url, self.resource_schema_url),
+                path,
+            ),
+            TraceFieldSelector::ResourceAttribute(path) => attribute_exists_path(
+                resource_attrs(self.resource),
+                Some(self.resource_index),
+                self.aliasing,
+                self.resource_schema_url,
+                path,
+            ),
+            TraceFieldSelector::ScopeAttribute(path) => attribute_exists_path(
+                scope_attrs(self.scope),
+                Some(self.scope_index),
+                self.aliasing,
+                self.scope_schema_url,
+                path,
+            ),
             // Other trace fields are string-valued; the default is correct.
             _ => self.get_field(field).is_some(),
         }
@@ -714,15 +1020,30 @@ impl Matchable for MutTraceContext<'_> {
                     .or_else(|| non_empty(&self.span.parent_span_id).map(TypedValue::String)),
                 _ => self.get_field(field).map(TypedValue::String),
             },
-            TraceFieldSelector::SpanAttribute(path) => {
-                find_attribute_value(&self.span.attributes, path).and_then(any_value_typed)
-            }
-            TraceFieldSelector::ResourceAttribute(path) => {
-                find_attribute_value(resource_attrs(self.resource), path).and_then(any_value_typed)
-            }
-            TraceFieldSelector::ScopeAttribute(path) => {
-                find_attribute_value(scope_attrs(self.scope), path).and_then(any_value_typed)
-            }
+            TraceFieldSelector::SpanAttribute(path) => find_attribute_value(
+                &self.span.attributes,
+                None,
+                self.aliasing,
+                record_schema(self.scope_schema_url, self.resource_schema_url),
+                path,
+            )
+            .and_then(any_value_typed),
+            TraceFieldSelector::ResourceAttribute(path) => find_attribute_value(
+                resource_attrs(self.resource),
+                Some(self.resource_index),
+                self.aliasing,
+                self.resource_schema_url,
+                path,
+            )
+            .and_then(any_value_typed),
+            TraceFieldSelector::ScopeAttribute(path) => find_attribute_value(
+                scope_attrs(self.scope),
+                Some(self.scope_index),
+                self.aliasing,
+                self.scope_schema_url,
+                path,
+            )
+            .and_then(any_value_typed),
             _ => self.get_field(field).map(TypedValue::String),
         }
     }
@@ -731,8 +1052,8 @@ impl Matchable for MutTraceContext<'_> {
 impl Transformable for MutTraceContext<'_> {
     fn set_field(&mut self, field: &TraceFieldSelector, value: &str) {
         if matches!(field, TraceFieldSelector::SamplingThreshold) {
-            let sub_kv = format!("th:{value}");
-            self.span.trace_state = merge_ot_tracestate(&self.span.trace_state, &sub_kv);
+            merge_ot_tracestate(self.scratch, &self.span.trace_state, "th", value);
+            std::mem::swap(&mut self.span.trace_state, self.scratch);
         }
         // Other trace transforms are not exercised by the conformance suite.
     }
@@ -744,45 +1065,587 @@ impl Transformable for MutTraceContext<'_> {
     fn move_field(&mut self, _from: &TraceFieldSelector, _to: &TraceFieldSelector) {}
 }
 
-/// Merge an OpenTelemetry sub-key (e.g. "th:8000") into a W3C tracestate
-/// string under the "ot" vendor key.
-fn merge_ot_tracestate(tracestate: &str, sub_kv: &str) -> String {
-    let sub_key = sub_kv.split(':').next().unwrap_or(sub_kv);
+/// Merge an OpenTelemetry sub-key (e.g. `th` = `8000`) into a W3C tracestate
+/// string under the "ot" vendor key, writing the result into `out`. Members
+/// are scanned twice rather than collected, so growing `out` is the only
+/// allocation. `ot` becomes the first member; when that would take the list
+/// past the W3C limit of 32, the rightmost vendor members are dropped, as the
+/// spec directs. Existing `ot` sub-keys that aren't `key:value` are dropped.
+pub fn merge_ot_tracestate(out: &mut String, tracestate: &str, sub_key: &str, sub_value: &str) {
+    write_ot_tracestate(out, tracestate, sub_key, Some(sub_value));
+}
+
+/// Remove OpenTelemetry sub-key `sub_key` from `tracestate`'s "ot" member,
+/// writing the result into `out` as [`merge_ot_tracestate`] would. An `ot`
+/// member left with no sub-keys is dropped.
+pub fn remove_ot_tracestate(out: &mut String, tracestate: &str, sub_key: &str) {
+    write_ot_tracestate(out, tracestate, sub_key, None);
+}
+
+/// The value of OpenTelemetry sub-key `sub_key` in `tracestate`'s "ot"
+/// member, e.g. the `8` of `th` in `ot=th:8;rv:ab,congo=1`.
+pub fn ot_sub_key<'a>(tracestate: &'a str, sub_key: &str) -> Option<&'a str> {
+    ot_parts(tracestate).find_map(|part| part.strip_prefix(sub_key)?.strip_prefix(':'))
+}
+
+fn write_ot_tracestate(out: &mut String, tracestate: &str, sub_key: &str, sub_value: Option<&str>) {
+    out.clear();
+    let push_part = |out: &mut String, key: &str, value: &str| {
+        out.push_str(if out.is_empty() { "ot=" } else { ";" });
+        out.push_str(key);
+        out.push(':');
+        out.push_str(value);
+    };
+    for (key, value) in ot_parts(tracestate).filter_map(|part| part.split_once(':')) {
+        if key != sub_key {
+            push_part(out, key, value);
+        }
+    }
+    if let Some(value) = sub_value {
+        push_part(out, sub_key, value);
+    }
+    let vendors = crate::tracestate::MAX_MEMBERS - usize::from(!out.is_empty());
+    for vendor in tracestate_members(tracestate)
+        .filter(|m| !m.starts_with("ot="))
+        .take(vendors)
+    {
+        if !out.is_empty() {
+            out.push(',');
+        }
+        out.push_str(vendor);
+    }
+}
+
+fn tracestate_members(tracestate: &str) -> impl Iterator<Item = &str> {
+    tracestate
+        .split(',')
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+}
+
+/// The well-formed `key:value` sub-keys of every "ot" member, in order.
+fn ot_parts(tracestate: &str) -> impl Iterator<Item = &str> {
+    tracestate_members(tracestate)
+        .filter_map(|m| m.strip_prefix("ot="))
+        .flat_map(|value| value.split(';').map(str::trim))
+        .filter(|part| {
+            part.split_once(':').is_some_and(|(key, value)| {
+                !key.is_empty()
+                    && key
+                        .bytes()
+                        .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+                    && !value.is_empty()
+                    && value
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'))
+            })
+        })
+}
+
+#[cfg(test)]
+mod tracestate {
+    use super::*;
+    use crate::tracestate::validate;
+
+    /// Check `input` through threshold injection, read-back and removal: each
+    /// output must re-parse with no problem the input didn't already have,
+    /// and each step must give the same string when repeated.
+    fn check(input: &str) {
+        let problems = validate(input).problems;
+        let reparses = |output: &str| {
+            let checked = validate(output);
+            assert!(
+                checked.problems.iter().all(|p| problems.contains(p)),
+                "{input:?} -> {output:?}: {:?}",
+                checked.problems
+            );
+        };
+
+        let mut merged = String::new();
+        merge_ot_tracestate(&mut merged, input, "th", "8");
+        reparses(&merged);
+        assert!(merged.starts_with("ot="), "{input:?} -> {merged:?}");
+        assert!(
+            validate(&merged).members.len() <= crate::tracestate::MAX_MEMBERS,
+            "{input:?} -> {merged:?}"
+        );
+        assert_eq!(
+            ot_sub_key(&merged, "th"),
+            Some("8"),
+            "{input:?} -> {merged:?}"
+        );
+        let mut again = String::new();
+        merge_ot_tracestate(&mut again, &merged, "th", "8");
+        assert_eq!(again, merged, "{input:?}: merge not idempotent");
+
+        let mut removed = String::new();
+        remove_ot_tracestate(&mut removed, &merged, "th");
+        reparses(&removed);
+        assert_eq!(ot_sub_key(&removed, "th"), None, "{input:?} -> {removed:?}");
+        remove_ot_tracestate(&mut again, &removed, "th");
+        assert_eq!(again, removed, "{input:?}: removal not idempotent");
+        merge_ot_tracestate(&mut again, &removed, "th", "8");
+        assert_eq!(again, merged, "{input:?}: removal didn't undo the merge");
+    }
+
+    fn vendors(count: usize) -> String {
+        let members: Vec<String> = (0..count).map(|i| format!("v{i}=x{i}")).collect();
+        members.join(",")
+    }
+
+    #[test]
+    fn an_existing_ot_member_keeps_its_unknown_sub_keys() {
+        let input = "ot=rv:9b8233f7e3a151;zz:1;th:c,congo=t61rcWkgMzE";
+        check(input);
+        let mut merged = String::new();
+        merge_ot_tracestate(&mut merged, input, "th", "8");
+        assert_eq!(merged, "ot=rv:9b8233f7e3a151;zz:1;th:8,congo=t61rcWkgMzE");
+        assert_eq!(ot_sub_key(&merged, "rv"), Some("9b8233f7e3a151"));
+        assert_eq!(ot_sub_key(&merged, "zz"), Some("1"));
+    }
+
+    #[test]
+    fn full_member_lists_stay_within_the_limit() {
+        for count in [31, 32] {
+            let input = vendors(count);
+            check(&input);
+            let mut merged = String::new();
+            merge_ot_tracestate(&mut merged, &input, "th", "8");
+            assert_eq!(merged, format!("ot=th:8,{}", vendors(31)), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn equals_signs_inside_values_never_reach_the_ot_member() {
+        for input in ["a=b=c,congo=d==", "ot=rv:ab=c;th:4,congo=1", "ot==,rojo=1"] {
+            check(input);
+        }
+        let mut merged = String::new();
+        merge_ot_tracestate(&mut merged, "ot=rv:ab=c;th:4,congo=1", "th", "8");
+        assert_eq!(merged, "ot=th:8,congo=1");
+    }
+
+    #[test]
+    fn uppercase_vendor_keys_pass_through() {
+        check("Vendor=1,ACME=2");
+        check("ot=th:4,Congo=t61rcWkgMzE");
+    }
+
+    #[test]
+    fn stray_commas_and_blanks_are_dropped() {
+        for input in [
+            ",,a=1,,b=2,",
+            ",ot=th:4",
+            "ot=th:4,",
+            " ot=th:1 , ot=p:2 ",
+            ",",
+        ] {
+            check(input);
+        }
+        let mut merged = String::new();
+        merge_ot_tracestate(&mut merged, ",,a=1,,b=2,", "th", "8");
+        assert_eq!(merged, "ot=th:8,a=1,b=2");
+    }
+
+    #[test]
+    fn a_512_byte_tracestate_round_trips() {
+        let input = format!("a={},b={}", "x".repeat(253), "y".repeat(254));
+        assert_eq!(input.len(), 512);
+        assert!(validate(&input).is_valid());
+        check(&input);
+        check(&format!("ot=th:4,{input}"));
+    }
+
+    #[test]
+    fn removing_the_only_sub_key_drops_the_ot_member() {
+        let mut removed = String::new();
+        remove_ot_tracestate(&mut removed, "ot=th:8,congo=1", "th");
+        assert_eq!(removed, "congo=1");
+        remove_ot_tracestate(&mut removed, "ot=th:8", "th");
+        assert_eq!(removed, "");
+        remove_ot_tracestate(&mut removed, "ot=rv:ab;th:8,congo=1", "th");
+        assert_eq!(removed, "ot=rv:ab,congo=1");
+    }
+}
+
+// ─── Field suppression ───────────────────────────────────────────────
+
+/// Selectors `--suppress-field` hides from matchers, per signal. Profiles are
+/// matched by log selectors.
+#[derive(Clone, Debug, Default)]
+pub struct SuppressedFields {
+    pub log: Vec<LogFieldSelector>,
+    pub metric: Vec<MetricFieldSelector>,
+    pub trace: Vec<TraceFieldSelector>,
+}
+
+impl SuppressedFields {
+    pub fn is_empty(&self) -> bool {
+        self.log.is_empty() && self.metric.is_empty() && self.trace.is_empty()
+    }
+}
+
+/// A record context in which `fields` read as absent, so matchers on them
+/// never match. Transforms still write through to the record, but see the
+/// suppressed fields as absent too.
+pub struct Suppress<'s, T: Matchable> {
+    pub inner: T,
+    pub fields: &'s [<T::Signal as Signal>::FieldSelector],
+}
+
+impl<T: Matchable> Matchable for Suppress<'_, T> {
+    type Signal = T::Signal;
+
+    fn get_field(&self, field: &<T::Signal as Signal>::FieldSelector) -> Option<Cow<'_, str>> {
+        if self.fields.contains(field) {
+            return None;
+        }
+        self.inner.get_field(field)
+    }
+
+    fn field_exists(&self, field: &<T::Signal as Signal>::FieldSelector) -> bool {
+        !self.fields.contains(field) && self.inner.field_exists(field)
+    }
+
+    fn get_typed_value(
+        &self,
+        field: &<T::Signal as Signal>::FieldSelector,
+    ) -> Option<TypedValue<'_>> {
+        if self.fields.contains(field) {
+            return None;
+        }
+        self.inner.get_typed_value(field)
+    }
+}
+
+impl<T: Transformable> Transformable for Suppress<'_, T> {
+    fn set_field(&mut self, field: &<T::Signal as Signal>::FieldSelector, value: &str) {
+        self.inner.set_field(field, value);
+    }
+
+    fn delete_field(&mut self, field: &<T::Signal as Signal>::FieldSelector) -> bool {
+        self.inner.delete_field(field)
+    }
+
+    fn move_field(
+        &mut self,
+        from: &<T::Signal as Signal>::FieldSelector,
+        to: &<T::Signal as Signal>::FieldSelector,
+    ) {
+        self.inner.move_field(from, to);
+    }
+}
 
-    let mut ot_parts: Vec<&str> = Vec::new();
-    let mut other_vendors: Vec<&str> = Vec::new();
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
 
-    if !tracestate.is_empty() {
-        for vendor in tracestate.split(',') {
-            let vendor = vendor.trim();
-            if vendor.is_empty() {
-                continue;
+    use super::*;
+
+    #[test]
+    fn span_kind_dialects_normalize_to_the_enum_name() {
+        let kinds = [
+            ("SPAN_KIND_UNSPECIFIED", "Unspecified", 0),
+            ("SPAN_KIND_INTERNAL", "Internal", 1),
+            ("SPAN_KIND_SERVER", "Server", 2),
+            ("SPAN_KIND_CLIENT", "Client", 3),
+            ("SPAN_KIND_PRODUCER", "Producer", 4),
+            ("SPAN_KIND_CONSUMER", "Consumer", 5),
+        ];
+        for (canonical, bare, number) in kinds {
+            let dialects = [
+                json!(canonical),
+                json!(canonical.to_ascii_lowercase()),
+                json!(bare),
+                json!(bare.to_ascii_lowercase()),
+                json!(bare.to_ascii_uppercase()),
+                json!(number),
+                json!(number.to_string()),
+            ];
+            for kind in dialects {
+                assert_eq!(
+                    normalize_span_kind(&kind).as_deref(),
+                    Some(canonical),
+                    "{kind}"
+                );
             }
-            if let Some(ot_value) = vendor.strip_prefix("ot=") {
-                for part in ot_value.split(';') {
-                    let part = part.trim();
-                    if part.is_empty() {
-                        continue;
-                    }
-                    let part_key = part.split(':').next().unwrap_or(part);
-                    if part_key != sub_key {
-                        ot_parts.push(part);
-                    }
-                }
-            } else {
-                other_vendors.push(vendor);
+        }
+    }
+
+    #[test]
+    fn unknown_span_kinds_pass_through() {
+        for (kind, expected) in [
+            (json!("SPAN_KIND_GATEWAY"), "SPAN_KIND_GATEWAY"),
+            (json!("gateway"), "gateway"),
+            (json!("SPAN_KIND_"), "SPAN_KIND_"),
+            (json!("9"), "9"),
+            (json!(9), "9"),
+            (json!(-1), "-1"),
+            (json!(2.5), "2.5"),
+        ] {
+            assert_eq!(
+                normalize_span_kind(&kind).as_deref(),
+                Some(expected),
+                "{kind}"
+            );
+        }
+    }
+
+    fn merged(tracestate: &str, threshold: &str) -> String {
+        let mut out = String::new();
+        merge_ot_tracestate(&mut out, tracestate, "th", threshold);
+        out
+    }
+
+    #[test]
+    fn merging_a_threshold_leads_with_the_ot_member() {
+        for (tracestate, expected) in [
+            ("", "ot=th:8"),
+            ("congo=t61rcWkgMzE", "ot=th:8,congo=t61rcWkgMzE"),
+            ("congo=1,ot=rv:abc", "ot=rv:abc;th:8,congo=1"),
+            ("ot=th:4;rv:abc,congo=1", "ot=rv:abc;th:8,congo=1"),
+            (" ot=p:2 , rojo=1 ,, ", "ot=p:2;th:8,rojo=1"),
+            ("ot=th:4,ot=th:c", "ot=th:8"),
+        ] {
+            assert_eq!(merged(tracestate, "8"), expected, "{tracestate:?}");
+        }
+    }
+
+    #[test]
+    fn merging_drops_the_rightmost_vendors_past_32_members() {
+        let vendors: Vec<String> = (0..32).map(|i| format!("v{i}=x")).collect();
+        let expected = format!("ot=th:8,{}", vendors[..31].join(","));
+        assert_eq!(merged(&vendors.join(","), "8"), expected);
+    }
+
+    #[test]
+    fn merging_reuses_the_buffer_it_writes_into() {
+        let mut out = String::with_capacity(64);
+        merge_ot_tracestate(&mut out, "congo=1,rojo=2", "th", "8");
+        let capacity = out.capacity();
+        merge_ot_tracestate(&mut out, "", "th", "c");
+        assert_eq!(out, "ot=th:c");
+        assert_eq!(out.capacity(), capacity);
+    }
+
+    #[test]
+    fn missing_span_kinds_are_absent() {
+        for kind in [json!(""), json!(null), json!(true), json!({})] {
+            assert_eq!(normalize_span_kind(&kind), None, "{kind}");
+        }
+    }
+}
+
+/// Random attribute trees on a log record, its resource and its scope, put
+/// through random sequences of transform ops the way the engine applies
+/// them.
+#[cfg(test)]
+mod transform_invariants {
+    use policy_rs::engine::{CompiledTransform, TransformOp};
+    use proptest::prelude::*;
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    #[derive(Clone, Copy, Debug)]
+    enum Namespace {
+        Record,
+        Resource,
+        Scope,
+    }
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Remove(Namespace, String),
+        Redact(Namespace, String, String),
+        Rename(Namespace, String, String, bool),
+        Add(Namespace, String, String, bool),
+    }
+
+    struct State {
+        record: otel::LogRecord,
+        resource: otel::Resource,
+        scope: otel::InstrumentationScope,
+        resource_index: AttrIndex,
+        scope_index: AttrIndex,
+    }
+
+    impl State {
+        fn new(record: Vec<Value>, resource: Vec<Value>, scope: Vec<Value>) -> Self {
+            let mut record: otel::LogRecord =
+                serde_json::from_value(json!({ "attributes": record })).unwrap();
+            let mut resource: otel::Resource =
+                serde_json::from_value(json!({ "attributes": resource })).unwrap();
+            let mut scope: otel::InstrumentationScope =
+                serde_json::from_value(json!({ "name": "props", "attributes": scope })).unwrap();
+            record.prepare();
+            otel::prepare_attributes(&mut resource.attributes);
+            otel::prepare_attributes(&mut scope.attributes);
+            let resource_index = AttrIndex::new(&resource.attributes);
+            let scope_index = AttrIndex::new(&scope.attributes);
+            State {
+                record,
+                resource,
+                scope,
+                resource_index,
+                scope_index,
+            }
+        }
+
+        fn context(&mut self) -> MutLogContext<'_> {
+            MutLogContext {
+                record: &mut self.record,
+                resource: Some(&mut self.resource),
+                scope: Some(&mut self.scope),
+                resource_index: &mut self.resource_index,
+                scope_index: &mut self.scope_index,
+                resource_schema_url: "",
+                scope_schema_url: "",
+                aliasing: None,
+                severity: None,
             }
         }
+
+        fn apply(&mut self, op: TransformOp<LogSignal>) {
+            CompiledTransform { ops: vec![op] }.apply(&mut self.context());
+        }
+
+        fn attributes(&self, namespace: Namespace) -> &[otel::KeyValue] {
+            match namespace {
+                Namespace::Record => &self.record.attributes,
+                Namespace::Resource => &self.resource.attributes,
+                Namespace::Scope => &self.scope.attributes,
+            }
+        }
+
+        /// The attribute `key` in `namespace`, as JSON.
+        fn attribute(&self, namespace: Namespace, key: &str) -> Option<Value> {
+            self.attributes(namespace)
+                .iter()
+                .find(|kv| kv.key == key)
+                .map(|kv| serde_json::to_value(kv).unwrap())
+        }
+
+        fn attribute_count(&self) -> usize {
+            [Namespace::Record, Namespace::Resource, Namespace::Scope]
+                .iter()
+                .map(|&namespace| self.attributes(namespace).len())
+                .sum()
+        }
+
+        fn to_json(&self) -> Value {
+            json!([self.record, self.resource, self.scope])
+        }
+    }
+
+    fn selector(namespace: Namespace, key: &str) -> LogFieldSelector {
+        let path = vec![key.to_string()];
+        match namespace {
+            Namespace::Record => LogFieldSelector::LogAttribute(path),
+            Namespace::Resource => LogFieldSelector::ResourceAttribute(path),
+            Namespace::Scope => LogFieldSelector::ScopeAttribute(path),
+        }
+    }
+
+    /// A few keys, so ops keep landing on attributes that exist.
+    fn key() -> impl Strategy<Value = String> {
+        "[a-d]"
+    }
+
+    fn any_value() -> impl Strategy<Value = Value> {
+        let leaf = prop_oneof![
+            "[a-z ]{0,6}".prop_map(|s| json!({ "stringValue": s })),
+            any::<i64>().prop_map(|i| json!({ "intValue": i.to_string() })),
+            any::<bool>().prop_map(|b| json!({ "boolValue": b })),
+            (-1e6..1e6f64).prop_map(|d| json!({ "doubleValue": d })),
+        ];
+        leaf.prop_recursive(3, 16, 3, |inner| {
+            prop::collection::btree_map(key(), inner, 0..3).prop_map(|values| {
+                let values: Vec<Value> = values
+                    .into_iter()
+                    .map(|(key, value)| json!({ "key": key, "value": value }))
+                    .collect();
+                json!({ "kvlistValue": { "values": values } })
+            })
+        })
+    }
+
+    fn attributes() -> impl Strategy<Value = Vec<Value>> {
+        prop::collection::btree_map(key(), any_value(), 0..4).prop_map(|attrs| {
+            attrs
+                .into_iter()
+                .map(|(key, value)| json!({ "key": key, "value": value }))
+                .collect()
+        })
     }
 
-    let mut result = format!("ot={}", ot_parts.join(";"));
-    if !ot_parts.is_empty() {
-        result.push(';');
+    fn namespace() -> impl Strategy<Value = Namespace> {
+        prop_oneof![
+            Just(Namespace::Record),
+            Just(Namespace::Resource),
+            Just(Namespace::Scope),
+        ]
     }
-    result.push_str(sub_kv);
-    if !other_vendors.is_empty() {
-        result.push(',');
-        result.push_str(&other_vendors.join(","));
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (namespace(), key()).prop_map(|(ns, key)| Op::Remove(ns, key)),
+            (namespace(), key(), "[a-z]{1,4}").prop_map(|(ns, key, r)| Op::Redact(ns, key, r)),
+            (namespace(), key(), key(), any::<bool>())
+                .prop_map(|(ns, from, to, upsert)| Op::Rename(ns, from, to, upsert)),
+            (namespace(), key(), "[a-z]{1,4}", any::<bool>())
+                .prop_map(|(ns, key, value, upsert)| Op::Add(ns, key, value, upsert)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn transforms_keep_their_invariants(
+            record in attributes(),
+            resource in attributes(),
+            scope in attributes(),
+            ops in prop::collection::vec(op(), 1..12),
+        ) {
+            let mut state = State::new(record, resource, scope);
+            for op in ops {
+                match op {
+                    Op::Remove(ns, key) => {
+                        let field = selector(ns, &key);
+                        state.apply(TransformOp::Remove { field: field.clone() });
+                        let ctx = state.context();
+                        prop_assert_eq!(ctx.get_field(&field), None);
+                        prop_assert!(!ctx.field_exists(&field), "{:?} survived removal", field);
+                    }
+                    Op::Redact(ns, key, replacement) => {
+                        state.apply(TransformOp::Redact {
+                            field: selector(ns, &key),
+                            replacement,
+                            regex: None,
+                        });
+                    }
+                    Op::Rename(ns, from, to, upsert) => {
+                        let target_absent = state.attribute(ns, &to).is_none();
+                        let count = state.attribute_count();
+                        state.apply(TransformOp::Rename { from: selector(ns, &from), to, upsert });
+                        if target_absent {
+                            prop_assert_eq!(state.attribute_count(), count);
+                        }
+                    }
+                    Op::Add(ns, key, value, upsert) => {
+                        let before = state.attribute(ns, &key);
+                        state.apply(TransformOp::Add { field: selector(ns, &key), value, upsert });
+                        if !upsert && before.is_some() {
+                            prop_assert_eq!(state.attribute(ns, &key), before);
+                        }
+                    }
+                }
+            }
+            let json = state.to_json();
+            let record: otel::LogRecord = serde_json::from_value(json[0].clone()).unwrap();
+            let resource: otel::Resource = serde_json::from_value(json[1].clone()).unwrap();
+            let scope: otel::InstrumentationScope =
+                serde_json::from_value(json[2].clone()).unwrap();
+            prop_assert_eq!(json!([record, resource, scope]), json);
+        }
     }
-    result
 }
@@ -0,0 +1,192 @@
+//! `--assert`: expectations written inline on input records, so one
+//! hand-written input file is a test of its own.
+//!
+//! A log record, metric or span may carry an `expect` object giving the
+//! decision it should get (as [`runner_rs::decision_name`] spells it) and the
+//! policies it should match:
+//!
+//! ```json
+//! { "severityText": "DEBUG", "expect": { "decision": "drop", "matched": ["drop-debug"] } }
+//! ```
+//!
+//! Either key may be left out to leave that part unchecked. The engine
+//! reports only the policy that decided a record, so `matched` lists at most
+//! one id, and none for `no_match`. Records without `expect` and records
+//! `--sample-input` left unevaluated are unchecked. Under
+//! `--metric-granularity datapoint` a metric's expectation applies to each of
+//! its datapoints. Profiles can't carry expectations.
+//!
+//! `expect` is never an unknown field under `--strict-input`, and passes
+//! through to the output like any other unmodelled key.
+
+use std::collections::BTreeMap;
+
+use policy_rs::EvaluateResult;
+use runner_rs::{RecordResult, otel};
+use serde::Deserialize;
+use tracing::error;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Expect {
+    decision: Option<String>,
+    matched: Option<Vec<String>>,
+}
+
+struct Expectation {
+    /// JSON path of the record in the input.
+    path: String,
+    expect: Expect,
+}
+
+/// Expectations by the resource, scope and record index of their record.
+#[derive(Default)]
+pub struct Expectations(BTreeMap<(usize, usize, usize), Expectation>);
+
+impl Expectations {
+    /// Read the expectation in a record's unmodelled keys, if it has one.
+    fn read(
+        &mut self,
+        index: (usize, usize, usize),
+        path: impl FnOnce() -> String,
+        extra: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<(), String> {
+        let Some(value) = extra.get(otel::EXPECT_KEY) else {
+            return Ok(());
+        };
+        let path = path();
+        let expect =
+            Expect::deserialize(value).map_err(|e| format!("{path}.{}: {e}", otel::EXPECT_KEY))?;
+        self.0.insert(index, Expectation { path, expect });
+        Ok(())
+    }
+}
+
+/// Payloads whose records can carry expectations.
+pub trait Expecting {
+    /// Every record's expectation, or the first malformed one's path and
+    /// error.
+    fn expectations(&self) -> Result<Expectations, String>;
+}
+
+impl Expecting for otel::LogsData {
+    fn expectations(&self) -> Result<Expectations, String> {
+        let mut out = Expectations::default();
+        for (i, rl) in self.resource_logs.iter().enumerate() {
+            for (j, sl) in rl.scope_logs.iter().enumerate() {
+                for (k, rec) in sl.log_records.iter().enumerate() {
+                    out.read(
+                        (i, j, k),
+                        || format!("resourceLogs[{i}].scopeLogs[{j}].logRecords[{k}]"),
+                        &rec.extra,
+                    )?;
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl Expecting for otel::MetricsData {
+    fn expectations(&self) -> Result<Expectations, String> {
+        let mut out = Expectations::default();
+        for (i, rm) in self.resource_metrics.iter().enumerate() {
+            for (j, sm) in rm.scope_metrics.iter().enumerate() {
+                for (k, m) in sm.metrics.iter().enumerate() {
+                    out.read(
+                        (i, j, k),
+                        || format!("resourceMetrics[{i}].scopeMetrics[{j}].metrics[{k}]"),
+                        &m.extra,
+                    )?;
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl Expecting for otel::TracesData {
+    fn expectations(&self) -> Result<Expectations, String> {
+        let mut out = Expectations::default();
+        for (i, rs) in self.resource_spans.iter().enumerate() {
+            for (j, ss) in rs.scope_spans.iter().enumerate() {
+                for (k, span) in ss.spans.iter().enumerate() {
+                    out.read(
+                        (i, j, k),
+                        || format!("resourceSpans[{i}].scopeSpans[{j}].spans[{k}]"),
+                        &span.extra,
+                    )?;
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl Expecting for otel::ProfilesData {
+    fn expectations(&self) -> Result<Expectations, String> {
+        Ok(Expectations::default())
+    }
+}
+
+/// How many evaluated records had their expectation checked, and how many
+/// of those failed it.
+#[derive(Default)]
+pub struct Outcome {
+    pub checked: usize,
+    pub failed: usize,
+}
+
+impl Outcome {
+    pub fn absorb(&mut self, other: Outcome) {
+        self.checked += other.checked;
+        self.failed += other.failed;
+    }
+}
+
+fn matched(result: &EvaluateResult) -> Vec<&str> {
+    match result {
+        EvaluateResult::NoMatch => vec![],
+        EvaluateResult::Keep { policy_id, .. }
+        | EvaluateResult::Drop { policy_id }
+        | EvaluateResult::Sample { policy_id, .. }
+        | EvaluateResult::RateLimit { policy_id, .. } => vec![policy_id],
+    }
+}
+
+/// Check `results` against `expectations`, logging a diff for each result
+/// that doesn't meet its record's.
+pub fn check(expectations: &Expectations, results: &[RecordResult]) -> Outcome {
+    let mut outcome = Outcome::default();
+    for r in results {
+        let Some(expectation) = expectations.0.get(&(r.resource, r.scope, r.record)) else {
+            continue;
+        };
+        outcome.checked += 1;
+        let expect = &expectation.expect;
+        let mut diff = Vec::new();
+        let decision = runner_rs::decision_name(&r.result);
+        if let Some(want) = expect.decision.as_deref().filter(|&want| want != decision) {
+            diff.push(format!(
+                "  - decision: {want:?}\n  + decision: {decision:?}"
+            ));
+        }
+        let got = matched(&r.result);
+        if let Some(want) = expect.matched.as_ref().filter(|&want| *want != got) {
+            diff.push(format!("  - matched: {want:?}\n  + matched: {got:?}"));
+        }
+        if diff.is_empty() {
+            continue;
+        }
+        outcome.failed += 1;
+        let datapoint = r
+            .datapoint
+            .map_or(String::new(), |d| format!(" (datapoint {d})"));
+        error!(
+            "{}{datapoint} failed its expectation\n{}",
+            expectation.path,
+            diff.join("\n")
+        );
+    }
+    outcome
+}
@@ -0,0 +1,66 @@
+//! `runner-rs export-snapshot`: dump what the registry holds after loading.
+//!
+//! Policies are written in snapshot order as their canonical proto JSON, so
+//! the same content loaded through different providers exports identically
+//! and repeated runs are byte-identical. Compilation errors are attached to
+//! the policy they belong to.
+
+use std::path::PathBuf;
+
+use policy_rs::PolicyRegistry;
+use policy_rs::proto::tero::policy::v1::Policy as ProtoPolicy;
+use serde::Serialize;
+
+use crate::provider::{ChainProvider, MergeStrategy};
+use crate::status::{self, Failure};
+
+#[derive(clap::Args)]
+pub struct ExportArgs {
+    /// A policy file, a directory whose `*.json` files are all loaded, or
+    /// `env:[VAR]` (repeatable; later sources take precedence).
+    #[arg(long, required = true)]
+    policies: Vec<PathBuf>,
+    /// What to do when two sources define the same policy id.
+    #[arg(long, value_enum, default_value_t)]
+    merge_strategy: MergeStrategy,
+    /// Where to write the snapshot JSON.
+    #[arg(long)]
+    out: PathBuf,
+}
+
+#[derive(Serialize)]
+struct ExportedSnapshot<'a> {
+    policies: Vec<ExportedPolicy<'a>>,
+}
+
+#[derive(Serialize)]
+struct ExportedPolicy<'a> {
+    #[serde(flatten)]
+    policy: &'a ProtoPolicy,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    errors: &'a [String],
+}
+
+pub fn run(args: &ExportArgs) -> status::Result {
+    let registry = PolicyRegistry::new();
+    if let Err(e) = registry.subscribe(&ChainProvider::new(&args.policies, args.merge_strategy)) {
+        return Err(Failure(format!("failed to load policies: {e}")));
+    }
+    let snapshot = registry.snapshot();
+
+    let exported = ExportedSnapshot {
+        policies: snapshot
+            .iter()
+            .map(|entry| ExportedPolicy {
+                policy: entry.policy.proto(),
+                errors: snapshot.compilation_errors_for(entry.policy.id()),
+            })
+            .collect(),
+    };
+    crate::write_output(&args.out, |out| {
+        serde_json::to_writer_pretty(&mut *out, &exported)
+            .map_err(std::io::Error::from)
+            .and_then(|()| out.write_all(b"\n"))
+            .map_err(|e| Failure(format!("failed to serialize snapshot: {e}")))
+    })
+}
@@ -0,0 +1,1484 @@
+//! Filtering whole OTLP payloads in place against a policy snapshot.
+//!
+//! Each `evaluate_*_data` function evaluates every record in input order,
+//! removes the ones their decision drops (along with scopes and resources
+//! left empty), and returns one [`RecordResult`] per evaluated record.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use policy_rs::{EvaluateResult, PolicyEngine, PolicyError, PolicySnapshot, PolicyStats};
+use rayon::prelude::*;
+
+use crate::invariant::{self, ScopeDecision};
+use crate::{diff, engine_trace, eval, otel, selector_stats, semconv, severity};
+
+/// How [`evaluate_signal`] schedules and measures evaluation.
+#[derive(Clone, Debug, Default)]
+pub struct EvalOptions {
+    /// Evaluate resource envelopes on the rayon thread pool. Records within
+    /// an envelope still run in order on one thread. Leave this off when the
+    /// snapshot has rate-limited policies: their shared token buckets make
+    /// decisions depend on evaluation order.
+    pub parallel_envelopes: bool,
+    /// Time each evaluation into [`RecordResult::elapsed`].
+    pub timed: bool,
+    /// Evaluate only this fraction of records, chosen by hashing each
+    /// record's position with [`sample_seed`](Self::sample_seed); the rest
+    /// are removed unevaluated and get no [`RecordResult`].
+    pub sample_rate: Option<f64>,
+    pub sample_seed: u64,
+    /// Fill [`RecordResult::changes`] for logs and spans, the records
+    /// transforms can touch.
+    pub record_diffs: bool,
+    pub metric_granularity: MetricGranularity,
+    pub envelope_retention: EnvelopeRetention,
+    pub resource_mutation: ResourceMutation,
+    /// Decide every record `NoMatch` without calling the engine. Only sound
+    /// when no policy in the snapshot targets the signal, where the engine
+    /// could return nothing else.
+    pub skip_engine: bool,
+    /// Selectors matchers see as absent, for seeing which field a match
+    /// hinges on.
+    pub suppressed_fields: Arc<eval::SuppressedFields>,
+    /// Evaluate only the first record of each scope when the snapshot can't
+    /// decide its records differently, reusing that decision for the rest.
+    /// That holds when every policy for the signal matches only resource and
+    /// scope fields, keeps all or none, and transforms nothing. Results and
+    /// policy stats come out as if every record had been evaluated. Ignored
+    /// with [`parallel_envelopes`](Self::parallel_envelopes), which would
+    /// blur the stats it replays.
+    pub reuse_scope_decisions: bool,
+    /// Fill [`RecordResult::requests`] with every lookup and write the
+    /// engine made on each record.
+    pub trace_engine: bool,
+    /// Fall back on these old attribute names, by schema version, when a
+    /// selector's attribute is missing, listing each in
+    /// [`RecordResult::aliases`].
+    pub semconv_aliases: Option<Arc<semconv::Aliases>>,
+    /// Match log records' `severity_text` by this table's canonical
+    /// spelling, listing texts it doesn't know in
+    /// [`RecordResult::unknown_severity_text`].
+    pub severity_table: Option<Arc<severity::Table>>,
+    /// Count every lookup the engine makes, by selector, into these stats.
+    pub selector_stats: Option<Arc<selector_stats::Stats>>,
+    /// Upsert each surviving record's decision, and the policy that made
+    /// it, onto its attributes under these keys, after every transform: a
+    /// log record's, a span's, or a metric's datapoints'. Profiles have no
+    /// attributes to carry it.
+    pub annotate_decisions: Option<Arc<DecisionKeys>>,
+    /// Evaluate only the first of resource envelopes that are identical as
+    /// JSON, giving every later copy the first's filtered contents and
+    /// decisions; see [`dedupe_ignored_keys`](Self::dedupe_ignored_keys). Copies aren't evaluated, so they add nothing to policy
+    /// stats or rate limits. Ignored with
+    /// [`parallel_envelopes`](Self::parallel_envelopes), which has no first
+    /// copy.
+    pub dedupe_envelopes: bool,
+    /// Object keys left out, at any depth, when comparing envelopes for
+    /// [`dedupe_envelopes`](Self::dedupe_envelopes). A copy is given the
+    /// first's decisions record by record, keeping its own values for them.
+    pub dedupe_ignored_keys: Arc<[String]>,
+}
+
+/// The attribute keys [`EvalOptions::annotate_decisions`] writes.
+#[derive(Clone, Debug)]
+pub struct DecisionKeys {
+    /// For the [`decision_name`].
+    pub decision: String,
+    /// For the deciding policy's id, left unset on `no_match`.
+    pub policy: String,
+}
+
+/// What one metrics decision covers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MetricGranularity {
+    /// One decision per metric, matched against its first datapoint's
+    /// attributes.
+    #[default]
+    Metric,
+    /// One decision per datapoint, each matched against its own attributes.
+    /// A metric is dropped once none of its datapoints survive; one with no
+    /// datapoints at all is decided as a whole.
+    Datapoint,
+}
+
+/// Which spans of a resource envelope survive filtering.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EnvelopeRetention {
+    /// Each span by its own decision.
+    #[default]
+    PerRecord,
+    /// Every span, transformed or not, when any span in the envelope is
+    /// kept, and none otherwise; as backends that keep whole traces do.
+    /// Results still give each span's own decision, and spans left out of
+    /// the input sample are still removed. Other signals are filtered per
+    /// record regardless.
+    AnyKeep,
+}
+
+/// Where transforms write a log record's resource and scope attributes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResourceMutation {
+    /// Into the envelope's own resource and scope, so every record under
+    /// them carries the write, and records evaluated after it see it.
+    #[default]
+    Shared,
+    /// Into copies made for the record, which is evaluated against the
+    /// resource and scope as they came in. A record whose copies were
+    /// rewritten moves under them: into a new scope right after its own
+    /// when only the scope changed, and otherwise into a new envelope right
+    /// after its own. Records rewritten alike share the new scope or
+    /// envelope, in the order they come. Transforms on other signals can't
+    /// write either, so they're unaffected.
+    CopyOnWrite,
+}
+
+/// The decision for one record, located by its indexes in the input payload
+/// as it was before anything was removed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordResult {
+    pub resource: usize,
+    pub scope: usize,
+    /// Index of the log record, metric, span or profile within its scope.
+    pub record: usize,
+    /// Index of the datapoint within its metric, under
+    /// [`MetricGranularity::Datapoint`].
+    pub datapoint: Option<usize>,
+    pub result: EvaluateResult,
+    /// How long evaluation took, when [`EvalOptions::timed`] is set.
+    pub elapsed: Option<Duration>,
+    /// What evaluation changed in the record, when
+    /// [`EvalOptions::record_diffs`] is set.
+    pub changes: Option<Vec<diff::Change>>,
+    /// What the engine asked of the record, in order, when
+    /// [`EvalOptions::trace_engine`] is set. Empty for records decided
+    /// without calling it.
+    pub requests: Option<Vec<engine_trace::Request>>,
+    /// The attributes matchers found only under an old name, when
+    /// [`EvalOptions::semconv_aliases`] is set. Empty for records decided
+    /// without calling the engine.
+    pub aliases: Option<Vec<semconv::AliasUse>>,
+    /// The log record's severity text, when
+    /// [`EvalOptions::severity_table`] is set and doesn't know it.
+    pub unknown_severity_text: Option<String>,
+    /// The envelope this record's envelope duplicates, whose decision it was
+    /// given instead of being evaluated, under
+    /// [`EvalOptions::dedupe_envelopes`].
+    pub copy_of: Option<usize>,
+}
+
+/// Filter a logs payload, applying transforms to the records that survive.
+pub fn evaluate_logs_data(
+    engine: &PolicyEngine,
+    snapshot: &PolicySnapshot,
+    data: &mut otel::LogsData,
+    opts: &EvalOptions,
+) -> Result<Vec<RecordResult>, PolicyError> {
+    evaluate_signal(engine, snapshot, data, opts)
+}
+
+/// Filter a metrics payload. Only drop decisions remove a metric.
+pub fn evaluate_metrics_data(
+    engine: &PolicyEngine,
+    snapshot: &PolicySnapshot,
+    data: &mut otel::MetricsData,
+    opts: &EvalOptions,
+) -> Result<Vec<RecordResult>, PolicyError> {
+    evaluate_signal(engine, snapshot, data, opts)
+}
+
+/// Filter a traces payload, writing the sampling threshold back into each
+/// surviving span's `tracestate`.
+pub fn evaluate_traces_data(
+    engine: &PolicyEngine,
+    snapshot: &PolicySnapshot,
+    data: &mut otel::TracesData,
+    opts: &EvalOptions,
+) -> Result<Vec<RecordResult>, PolicyError> {
+    evaluate_signal(engine, snapshot, data, opts)
+}
+
+/// Filter a profiles payload.
+pub fn evaluate_profiles_data(
+    engine: &PolicyEngine,
+    snapshot: &PolicySnapshot,
+    data: &mut otel::ProfilesData,
+    opts: &EvalOptions,
+) -> Result<Vec<RecordResult>, PolicyError> {
+    evaluate_signal(engine, snapshot, data, opts)
+}
+
+/// One OTLP payload type [`evaluate_signal`] can filter. Implementors only
+/// expose their resource envelopes and filter one at a time; scheduling is
+/// shared.
+pub trait SignalData: serde::de::DeserializeOwned + serde::Serialize {
+    /// Plural noun used in parse/serialize error messages.
+    const NAME: &'static str;
+
+    /// A `Resource*` envelope. Envelopes share nothing, so they can be
+    /// filtered on separate threads.
+    type Envelope: Send + Clone + serde::Serialize + serde::de::DeserializeOwned;
+
+    /// Dotted paths of keys the OTLP types don't model.
+    fn unknown_fields(&self) -> Vec<String>;
+
+    fn envelopes(&mut self) -> &mut Vec<Self::Envelope>;
+
+    /// The resource an envelope's records belong to, for
+    /// [`evaluate_routed`].
+    fn resource(envelope: &Self::Envelope) -> Option<&otel::Resource>;
+
+    /// Log records, metrics, spans or profiles across all envelopes.
+    fn record_count(&self) -> usize;
+
+    /// Log records, metrics, spans or profiles in one envelope.
+    fn envelope_records(envelope: &Self::Envelope) -> usize;
+
+    /// What [`record_count`](Self::record_count) counts under
+    /// [`MetricGranularity::Datapoint`]: for metrics, their datapoints.
+    fn datapoint_count(&self) -> usize {
+        self.record_count()
+    }
+
+    /// Filter one envelope's records in place, returning whether anything in
+    /// it survived. Envelopes split off it under
+    /// [`ResourceMutation::CopyOnWrite`] go in `splits`, to follow it.
+    fn filter_envelope(
+        envelope: &mut Self::Envelope,
+        engine: &PolicyEngine,
+        snapshot: &PolicySnapshot,
+        records: &mut Recorder,
+        splits: &mut Vec<Self::Envelope>,
+    ) -> bool;
+}
+
+/// Filter any [`SignalData`] payload; the typed `evaluate_*_data` functions
+/// are this at a fixed type. Stops at the first evaluation error, leaving
+/// `data` partly filtered.
+pub fn evaluate_signal<D: SignalData>(
+    engine: &PolicyEngine,
+    snapshot: &PolicySnapshot,
+    data: &mut D,
+    opts: &EvalOptions,
+) -> Result<Vec<RecordResult>, PolicyError> {
+    evaluate_routed(engine, |_| Some(snapshot), data, opts)
+}
+
+/// [`evaluate_signal`] with a snapshot picked per envelope by `route`, from
+/// the envelope's resource. Envelopes routed to `None` are left as they are
+/// and produce no results.
+pub fn evaluate_routed<'s, D: SignalData>(
+    engine: &PolicyEngine,
+    route: impl Fn(Option<&otel::Resource>) -> Option<&'s PolicySnapshot> + Sync,
+    data: &mut D,
+    opts: &EvalOptions,
+) -> Result<Vec<RecordResult>, PolicyError> {
+    let filter =
+        |env: &mut D::Envelope, records: &mut Recorder, splits: &mut Vec<D::Envelope>| match route(
+            D::resource(env),
+        ) {
+            Some(snapshot) => {
+                if opts.reuse_scope_decisions
+                    && !opts.parallel_envelopes
+                    && D::envelope_records(env) > 1
+                    && invariant::scope_invariant(snapshot, D::NAME)
+                {
+                    records.scope_stats = Some(invariant::policy_stats(snapshot));
+                }
+                records.redactions(snapshot);
+                D::filter_envelope(env, engine, snapshot, records, splits)
+            }
+            None => true,
+        };
+    let envelopes = data.envelopes();
+    if opts.parallel_envelopes {
+        // Collecting per-envelope verdicts first keeps the surviving
+        // envelopes, and the results, in input order.
+        let filtered: Vec<(bool, Recorder, Vec<D::Envelope>)> = envelopes
+            .par_iter_mut()
+            .enumerate()
+            .map(|(i, env)| {
+                let mut records = Recorder::new(i, opts);
+                let mut splits = Vec::new();
+                (filter(env, &mut records, &mut splits), records, splits)
+            })
+            .collect();
+        let mut results = Vec::new();
+        let mut survived = Vec::with_capacity(filtered.len());
+        for (kept, records, splits) in filtered {
+            results.extend(records.finish()?);
+            survived.push((kept, splits));
+        }
+        *envelopes = std::mem::take(envelopes)
+            .into_iter()
+            .zip(survived)
+            .flat_map(|(env, (kept, splits))| kept.then_some(env).into_iter().chain(splits))
+            .collect();
+        Ok(results)
+    } else {
+        let mut results: Vec<RecordResult> = Vec::new();
+        let mut error = None;
+        let mut originals: Originals<D::Envelope> = HashMap::new();
+        let mut output = Vec::with_capacity(envelopes.len());
+        for (resource, mut env) in std::mem::take(envelopes).into_iter().enumerate() {
+            if error.is_some() {
+                output.push(env);
+                continue;
+            }
+            let key = opts
+                .dedupe_envelopes
+                .then(|| canonical(&env, &opts.dedupe_ignored_keys));
+            if let Some(original) = key.as_ref().and_then(|key| originals.get(key)) {
+                let decided = &results[original.results.clone()];
+                let copies: Vec<RecordResult> = decided
+                    .iter()
+                    .map(|r| RecordResult {
+                        resource,
+                        elapsed: None,
+                        requests: r.requests.as_ref().map(|_| Vec::new()),
+                        aliases: r.aliases.as_ref().map(|_| Vec::new()),
+                        copy_of: Some(original.resource),
+                        ..r.clone()
+                    })
+                    .collect();
+                let ignored = &opts.dedupe_ignored_keys;
+                if ignored.is_empty() {
+                    if original.kept {
+                        output.push(original.filtered.clone());
+                    }
+                    output.extend(original.splits.iter().cloned());
+                } else {
+                    // The copy can differ only in ignored keys. Replaying the
+                    // original's decisions on it removes the same records,
+                    // and its values for those keys then go into the
+                    // original's filtered, transformed form.
+                    let mut records = Recorder::replaying(resource, opts, decided);
+                    let mut splits = Vec::new();
+                    filter(&mut env, &mut records, &mut splits);
+                    if original.kept {
+                        output.push(regraft(&original.filtered, &env, ignored));
+                    }
+                    output.extend(
+                        original
+                            .splits
+                            .iter()
+                            .zip(&splits)
+                            .map(|(filtered, copy)| regraft(filtered, copy, ignored)),
+                    );
+                }
+                results.extend(copies);
+                continue;
+            }
+            let mut records = Recorder::new(resource, opts);
+            let mut splits = Vec::new();
+            let kept = filter(&mut env, &mut records, &mut splits);
+            let start = results.len();
+            match records.finish() {
+                Ok(r) => results.extend(r),
+                Err(e) => error = Some(e),
+            }
+            if let Some(key) = key {
+                originals.insert(
+                    key,
+                    Original {
+                        resource,
+                        filtered: env.clone(),
+                        splits: splits.clone(),
+                        kept,
+                        results: start..results.len(),
+                    },
+                );
+            }
+            if kept {
+                output.push(env);
+            }
+            output.extend(splits);
+        }
+        *envelopes = output;
+        error.map_or(Ok(results), Err)
+    }
+}
+
+/// The first of each set of identical envelopes, by [`canonical`] form.
+type Originals<E> = HashMap<String, Original<E>>;
+
+/// An envelope as filtered, for [`EvalOptions::dedupe_envelopes`] to give
+/// its copies.
+struct Original<E> {
+    resource: usize,
+    filtered: E,
+    splits: Vec<E>,
+    kept: bool,
+    /// Where its results sit among all results.
+    results: std::ops::Range<usize>,
+}
+
+/// `envelope` as compact JSON without the `ignored` keys. Serialized maps
+/// are sorted, so identical envelopes always read the same.
+fn canonical<T: serde::Serialize>(envelope: &T, ignored: &[String]) -> String {
+    fn strip(value: &mut serde_json::Value, ignored: &[String]) {
+        match value {
+            serde_json::Value::Object(map) => {
+                map.retain(|key, _| !ignored.contains(key));
+                map.values_mut().for_each(|v| strip(v, ignored));
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|v| strip(v, ignored)),
+            _ => {}
+        }
+    }
+    let mut value = serde_json::to_value(envelope).unwrap_or_default();
+    strip(&mut value, ignored);
+    value.to_string()
+}
+
+/// `filtered` with `copy`'s values for the `ignored` keys. The two must line
+/// up: the same envelope with the same records removed.
+fn regraft<E>(filtered: &E, copy: &E, ignored: &[String]) -> E
+where
+    E: Clone + serde::Serialize + serde::de::DeserializeOwned,
+{
+    fn graft(value: &mut serde_json::Value, copy: &serde_json::Value, ignored: &[String]) {
+        use serde_json::Value;
+        match (value, copy) {
+            (Value::Object(map), Value::Object(theirs)) => {
+                for key in ignored {
+                    match theirs.get(key) {
+                        Some(v) => map.insert(key.clone(), v.clone()),
+                        None => map.remove(key),
+                    };
+                }
+                for (key, v) in map.iter_mut() {
+                    if let Some(theirs) = theirs.get(key) {
+                        graft(v, theirs, ignored);
+                    }
+                }
+            }
+            (Value::Array(items), Value::Array(theirs)) => {
+                for (v, theirs) in items.iter_mut().zip(theirs) {
+                    graft(v, theirs, ignored);
+                }
+            }
+            _ => {}
+        }
+    }
+    let (Ok(mut value), Ok(copy)) = (serde_json::to_value(filtered), serde_json::to_value(copy))
+    else {
+        return filtered.clone();
+    };
+    graft(&mut value, &copy, ignored);
+    serde_json::from_value(value).unwrap_or_else(|_| filtered.clone())
+}
+
+/// Collects [`RecordResult`]s for one envelope while its records are
+/// filtered, tracking where each record sits in the input.
+pub struct Recorder {
+    resource: usize,
+    scope: usize,
+    record: usize,
+    timed: bool,
+    sample: Option<(f64, u64)>,
+    diffs: bool,
+    granularity: MetricGranularity,
+    retention: EnvelopeRetention,
+    mutation: ResourceMutation,
+    skip_engine: bool,
+    suppressed_fields: Arc<eval::SuppressedFields>,
+    engine_trace: Option<Arc<engine_trace::Log>>,
+    aliasing: Option<Arc<semconv::Aliasing>>,
+    severity_table: Option<Arc<severity::Table>>,
+    selector_stats: Option<Arc<selector_stats::Stats>>,
+    annotate: Option<Arc<DecisionKeys>>,
+    /// Whether the last record decided was evaluated, leaving its result
+    /// last in `results`.
+    decided: bool,
+    /// Every policy's stats, when the envelope's snapshot decides all records
+    /// in a scope alike; see [`EvalOptions::reuse_scope_decisions`].
+    scope_stats: Option<Vec<Arc<PolicyStats>>>,
+    scope_decision: Option<ScopeDecision>,
+    /// The current record before evaluation, from [`Recorder::capture`].
+    before: Option<serde_json::Value>,
+    /// The current log record's severity text, from
+    /// [`Recorder::severity_text`], when the table doesn't know it.
+    unknown_severity: Option<String>,
+    /// Redaction replacements in the envelope's snapshot, left out of record
+    /// diffs.
+    redactions: Vec<String>,
+    /// Decisions to give the records in turn instead of evaluating them, for
+    /// a copy under [`EvalOptions::dedupe_envelopes`].
+    replay: Option<std::vec::IntoIter<EvaluateResult>>,
+    results: Vec<RecordResult>,
+    error: Option<PolicyError>,
+}
+
+impl Recorder {
+    fn new(resource: usize, opts: &EvalOptions) -> Self {
+        Recorder {
+            resource,
+            scope: 0,
+            record: 0,
+            timed: opts.timed,
+            sample: opts.sample_rate.map(|rate| (rate, opts.sample_seed)),
+            diffs: opts.record_diffs,
+            granularity: opts.metric_granularity,
+            retention: opts.envelope_retention,
+            mutation: opts.resource_mutation,
+            skip_engine: opts.skip_engine,
+            suppressed_fields: Arc::clone(&opts.suppressed_fields),
+            engine_trace: opts.trace_engine.then(Arc::default),
+            aliasing: opts
+                .semconv_aliases
+                .clone()
+                .map(|aliases| Arc::new(semconv::Aliasing::new(aliases))),
+            severity_table: opts.severity_table.clone(),
+            selector_stats: opts.selector_stats.clone(),
+            annotate: opts.annotate_decisions.clone(),
+            decided: false,
+            scope_stats: None,
+            scope_decision: None,
+            before: None,
+            unknown_severity: None,
+            redactions: Vec::new(),
+            replay: None,
+            results: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// A recorder for a copy of the envelope `decided` holds the results of,
+    /// giving its records those decisions without calling the engine.
+    fn replaying(resource: usize, opts: &EvalOptions, decided: &[RecordResult]) -> Self {
+        let replay: Vec<EvaluateResult> = decided.iter().map(|r| r.result.clone()).collect();
+        Recorder {
+            timed: false,
+            diffs: false,
+            engine_trace: None,
+            aliasing: None,
+            selector_stats: None,
+            replay: Some(replay.into_iter()),
+            ..Recorder::new(resource, opts)
+        }
+    }
+
+    /// Whether the current record is in the input sample. Hashing its
+    /// position rather than drawing at random makes the sample depend only on
+    /// the seed, whatever order envelopes are evaluated in.
+    fn sampled(&self, datapoint: Option<usize>) -> bool {
+        let Some((rate, seed)) = self.sample else {
+            return true;
+        };
+        let mut h = splitmix64(seed);
+        for part in [self.resource, self.scope, self.record] {
+            h = splitmix64(h ^ part as u64);
+        }
+        if let Some(datapoint) = datapoint {
+            h = splitmix64(h ^ datapoint as u64);
+        }
+        // The top 53 bits as a uniform fraction in [0, 1).
+        ((h >> 11) as f64 / (1u64 << 53) as f64) < rate
+    }
+
+    /// Whether the next record is in the input sample, and so will be
+    /// evaluated rather than removed.
+    pub fn in_sample(&self) -> bool {
+        self.sampled(None)
+    }
+
+    /// Move on to scope `scope`; record indexes restart at zero.
+    pub fn scope(&mut self, scope: usize) {
+        self.scope = scope;
+        self.record = 0;
+        self.scope_decision = None;
+    }
+
+    /// Note the replacements `snapshot`'s policies redact with, when record
+    /// diffs are on.
+    fn redactions(&mut self, snapshot: &PolicySnapshot) {
+        if !self.diffs {
+            return;
+        }
+        self.redactions = snapshot
+            .iter()
+            .filter(|entry| entry.policy.enabled())
+            .filter_map(|entry| entry.policy.log_target()?.transform.as_ref())
+            .flat_map(|transform| &transform.redact)
+            .map(|redact| redact.replacement.clone())
+            .collect();
+    }
+
+    /// Keep the next record as it is before evaluation, to diff against in
+    /// [`Recorder::changes`]. Does nothing unless record diffs are on.
+    pub fn capture<T: serde::Serialize>(&mut self, record: &T) {
+        if self.diffs {
+            self.before = serde_json::to_value(record).ok();
+        }
+    }
+
+    /// Attach what evaluation changed in the record to its result, if it was
+    /// captured and evaluated.
+    pub fn changes<T: serde::Serialize>(&mut self, record: &T) {
+        let Some(before) = self.before.take() else {
+            return;
+        };
+        if let (Some(result), Ok(after)) = (self.results.last_mut(), serde_json::to_value(record)) {
+            result.changes = Some(diff::diff(&before, &after, &self.redactions));
+        }
+    }
+
+    /// Note the next log record's severity text, for its result to list if
+    /// the severity table doesn't know it.
+    pub fn severity_text(&mut self, text: &str) {
+        self.unknown_severity = self
+            .severity_table
+            .as_deref()
+            .filter(|table| !text.is_empty() && !table.knows(text))
+            .map(|_| text.to_string());
+    }
+
+    pub fn metric_granularity(&self) -> MetricGranularity {
+        self.granularity
+    }
+
+    pub fn envelope_retention(&self) -> EnvelopeRetention {
+        self.retention
+    }
+
+    pub fn resource_mutation(&self) -> ResourceMutation {
+        self.mutation
+    }
+
+    pub fn suppressed_fields(&self) -> Arc<eval::SuppressedFields> {
+        Arc::clone(&self.suppressed_fields)
+    }
+
+    /// Where record contexts log the engine's requests, when they're traced.
+    pub fn engine_trace(&self) -> Option<Arc<engine_trace::Log>> {
+        self.engine_trace.clone()
+    }
+
+    /// The aliases record contexts fall back on, when there are any.
+    pub fn aliasing(&self) -> Option<Arc<semconv::Aliasing>> {
+        self.aliasing.clone()
+    }
+
+    /// The table record contexts match severity texts through, when they're
+    /// normalized.
+    pub fn severity_table(&self) -> Option<Arc<severity::Table>> {
+        self.severity_table.clone()
+    }
+
+    /// Where record contexts count the engine's lookups, when they're
+    /// counted.
+    pub fn selector_stats(&self) -> Option<Arc<selector_stats::Stats>> {
+        self.selector_stats.clone()
+    }
+
+    /// The attributes [`EvalOptions::annotate_decisions`] puts on the record
+    /// just decided; none when it's off or the record wasn't evaluated.
+    pub fn annotation(&self) -> Vec<(String, String)> {
+        let (Some(keys), true, Some(r)) = (&self.annotate, self.decided, self.results.last())
+        else {
+            return Vec::new();
+        };
+        let mut annotation = vec![(keys.decision.clone(), decision_name(&r.result).to_string())];
+        if let Some(policy) = decision_policy(&r.result) {
+            annotation.push((keys.policy.clone(), policy.to_string()));
+        }
+        annotation
+    }
+
+    /// Evaluate the next record and say whether it survives according to
+    /// `verdict`. After an error nothing more is evaluated and every
+    /// remaining record is kept.
+    pub fn decide(
+        &mut self,
+        evaluate: impl FnOnce() -> Result<EvaluateResult, PolicyError>,
+        verdict: fn(&EvaluateResult) -> bool,
+    ) -> bool {
+        let kept = self.decide_at(None, evaluate, verdict);
+        self.record += 1;
+        kept
+    }
+
+    /// [`decide`](Self::decide) for one datapoint of the current metric,
+    /// which stays current until [`next_record`](Self::next_record).
+    pub fn decide_datapoint(
+        &mut self,
+        datapoint: usize,
+        evaluate: impl FnOnce() -> Result<EvaluateResult, PolicyError>,
+        verdict: fn(&EvaluateResult) -> bool,
+    ) -> bool {
+        self.decide_at(Some(datapoint), evaluate, verdict)
+    }
+
+    /// Move on from a metric decided datapoint by datapoint.
+    pub fn next_record(&mut self) {
+        self.record += 1;
+    }
+
+    /// `evaluate`, or the current scope's decision when it can be reused.
+    fn evaluate_in_scope(
+        &mut self,
+        evaluate: impl FnOnce() -> Result<EvaluateResult, PolicyError>,
+    ) -> Result<EvaluateResult, PolicyError> {
+        let Some(stats) = &self.scope_stats else {
+            return evaluate();
+        };
+        if let Some(decision) = &self.scope_decision {
+            return Ok(decision.reuse(stats));
+        }
+        let decision = ScopeDecision::make(stats, evaluate)?;
+        let result = decision.result().clone();
+        self.scope_decision = Some(decision);
+        Ok(result)
+    }
+
+    fn decide_at(
+        &mut self,
+        datapoint: Option<usize>,
+        evaluate: impl FnOnce() -> Result<EvaluateResult, PolicyError>,
+        verdict: fn(&EvaluateResult) -> bool,
+    ) -> bool {
+        self.decided = false;
+        if self.error.is_some() {
+            self.before = None;
+            return true;
+        }
+        if !self.sampled(datapoint) {
+            self.before = None;
+            return false;
+        }
+        let started = self.timed.then(Instant::now);
+        let evaluated = if let Some(replay) = self.replay.as_mut() {
+            Ok(replay.next().unwrap_or(EvaluateResult::NoMatch))
+        } else if self.skip_engine {
+            Ok(EvaluateResult::NoMatch)
+        } else {
+            self.evaluate_in_scope(evaluate)
+        };
+        let result = match evaluated {
+            Ok(result) => result,
+            Err(e) => {
+                self.before = None;
+                self.error = Some(e);
+                return true;
+            }
+        };
+        let elapsed = started.map(|s| s.elapsed());
+        tracing::trace!(?result, "evaluated record");
+        let kept = verdict(&result);
+        self.results.push(RecordResult {
+            resource: self.resource,
+            scope: self.scope,
+            record: self.record,
+            datapoint,
+            result,
+            elapsed,
+            changes: None,
+            requests: self.engine_trace.as_deref().map(engine_trace::take),
+            aliases: self.aliasing.as_deref().map(semconv::Aliasing::take),
+            unknown_severity_text: self.unknown_severity.take(),
+            copy_of: None,
+        });
+        self.decided = true;
+        kept
+    }
+
+    fn finish(self) -> Result<Vec<RecordResult>, PolicyError> {
+        self.error.map_or(Ok(self.results), Err)
+    }
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Whether a record survives its evaluation. Trace evaluation never yields
+/// `RateLimit`, so the same verdict serves logs, traces and profiles.
+pub fn keeps(result: &EvaluateResult) -> bool {
+    match result {
+        EvaluateResult::Drop { .. } => false,
+        EvaluateResult::Sample { keep, .. } => *keep,
+        EvaluateResult::RateLimit { allowed, .. } => *allowed,
+        _ => true,
+    }
+}
+
+/// A short, stable name for the kind of decision: `no_match`, `keep`, `drop`,
+/// `sample` or `rate_limit`.
+pub fn decision_name(result: &EvaluateResult) -> &'static str {
+    match result {
+        EvaluateResult::NoMatch => "no_match",
+        EvaluateResult::Keep { .. } => "keep",
+        EvaluateResult::Drop { .. } => "drop",
+        EvaluateResult::Sample { .. } => "sample",
+        EvaluateResult::RateLimit { .. } => "rate_limit",
+    }
+}
+
+/// The id of the policy behind `result`, unless nothing matched.
+pub fn decision_policy(result: &EvaluateResult) -> Option<&str> {
+    match result {
+        EvaluateResult::NoMatch => None,
+        EvaluateResult::Keep { policy_id, .. }
+        | EvaluateResult::Drop { policy_id }
+        | EvaluateResult::Sample { policy_id, .. }
+        | EvaluateResult::RateLimit { policy_id, .. } => Some(policy_id),
+    }
+}
+
+/// Upsert an [`annotation`](Recorder::annotation) onto `attributes`.
+fn annotate(attributes: &mut Vec<otel::KeyValue>, annotation: &[(String, String)]) {
+    for (key, value) in annotation {
+        eval::set_attr(attributes, key, value);
+    }
+}
+
+/// Metrics are only ever dropped outright; sampling doesn't apply to them.
+fn keeps_metric(result: &EvaluateResult) -> bool {
+    !matches!(result, EvaluateResult::Drop { .. })
+}
+
+fn resource_attrs(resource: Option<&otel::Resource>) -> &[otel::KeyValue] {
+    resource.map_or(&[], |r| &r.attributes)
+}
+
+fn scope_attrs(scope: Option<&otel::InstrumentationScope>) -> &[otel::KeyValue] {
+    scope.map_or(&[], |s| &s.attributes)
+}
+
+impl SignalData for otel::LogsData {
+    const NAME: &'static str = "logs";
+    type Envelope = otel::ResourceLogs;
+
+    fn unknown_fields(&self) -> Vec<String> {
+        otel::LogsData::unknown_fields(self)
+    }
+
+    fn envelopes(&mut self) -> &mut Vec<otel::ResourceLogs> {
+        &mut self.resource_logs
+    }
+
+    fn resource(envelope: &otel::ResourceLogs) -> Option<&otel::Resource> {
+        envelope.resource.as_ref()
+    }
+
+    fn record_count(&self) -> usize {
+        self.resource_logs.iter().map(Self::envelope_records).sum()
+    }
+
+    fn envelope_records(rl: &otel::ResourceLogs) -> usize {
+        rl.scope_logs.iter().map(|sl| sl.log_records.len()).sum()
+    }
+
+    fn filter_envelope(
+        rl: &mut otel::ResourceLogs,
+        engine: &PolicyEngine,
+        snapshot: &PolicySnapshot,
+        records: &mut Recorder,
+        splits: &mut Vec<otel::ResourceLogs>,
+    ) -> bool {
+        if let Some(r) = rl.resource.as_mut() {
+            otel::prepare_attributes(&mut r.attributes);
+        }
+        let suppressed = records.suppressed_fields();
+        let trace = records.engine_trace();
+        let aliasing = records.aliasing();
+        let severity = records.severity_table();
+        let stats = records.selector_stats();
+        let copy_on_write = records.resource_mutation() == ResourceMutation::CopyOnWrite;
+        let mut moved = Vec::new();
+        let mut resource_index = eval::AttrIndex::new(resource_attrs(rl.resource.as_ref()));
+        for (i, sl) in rl.scope_logs.iter_mut().enumerate() {
+            records.scope(i);
+            if let Some(s) = sl.scope.as_mut() {
+                otel::prepare_attributes(&mut s.attributes);
+            }
+            let mut scope_index = eval::AttrIndex::new(scope_attrs(sl.scope.as_ref()));
+            sl.log_records.retain_mut(|rec| {
+                rec.prepare();
+                records.severity_text(&rec.severity_text);
+                records.capture(&*rec);
+                let mut copies =
+                    copy_on_write.then(|| Copies::new(rl.resource.as_ref(), sl.scope.as_ref()));
+                let (resource, scope, r_index, s_index) = match copies.as_mut() {
+                    Some(c) => (
+                        c.resource.as_mut(),
+                        c.scope.as_mut(),
+                        &mut c.resource_index,
+                        &mut c.scope_index,
+                    ),
+                    None => (
+                        rl.resource.as_mut(),
+                        sl.scope.as_mut(),
+                        &mut resource_index,
+                        &mut scope_index,
+                    ),
+                };
+                let mut ctx = engine_trace::Traced {
+                    inner: eval::Suppress {
+                        inner: eval::MutLogContext {
+                            record: rec,
+                            resource,
+                            scope,
+                            resource_index: r_index,
+                            scope_index: s_index,
+                            resource_schema_url: &rl.schema_url,
+                            scope_schema_url: &sl.schema_url,
+                            aliasing: aliasing.as_deref(),
+                            severity: severity.as_deref(),
+                        },
+                        fields: &suppressed.log,
+                    },
+                    log: trace.as_deref(),
+                    stats: stats.as_deref(),
+                };
+                let kept =
+                    records.decide(|| engine.evaluate_and_transform(snapshot, &mut ctx), keeps);
+                records.changes(&*rec);
+                if kept {
+                    annotate(&mut rec.attributes, &records.annotation());
+                }
+                if let Some(copies) = copies.filter(|_| kept) {
+                    let resource = copies
+                        .resource
+                        .filter(|r| !same(&rl.resource.as_ref(), &Some(r)));
+                    if resource.is_some() || !same(&sl.scope, &copies.scope) {
+                        moved.push(Moved {
+                            from: i,
+                            resource,
+                            scope: copies.scope,
+                            record: std::mem::take(rec),
+                        });
+                        return false;
+                    }
+                }
+                kept
+            });
+        }
+        if !moved.is_empty() {
+            *splits = split_logs(rl, moved);
+        }
+        rl.scope_logs.retain(|sl| !sl.log_records.is_empty());
+        !rl.scope_logs.is_empty()
+    }
+}
+
+/// A log record's own copies of its resource and scope, for
+/// [`ResourceMutation::CopyOnWrite`].
+struct Copies {
+    resource: Option<otel::Resource>,
+    scope: Option<otel::InstrumentationScope>,
+    resource_index: eval::AttrIndex,
+    scope_index: eval::AttrIndex,
+}
+
+impl Copies {
+    fn new(resource: Option<&otel::Resource>, scope: Option<&otel::InstrumentationScope>) -> Self {
+        Copies {
+            resource_index: eval::AttrIndex::new(resource_attrs(resource)),
+            scope_index: eval::AttrIndex::new(scope_attrs(scope)),
+            resource: resource.cloned(),
+            scope: scope.cloned(),
+        }
+    }
+}
+
+/// A kept log record whose copies a transform rewrote, moved out of its
+/// scope.
+struct Moved {
+    /// Index of the scope it came from.
+    from: usize,
+    /// Its resource, if that was rewritten.
+    resource: Option<otel::Resource>,
+    scope: Option<otel::InstrumentationScope>,
+    record: otel::LogRecord,
+}
+
+/// Put each `moved` record under its own scope and resource: new scopes go
+/// right after the ones they came from, and new resources into the
+/// envelopes returned, in order.
+fn split_logs(rl: &mut otel::ResourceLogs, moved: Vec<Moved>) -> Vec<otel::ResourceLogs> {
+    // New scopes, each with the index of the scope it split from.
+    let mut scopes: Vec<(usize, otel::ScopeLogs)> = Vec::new();
+    let mut envelopes: Vec<(otel::Resource, Vec<(usize, otel::ScopeLogs)>)> = Vec::new();
+    for m in moved {
+        let target = match m.resource {
+            None => &mut scopes,
+            Some(resource) => {
+                let at = match envelopes.iter().position(|(r, _)| same(r, &resource)) {
+                    Some(at) => at,
+                    None => {
+                        envelopes.push((resource, Vec::new()));
+                        envelopes.len() - 1
+                    }
+                };
+                &mut envelopes[at].1
+            }
+        };
+        let at = match target
+            .iter()
+            .position(|(from, sl)| *from == m.from && same(&sl.scope, &m.scope))
+        {
+            Some(at) => at,
+            None => {
+                let source = &rl.scope_logs[m.from];
+                target.push((
+                    m.from,
+                    otel::ScopeLogs {
+                        scope: m.scope,
+                        log_records: Vec::new(),
+                        schema_url: source.schema_url.clone(),
+                        extra: source.extra.clone(),
+                    },
+                ));
+                target.len() - 1
+            }
+        };
+        target[at].1.log_records.push(m.record);
+    }
+    if !scopes.is_empty() {
+        // A stable sort by origin puts each new scope after its original.
+        let mut all: Vec<(usize, otel::ScopeLogs)> = std::mem::take(&mut rl.scope_logs)
+            .into_iter()
+            .enumerate()
+            .chain(scopes)
+            .collect();
+        all.sort_by_key(|(from, _)| *from);
+        rl.scope_logs = all.into_iter().map(|(_, sl)| sl).collect();
+    }
+    envelopes
+        .into_iter()
+        .map(|(resource, scopes)| otel::ResourceLogs {
+            resource: Some(resource),
+            scope_logs: scopes.into_iter().map(|(_, sl)| sl).collect(),
+            schema_url: rl.schema_url.clone(),
+            extra: rl.extra.clone(),
+        })
+        .collect()
+}
+
+/// Whether `a` and `b` serialize alike; the OTLP types don't compare.
+fn same<T: serde::Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+impl SignalData for otel::MetricsData {
+    const NAME: &'static str = "metrics";
+    type Envelope = otel::ResourceMetrics;
+
+    fn unknown_fields(&self) -> Vec<String> {
+        otel::MetricsData::unknown_fields(self)
+    }
+
+    fn envelopes(&mut self) -> &mut Vec<otel::ResourceMetrics> {
+        &mut self.resource_metrics
+    }
+
+    fn resource(envelope: &otel::ResourceMetrics) -> Option<&otel::Resource> {
+        envelope.resource.as_ref()
+    }
+
+    fn record_count(&self) -> usize {
+        self.resource_metrics
+            .iter()
+            .map(Self::envelope_records)
+            .sum()
+    }
+
+    fn envelope_records(rm: &otel::ResourceMetrics) -> usize {
+        rm.scope_metrics.iter().map(|sm| sm.metrics.len()).sum()
+    }
+
+    fn datapoint_count(&self) -> usize {
+        self.resource_metrics
+            .iter()
+            .flat_map(|rm| &rm.scope_metrics)
+            .flat_map(|sm| &sm.metrics)
+            .map(|m| m.data.as_ref().map_or(0, |d| d.datapoint_count()).max(1))
+            .sum()
+    }
+
+    fn filter_envelope(
+        rm: &mut otel::ResourceMetrics,
+        engine: &PolicyEngine,
+        snapshot: &PolicySnapshot,
+        records: &mut Recorder,
+        _splits: &mut Vec<otel::ResourceMetrics>,
+    ) -> bool {
+        let suppressed = records.suppressed_fields();
+        let trace = records.engine_trace();
+        let aliasing = records.aliasing();
+        let stats = records.selector_stats();
+        let resource_index = eval::AttrIndex::new(resource_attrs(rm.resource.as_ref()));
+        for (i, sm) in rm.scope_metrics.iter_mut().enumerate() {
+            records.scope(i);
+            let scope_index = eval::AttrIndex::new(scope_attrs(sm.scope.as_ref()));
+            sm.metrics.retain_mut(|m| {
+                let points = match &m.data {
+                    Some(data) if records.metric_granularity() == MetricGranularity::Datapoint => {
+                        data.datapoint_attributes()
+                    }
+                    _ => Vec::new(),
+                };
+                let ctx = eval::MetricContext {
+                    metric: m,
+                    datapoint_attributes: m
+                        .data
+                        .as_ref()
+                        .map(|d| d.first_datapoint_attributes())
+                        .unwrap_or(&[]),
+                    resource: rm.resource.as_ref(),
+                    scope: sm.scope.as_ref(),
+                    resource_index: &resource_index,
+                    scope_index: &scope_index,
+                    resource_schema_url: &rm.schema_url,
+                    scope_schema_url: &sm.schema_url,
+                    aliasing: aliasing.as_deref(),
+                };
+                if points.is_empty() {
+                    let ctx = engine_trace::Traced {
+                        inner: eval::Suppress {
+                            inner: ctx,
+                            fields: &suppressed.metric,
+                        },
+                        log: trace.as_deref(),
+                        stats: stats.as_deref(),
+                    };
+                    let kept = records.decide(|| engine.evaluate(snapshot, &ctx), keeps_metric);
+                    if let Some(data) = m.data.as_mut().filter(|_| kept) {
+                        let annotation = records.annotation();
+                        data.update_datapoint_attributes(None, |attrs| {
+                            annotate(attrs, &annotation)
+                        });
+                    }
+                    return kept;
+                }
+                let mut annotations = Vec::new();
+                let keep: Vec<bool> = points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, attrs)| {
+                        let ctx = engine_trace::Traced {
+                            inner: eval::Suppress {
+                                inner: eval::MetricContext {
+                                    datapoint_attributes: attrs,
+                                    ..ctx
+                                },
+                                fields: &suppressed.metric,
+                            },
+                            log: trace.as_deref(),
+                            stats: stats.as_deref(),
+                        };
+                        let kept = records.decide_datapoint(
+                            i,
+                            || engine.evaluate(snapshot, &ctx),
+                            keeps_metric,
+                        );
+                        if kept {
+                            annotations.push((i, records.annotation()));
+                        }
+                        kept
+                    })
+                    .collect();
+                records.next_record();
+                drop(points);
+                if let Some(data) = &mut m.data {
+                    for (i, annotation) in &annotations {
+                        data.update_datapoint_attributes(Some(*i), |attrs| {
+                            annotate(attrs, annotation)
+                        });
+                    }
+                    data.retain_datapoints(&keep);
+                }
+                keep.contains(&true)
+            });
+        }
+        rm.scope_metrics.retain(|sm| !sm.metrics.is_empty());
+        !rm.scope_metrics.is_empty()
+    }
+}
+
+impl SignalData for otel::TracesData {
+    const NAME: &'static str = "traces";
+    type Envelope = otel::ResourceSpans;
+
+    fn unknown_fields(&self) -> Vec<String> {
+        otel::TracesData::unknown_fields(self)
+    }
+
+    fn envelopes(&mut self) -> &mut Vec<otel::ResourceSpans> {
+        &mut self.resource_spans
+    }
+
+    fn resource(envelope: &otel::ResourceSpans) -> Option<&otel::Resource> {
+        envelope.resource.as_ref()
+    }
+
+    fn record_count(&self) -> usize {
+        self.resource_spans.iter().map(Self::envelope_records).sum()
+    }
+
+    fn envelope_records(rs: &otel::ResourceSpans) -> usize {
+        rs.scope_spans.iter().map(|ss| ss.spans.len()).sum()
+    }
+
+    fn filter_envelope(
+        rs: &mut otel::ResourceSpans,
+        engine: &PolicyEngine,
+        snapshot: &PolicySnapshot,
+        records: &mut Recorder,
+        _splits: &mut Vec<otel::ResourceSpans>,
+    ) -> bool {
+        if let Some(r) = rs.resource.as_mut() {
+            otel::prepare_attributes(&mut r.attributes);
+        }
+        let suppressed = records.suppressed_fields();
+        let trace = records.engine_trace();
+        let aliasing = records.aliasing();
+        let stats = records.selector_stats();
+        let resource_index = eval::AttrIndex::new(resource_attrs(rs.resource.as_ref()));
+        let any_keep = records.envelope_retention() == EnvelopeRetention::AnyKeep;
+        let mut any_kept = false;
+        for (i, ss) in rs.scope_spans.iter_mut().enumerate() {
+            records.scope(i);
+            if let Some(s) = ss.scope.as_mut() {
+                otel::prepare_attributes(&mut s.attributes);
+            }
+            let scope_index = eval::AttrIndex::new(scope_attrs(ss.scope.as_ref()));
+            let mut scratch = String::new();
+            ss.spans.retain_mut(|span| {
+                span.prepare();
+                records.capture(&*span);
+                let mut ctx = engine_trace::Traced {
+                    inner: eval::Suppress {
+                        inner: eval::MutTraceContext {
+                            span,
+                            resource: rs.resource.as_ref(),
+                            scope: ss.scope.as_ref(),
+                            resource_index: &resource_index,
+                            scope_index: &scope_index,
+                            resource_schema_url: &rs.schema_url,
+                            scope_schema_url: &ss.schema_url,
+                            aliasing: aliasing.as_deref(),
+                            scratch: &mut scratch,
+                        },
+                        fields: &suppressed.trace,
+                    },
+                    log: trace.as_deref(),
+                    stats: stats.as_deref(),
+                };
+                let sampled = records.in_sample();
+                let kept = records.decide(|| engine.evaluate_trace(snapshot, &mut ctx), keeps);
+                records.changes(&*span);
+                any_kept |= kept;
+                let survives = kept || (any_keep && sampled);
+                if survives {
+                    annotate(&mut span.attributes, &records.annotation());
+                }
+                survives
+            });
+        }
+        if any_keep && !any_kept {
+            rs.scope_spans.clear();
+        }
+        rs.scope_spans.retain(|ss| !ss.spans.is_empty());
+        !rs.scope_spans.is_empty()
+    }
+}
+
+impl SignalData for otel::ProfilesData {
+    const NAME: &'static str = "profiles";
+    type Envelope = otel::ResourceProfiles;
+
+    fn unknown_fields(&self) -> Vec<String> {
+        otel::ProfilesData::unknown_fields(self)
+    }
+
+    fn envelopes(&mut self) -> &mut Vec<otel::ResourceProfiles> {
+        &mut self.resource_profiles
+    }
+
+    fn resource(envelope: &otel::ResourceProfiles) -> Option<&otel::Resource> {
+        envelope.resource.as_ref()
+    }
+
+    fn record_count(&self) -> usize {
+        self.resource_profiles
+            .iter()
+            .map(Self::envelope_records)
+            .sum()
+    }
+
+    fn envelope_records(rp: &otel::ResourceProfiles) -> usize {
+        rp.scope_profiles.iter().map(|sp| sp.profiles.len()).sum()
+    }
+
+    fn filter_envelope(
+        rp: &mut otel::ResourceProfiles,
+        engine: &PolicyEngine,
+        snapshot: &PolicySnapshot,
+        records: &mut Recorder,
+        _splits: &mut Vec<otel::ResourceProfiles>,
+    ) -> bool {
+        if let Some(r) = rp.resource.as_mut() {
+            otel::prepare_attributes(&mut r.attributes);
+        }
+        let suppressed = records.suppressed_fields();
+        let trace = records.engine_trace();
+        let aliasing = records.aliasing();
+        let stats = records.selector_stats();
+        let resource_index = eval::AttrIndex::new(resource_attrs(rp.resource.as_ref()));
+        for (i, sp) in rp.scope_profiles.iter_mut().enumerate() {
+            records.scope(i);
+            if let Some(s) = sp.scope.as_mut() {
+                otel::prepare_attributes(&mut s.attributes);
+            }
+            let scope_index = eval::AttrIndex::new(scope_attrs(sp.scope.as_ref()));
+            let ctx = engine_trace::Traced {
+                inner: eval::Suppress {
+                    inner: eval::ProfileContext {
+                        resource: rp.resource.as_ref(),
+                        scope: sp.scope.as_ref(),
+                        resource_index: &resource_index,
+                        scope_index: &scope_index,
+                        resource_schema_url: &rp.schema_url,
+                        scope_schema_url: &sp.schema_url,
+                        aliasing: aliasing.as_deref(),
+                    },
+                    fields: &suppressed.log,
+                },
+                log: trace.as_deref(),
+                stats: stats.as_deref(),
+            };
+            // Every profile under one scope shares the same envelope, but each
+            // is still evaluated on its own so sampling and rate limits count
+            // profiles rather than scopes.
+            sp.profiles
+                .retain(|_| records.decide(|| engine.evaluate(snapshot, &ctx), keeps));
+        }
+        rp.scope_profiles.retain(|sp| !sp.profiles.is_empty());
+        !rp.scope_profiles.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use policy_rs::{FileProvider, PolicyRegistry};
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    /// A log envelope of `n` records, `r0`, `r1`..., observed at `observed`
+    /// plus their index and sampled by their `request_id`.
+    fn envelope(n: usize, observed: u64) -> Value {
+        let records: Vec<Value> = (0..n)
+            .map(|i| {
+                json!({
+                    "body": { "stringValue": format!("r{i}") },
+                    "observedTimeUnixNano": (observed + i as u64).to_string(),
+                    "attributes": [request_id(i)],
+                })
+            })
+            .collect();
+        json!({
+            "resource": { "attributes": [
+                { "key": "service.name", "value": { "stringValue": "api" } },
+            ] },
+            "scopeLogs": [{ "logRecords": records }],
+        })
+    }
+
+    /// Trace-id shaped, so sampling reads its randomness straight from the
+    /// value instead of hashing near-identical strings.
+    fn request_id(i: usize) -> Value {
+        let id = format!("{:032x}", (i as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        json!({ "key": "request_id", "value": { "stringValue": id } })
+    }
+
+    #[test]
+    fn a_deduplicated_copy_gets_the_originals_sample_decisions() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let policies = json!({ "policies": [{
+            "id": "sample-api",
+            "name": "sample-api",
+            "log": {
+                "match": [{ "resource_attribute": "service.name", "exact": "api" }],
+                "keep": "50%",
+                "sample_key": { "log_attribute": "request_id" },
+                "transform": { "add": [
+                    { "log_attribute": "sampled", "value": "yes", "upsert": true },
+                ] },
+            },
+        }] });
+        std::fs::write(file.path(), policies.to_string()).unwrap();
+        let registry = PolicyRegistry::new();
+        registry.subscribe(&FileProvider::new(file.path())).unwrap();
+        let mut logs: otel::LogsData = serde_json::from_value(json!({
+            "resourceLogs": [envelope(32, 1000), envelope(32, 2000)],
+        }))
+        .unwrap();
+        let opts = EvalOptions {
+            dedupe_envelopes: true,
+            dedupe_ignored_keys: vec!["observedTimeUnixNano".to_string()].into(),
+            ..EvalOptions::default()
+        };
+        let results =
+            evaluate_logs_data(&PolicyEngine::new(), &registry.snapshot(), &mut logs, &opts)
+                .unwrap();
+
+        let decisions = |resource: usize| -> Vec<(usize, bool)> {
+            results
+                .iter()
+                .filter(|r| r.resource == resource)
+                .map(|r| (r.record, keeps(&r.result)))
+                .collect()
+        };
+        let original = decisions(0);
+        assert_eq!(original.len(), 32);
+        assert_eq!(decisions(1), original);
+        assert!(original.iter().any(|(_, kept)| *kept));
+        assert!(original.iter().any(|(_, kept)| !*kept));
+        assert!(
+            results
+                .iter()
+                .all(|r| r.copy_of == (r.resource == 1).then_some(0))
+        );
+
+        // Each copy keeps the same records, transformed, with its own
+        // observed times.
+        let kept: Vec<usize> = original
+            .iter()
+            .filter(|(_, kept)| *kept)
+            .map(|(record, _)| *record)
+            .collect();
+        let [first, copy] = &logs.resource_logs[..] else {
+            panic!("expected both envelopes to survive");
+        };
+        for (rl, observed) in [(first, 1000), (copy, 2000)] {
+            let records = &rl.scope_logs[0].log_records;
+            let found: Vec<Value> = records
+                .iter()
+                .map(|r| serde_json::to_value(r).unwrap())
+                .map(|r| json!([r["body"], r["observedTimeUnixNano"], r["attributes"]]))
+                .collect();
+            let expected: Vec<Value> = kept
+                .iter()
+                .map(|i| {
+                    json!([
+                        { "stringValue": format!("r{i}") },
+                        (observed + *i as u64).to_string(),
+                        [
+                            request_id(*i),
+                            { "key": "sampled", "value": { "stringValue": "yes" } },
+                        ],
+                    ])
+                })
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! Spotting snapshots whose decisions can't vary between the records of one
+//! scope, so [`filter`](crate::filter) can evaluate the first record of each
+//! scope and reuse its decision for the rest.
+//!
+//! A snapshot qualifies for a signal when every enabled policy for it
+//! - matches only resource and scope selectors: attributes, schema URLs and,
+//!   for metrics and traces, the scope's name and version
+//! - keeps all or none, so no sampling or rate limit draws per record
+//! - transforms nothing, so evaluation never writes to the record; a trace
+//!   policy keeping everything at 100% writes the sampling threshold, so it
+//!   has to leave `keep` unset instead
+//!
+//! Evaluation's other side effect, the per-policy hit and miss counts, is
+//! replayed for every reused decision by [`ScopeDecision`].
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+use policy_rs::proto::tero::policy::v1::{
+    LogField, MetricField, TraceField, log_matcher, metric_matcher, trace_matcher,
+};
+use policy_rs::{EvaluateResult, Policy, PolicySnapshot, PolicyStats};
+
+/// Whether every enabled policy `snapshot` holds for `signal` (a
+/// [`SignalData::NAME`](crate::SignalData::NAME)) decides every record under
+/// one scope alike.
+pub(crate) fn scope_invariant(snapshot: &PolicySnapshot, signal: &str) -> bool {
+    snapshot
+        .iter()
+        .filter(|entry| entry.policy.enabled())
+        .all(|entry| match signal {
+            "logs" | "profiles" => log_invariant(&entry.policy),
+            "metrics" => metric_invariant(&entry.policy),
+            "traces" => trace_invariant(&entry.policy),
+            _ => false,
+        })
+}
+
+/// `true` for policies that aren't log policies: they don't take part.
+fn log_invariant(policy: &Policy) -> bool {
+    let Some(target) = policy.log_target() else {
+        return true;
+    };
+    matches!(target.keep.as_str(), "" | "all" | "none")
+        && target.transform.is_none()
+        && target.r#match.iter().all(|m| match &m.field {
+            Some(log_matcher::Field::LogField(f)) => matches!(
+                LogField::try_from(*f),
+                Ok(LogField::ResourceSchemaUrl | LogField::ScopeSchemaUrl)
+            ),
+            Some(
+                log_matcher::Field::ResourceAttribute(_) | log_matcher::Field::ScopeAttribute(_),
+            ) => true,
+            _ => false,
+        })
+}
+
+fn metric_invariant(policy: &Policy) -> bool {
+    let Some(target) = policy.metric_target() else {
+        return true;
+    };
+    target.r#match.iter().all(|m| match &m.field {
+        Some(metric_matcher::Field::MetricField(f)) => matches!(
+            MetricField::try_from(*f),
+            Ok(MetricField::ResourceSchemaUrl
+                | MetricField::ScopeSchemaUrl
+                | MetricField::ScopeName
+                | MetricField::ScopeVersion)
+        ),
+        Some(
+            metric_matcher::Field::ResourceAttribute(_) | metric_matcher::Field::ScopeAttribute(_),
+        ) => true,
+        _ => false,
+    })
+}
+
+fn trace_invariant(policy: &Policy) -> bool {
+    let Some(target) = policy.trace_target() else {
+        return true;
+    };
+    target.keep.as_ref().is_none_or(|k| k.percentage == 0.0)
+        && target.r#match.iter().all(|m| match &m.field {
+            Some(trace_matcher::Field::TraceField(f)) => matches!(
+                TraceField::try_from(*f),
+                Ok(TraceField::ResourceSchemaUrl
+                    | TraceField::ScopeSchemaUrl
+                    | TraceField::ScopeName
+                    | TraceField::ScopeVersion)
+            ),
+            Some(
+                trace_matcher::Field::ResourceAttribute(_)
+                | trace_matcher::Field::ScopeAttribute(_),
+            ) => true,
+            _ => false,
+        })
+}
+
+/// The decision made for the current scope, and what making it added to
+/// each policy's hit and miss counts.
+pub(crate) struct ScopeDecision {
+    result: EvaluateResult,
+    added: Vec<(u64, u64)>,
+}
+
+/// Every policy's stats in `snapshot`, in snapshot order.
+pub(crate) fn policy_stats(snapshot: &PolicySnapshot) -> Vec<Arc<PolicyStats>> {
+    snapshot
+        .iter()
+        .map(|entry| Arc::clone(&entry.stats))
+        .collect()
+}
+
+fn counts(stats: &[Arc<PolicyStats>]) -> Vec<(u64, u64)> {
+    stats
+        .iter()
+        .map(|s| {
+            (
+                s.match_hits.load(Ordering::Relaxed),
+                s.match_misses.load(Ordering::Relaxed),
+            )
+        })
+        .collect()
+}
+
+impl ScopeDecision {
+    /// Run `evaluate` and note what it added to `stats`. Nothing else may
+    /// evaluate against the snapshot meanwhile.
+    pub(crate) fn make<E>(
+        stats: &[Arc<PolicyStats>],
+        evaluate: impl FnOnce() -> Result<EvaluateResult, E>,
+    ) -> Result<ScopeDecision, E> {
+        let before = counts(stats);
+        let result = evaluate()?;
+        let added = counts(stats)
+            .into_iter()
+            .zip(before)
+            .map(|((hits, misses), (hits0, misses0))| (hits - hits0, misses - misses0))
+            .collect();
+        Ok(ScopeDecision { result, added })
+    }
+
+    pub(crate) fn result(&self) -> &EvaluateResult {
+        &self.result
+    }
+
+    /// The decision again for another record, counted as evaluating it would
+    /// have.
+    pub(crate) fn reuse(&self, stats: &[Arc<PolicyStats>]) -> EvaluateResult {
+        for (s, &(hits, misses)) in stats.iter().zip(&self.added) {
+            if hits > 0 {
+                s.match_hits.fetch_add(hits, Ordering::Relaxed);
+            }
+            if misses > 0 {
+                s.match_misses.fetch_add(misses, Ordering::Relaxed);
+            }
+        }
+        self.result.clone()
+    }
+}
@@ -0,0 +1,48 @@
+//! The runner's evaluation core: OTel-shaped payload types, the policy
+//! evaluation contexts over them, and whole-payload filtering.
+//!
+//! The `runner-rs` binary is a thin layer over this: it loads policies,
+//! parses the input into one of the [`otel`] payload types, filters it with
+//! the matching `evaluate_*_data` function and writes what's left. Records
+//! held one at a time go through [`evaluate_value`] instead.
+//!
+//! ```
+//! use policy_rs::{FileProvider, PolicyEngine, PolicyRegistry};
+//! use runner_rs::{EvalOptions, evaluate_logs_data, otel};
+//!
+//! let registry = PolicyRegistry::new();
+//! registry.subscribe(&FileProvider::new("../../testcases/logs_exact_drop/policies.json"))?;
+//! let snapshot = registry.snapshot();
+//!
+//! let input = std::fs::read_to_string("../../testcases/logs_exact_drop/input.json")?;
+//! let mut logs: otel::LogsData = serde_json::from_str(&input)?;
+//! let results = evaluate_logs_data(&PolicyEngine::new(), &snapshot, &mut logs, &EvalOptions::default())?;
+//! for r in &results {
+//!     println!("{}/{}/{}: {:?}", r.resource, r.scope, r.record, r.result);
+//! }
+//! // The health check is dropped; the login passes through.
+//! assert_eq!(logs.resource_logs[0].scope_logs[0].log_records.len(), 1);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub mod check;
+pub mod diff;
+pub mod engine_trace;
+pub mod eval;
+mod filter;
+mod invariant;
+pub mod otel;
+pub mod selector_display;
+pub mod selector_stats;
+pub mod semconv;
+pub mod severity;
+mod single;
+pub mod tracestate;
+
+pub use filter::{
+    DecisionKeys, EnvelopeRetention, EvalOptions, MetricGranularity, RecordResult, Recorder,
+    ResourceMutation, SignalData, decision_name, decision_policy, evaluate_logs_data,
+    evaluate_metrics_data, evaluate_profiles_data, evaluate_routed, evaluate_signal,
+    evaluate_traces_data, keeps,
+};
+pub use single::{Signal, ValueError, ValueResult, evaluate_value};
@@ -0,0 +1,291 @@
+//! `runner-rs lint`: static checks over a loaded policy snapshot.
+//!
+//! Each finding carries a stable code so CI can `--deny` the ones it cares
+//! about:
+//! - `L001` a policy can never decide because a drop policy for the same
+//!   signal matches whenever it does (its matchers are a subset), and the
+//!   most restrictive decision always wins
+//! - `L002` a policy failed to compile (bad regex, invalid keep, ...)
+//! - `L003` a sampling percentage outside 0–100
+//!
+//! Selectors can't name a field from the wrong signal: each signal's matchers
+//! are typed in the proto, so such a policy fails to parse rather than lint.
+
+use std::path::PathBuf;
+
+use policy_rs::{Policy, PolicyRegistry, PolicySnapshot};
+
+use crate::provider::{ChainProvider, MergeStrategy};
+use crate::status::{self, Failure};
+
+#[derive(clap::Args)]
+pub struct LintArgs {
+    /// A policy file, a directory whose `*.json` files are all loaded, or
+    /// `env:[VAR]` (repeatable; later sources take precedence).
+    #[arg(long, required = true)]
+    policies: Vec<PathBuf>,
+    /// What to do when two sources define the same policy id.
+    #[arg(long, value_enum, default_value_t)]
+    merge_strategy: MergeStrategy,
+    /// Exit non-zero when a finding with this code is reported (repeatable).
+    #[arg(long, value_name = "CODE")]
+    deny: Vec<String>,
+}
+
+struct Finding {
+    code: &'static str,
+    policy_id: String,
+    message: String,
+}
+
+pub fn run(args: &LintArgs) -> status::Result {
+    let registry = PolicyRegistry::new();
+    if let Err(e) = registry.subscribe(&ChainProvider::new(&args.policies, args.merge_strategy)) {
+        return Err(Failure(format!("failed to load policies: {e}")));
+    }
+    let snapshot = registry.snapshot();
+
+    let findings = lint(&snapshot);
+    for f in &findings {
+        println!("{} {}: {}", f.code, f.policy_id, f.message);
+    }
+    let denied = findings
+        .iter()
+        .filter(|f| args.deny.iter().any(|code| code == f.code))
+        .count();
+    if denied > 0 {
+        return Err(Failure(format!("{denied} denied finding(s)")));
+    }
+    Ok(())
+}
+
+fn lint(snapshot: &PolicySnapshot) -> Vec<Finding> {
+    let policies: Vec<&Policy> = snapshot
+        .iter()
+        .map(|entry| &entry.policy)
+        .filter(|p| p.enabled())
+        .collect();
+    let mut findings = Vec::new();
+    for policy in &policies {
+        for error in snapshot.compilation_errors_for(policy.id()) {
+            findings.push(Finding {
+                code: "L002",
+                policy_id: policy.id().to_string(),
+                message: error.clone(),
+            });
+        }
+        if let Some(pct) = sample_percentage(policy)
+            && !(0.0..=100.0).contains(&pct)
+        {
+            findings.push(Finding {
+                code: "L003",
+                policy_id: policy.id().to_string(),
+                message: format!("sampling percentage {pct} is outside 0-100"),
+            });
+        }
+        // A drop that failed to compile never runs, so it shadows nothing.
+        if let Some(drop) = policies.iter().find(|other| {
+            other.id() != policy.id()
+                && snapshot.compilation_errors_for(other.id()).is_empty()
+                && shadows(other, policy)
+        }) {
+            findings.push(Finding {
+                code: "L001",
+                policy_id: policy.id().to_string(),
+                message: format!(
+                    "never decides: drop policy '{}' matches whenever it does",
+                    drop.id()
+                ),
+            });
+        }
+    }
+    findings
+}
+
+/// Whether `drop` is a drop-everything policy whose matchers all appear in
+/// `other` for the same signal, so `other` can only match alongside it. Two
+/// drops shadow nothing: either one deciding gives the same outcome.
+fn shadows(drop: &Policy, other: &Policy) -> bool {
+    if !drops_all(drop) || drops_all(other) {
+        return false;
+    }
+    fn subset<T: PartialEq>(a: &[T], b: &[T]) -> bool {
+        a.iter().all(|m| b.contains(m))
+    }
+    if let (Some(d), Some(o)) = (drop.log_target(), other.log_target()) {
+        return subset(&d.r#match, &o.r#match);
+    }
+    if let (Some(d), Some(o)) = (drop.metric_target(), other.metric_target()) {
+        return subset(&d.r#match, &o.r#match);
+    }
+    if let (Some(d), Some(o)) = (drop.trace_target(), other.trace_target()) {
+        return subset(&d.r#match, &o.r#match);
+    }
+    false
+}
+
+fn drops_all(policy: &Policy) -> bool {
+    if let Some(t) = policy.log_target() {
+        return t.keep == "none";
+    }
+    if let Some(t) = policy.metric_target() {
+        return !t.keep;
+    }
+    if let Some(t) = policy.trace_target() {
+        return t.keep.as_ref().is_some_and(|k| k.percentage == 0.0);
+    }
+    false
+}
+
+fn sample_percentage(policy: &Policy) -> Option<f64> {
+    if let Some(t) = policy.log_target() {
+        return t.keep.strip_suffix('%')?.trim().parse().ok();
+    }
+    policy
+        .trace_target()
+        .and_then(|t| t.keep.as_ref())
+        .map(|k| f64::from(k.percentage))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn write_policies(policies: Value) -> tempfile::NamedTempFile {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), json!({ "policies": policies }).to_string()).unwrap();
+        file
+    }
+
+    fn findings(policies: Value) -> Vec<(&'static str, String)> {
+        let file = write_policies(policies);
+        let registry = PolicyRegistry::new();
+        registry
+            .subscribe(&policy_rs::FileProvider::new(file.path()))
+            .unwrap();
+        lint(&registry.snapshot())
+            .into_iter()
+            .map(|f| (f.code, f.policy_id))
+            .collect()
+    }
+
+    fn log_policy(id: &str, matchers: Value, keep: &str) -> Value {
+        json!({ "id": id, "name": id, "log": { "match": matchers, "keep": keep } })
+    }
+
+    fn trace_policy(id: &str, percentage: f64) -> Value {
+        json!({
+            "id": id,
+            "name": id,
+            "trace": { "match": [service(id)], "keep": { "percentage": percentage } },
+        })
+    }
+
+    fn service(name: &str) -> Value {
+        json!({ "resource_attribute": "service.name", "exact": name })
+    }
+
+    fn severity(text: &str) -> Value {
+        json!({ "log_field": "severity_text", "exact": text })
+    }
+
+    #[test]
+    fn l001_flags_a_policy_a_drop_always_preempts() {
+        let found = findings(json!([
+            log_policy("drop-api", json!([service("api")]), "none"),
+            log_policy(
+                "keep-api-errors",
+                json!([service("api"), severity("ERROR")]),
+                "all"
+            ),
+        ]));
+        assert_eq!(found, [("L001", "keep-api-errors".to_string())]);
+    }
+
+    #[test]
+    fn l001_ignores_drops_that_match_less_often() {
+        let found = findings(json!([
+            log_policy(
+                "drop-api-debug",
+                json!([service("api"), severity("DEBUG")]),
+                "none"
+            ),
+            log_policy("keep-api", json!([service("api")]), "all"),
+            log_policy("drop-web", json!([service("web")]), "none"),
+            log_policy(
+                "drop-web-debug",
+                json!([service("web"), severity("DEBUG")]),
+                "none"
+            ),
+        ]));
+        assert_eq!(found, []);
+    }
+
+    #[test]
+    fn l002_reports_policies_that_fail_to_compile() {
+        let bad = log_policy(
+            "bad-regex",
+            json!([{ "log_field": "body", "regex": "(" }]),
+            "none",
+        );
+        assert_eq!(findings(json!([bad])), [("L002", "bad-regex".to_string())]);
+    }
+
+    #[test]
+    fn l002_ignores_policies_that_compile() {
+        let good = log_policy(
+            "good",
+            json!([{ "log_field": "body", "regex": "^ok$" }]),
+            "none",
+        );
+        assert_eq!(findings(json!([good])), []);
+    }
+
+    #[test]
+    fn l003_flags_sampling_outside_0_to_100() {
+        let found = findings(json!([
+            trace_policy("over", 150.0),
+            trace_policy("under", -5.0)
+        ]));
+        assert_eq!(
+            found,
+            [("L003", "over".to_string()), ("L003", "under".to_string())]
+        );
+    }
+
+    #[test]
+    fn l003_accepts_the_bounds() {
+        let found = findings(json!([
+            log_policy("all", json!([service("api")]), "100%"),
+            log_policy("half", json!([service("web")]), "50%"),
+            trace_policy("none", 0.0),
+            trace_policy("every", 100.0),
+        ]));
+        assert_eq!(found, []);
+    }
+
+    #[test]
+    fn deny_fails_only_on_the_denied_codes() {
+        let file = write_policies(json!([
+            log_policy("drop-api", json!([service("api")]), "none"),
+            log_policy(
+                "keep-api-errors",
+                json!([service("api"), severity("ERROR")]),
+                "all"
+            ),
+        ]));
+        let args = |deny: &[&str]| LintArgs {
+            policies: vec![file.path().to_path_buf()],
+            merge_strategy: MergeStrategy::Strict,
+            deny: deny.iter().map(|code| code.to_string()).collect(),
+        };
+        assert!(run(&args(&[])).is_ok());
+        assert!(run(&args(&["L002", "L003"])).is_ok());
+        let Err(Failure(message)) = run(&args(&["L001"])) else {
+            panic!("a denied L001 finding should fail");
+        };
+        assert_eq!(message, "1 denied finding(s)");
+    }
+}
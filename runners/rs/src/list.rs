@@ -0,0 +1,52 @@
+//! `runner-rs list-policies`: which policies load, and where each came from.
+//!
+//! Prints one `<id>\t<signal>\t<source>` line per policy in snapshot order,
+//! where the source is the `--policies` argument that supplied its effective
+//! definition, then a count of enabled policies per signal on stderr. Useful
+//! for checking what `--merge-strategy override` resolved to, or why input of
+//! some signal never matches.
+
+use std::path::PathBuf;
+
+use policy_rs::PolicyRegistry;
+
+use crate::provider::{ChainProvider, MergeStrategy};
+use crate::status::{self, Failure};
+
+#[derive(clap::Args)]
+pub struct ListArgs {
+    /// A policy file, a directory whose `*.json` files are all loaded, or
+    /// `env:[VAR]` (repeatable; later sources take precedence).
+    #[arg(long, required = true)]
+    policies: Vec<PathBuf>,
+    /// What to do when two sources define the same policy id.
+    #[arg(long, value_enum, default_value_t)]
+    merge_strategy: MergeStrategy,
+}
+
+pub fn run(args: &ListArgs) -> status::Result {
+    let registry = PolicyRegistry::new();
+    let chain = ChainProvider::new(&args.policies, args.merge_strategy);
+    if let Err(e) = registry.subscribe(&chain) {
+        return Err(Failure(format!("failed to load policies: {e}")));
+    }
+    let mut counts = [("log", 0), ("metric", 0), ("trace", 0)];
+    for entry in registry.snapshot().iter() {
+        let id = entry.policy.id();
+        let signal = crate::policy_signal(&entry.policy);
+        println!(
+            "{id}\t{}\t{}",
+            signal.unwrap_or("-"),
+            chain.source_of(id).unwrap_or("-")
+        );
+        if let Some((_, n)) = counts.iter_mut().find(|(s, _)| Some(*s) == signal)
+            && entry.policy.enabled()
+        {
+            *n += 1;
+        }
+    }
+    for (signal, n) in counts {
+        eprintln!("{signal}: {n} enabled");
+    }
+    Ok(())
+}
@@ -1,32 +1,636 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::fs;
-use std::process;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-use clap::Parser;
+use cardinality::Profiled;
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
+use expect::Expecting;
 use policy_rs::{
-    ContentType, FileProvider, GrpcProvider, GrpcProviderConfig, HttpProvider, HttpProviderConfig,
-    PolicyEngine, PolicyProvider, PolicyRegistry,
+    ContentType, GrpcProvider, GrpcProviderConfig, HttpProvider, HttpProviderConfig, PolicyEngine,
+    PolicyProvider, PolicyRegistry,
 };
+use runner_rs::check::{self, CheckInput};
+use runner_rs::selector_display::{self, Selector};
+use runner_rs::selector_stats;
+use runner_rs::{EvalOptions, RecordResult, SignalData, evaluate_routed, otel};
+use runner_rs::{eval, semconv, severity};
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
+use stamp::Stamped;
+use status::Failure;
+use strip::Stripped;
+use temporality::Synthesize;
+use tracing::{debug, error, warn};
+use tracing_subscriber::filter::LevelFilter;
+use utf8::Replaced;
+use validate::Validated;
+use warnings::Warnings;
+use window::Windowed;
 
-mod eval;
-mod otel;
+mod atomic_file;
+mod cardinality;
+mod config;
+mod drops;
+mod expect;
+mod export;
+mod lint;
+mod list;
+mod manifest;
+mod match_test;
+mod object_url;
+mod profiles;
+mod prom_text;
+mod provider;
+mod shadow;
+mod stamp;
+mod status;
+mod strip;
+mod telemetry;
+mod temporality;
+mod tenant;
+mod timing;
+mod trace_engine;
+mod utf8;
+mod validate;
+mod warnings;
+mod window;
+mod zipkin;
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    args: Args,
+    /// Least severe diagnostics written to stderr.
+    #[arg(long, global = true, value_enum, default_value_t = LogLevel::Warn, env = "POLICY_RUNNER_LOG_LEVEL")]
+    log_level: LogLevel,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        env = "POLICY_RUNNER_LOG_FORMAT"
+    )]
+    log_format: LogFormat,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Report policies that can never decide, fail to compile, or sample
+    /// outside 0-100%.
+    Lint(lint::LintArgs),
+    /// Write the loaded policy snapshot as normalized, diffable JSON.
+    ExportSnapshot(export::ExportArgs),
+    /// List the loaded policies and the source each one came from.
+    ListPolicies(list::ListArgs),
+    /// Re-run a `--manifest` run and check its output hash still matches.
+    Replay(manifest::ReplayArgs),
+    /// Check a selector pattern against a sample value with the engine's own
+    /// matching.
+    MatchTest(match_test::MatchTestArgs),
+    /// Line up two `--trace-engine` traces record by record and report where
+    /// each differing record's requests first diverge.
+    CompareEngineTrace(trace_engine::CompareArgs),
+    /// List the `--profile` flag bundles, built-in and from a config file,
+    /// with the flags each sets.
+    Profiles(profiles::ProfilesArgs),
+}
+
+#[derive(clap::Args)]
 struct Args {
+    /// TOML file of run-mode flags, keyed by long flag name. Flags on the
+    /// command line win over `POLICY_RUNNER_*` variables, which win over it.
+    #[arg(long, env = "POLICY_RUNNER_CONFIG", value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Named bundle of flags, from the config file's `profiles` table or
+    /// built in (`conformance`, `replay`). Flags on the command line or in
+    /// `POLICY_RUNNER_*` variables win over it; it wins over the rest of
+    /// the config file.
+    #[arg(long, env = "POLICY_RUNNER_PROFILE", value_name = "NAME")]
+    profile: Option<String>,
+    /// A policy file, a directory whose `*.json` files are all loaded,
+    /// `env:[VAR]` to read them from an environment variable, or an `s3://`
+    /// or `gs://` URL (with the `object-store` feature). Repeatable; sources
+    /// are merged in the order given.
     #[arg(long)]
-    policies: Option<String>,
-    #[arg(long)]
+    policies: Vec<PathBuf>,
+    /// What to do when two `--policies` sources define the same policy id:
+    /// fail (`strict`) or let the later source win (`override`).
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        env = "POLICY_RUNNER_MERGE_STRATEGY"
+    )]
+    merge_strategy: provider::MergeStrategy,
+    #[arg(long, env = "POLICY_RUNNER_SERVER")]
     server: Option<String>,
-    #[arg(long)]
+    /// Environment variable holding a bearer token for `--server` requests.
+    #[arg(long, env = "POLICY_RUNNER_SERVER_TOKEN_ENV")]
+    server_token_env: Option<String>,
+    #[arg(long, env = "POLICY_RUNNER_GRPC")]
     grpc: Option<String>,
-    #[arg(long)]
-    input: String,
-    #[arg(long)]
-    output: String,
-    #[arg(long)]
-    stats: Option<String>,
-    #[arg(long)]
-    signal: String,
+    /// Resource attribute naming each envelope's tenant, for
+    /// `--tenant-policies`.
+    #[arg(
+        long,
+        value_name = "ATTRIBUTE",
+        requires = "tenant_policies",
+        env = "POLICY_RUNNER_TENANT_KEY"
+    )]
+    tenant_key: Option<String>,
+    /// `tenant=path` policy sets (repeatable). Envelopes of other tenants use
+    /// the main policy source if there is one, and pass through unevaluated
+    /// otherwise.
+    #[arg(
+        long,
+        value_name = "TENANT=PATH",
+        requires = "tenant_key",
+        value_delimiter = ',',
+        env = "POLICY_RUNNER_TENANT_POLICIES"
+    )]
+    tenant_policies: Vec<String>,
+    /// Candidate policies to evaluate against every record without affecting
+    /// any decision or transform (repeatable). What they would have done is
+    /// added to the annotations as `shadow` and totalled in the summary.
+    #[arg(
+        long,
+        value_name = "PATH",
+        env = "POLICY_RUNNER_SHADOW_POLICIES",
+        value_delimiter = ','
+    )]
+    shadow_policies: Vec<PathBuf>,
+    /// The input file, or an `s3://` or `gs://` URL (with the
+    /// `object-store` feature), which `--mmap` leaves unmapped.
+    #[arg(long, env = "POLICY_RUNNER_INPUT")]
+    input: Option<PathBuf>,
+    /// Read the input as OTLP JSON, as a Prometheus scrape dump in the text
+    /// exposition format or OpenMetrics (`prom-text`), or as a Zipkin v2
+    /// JSON dump (`zipkin-json`). The others are evaluated as the OTLP
+    /// metrics or traces payload they map to (see `prom_text` and
+    /// `zipkin`).
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        conflicts_with = "input_lines",
+        env = "POLICY_RUNNER_FORMAT"
+    )]
+    format: InputFormat,
+    /// Output file, or a directory to write `<input stem>.transformed.json`
+    /// into.
+    #[arg(long, env = "POLICY_RUNNER_OUTPUT")]
+    output: Option<PathBuf>,
+    /// Write the run's warnings, and what `--rate-limit-trace`,
+    /// `--record-diffs`, `--semconv-aliases`, `--shadow-policies`, `--utf8
+    /// lossy` and `--dedupe-envelopes` report, to this JSON file, keeping
+    /// `--output` plain OTLP.
+    #[arg(long, value_name = "PATH", env = "POLICY_RUNNER_ANNOTATIONS")]
+    annotations: Option<PathBuf>,
+    /// Split the output into standalone payloads of at most this many
+    /// records, written as `<output stem>.0001.json` and so on beside
+    /// `--output`, which then holds a manifest listing them. Resource
+    /// envelopes are never split across shards.
+    #[arg(
+        long,
+        value_name = "RECORDS",
+        env = "POLICY_RUNNER_OUTPUT_SHARD_RECORDS"
+    )]
+    output_shard_records: Option<usize>,
+    /// Create the `--output` directory if it doesn't exist.
+    #[arg(long, env = "POLICY_RUNNER_CREATE_OUTPUT_DIR")]
+    create_output_dir: bool,
+    #[arg(long, env = "POLICY_RUNNER_STATS")]
+    stats: Option<PathBuf>,
+    /// Detected from the payload's top-level key when omitted.
+    #[arg(long, env = "POLICY_RUNNER_SIGNAL")]
+    signal: Option<String>,
+    /// Read the input as JSON lines, one OTLP export per line as the
+    /// collector's file exporter writes them, each of any signal. A line's
+    /// signal is its own top-level `signal` field if it has one, then
+    /// `--signal`, then detected from the payload. The output is JSON lines
+    /// too, as are the annotations, each tagged with its input line's
+    /// `signal` and `line` number, and the summary totals each signal apart.
+    #[arg(
+        long,
+        conflicts_with_all = ["output_shard_records", "drops_sidecar", "suppress_field"],
+        env = "POLICY_RUNNER_INPUT_LINES"
+    )]
+    input_lines: bool,
+    /// Reject input carrying keys the OTLP types don't model instead of
+    /// passing them through to the output.
+    #[arg(long, env = "POLICY_RUNNER_STRICT_INPUT")]
+    strict_input: bool,
+    /// Check the input for values that parse but can't be real telemetry
+    /// (malformed ids, missing timestamps, metrics with no data, unknown
+    /// enum values) and warn about each, or fail with `strict`.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "warn",
+        env = "POLICY_RUNNER_CHECK_INPUT"
+    )]
+    check_input: Option<CheckMode>,
+    /// Fail instead of warning when no loaded policy applies to the input's
+    /// signal.
+    #[arg(long, env = "POLICY_RUNNER_STRICT_SIGNALS")]
+    strict_signals: bool,
+    /// Call the engine for every record: even when no loaded policy applies
+    /// to the input's signal, rather than deciding them all `no_match` up
+    /// front, and even when the policies can only decide a scope's records
+    /// alike, rather than reusing the first record's decision.
+    #[arg(long, env = "POLICY_RUNNER_NO_FAST_PATH")]
+    no_fast_path: bool,
+    /// Check each record's inline `expect` object (`{"decision": "drop",
+    /// "matched": ["policy-a"]}`) against its result, and exit non-zero,
+    /// after writing the output, if any record fails its expectation.
+    #[arg(long, env = "POLICY_RUNNER_ASSERT")]
+    assert: bool,
+    /// Exit non-zero, after writing the output, if any warning was raised.
+    #[arg(long, env = "POLICY_RUNNER_DENY_WARNINGS")]
+    deny_warnings: bool,
+    /// Refuse input files larger than this many bytes.
+    #[arg(long, value_name = "BYTES", env = "POLICY_RUNNER_MAX_INPUT_BYTES")]
+    max_input_bytes: Option<u64>,
+    /// Abort when the input holds more than this many records.
+    #[arg(long, value_name = "N", env = "POLICY_RUNNER_MAX_RECORDS_HARD")]
+    max_records_hard: Option<usize>,
+    /// Evaluate only this fraction (0-1] of the input's records, dropping the
+    /// rest unevaluated. Which records are picked depends only on `--seed`.
+    #[arg(long, value_name = "RATE", value_parser = parse_sample_rate, env = "POLICY_RUNNER_SAMPLE_INPUT")]
+    sample_input: Option<f64>,
+    /// Seed for `--sample-input`.
+    #[arg(long, default_value_t = 0, env = "POLICY_RUNNER_SEED")]
+    seed: u64,
+    /// Remove records timestamped before this RFC 3339 time before
+    /// evaluating (inclusive).
+    #[arg(long, value_name = "RFC3339", value_parser = window::parse_rfc3339, env = "POLICY_RUNNER_TIME_FROM")]
+    time_from: Option<u64>,
+    /// Remove records timestamped at or after this RFC 3339 time before
+    /// evaluating (exclusive).
+    #[arg(long, value_name = "RFC3339", value_parser = window::parse_rfc3339, env = "POLICY_RUNNER_TIME_TO")]
+    time_to: Option<u64>,
+    /// Keep or remove records with a zero or absent timestamp under
+    /// `--time-from`/`--time-to`.
+    #[arg(long, value_enum, default_value_t, env = "POLICY_RUNNER_TIME_MISSING")]
+    time_missing: window::Missing,
+    /// JSON table of older semantic-convention attribute names by schema
+    /// version (`{"1.20.0": {"http.method": "http.request.method"}}`).
+    /// Selectors for a current name that finds nothing fall back to its old
+    /// name on records under that schema. Matching only; transforms are
+    /// unaffected.
+    #[arg(long, value_name = "PATH", env = "POLICY_RUNNER_SEMCONV_ALIASES")]
+    semconv_aliases: Option<PathBuf>,
+    /// Match `severity_text` by its canonical spelling (`WARN` for `Warning`,
+    /// `warn` or `W`) from a built-in table. Output keeps the original text.
+    #[arg(long, env = "POLICY_RUNNER_NORMALIZE_SEVERITY_TEXT")]
+    normalize_severity_text: bool,
+    /// TOML table of extra `spelling = "CANONICAL"` entries for
+    /// `--normalize-severity-text`, overriding built-in ones.
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "normalize_severity_text",
+        env = "POLICY_RUNNER_SEVERITY_TEXT_TABLE"
+    )]
+    severity_text_table: Option<PathBuf>,
+    /// Make one decision per metric, matched against its first datapoint's
+    /// attributes, or one per datapoint. Under `datapoint` a metric is
+    /// dropped once none of its datapoints survive.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        env = "POLICY_RUNNER_METRIC_GRANULARITY"
+    )]
+    metric_granularity: MetricGranularity,
+    /// Filter spans one by one, or keep every span of a resource envelope
+    /// when any of them is kept and drop the envelope otherwise. Spans are
+    /// still decided one by one.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        env = "POLICY_RUNNER_ENVELOPE_RETENTION",
+        conflicts_with = "drops_sidecar"
+    )]
+    envelope_retention: EnvelopeRetention,
+    /// Let a transform's writes to a log record's resource or scope
+    /// attributes reach every record under them, or give the record its own
+    /// copies, splitting it off into a new scope or resource envelope.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        env = "POLICY_RUNNER_RESOURCE_MUTATION"
+    )]
+    resource_mutation: ResourceMutation,
+    /// Evaluate only the first of identical resource envelopes, such as a
+    /// retried export captured twice, and give each later copy its output
+    /// and decisions.
+    #[arg(
+        long,
+        conflicts_with = "parallel",
+        env = "POLICY_RUNNER_DEDUPE_ENVELOPES"
+    )]
+    dedupe_envelopes: bool,
+    /// Leave this object key out, at any depth, when comparing envelopes
+    /// for `--dedupe-envelopes`, e.g. `observedTimeUnixNano` (repeatable).
+    #[arg(
+        long,
+        value_name = "KEY",
+        requires = "dedupe_envelopes",
+        env = "POLICY_RUNNER_DEDUPE_IGNORE_KEY",
+        value_delimiter = ','
+    )]
+    dedupe_ignore_key: Vec<String>,
+    /// Rewrite every sum's and histogram's aggregation temporality to this
+    /// before evaluating, to exercise temporality policies both ways.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "TEMPORALITY",
+        env = "POLICY_RUNNER_SYNTHESIZE_TEMPORALITY"
+    )]
+    synthesize_temporality: Option<temporality::Temporality>,
+    /// Memory-map the input instead of reading it onto the heap.
+    #[arg(long, env = "POLICY_RUNNER_MMAP")]
+    mmap: bool,
+    /// Refuse input that isn't valid UTF-8, or replace each invalid
+    /// sequence with U+FFFD and list the records it touched.
+    #[arg(long, value_enum, default_value_t, env = "POLICY_RUNNER_UTF8")]
+    utf8: Utf8,
+    /// Load only policies whose id matches this glob (repeatable).
+    #[arg(
+        long,
+        value_name = "GLOB",
+        env = "POLICY_RUNNER_ONLY_POLICY",
+        value_delimiter = ','
+    )]
+    only_policy: Vec<String>,
+    /// Skip policies whose id matches this glob (repeatable).
+    #[arg(
+        long,
+        value_name = "GLOB",
+        env = "POLICY_RUNNER_EXCLUDE_POLICY",
+        value_delimiter = ','
+    )]
+    exclude_policy: Vec<String>,
+    /// Remove attributes whose key matches this glob from every resource,
+    /// scope and record right after parsing, so that neither policies nor
+    /// the output see them (repeatable).
+    #[arg(
+        long,
+        value_name = "GLOB",
+        env = "POLICY_RUNNER_STRIP_ATTRIBUTES",
+        value_delimiter = ','
+    )]
+    strip_attributes: Vec<String>,
+    /// Upsert `tero.policy.hash` and `tero.policy.runner_version`, naming
+    /// the policies and runner that produced the output, onto every output
+    /// envelope's resource, after evaluation.
+    #[arg(long, env = "POLICY_RUNNER_STAMP_RESOURCE")]
+    stamp_resource: bool,
+    /// Also stamp this attribute, or give a default one this value
+    /// (repeatable).
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        value_parser = stamp::parse_attribute,
+        requires = "stamp_resource",
+        env = "POLICY_RUNNER_STAMP_ATTRIBUTE",
+        value_delimiter = ','
+    )]
+    stamp_attribute: Vec<(String, String)>,
+    /// Upsert each surviving record's decision and deciding policy onto
+    /// its attributes (log records', spans', metrics' datapoints'), after
+    /// every transform, as `tero.decision` and `tero.policy`.
+    #[arg(long, env = "POLICY_RUNNER_ANNOTATE_DECISIONS")]
+    annotate_decisions: bool,
+    /// The attribute `--annotate-decisions` writes the decision to.
+    #[arg(
+        long,
+        value_name = "KEY",
+        default_value = "tero.decision",
+        requires = "annotate_decisions",
+        env = "POLICY_RUNNER_DECISION_KEY"
+    )]
+    decision_key: String,
+    /// The attribute `--annotate-decisions` writes the deciding policy's id
+    /// to.
+    #[arg(
+        long,
+        value_name = "KEY",
+        default_value = "tero.policy",
+        requires = "annotate_decisions",
+        env = "POLICY_RUNNER_DECISION_POLICY_KEY"
+    )]
+    decision_policy_key: String,
+    /// Check every surviving span's tracestate against the W3C rules once
+    /// evaluation and transforms are done, raising W008 for each invalid
+    /// one.
+    #[arg(long, env = "POLICY_RUNNER_VALIDATE_TRACESTATE")]
+    validate_tracestate: bool,
+    /// Keep the members of an invalid tracestate that pass, or remove its
+    /// span from the output.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        requires = "validate_tracestate",
+        env = "POLICY_RUNNER_TRACESTATE_INVALID"
+    )]
+    tracestate_invalid: validate::Invalid,
+    /// Hide this field from matchers, as if records didn't carry it, to see
+    /// which field a match hinges on (repeatable). Written as in
+    /// `log_field:body` or `span_attribute["http.route"]`.
+    #[arg(long, value_name = "SELECTOR", env = "POLICY_RUNNER_SUPPRESS_FIELD")]
+    suppress_field: Vec<String>,
+    /// Evaluate independent resource envelopes on a thread pool.
+    #[arg(long, value_enum, env = "POLICY_RUNNER_PARALLEL")]
+    parallel: Option<Parallel>,
+    /// Write counters about this run (records evaluated, decisions by type,
+    /// evaluation latency, policy loads, parse errors) to this JSON file.
+    #[arg(long, value_name = "PATH", env = "POLICY_RUNNER_SELF_TELEMETRY_FILE")]
+    self_telemetry_file: Option<PathBuf>,
+    /// Don't print the end-of-run summary to stderr.
+    #[arg(long, env = "POLICY_RUNNER_QUIET")]
+    quiet: bool,
+    /// Also write the end-of-run summary to this JSON file.
+    #[arg(long, value_name = "PATH", env = "POLICY_RUNNER_SUMMARY_JSON")]
+    summary_json: Option<PathBuf>,
+    /// When the process ends, however it ends, write its exit code, error,
+    /// record and decision counts, phase durations and the files it wrote to
+    /// this JSON file.
+    #[arg(long, value_name = "PATH", env = "POLICY_RUNNER_STATUS_FILE")]
+    status_file: Option<PathBuf>,
+    /// Write every evaluated record's latency to this file as an OTLP
+    /// metrics JSON histogram.
+    #[arg(long, value_name = "PATH", env = "POLICY_RUNNER_TIMING_HISTOGRAM")]
+    timing_histogram: Option<PathBuf>,
+    /// Upper bounds, in microseconds, of the `--timing-histogram` buckets.
+    #[arg(
+        long,
+        value_name = "US",
+        value_delimiter = ',',
+        value_parser = timing::parse_bound,
+        default_values_t = timing::DEFAULT_BOUNDS_US,
+        env = "POLICY_RUNNER_TIMING_HISTOGRAM_BOUNDS"
+    )]
+    timing_histogram_bounds: Vec<f64>,
+    /// Add `rate_limit_traces` to the annotations: each rate-limited policy's
+    /// allow/deny decisions in evaluation order, with record indexes.
+    #[arg(long, env = "POLICY_RUNNER_RATE_LIMIT_TRACE")]
+    rate_limit_trace: bool,
+    /// Add `record_diffs` to the annotations: for each log record or span a
+    /// transform changed, the paths changed with their values before and
+    /// after.
+    #[arg(long, env = "POLICY_RUNNER_RECORD_DIFFS")]
+    record_diffs: bool,
+    /// Write `<output>.drops.json` listing, per array in the input, how many
+    /// records evaluation removed and their indexes.
+    #[arg(long, env = "POLICY_RUNNER_DROPS_SIDECAR")]
+    drops_sidecar: bool,
+    /// Write a report of each attribute key's distinct values, most common
+    /// values and presence across the input, by resource, scope and record,
+    /// to this JSON file.
+    #[arg(long, value_name = "PATH", env = "POLICY_RUNNER_PROFILE_ATTRIBUTES")]
+    profile_attributes: Option<PathBuf>,
+    /// Write every lookup and write the engine makes on each record, with
+    /// its answer, to this JSON-lines file, for `compare-engine-trace`.
+    /// Turns off the fast paths `--no-fast-path` does, so every record is
+    /// traced.
+    #[arg(long, value_name = "PATH", env = "POLICY_RUNNER_TRACE_ENGINE")]
+    trace_engine: Option<PathBuf>,
+    /// Write how many times the engine looked up each selector, and how
+    /// often it found the field, to this JSON file, most requested first.
+    /// Built with the `selector-timing` feature, also the time spent in
+    /// `get_field`. Records a fast path decides without the engine add
+    /// nothing.
+    #[arg(long, value_name = "PATH", env = "POLICY_RUNNER_SELECTOR_STATS")]
+    selector_stats: Option<PathBuf>,
+    /// Write a manifest of this run (runner version, effective flags, and
+    /// hashes of the policies, input and output) for `replay`.
+    #[arg(long, value_name = "PATH", env = "POLICY_RUNNER_MANIFEST")]
+    manifest: Option<PathBuf>,
+}
+
+fn parse_sample_rate(s: &str) -> Result<f64, String> {
+    let rate: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if rate > 0.0 && rate <= 1.0 {
+        Ok(rate)
+    } else {
+        Err(format!("{rate} is not in (0, 1]"))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum CheckMode {
+    Warn,
+    Strict,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Parallel {
+    Envelopes,
+}
+
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+enum MetricGranularity {
+    #[default]
+    Metric,
+    Datapoint,
+}
+
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+enum EnvelopeRetention {
+    #[default]
+    PerRecord,
+    AnyKeep,
+}
+
+impl From<EnvelopeRetention> for runner_rs::EnvelopeRetention {
+    fn from(retention: EnvelopeRetention) -> Self {
+        match retention {
+            EnvelopeRetention::PerRecord => runner_rs::EnvelopeRetention::PerRecord,
+            EnvelopeRetention::AnyKeep => runner_rs::EnvelopeRetention::AnyKeep,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+enum ResourceMutation {
+    #[default]
+    Shared,
+    Cow,
+}
+
+impl From<ResourceMutation> for runner_rs::ResourceMutation {
+    fn from(mutation: ResourceMutation) -> Self {
+        match mutation {
+            ResourceMutation::Shared => runner_rs::ResourceMutation::Shared,
+            ResourceMutation::Cow => runner_rs::ResourceMutation::CopyOnWrite,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum InputFormat {
+    #[default]
+    OtlpJson,
+    PromText,
+    ZipkinJson,
+}
+
+impl InputFormat {
+    /// The signal input in this format always is, unless it's OTLP.
+    fn signal(self) -> Option<&'static str> {
+        match self {
+            InputFormat::OtlpJson => None,
+            InputFormat::PromText => Some("metric"),
+            InputFormat::ZipkinJson => Some("trace"),
+        }
+    }
+
+    /// `input` as the OTLP JSON payload it maps to.
+    fn to_otlp(self, input: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            InputFormat::OtlpJson => Ok(input.to_vec()),
+            InputFormat::PromText => std::str::from_utf8(input)
+                .map_err(|e| e.to_string())
+                .and_then(prom_text::parse)
+                .and_then(|data| serde_json::to_vec(&data).map_err(|e| e.to_string())),
+            InputFormat::ZipkinJson => zipkin::parse(input)
+                .and_then(|data| serde_json::to_vec(&data).map_err(|e| e.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum Utf8 {
+    #[default]
+    Strict,
+    Lossy,
+}
+
+impl From<MetricGranularity> for runner_rs::MetricGranularity {
+    fn from(granularity: MetricGranularity) -> Self {
+        match granularity {
+            MetricGranularity::Metric => runner_rs::MetricGranularity::Metric,
+            MetricGranularity::Datapoint => runner_rs::MetricGranularity::Datapoint,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -42,13 +646,52 @@ struct PolicyHit {
     misses: u64,
 }
 
-fn is_zero(v: &u64) -> bool {
-    *v == 0
+fn is_zero<T: Default + PartialEq>(v: &T) -> bool {
+    *v == T::default()
+}
+
+// ─── Logging ─────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Diagnostics go to stderr; stdout stays reserved for subcommand output.
+/// Per-record events are all at trace level, so below it they are skipped at
+/// the callsite without formatting anything.
+fn init_logging(level: LogLevel, format: LogFormat) {
+    let level = match level {
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Trace => LevelFilter::TRACE,
+    };
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false)
+        .with_writer(std::io::stderr);
+    match format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
 }
 
 // ─── Stats ───────────────────────────────────────────────────────────
 
-fn write_stats(path: &str, registry: &PolicyRegistry) {
+fn write_stats(path: &Path, registry: &PolicyRegistry) -> status::Result {
     let snapshot = registry.snapshot();
     let mut policies = Vec::new();
     for entry in snapshot.iter() {
@@ -63,273 +706,2009 @@ fn write_stats(path: &str, registry: &PolicyRegistry) {
     }
     policies.sort_by(|a, b| a.policy_id.cmp(&b.policy_id));
     let output = StatsOutput { policies };
-    let data = serde_json::to_string(&output).unwrap_or_else(|e| {
-        eprintln!("failed to serialize stats: {e}");
-        process::exit(1);
-    });
-    fs::write(path, data).unwrap_or_else(|e| {
-        eprintln!("failed to write stats: {e}");
-        process::exit(1);
-    });
+    let data = serde_json::to_string(&output)
+        .map_err(|e| Failure(format!("failed to serialize stats: {e}")))?;
+    atomic_file::write(path, data).map_err(|e| Failure(format!("failed to write stats: {e}")))
+}
+
+/// One selector's line in the `--selector-stats` report.
+#[derive(Serialize)]
+struct SelectorUsage {
+    selector: String,
+    #[serde(flatten)]
+    counts: selector_stats::Counts,
+}
+
+#[derive(Serialize)]
+struct SelectorStatsOutput {
+    /// Whether `get_field_nanos` was measured.
+    timed: bool,
+    selectors: Vec<SelectorUsage>,
+}
+
+fn write_selector_stats(path: &Path, stats: &selector_stats::Stats) -> status::Result {
+    let mut selectors: Vec<SelectorUsage> = stats
+        .snapshot()
+        .into_iter()
+        .map(|(selector, counts)| SelectorUsage { selector, counts })
+        .collect();
+    // Ties stay in selector order.
+    selectors.sort_by_key(|s| std::cmp::Reverse(s.counts.requests));
+    let output = SelectorStatsOutput {
+        timed: selector_stats::TIMED,
+        selectors,
+    };
+    let data = serde_json::to_string_pretty(&output)
+        .map_err(|e| Failure(format!("failed to serialize selector stats: {e}")))?;
+    atomic_file::write(path, data)
+        .map_err(|e| Failure(format!("failed to write selector stats: {e}")))
+}
+
+// ─── Summary ─────────────────────────────────────────────────────────
+
+/// End-of-run totals, printed to stderr unless `--quiet` and written as JSON
+/// with `--summary-json`.
+#[derive(Serialize)]
+struct Summary {
+    signal: String,
+    #[serde(flatten)]
+    totals: Totals,
+    /// The totals of each signal in `--input-lines` input.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    signals: BTreeMap<&'static str, Totals>,
+    policies_loaded: usize,
+    /// Policies that failed to compile and so never matched.
+    policy_errors: usize,
+    /// Log records by severity text `--normalize-severity-text` didn't know.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_severity_texts: BTreeMap<String, usize>,
+    /// What each `--shadow-policies` policy would have done.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    shadow: BTreeMap<String, shadow::Impact>,
+    elapsed_ms: f64,
+    records_per_sec: f64,
+}
+
+/// Record counts of a [`Summary`].
+#[derive(Serialize)]
+struct Totals {
+    /// Records in the input; more than `records` under `--sample-input`.
+    input_records: usize,
+    /// Records evaluated.
+    records: usize,
+    /// Records per decision kind, keyed by [`runner_rs::decision_name`].
+    decisions: BTreeMap<&'static str, usize>,
+    kept: usize,
+    dropped: usize,
+    /// Records `--time-from`/`--time-to` removed before evaluation, in
+    /// datapoints for metrics; not in `input_records`.
+    #[serde(skip_serializing_if = "is_zero")]
+    outside_window: usize,
+    /// Records `--utf8 lossy` replaced invalid UTF-8 in.
+    #[serde(skip_serializing_if = "is_zero")]
+    utf8_replaced: usize,
+    /// Attributes `--strip-attributes` removed, from the input and from
+    /// evaluation's output.
+    #[serde(skip_serializing_if = "is_zero")]
+    stripped_attributes: usize,
+    /// Records given an earlier envelope's decision by
+    /// `--dedupe-envelopes`; in `records`, but not evaluated.
+    #[serde(skip_serializing_if = "is_zero")]
+    deduplicated: usize,
+}
+
+impl Totals {
+    fn new(processed: &Processed) -> Self {
+        let results = &processed.results;
+        let mut decisions = BTreeMap::new();
+        let mut kept = 0;
+        for r in results {
+            *decisions
+                .entry(runner_rs::decision_name(&r.result))
+                .or_default() += 1;
+            kept += usize::from(runner_rs::keeps(&r.result));
+        }
+        Totals {
+            input_records: processed.input_records,
+            records: results.len(),
+            decisions,
+            kept,
+            dropped: results.len() - kept,
+            outside_window: processed.outside_window,
+            utf8_replaced: processed.utf8_replaced.len(),
+            stripped_attributes: processed.stripped_attributes,
+            deduplicated: results.iter().filter(|r| r.copy_of.is_some()).count(),
+        }
+    }
+}
+
+impl Summary {
+    fn new(
+        signal: &str,
+        processed: &Processed,
+        snapshot: &policy_rs::PolicySnapshot,
+        shadow_snapshot: Option<&policy_rs::PolicySnapshot>,
+        elapsed: Duration,
+    ) -> Self {
+        let records = processed.results.len();
+        let secs = elapsed.as_secs_f64();
+        Summary {
+            signal: signal.to_string(),
+            totals: Totals::new(processed),
+            signals: BTreeMap::new(),
+            policies_loaded: snapshot.iter().count(),
+            policy_errors: policy_errors(snapshot),
+            unknown_severity_texts: unknown_severity_texts(&processed.results),
+            shadow: shadow_snapshot
+                .map(|s| shadow::impact(s, &processed.shadow))
+                .unwrap_or_default(),
+            elapsed_ms: secs * 1000.0,
+            records_per_sec: if secs > 0.0 {
+                records as f64 / secs
+            } else {
+                0.0
+            },
+        }
+    }
+
+    fn print(&self) {
+        let totals = &self.totals;
+        let decisions: Vec<String> = totals
+            .decisions
+            .iter()
+            .map(|(name, n)| format!("{name} {n}"))
+            .collect();
+        eprintln!(
+            "summary: {} {} records in {:.1}ms ({:.0} records/s)",
+            totals.records, self.signal, self.elapsed_ms, self.records_per_sec
+        );
+        if totals.records != totals.input_records {
+            eprintln!(
+                "  sampled {} of {} input records",
+                totals.records, totals.input_records
+            );
+        }
+        eprintln!("  decisions: {}", decisions.join(", "));
+        eprintln!("  kept {}, dropped {}", totals.kept, totals.dropped);
+        if totals.outside_window > 0 {
+            eprintln!(
+                "  {} records outside the time window",
+                totals.outside_window
+            );
+        }
+        if totals.utf8_replaced > 0 {
+            eprintln!(
+                "  invalid UTF-8 replaced in {} records",
+                totals.utf8_replaced
+            );
+        }
+        if totals.stripped_attributes > 0 {
+            eprintln!("  stripped {} attributes", totals.stripped_attributes);
+        }
+        if totals.deduplicated > 0 {
+            eprintln!(
+                "  {} records decided as duplicates of earlier envelopes",
+                totals.deduplicated
+            );
+        }
+        for (signal, t) in &self.signals {
+            eprintln!(
+                "  {signal}: {} records, kept {}, dropped {}",
+                t.records, t.kept, t.dropped
+            );
+        }
+        eprintln!(
+            "  policies: {} loaded, {} with errors",
+            self.policies_loaded, self.policy_errors
+        );
+        if !self.unknown_severity_texts.is_empty() {
+            let texts: Vec<String> = self
+                .unknown_severity_texts
+                .iter()
+                .map(|(text, n)| format!("{text:?} {n}"))
+                .collect();
+            eprintln!("  unknown severity texts: {}", texts.join(", "));
+        }
+        shadow::print(&self.shadow);
+    }
+}
+
+/// Policies in `snapshot` that failed to compile.
+fn policy_errors(snapshot: &policy_rs::PolicySnapshot) -> usize {
+    snapshot
+        .iter()
+        .filter(|e| !snapshot.compilation_errors_for(e.policy.id()).is_empty())
+        .count()
+}
+
+/// Evaluated log records by severity text the severity table didn't know.
+fn unknown_severity_texts(results: &[RecordResult]) -> BTreeMap<String, usize> {
+    let mut texts = BTreeMap::new();
+    for text in results
+        .iter()
+        .filter_map(|r| r.unknown_severity_text.as_ref())
+    {
+        *texts.entry(text.clone()).or_default() += 1;
+    }
+    texts
+}
+
+fn write_summary(path: &Path, summary: &Summary) -> status::Result {
+    let data = serde_json::to_string(summary)
+        .map_err(|e| Failure(format!("failed to serialize summary: {e}")))?;
+    atomic_file::write(path, data).map_err(|e| Failure(format!("failed to write summary: {e}")))
+}
+
+// ─── Input ───────────────────────────────────────────────────────────
+
+/// Raw input bytes, either read onto the heap or memory-mapped (`--mmap`).
+/// Either way the parser sees one contiguous `&[u8]` and validates UTF-8 as it
+/// goes, so mapping skips the up-front copy without adding a validation pass.
+enum InputData {
+    Heap(Vec<u8>),
+    Mapped(memmap2::Mmap),
+}
+
+impl InputData {
+    fn open(path: &Path, mmap: bool) -> std::io::Result<Self> {
+        if !mmap {
+            return fs::read(path).map(InputData::Heap);
+        }
+        let file = fs::File::open(path)?;
+        // SAFETY: the mapping is read-only and its length is fixed when it is
+        // created. The input must not be modified while the runner is using
+        // it: on Unix, truncating it underneath us faults the process with
+        // SIGBUS, and other writes show up as corrupt input. On Windows the
+        // open mapping stops other processes from truncating the file.
+        unsafe { memmap2::Mmap::map(&file) }.map(InputData::Mapped)
+    }
+
+    /// `--utf8 lossy`: the input with invalid sequences replaced, copied
+    /// onto the heap if there were any, and whether there were.
+    fn lossy(self) -> (Self, bool) {
+        match utf8::replace(&self) {
+            Some(bytes) => (InputData::Heap(bytes), true),
+            None => (self, false),
+        }
+    }
+}
+
+/// Top-level keys of the OTLP payloads. Deserializing into this walks the
+/// document without building anything, which is enough to tell the signal
+/// apart before the real, typed parse.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignalPeek {
+    resource_logs: Option<IgnoredAny>,
+    resource_metrics: Option<IgnoredAny>,
+    resource_spans: Option<IgnoredAny>,
+    resource_profiles: Option<IgnoredAny>,
+    /// An `--input-lines` line's own signal.
+    signal: Option<String>,
+}
+
+/// The signals a payload can be.
+const SIGNALS: &[&str] = &["log", "metric", "trace", "profile"];
+
+/// The signal named by the payload's single `resource*` key, if exactly one
+/// is present.
+fn detect_signal(payload: &[u8]) -> Option<&'static str> {
+    let peek: SignalPeek = serde_json::from_slice(payload).ok()?;
+    peek.detected()
+}
+
+impl SignalPeek {
+    /// See [`detect_signal`].
+    fn detected(&self) -> Option<&'static str> {
+        let found = [
+            (self.resource_logs.is_some(), "log"),
+            (self.resource_metrics.is_some(), "metric"),
+            (self.resource_spans.is_some(), "trace"),
+            (self.resource_profiles.is_some(), "profile"),
+        ];
+        let mut present = found.iter().filter(|(hit, _)| *hit);
+        match (present.next(), present.next()) {
+            (Some((_, signal)), None) => Some(signal),
+            _ => None,
+        }
+    }
+}
+
+/// One export of `--input-lines` input.
+struct Line<'a> {
+    /// 1-based, counting blank lines.
+    number: usize,
+    signal: &'static str,
+    /// The `--signal` the line's own `signal` field overrode.
+    overrode: Option<&'static str>,
+    /// The export, without its `signal` field.
+    payload: Cow<'a, [u8]>,
+}
+
+/// The non-blank lines of `input`, each with its signal: its own `signal`
+/// field, else `default` (`--signal`), else detected.
+fn split_lines<'a>(
+    input: &'a [u8],
+    default: Option<&'static str>,
+) -> status::Result<Vec<Line<'a>>> {
+    let fail = |number: usize, message: &str| Failure(format!("line {number}: {message}"));
+    let lines: Vec<Line<'_>> = input
+        .split(|&b| b == b'\n')
+        .enumerate()
+        .filter(|(_, payload)| !payload.trim_ascii().is_empty())
+        .map(|(i, payload)| {
+            let number = i + 1;
+            let peek: Option<SignalPeek> = serde_json::from_slice(payload).ok();
+            let own = peek.as_ref().and_then(|p| p.signal.as_deref());
+            let Some(own) = own else {
+                let signal = default
+                    .or_else(|| peek.as_ref().and_then(SignalPeek::detected))
+                    .ok_or_else(|| fail(number, "could not detect signal"))?;
+                return Ok(Line {
+                    number,
+                    signal,
+                    overrode: None,
+                    payload: Cow::Borrowed(payload),
+                });
+            };
+            let signal = SIGNALS
+                .iter()
+                .find(|&&s| s == own)
+                .ok_or_else(|| fail(number, &format!("unknown signal {own:?}")))?;
+            // The rest of the line is the export, which has no such field.
+            let mut export: serde_json::Map<String, serde_json::Value> =
+                serde_json::from_slice(payload).map_err(|e| fail(number, &e.to_string()))?;
+            export.remove("signal");
+            Ok(Line {
+                number,
+                signal,
+                overrode: default.filter(|&d| d != *signal),
+                payload: Cow::Owned(serde_json::to_vec(&export).unwrap_or_default()),
+            })
+        })
+        .collect::<status::Result<_>>()?;
+    if lines.is_empty() {
+        return Err(Failure("input has no exports (--input-lines)".to_string()));
+    }
+    Ok(lines)
+}
+
+impl std::ops::Deref for InputData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            InputData::Heap(bytes) => bytes,
+            InputData::Mapped(map) => map,
+        }
+    }
 }
 
 // ─── Signal processing ──────────────────────────────────────────────
 
-async fn process_logs(
-    engine: &PolicyEngine,
-    snapshot: &policy_rs::PolicySnapshot,
-    input_data: &[u8],
-) -> Vec<u8> {
-    let mut data: otel::LogsData = serde_json::from_slice(input_data).unwrap_or_else(|e| {
-        eprintln!("failed to parse logs: {e}");
-        process::exit(1);
-    });
+/// Serialize filtered output straight into the output writer.
+fn write_json<T: Serialize>(data: &T, out: &mut dyn Write, what: &str) -> status::Result {
+    serde_json::to_writer(out, data)
+        .map_err(|e| Failure(format!("failed to serialize {what}: {e}")))
+}
+
+/// Stream output to `path` through an [`atomic_file::Staged`] file, so an
+/// interrupted run never leaves a truncated file that still looks like valid
+/// output.
+fn write_output(
+    path: &Path,
+    emit: impl FnOnce(&mut dyn Write) -> status::Result,
+) -> status::Result {
+    let failed = |e: std::io::Error| Failure(format!("failed to write output: {e}"));
+    let mut out = atomic_file::Staged::create(path).map_err(failed)?;
+    emit(&mut out)?;
+    out.commit().map_err(failed)
+}
 
-    for rl in &mut data.resource_logs {
-        if let Some(r) = rl.resource.as_mut() {
-            otel::prepare_attributes(&mut r.attributes);
+/// `--output` itself, or a file named after the input when it names a
+/// directory: an existing one, or any path ending in a separator. See
+/// [`output_name`] for the name.
+fn output_file(output: &Path, input: &Path, create_dir: bool) -> status::Result<PathBuf> {
+    let names_dir = output.is_dir()
+        || output
+            .as_os_str()
+            .as_encoded_bytes()
+            .last()
+            .is_some_and(|&b| std::path::is_separator(b as char));
+    if !names_dir {
+        return Ok(output.to_path_buf());
+    }
+    if !output.is_dir() {
+        if !create_dir {
+            return Err(Failure(format!(
+                "output directory {} does not exist (pass --create-output-dir)",
+                output.display()
+            )));
         }
-        for sl in &mut rl.scope_logs {
-            if let Some(s) = sl.scope.as_mut() {
-                otel::prepare_attributes(&mut s.attributes);
-            }
-            let mut kept = Vec::new();
-            for rec in sl.log_records.iter_mut() {
-                rec.prepare();
-                let mut ctx = eval::MutLogContext {
-                    record: rec,
-                    resource: rl.resource.as_mut(),
-                    scope: sl.scope.as_mut(),
-                    resource_schema_url: &rl.schema_url,
-                    scope_schema_url: &sl.schema_url,
-                };
-                let result = engine
-                    .evaluate_and_transform(snapshot, &mut ctx)
-                    .unwrap_or_else(|e| {
-                        eprintln!("evaluation error: {e}");
-                        process::exit(1);
-                    });
-                let should_keep = match &result {
-                    policy_rs::EvaluateResult::Drop { .. } => false,
-                    policy_rs::EvaluateResult::Sample { keep, .. } => *keep,
-                    policy_rs::EvaluateResult::RateLimit { allowed, .. } => *allowed,
-                    _ => true,
-                };
-                if should_keep {
-                    kept.push(rec.clone());
-                }
-            }
-            sl.log_records = kept;
+        fs::create_dir_all(output)
+            .map_err(|e| Failure(format!("failed to create output directory: {e}")))?;
+    }
+    Ok(output.join(output_name(input)))
+}
+
+/// The name `input`'s output gets in an output directory: `input.json`
+/// becomes `input.transformed.json`.
+fn output_name(input: &Path) -> OsString {
+    let mut name = input.file_stem().unwrap_or(input.as_os_str()).to_owned();
+    name.push(".transformed.json");
+    name
+}
+
+fn reject_unknown_fields(paths: &[String]) -> status::Result {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    for path in paths {
+        error!("unknown field: {path}");
+    }
+    telemetry::parse_error();
+    telemetry::flush();
+    Err(Failure(format!(
+        "input rejected: {} unknown field(s) (--strict-input)",
+        paths.len()
+    )))
+}
+
+fn report_violations(
+    violations: &[check::Violation],
+    mode: CheckMode,
+    warnings: &mut Warnings,
+) -> status::Result {
+    if violations.is_empty() {
+        return Ok(());
+    }
+    for v in violations {
+        match mode {
+            CheckMode::Warn => warnings.warn_record(
+                "W004",
+                v.path.clone(),
+                format!("{} ({})", v.message, v.rule),
+            ),
+            CheckMode::Strict => error!("{}: {} ({})", v.path, v.message, v.rule),
         }
-        rl.scope_logs.retain(|sl| !sl.log_records.is_empty());
     }
-    data.resource_logs.retain(|rl| !rl.scope_logs.is_empty());
+    if mode == CheckMode::Strict {
+        telemetry::parse_error();
+        telemetry::flush();
+        return Err(Failure(format!(
+            "input rejected: {} violation(s) (--check-input=strict)",
+            violations.len()
+        )));
+    }
+    Ok(())
+}
 
-    serde_json::to_vec(&data).unwrap_or_else(|e| {
-        eprintln!("failed to serialize logs: {e}");
-        process::exit(1);
-    })
+/// Warnings, `--rate-limit-trace` steps, `--record-diffs` changes,
+/// `--semconv-aliases` matches, `--shadow-policies` matches, `--utf8 lossy`
+/// replacements, `--dedupe-envelopes` copies and the `--input-lines` line,
+/// written to `--annotations`.
+#[derive(Serialize)]
+struct Annotations<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signal: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    warnings: &'a [warnings::Warning],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rate_limit_traces: Option<BTreeMap<String, Vec<RateLimitStep>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    record_diffs: Option<Vec<RecordDiff<'a>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    semconv_aliases: Option<Vec<AliasedRecord<'a>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shadow: Option<&'a [shadow::ShadowRecord]>,
+    /// JSON paths of the records whose content `--utf8 lossy` may have
+    /// altered.
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    utf8_replaced: &'a [String],
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    deduplicated: Vec<Deduplicated>,
 }
 
-async fn process_metrics(
-    engine: &PolicyEngine,
-    snapshot: &policy_rs::PolicySnapshot,
-    input_data: &[u8],
-) -> Vec<u8> {
-    let mut data: otel::MetricsData = serde_json::from_slice(input_data).unwrap_or_else(|e| {
-        eprintln!("failed to parse metrics: {e}");
-        process::exit(1);
-    });
+// ─── Sharding ────────────────────────────────────────────────────────
+
+/// `--output-shard-records`: the output is split into standalone payloads
+/// numbered beside `--output`, which gets a [`ShardManifest`] instead.
+struct Sharding {
+    output: PathBuf,
+    records: usize,
+}
+
+/// `out.json` becomes `out.0001.json` for shard 1.
+fn shard_path(output: &Path, index: usize) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(ext) => format!("{stem}.{index:04}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.{index:04}"),
+    };
+    output.with_file_name(name)
+}
+
+/// One shard: an output payload plus its 1-based `shard` index.
+#[derive(Serialize)]
+struct Shard<'a, D> {
+    #[serde(flatten)]
+    data: &'a D,
+    shard: usize,
+}
+
+#[derive(Serialize)]
+struct ShardEntry {
+    /// File name, beside the manifest.
+    path: String,
+    records: usize,
+}
+
+/// What `--output` holds under `--output-shard-records`: every shard in
+/// order and the records they hold between them. Annotation record indexes
+/// refer to the input rather than to any one shard.
+#[derive(Serialize)]
+struct ShardManifest {
+    shards: Vec<ShardEntry>,
+    records: usize,
+}
 
-    for rm in &mut data.resource_metrics {
-        for sm in &mut rm.scope_metrics {
-            let mut kept = Vec::new();
-            for m in &sm.metrics {
-                let dp_attrs = m
-                    .data
-                    .as_ref()
-                    .map(|d| d.first_datapoint_attributes())
-                    .unwrap_or(&[]);
-                let ctx = eval::MetricContext {
-                    metric: m,
-                    datapoint_attributes: dp_attrs,
-                    resource: rm.resource.as_ref(),
-                    scope: sm.scope.as_ref(),
-                    resource_schema_url: &rm.schema_url,
-                    scope_schema_url: &sm.schema_url,
-                };
-                let result = engine.evaluate(snapshot, &ctx).unwrap_or_else(|e| {
-                    eprintln!("evaluation error: {e}");
-                    process::exit(1);
+/// Write `data`'s resource envelopes out as shards of at most
+/// `sharding.records` records each. Envelopes are never split, so one
+/// holding more records than that makes a shard of its own. Leaves `data`
+/// with no envelopes.
+fn write_shards<D: SignalData>(
+    data: &mut D,
+    sharding: &Sharding,
+) -> status::Result<Vec<ShardEntry>> {
+    let mut envelopes = std::mem::take(data.envelopes()).into_iter().peekable();
+    let mut shards = Vec::new();
+    while envelopes.peek().is_some() {
+        let mut chunk = Vec::new();
+        let mut records = 0;
+        while let Some(envelope) = envelopes.next_if(|envelope| {
+            chunk.is_empty() || records + D::envelope_records(envelope) <= sharding.records
+        }) {
+            records += D::envelope_records(&envelope);
+            chunk.push(envelope);
+        }
+        *data.envelopes() = chunk;
+        let shard = shards.len() + 1;
+        let path = shard_path(&sharding.output, shard);
+        write_output(&path, |out| {
+            write_json(
+                &Shard {
+                    data: &*data,
+                    shard,
+                },
+                out,
+                D::NAME,
+            )
+        })?;
+        debug!(shard, records, path = %path.display(), "wrote shard");
+        shards.push(ShardEntry {
+            path: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            records,
+        });
+    }
+    data.envelopes().clear();
+    Ok(shards)
+}
+
+/// The changes evaluation made to one record; records left as they were are
+/// not listed.
+#[derive(Serialize)]
+struct RecordDiff<'a> {
+    resource: usize,
+    scope: usize,
+    record: usize,
+    changes: &'a [runner_rs::diff::Change],
+}
+
+fn record_diffs(results: &[RecordResult]) -> Vec<RecordDiff<'_>> {
+    results
+        .iter()
+        .filter_map(|r| {
+            let changes = r.changes.as_deref().filter(|c| !c.is_empty())?;
+            Some(RecordDiff {
+                resource: r.resource,
+                scope: r.scope,
+                record: r.record,
+                changes,
+            })
+        })
+        .collect()
+}
+
+/// The attributes one record matched under an old name; records that
+/// matched none are not listed.
+#[derive(Serialize)]
+struct AliasedRecord<'a> {
+    resource: usize,
+    scope: usize,
+    record: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    datapoint: Option<usize>,
+    aliases: &'a [semconv::AliasUse],
+}
+
+fn aliased_records(results: &[RecordResult]) -> Vec<AliasedRecord<'_>> {
+    results
+        .iter()
+        .filter_map(|r| {
+            let aliases = r.aliases.as_deref().filter(|a| !a.is_empty())?;
+            Some(AliasedRecord {
+                resource: r.resource,
+                scope: r.scope,
+                record: r.record,
+                datapoint: r.datapoint,
+                aliases,
+            })
+        })
+        .collect()
+}
+
+/// An envelope `--dedupe-envelopes` found identical to an earlier one, by
+/// their indexes in the input. Only copies with records decided are listed.
+#[derive(Serialize)]
+struct Deduplicated {
+    resource: usize,
+    copy_of: usize,
+}
+
+fn deduplicated(results: &[RecordResult]) -> Vec<Deduplicated> {
+    let mut copies: Vec<Deduplicated> = Vec::new();
+    for r in results {
+        let first_of_envelope = copies.last().is_none_or(|c| c.resource != r.resource);
+        if let Some(copy_of) = r.copy_of.filter(|_| first_of_envelope) {
+            copies.push(Deduplicated {
+                resource: r.resource,
+                copy_of,
+            });
+        }
+    }
+    copies
+}
+
+/// One rate-limited decision. `RateLimit` reports only whether the record
+/// was allowed, not the remaining budget.
+#[derive(Serialize)]
+struct RateLimitStep {
+    /// Position among all evaluated records, in evaluation order.
+    index: usize,
+    resource: usize,
+    scope: usize,
+    record: usize,
+    allowed: bool,
+}
+
+/// Each rate-limited policy's allow/deny sequence, in evaluation order.
+/// Rate limits force sequential evaluation, so that is input order.
+fn rate_limit_traces(results: &[RecordResult]) -> BTreeMap<String, Vec<RateLimitStep>> {
+    let mut traces: BTreeMap<String, Vec<RateLimitStep>> = BTreeMap::new();
+    for (index, r) in results.iter().enumerate() {
+        if let policy_rs::EvaluateResult::RateLimit {
+            policy_id, allowed, ..
+        } = &r.result
+        {
+            traces
+                .entry(policy_id.clone())
+                .or_default()
+                .push(RateLimitStep {
+                    index,
+                    resource: r.resource,
+                    scope: r.scope,
+                    record: r.record,
+                    allowed: *allowed,
                 });
-                if !matches!(result, policy_rs::EvaluateResult::Drop { .. }) {
-                    kept.push(m.clone());
-                }
-            }
-            sm.metrics = kept;
         }
-        rm.scope_metrics.retain(|sm| !sm.metrics.is_empty());
     }
-    data.resource_metrics
-        .retain(|rm| !rm.scope_metrics.is_empty());
+    traces
+}
+
+struct ProcessOptions {
+    /// Time each record's evaluation, for self-telemetry or
+    /// `--timing-histogram`.
+    timed: bool,
+    strict_input: bool,
+    check_input: Option<CheckMode>,
+    parallel_envelopes: bool,
+    max_records: Option<usize>,
+    sample_rate: Option<f64>,
+    sample_seed: u64,
+    rate_limit_trace: bool,
+    record_diffs: bool,
+    metric_granularity: runner_rs::MetricGranularity,
+    envelope_retention: runner_rs::EnvelopeRetention,
+    resource_mutation: runner_rs::ResourceMutation,
+    dedupe_envelopes: bool,
+    dedupe_ignored_keys: Arc<[String]>,
+    sharding: Option<Sharding>,
+    skip_engine: bool,
+    reuse_scope_decisions: bool,
+    suppressed_fields: Arc<eval::SuppressedFields>,
+    semconv_aliases: Option<Arc<semconv::Aliases>>,
+    /// `--normalize-severity-text` table.
+    severity_table: Option<Arc<severity::Table>>,
+    /// `--strip-attributes` globs.
+    strip_attributes: Vec<String>,
+    /// `--stamp-resource` attributes; empty unless stamping.
+    stamp: Vec<(String, String)>,
+    annotate_decisions: Option<Arc<runner_rs::DecisionKeys>>,
+    /// `--validate-tracestate`, and what to do with an invalid one.
+    validate_tracestate: Option<validate::Invalid>,
+    assert: bool,
+    /// `--time-from`/`--time-to`.
+    window: Option<window::Window>,
+    synthesize_temporality: Option<temporality::Temporality>,
+    /// Whether `--utf8 lossy` replaced anything in the input.
+    utf8_replaced: bool,
+    /// `--trace-engine` output, across every payload processed.
+    engine_trace: Option<RefCell<trace_engine::Writer>>,
+    /// `--selector-stats` counts, across every payload processed.
+    selector_stats: Option<Arc<selector_stats::Stats>>,
+    /// `--profile-attributes` tallies, across every payload processed.
+    profile: Option<RefCell<cardinality::Profile>>,
+    /// The signal and number of the `--input-lines` line being processed.
+    line: Option<(&'static str, usize)>,
+}
+
+/// What [`process_signal`] evaluated, out of how many input records.
+#[derive(Default)]
+struct Processed {
+    results: Vec<RecordResult>,
+    /// `--shadow-policies` matches.
+    shadow: Vec<shadow::ShadowRecord>,
+    input_records: usize,
+    /// Records `--time-from`/`--time-to` removed.
+    outside_window: usize,
+    /// Attributes `--strip-attributes` removed.
+    stripped_attributes: usize,
+    /// `--assert` results.
+    expectations: expect::Outcome,
+    /// JSON paths of the records `--utf8 lossy` may have altered.
+    utf8_replaced: Vec<String>,
+    /// What `--annotations` gets. Each `--input-lines` line has its own, so
+    /// these aren't absorbed.
+    annotations: serde_json::Value,
+}
+
+impl Processed {
+    /// Add another `--input-lines` line's results to these.
+    fn absorb(&mut self, other: Processed) {
+        self.results.extend(other.results);
+        self.shadow.extend(other.shadow);
+        self.input_records += other.input_records;
+        self.outside_window += other.outside_window;
+        self.stripped_attributes += other.stripped_attributes;
+        self.expectations.absorb(other.expectations);
+        self.utf8_replaced.extend(other.utf8_replaced);
+    }
+}
+
+/// The `--signal` a policy applies to, or `None` for a policy without a
+/// target.
+fn policy_signal(policy: &policy_rs::Policy) -> Option<&'static str> {
+    if policy.log_target().is_some() {
+        Some("log")
+    } else if policy.metric_target().is_some() {
+        Some("metric")
+    } else if policy.trace_target().is_some() {
+        Some("trace")
+    } else {
+        None
+    }
+}
+
+/// Whether any enabled policy can match records of `signal`. Profiles are
+/// evaluated against log policies.
+fn covers_signal(snapshot: &policy_rs::PolicySnapshot, signal: &str) -> bool {
+    let target = if signal == "profile" { "log" } else { signal };
+    snapshot
+        .iter()
+        .any(|entry| entry.policy.enabled() && policy_signal(&entry.policy) == Some(target))
+}
+
+/// Parse `--suppress-field` selectors as `signal`'s selector type.
+fn suppressed_fields(selectors: &[String], signal: &str) -> status::Result<eval::SuppressedFields> {
+    fn parse_all<S: Selector>(selectors: &[String]) -> status::Result<Vec<S>> {
+        selectors
+            .iter()
+            .map(|text| {
+                selector_display::parse(text)
+                    .map_err(|e| Failure(format!("invalid --suppress-field {text:?}: {e}")))
+            })
+            .collect()
+    }
+    let mut fields = eval::SuppressedFields::default();
+    match signal {
+        "metric" => fields.metric = parse_all(selectors)?,
+        "trace" => fields.trace = parse_all(selectors)?,
+        _ => fields.log = parse_all(selectors)?,
+    }
+    Ok(fields)
+}
 
-    serde_json::to_vec(&data).unwrap_or_else(|e| {
-        eprintln!("failed to serialize metrics: {e}");
-        process::exit(1);
+/// Rate limits are shared token buckets, so which records get through depends
+/// on evaluation order; those snapshots must stay sequential to be repeatable.
+fn has_rate_limits(snapshot: &policy_rs::PolicySnapshot) -> bool {
+    snapshot.iter().any(|entry| {
+        entry
+            .policy
+            .log_target()
+            .is_some_and(|t| t.keep.contains('/'))
     })
 }
 
-async fn process_traces(
+/// Picks the snapshot for an envelope's resource, or `None` to pass it
+/// through unevaluated.
+type Route<'a> =
+    dyn Fn(Option<&otel::Resource>) -> Option<&'a policy_rs::PolicySnapshot> + Sync + 'a;
+
+/// [`process_signal`] for one signal's payload type.
+type Process = fn(
+    &PolicyEngine,
+    &Route<'_>,
+    Option<&policy_rs::PolicySnapshot>,
+    &[u8],
+    &ProcessOptions,
+    &mut Warnings,
+    &mut dyn Write,
+) -> status::Result<Processed>;
+
+/// `--max-input-bytes`, checked before reading so an oversized input never
+/// gets loaded.
+fn check_input_size(path: &Path, max: u64) -> status::Result {
+    let size = fs::metadata(path)
+        .map(|m| m.len())
+        .map_err(|e| Failure(format!("failed to read input: {e}")))?;
+    if size > max {
+        return Err(Failure(format!(
+            "input is {size} bytes; the limit is {max} (--max-input-bytes)"
+        )));
+    }
+    Ok(())
+}
+
+/// The records `data` holds, counted as they'll be evaluated, unless there
+/// are more than `--max-records-hard` allows.
+fn record_count<D: SignalData>(
+    data: &D,
+    granularity: runner_rs::MetricGranularity,
+    max: Option<usize>,
+) -> status::Result<usize> {
+    let records = match granularity {
+        runner_rs::MetricGranularity::Metric => data.record_count(),
+        runner_rs::MetricGranularity::Datapoint => data.datapoint_count(),
+    };
+    if let Some(max) = max.filter(|&max| records > max) {
+        return Err(Failure(format!(
+            "input has {records} records; the limit is {max} (--max-records-hard)"
+        )));
+    }
+    Ok(records)
+}
+
+fn processor(signal: &str) -> status::Result<Process> {
+    match signal {
+        "log" => Ok(process_signal::<otel::LogsData>),
+        "metric" => Ok(process_signal::<otel::MetricsData>),
+        "trace" => Ok(process_signal::<otel::TracesData>),
+        "profile" => Ok(process_signal::<otel::ProfilesData>),
+        other => Err(Failure(format!("unknown signal: {other}"))),
+    }
+}
+
+/// The [`ProcessOptions`] that differ between the signals of one run.
+struct SignalSetup {
+    process: Process,
+    skip_engine: bool,
+    suppressed_fields: Arc<eval::SuppressedFields>,
+}
+
+impl ProcessOptions {
+    fn apply(&mut self, setup: &SignalSetup) {
+        self.skip_engine = setup.skip_engine;
+        self.suppressed_fields = Arc::clone(&setup.suppressed_fields);
+    }
+}
+
+fn process_signal<
+    D: SignalData
+        + Clone
+        + CheckInput
+        + Expecting
+        + Profiled
+        + Replaced
+        + Stamped
+        + Stripped
+        + Synthesize
+        + Validated
+        + Windowed,
+>(
     engine: &PolicyEngine,
-    snapshot: &policy_rs::PolicySnapshot,
+    route: &Route<'_>,
+    shadow_snapshot: Option<&policy_rs::PolicySnapshot>,
     input_data: &[u8],
-) -> Vec<u8> {
-    let mut data: otel::TracesData = serde_json::from_slice(input_data).unwrap_or_else(|e| {
-        eprintln!("failed to parse traces: {e}");
-        process::exit(1);
-    });
-
-    for rs in &mut data.resource_spans {
-        if let Some(r) = rs.resource.as_mut() {
-            otel::prepare_attributes(&mut r.attributes);
+    opts: &ProcessOptions,
+    warnings: &mut Warnings,
+    out: &mut dyn Write,
+) -> status::Result<Processed> {
+    let _span = tracing::info_span!("process", signal = D::NAME).entered();
+    let mut data: D = serde_json::from_slice(input_data).map_err(|e| {
+        telemetry::parse_error();
+        telemetry::flush();
+        Failure(format!("failed to parse {}: {e}", D::NAME))
+    })?;
+    debug!(
+        bytes = input_data.len(),
+        envelopes = data.envelopes().len(),
+        "parsed input"
+    );
+    if opts.strict_input {
+        reject_unknown_fields(&data.unknown_fields())?;
+    }
+    let strip = |data: &mut D| {
+        if opts.strip_attributes.is_empty() {
+            0
+        } else {
+            data.strip_attributes(&opts.strip_attributes)
         }
-        for ss in &mut rs.scope_spans {
-            if let Some(s) = ss.scope.as_mut() {
-                otel::prepare_attributes(&mut s.attributes);
-            }
-            let mut kept = Vec::new();
-            for span in &mut ss.spans {
-                span.prepare();
-                let mut ctx = eval::MutTraceContext {
-                    span,
-                    resource: rs.resource.as_ref(),
-                    scope: ss.scope.as_ref(),
-                    resource_schema_url: &rs.schema_url,
-                    scope_schema_url: &ss.schema_url,
-                };
-                let result = engine
-                    .evaluate_trace(snapshot, &mut ctx)
-                    .unwrap_or_else(|e| {
-                        eprintln!("evaluation error: {e}");
-                        process::exit(1);
-                    });
-                let should_keep = match &result {
-                    policy_rs::EvaluateResult::Drop { .. } => false,
-                    policy_rs::EvaluateResult::Sample { keep, .. } => *keep,
-                    _ => true,
-                };
-                if should_keep {
-                    kept.push(span.clone());
-                }
-            }
-            ss.spans = kept;
+    };
+    let mut stripped_attributes = strip(&mut data);
+    let outside_window = opts
+        .window
+        .as_ref()
+        .map_or(0, |window| data.retain_window(window));
+    if let Some(temporality) = opts.synthesize_temporality {
+        let rewritten = data.synthesize_temporality(temporality);
+        if rewritten > 0 {
+            warnings.warn(
+                "W006",
+                format!(
+                    "--synthesize-temporality set {rewritten} metric(s) to {}",
+                    temporality.name()
+                ),
+            );
         }
-        rs.scope_spans.retain(|ss| !ss.spans.is_empty());
     }
-    data.resource_spans.retain(|rs| !rs.scope_spans.is_empty());
-
-    serde_json::to_vec(&data).unwrap_or_else(|e| {
-        eprintln!("failed to serialize traces: {e}");
-        process::exit(1);
+    if let Some(mode) = opts.check_input {
+        report_violations(&check::check(&data), mode, warnings)?;
+    }
+    if let Some(profile) = &opts.profile {
+        data.profile(&mut profile.borrow_mut());
+    }
+    let utf8_replaced = if opts.utf8_replaced {
+        data.replaced_records()
+    } else {
+        Vec::new()
+    };
+    let expectations = if opts.assert {
+        data.expectations()
+            .map_err(|e| Failure(format!("invalid expectation at {e}")))?
+    } else {
+        expect::Expectations::default()
+    };
+    let input_records = record_count(&data, opts.metric_granularity, opts.max_records)?;
+    let eval_opts = EvalOptions {
+        parallel_envelopes: opts.parallel_envelopes,
+        timed: opts.timed,
+        sample_rate: opts.sample_rate,
+        sample_seed: opts.sample_seed,
+        record_diffs: opts.record_diffs,
+        metric_granularity: opts.metric_granularity,
+        envelope_retention: opts.envelope_retention,
+        resource_mutation: opts.resource_mutation,
+        dedupe_envelopes: opts.dedupe_envelopes,
+        dedupe_ignored_keys: Arc::clone(&opts.dedupe_ignored_keys),
+        skip_engine: opts.skip_engine,
+        suppressed_fields: Arc::clone(&opts.suppressed_fields),
+        semconv_aliases: opts.semconv_aliases.clone(),
+        severity_table: opts.severity_table.clone(),
+        reuse_scope_decisions: opts.reuse_scope_decisions,
+        trace_engine: opts.engine_trace.is_some(),
+        selector_stats: opts.selector_stats.clone(),
+        annotate_decisions: opts.annotate_decisions.clone(),
+    };
+    let shadow_data = shadow_snapshot.is_some().then(|| data.clone());
+    let results = evaluate_routed(engine, route, &mut data, &eval_opts)
+        .map_err(|e| Failure(format!("evaluation error: {e}")))?;
+    for r in &results {
+        telemetry::record(&r.result, r.elapsed);
+    }
+    stripped_attributes += strip(&mut data);
+    if !opts.stamp.is_empty() {
+        data.stamp(&opts.stamp);
+    }
+    if let Some(invalid) = opts.validate_tracestate {
+        let action = match invalid {
+            validate::Invalid::Repair => "dropped the invalid members",
+            validate::Invalid::Error => "removed the span",
+        };
+        for failed in data.validate_tracestate(invalid) {
+            let problems: Vec<&str> = failed.problems.iter().map(|p| &p.message[..]).collect();
+            warnings.warn_record(
+                "W008",
+                failed.path,
+                format!("invalid tracestate: {}; {action}", problems.join(", ")),
+            );
+        }
+    }
+    let expectations = expect::check(&expectations, &results);
+    if let Some(writer) = &opts.engine_trace {
+        writer
+            .borrow_mut()
+            .write(D::NAME, opts.line.map(|(_, number)| number), &results)?;
+    }
+    debug!(
+        records = results.len(),
+        surviving = data.envelopes().len(),
+        "evaluated envelopes"
+    );
+    let shadow = match shadow_snapshot.zip(shadow_data) {
+        Some((snapshot, data)) => {
+            let shadow_opts = EvalOptions {
+                timed: false,
+                record_diffs: false,
+                skip_engine: false,
+                suppressed_fields: Arc::default(),
+                trace_engine: false,
+                selector_stats: None,
+                annotate_decisions: None,
+                ..eval_opts
+            };
+            shadow::records(&shadow::evaluate(snapshot, data, &shadow_opts)?, &results)
+        }
+        None => Vec::new(),
+    };
+    let annotations = Annotations {
+        signal: opts.line.map(|(signal, _)| signal),
+        line: opts.line.map(|(_, number)| number),
+        warnings: warnings.entries(),
+        rate_limit_traces: opts.rate_limit_trace.then(|| rate_limit_traces(&results)),
+        record_diffs: opts.record_diffs.then(|| record_diffs(&results)),
+        semconv_aliases: opts
+            .semconv_aliases
+            .is_some()
+            .then(|| aliased_records(&results)),
+        shadow: shadow_snapshot.is_some().then_some(&shadow[..]),
+        utf8_replaced: &utf8_replaced,
+        deduplicated: deduplicated(&results),
+    };
+    if let Some(ref sharding) = opts.sharding {
+        let shards = write_shards(&mut data, sharding)?;
+        let manifest = ShardManifest {
+            records: shards.iter().map(|s| s.records).sum(),
+            shards,
+        };
+        write_json(&manifest, out, "shard manifest")?;
+    } else {
+        write_json(&data, out, D::NAME)?;
+    }
+    let annotations = serde_json::to_value(&annotations)
+        .map_err(|e| Failure(format!("failed to serialize annotations: {e}")))?;
+    Ok(Processed {
+        results,
+        shadow,
+        input_records,
+        outside_window,
+        stripped_attributes,
+        expectations,
+        utf8_replaced,
+        annotations,
     })
 }
 
 // ─── Main ────────────────────────────────────────────────────────────
 
+fn usage() -> Failure {
+    eprintln!(
+        "usage: runner-rs [--config <path>] [--profile <name>] (--policies <path|url>... [--merge-strategy <strict|override>] | --server <url> | --grpc <url>) [--tenant-key <attribute> --tenant-policies <tenant=path>...] [--shadow-policies <path>...] --input <path|url> [--format <otlp-json|prom-text|zipkin-json>] --output <path|dir/> [--create-output-dir] [--output-shard-records <n>] [--annotations <path>] [--signal <log|metric|trace|profile>] [--input-lines] [--stats <path>] [--strict-input] [--check-input[=strict]] [--strict-signals] [--no-fast-path] [--assert] [--deny-warnings] [--semconv-aliases <path>] [--normalize-severity-text [--severity-text-table <path>]] [--metric-granularity <metric|datapoint>] [--envelope-retention <per-record|any-keep>] [--resource-mutation <shared|cow>] [--dedupe-envelopes [--dedupe-ignore-key <key>]...] [--synthesize-temporality <delta|cumulative>] [--mmap] [--utf8 <strict|lossy>] [--max-input-bytes <n>] [--max-records-hard <n>] [--parallel envelopes] [--sample-input <rate> [--seed <n>]] [--time-from <rfc3339>] [--time-to <rfc3339>] [--time-missing <include|exclude>] [--rate-limit-trace] [--record-diffs] [--drops-sidecar] [--only-policy <glob>]... [--exclude-policy <glob>]... [--strip-attributes <glob>]... [--stamp-resource [--stamp-attribute <key=value>]...] [--annotate-decisions [--decision-key <key>] [--decision-policy-key <key>]] [--validate-tracestate [--tracestate-invalid <repair|error>]] [--suppress-field <selector>]... [--self-telemetry-file <path>] [--summary-json <path>] [--status-file <path>] [--timing-histogram <path> [--timing-histogram-bounds <us>,...]] [--profile-attributes <path>] [--trace-engine <path>] [--selector-stats <path>] [--manifest <path>] [--quiet] [--log-level <level>] [--log-format <text|json>]\n       runner-rs lint --policies <path>... [--deny <code>]...\n       runner-rs export-snapshot --policies <path>... --out <path>\n       runner-rs list-policies --policies <path>... [--merge-strategy <strict|override>]\n       runner-rs replay --manifest <path>\n       runner-rs match-test --pattern <pattern> --value <string> [--kind <regex|exact|starts-with|ends-with|contains>] [--case-insensitive]\n       runner-rs compare-engine-trace <a> <b>\n       runner-rs profiles [--config <path>]"
+    );
+    Failure("missing required arguments".to_string())
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    let args = Args::parse();
+    let started = Instant::now();
+    let mut progress = status::Progress::default();
+    let outcome = run(started, &mut progress).await;
+    if let Err(Failure(ref message)) = outcome {
+        // Config errors happen before logging is set up, so they go straight
+        // to stderr.
+        if tracing::dispatcher::has_been_set() {
+            error!("{message}");
+        } else {
+            eprintln!("{message}");
+        }
+    }
+    let code = progress.finish(&outcome, started.elapsed());
+    std::process::exit(code);
+}
 
-    // Load policies
-    let registry = PolicyRegistry::new();
+/// Where a run's policies come from. `registry` holds them; without
+/// `configured`, only `--tenant-policies` sets apply.
+struct Providers {
+    registry: PolicyRegistry,
+    /// The `--server` or `--grpc` provider, which is asked to sync stats
+    /// back at the end of the run.
+    remote: Option<Remote>,
+    configured: bool,
+}
 
-    // Create provider based on mode
-    let file_provider;
-    let mut http_provider = None;
-    let mut grpc_provider = None;
-    let provider: &dyn PolicyProvider = if let Some(ref url) = args.server {
-        http_provider = Some(
-            HttpProvider::new_with_initial_fetch(
-                HttpProviderConfig::new(url).content_type(ContentType::Json),
-            )
+enum Remote {
+    Http(HttpProvider),
+    Grpc(GrpcProvider),
+}
+
+/// Subscribe a registry to the provider `args` name: `--server`, `--grpc`,
+/// or the `--policies` sources merged by `--merge-strategy`, filtered by
+/// `--only-policy` and `--exclude-policy`.
+async fn load_providers(args: &Args) -> status::Result<Providers> {
+    let remote = if let Some(ref url) = args.server {
+        let mut config = HttpProviderConfig::new(url).content_type(ContentType::Json);
+        if let Some(ref var) = args.server_token_env {
+            let token = std::env::var(var)
+                .map_err(|e| Failure(format!("failed to read server token from ${var}: {e}")))?;
+            config = config.header("Authorization", format!("Bearer {token}"));
+        }
+        let hp = HttpProvider::new_with_initial_fetch(config)
             .await
-            .unwrap_or_else(|e| {
-                eprintln!("failed to connect to server: {e}");
-                process::exit(1);
-            }),
-        );
-        http_provider.as_ref().unwrap()
+            .map_err(|e| Failure(format!("failed to connect to server: {e}")))?;
+        Some(Remote::Http(hp))
     } else if let Some(ref url) = args.grpc {
         let grpc_url = if url.contains("://") {
             url.clone()
         } else {
             format!("http://{url}")
         };
-        grpc_provider = Some(
-            GrpcProvider::new_with_initial_fetch(GrpcProviderConfig::new(&grpc_url))
-                .await
-                .unwrap_or_else(|e| {
-                    eprintln!("failed to connect to gRPC server: {e}");
-                    process::exit(1);
-                }),
-        );
-        grpc_provider.as_ref().unwrap()
-    } else if let Some(ref path) = args.policies {
-        file_provider = FileProvider::new(path);
-        &file_provider
+        let gp = GrpcProvider::new_with_initial_fetch(GrpcProviderConfig::new(&grpc_url))
+            .await
+            .map_err(|e| Failure(format!("failed to connect to gRPC server: {e}")))?;
+        Some(Remote::Grpc(gp))
     } else {
-        eprintln!(
-            "usage: runner-rs (--policies <path> | --server <url> | --grpc <url>) --input <path> --output <path> --signal <log|metric|trace> [--stats <path>]"
+        None
+    };
+
+    let chain;
+    let provider: &dyn PolicyProvider = match remote {
+        Some(Remote::Http(ref hp)) => hp,
+        Some(Remote::Grpc(ref gp)) => gp,
+        None if !args.policies.is_empty() => {
+            chain = provider::ChainProvider::new(&args.policies, args.merge_strategy);
+            &chain
+        }
+        None if !args.tenant_policies.is_empty() => {
+            return Ok(Providers {
+                registry: PolicyRegistry::new(),
+                remote: None,
+                configured: false,
+            });
+        }
+        None => return Err(usage()),
+    };
+    let filtered;
+    let provider = if args.only_policy.is_empty() && args.exclude_policy.is_empty() {
+        provider
+    } else {
+        filtered = provider::FilteredProvider::new(
+            provider,
+            args.only_policy.clone(),
+            args.exclude_policy.clone(),
         );
-        process::exit(1);
+        &filtered
+    };
+
+    let registry = PolicyRegistry::new();
+    registry
+        .subscribe(provider)
+        .map_err(|e| Failure(format!("failed to load policies: {e}")))?;
+    Ok(Providers {
+        registry,
+        remote,
+        configured: true,
+    })
+}
+
+/// `--status-file` or its variable, picked out of the raw command line
+/// without clap, so that one clap or the config file goes on to reject still
+/// gets its status file.
+fn status_file(argv: &[OsString]) -> Option<PathBuf> {
+    let mut path = None;
+    let mut args = argv.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        } else if arg == "--status-file" {
+            path = args.next().map(PathBuf::from);
+        } else if let Some(value) = arg.to_str().and_then(|a| a.strip_prefix("--status-file=")) {
+            path = Some(PathBuf::from(value));
+        }
+    }
+    path.or_else(|| std::env::var_os("POLICY_RUNNER_STATUS_FILE").map(PathBuf::from))
+}
+
+/// The failure for a command line clap rejected, ending the run with clap's
+/// exit code. `--help` and `--version` aren't failures: clap prints them and
+/// exits here.
+fn command_line_failure(error: clap::Error, progress: &mut status::Progress) -> Failure {
+    if matches!(
+        error.kind(),
+        clap::error::ErrorKind::DisplayHelp | clap::error::ErrorKind::DisplayVersion
+    ) {
+        error.exit();
+    }
+    progress.failure_code = Some(error.exit_code());
+    Failure(error.render().to_string().trim_end().to_string())
+}
+
+async fn run(started: Instant, progress: &mut status::Progress) -> status::Result {
+    let started_at = SystemTime::now();
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    progress.path = status_file(&argv);
+    let argv = config::apply(argv)?;
+    let matches = Cli::command()
+        .try_get_matches_from(&argv)
+        .map_err(|e| command_line_failure(e, progress))?;
+    let cli = Cli::from_arg_matches(&matches).map_err(|e| command_line_failure(e, progress))?;
+    init_logging(cli.log_level, cli.log_format);
+    match cli.command {
+        Some(Command::Lint(lint_args)) => return lint::run(&lint_args),
+        Some(Command::ExportSnapshot(export_args)) => return export::run(&export_args),
+        Some(Command::ListPolicies(list_args)) => return list::run(&list_args),
+        Some(Command::Replay(replay_args)) => return manifest::run(&replay_args),
+        Some(Command::MatchTest(match_args)) => return match_test::run(&match_args),
+        Some(Command::CompareEngineTrace(compare_args)) => return trace_engine::run(&compare_args),
+        Some(Command::Profiles(profiles_args)) => return profiles::run(&profiles_args),
+        None => {}
+    }
+    let args = cli.args;
+    progress.path = args.status_file.clone();
+    let (Some(input_path), Some(output_path)) = (args.input.as_deref(), args.output.as_deref())
+    else {
+        return Err(usage());
     };
+    let output_path = &output_file(output_path, input_path, args.create_output_dir)?;
 
-    if let Err(e) = registry.subscribe(provider) {
-        eprintln!("failed to load policies: {e}");
-        process::exit(1);
+    if let Some(ref path) = args.self_telemetry_file {
+        telemetry::enable(path);
     }
+    let semconv_aliases = match args.semconv_aliases {
+        Some(ref path) => Some(Arc::new(
+            fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| semconv::Aliases::from_json(&text).map_err(|e| e.to_string()))
+                .map_err(|e| {
+                    Failure(format!(
+                        "failed to load semconv aliases {}: {e}",
+                        path.display()
+                    ))
+                })?,
+        )),
+        None => None,
+    };
+    let severity_table = if args.normalize_severity_text {
+        Some(Arc::new(match args.severity_text_table {
+            Some(ref path) => fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| severity::Table::with_overrides(&text).map_err(|e| e.to_string()))
+                .map_err(|e| {
+                    Failure(format!(
+                        "failed to load severity text table {}: {e}",
+                        path.display()
+                    ))
+                })?,
+            None => severity::Table::builtin(),
+        }))
+    } else {
+        None
+    };
+
+    let Providers {
+        registry,
+        remote,
+        configured,
+    } = load_providers(&args).await?;
     let snapshot = registry.snapshot();
+    debug!(policies = snapshot.iter().count(), "loaded policies");
+    telemetry::policies_loaded(&snapshot);
+    let mut warnings = Warnings::default();
+    if !args.only_policy.is_empty() || !args.exclude_policy.is_empty() {
+        let ids: Vec<&str> = snapshot.iter().map(|e| e.policy.id()).collect();
+        warnings.warn(
+            "W002",
+            format!(
+                "policy filter active; effective policies: {}",
+                ids.join(", ")
+            ),
+        );
+    }
 
     // Reset stats
     for entry in snapshot.iter() {
         entry.stats.reset_all();
     }
 
+    let tenants = args
+        .tenant_key
+        .as_deref()
+        .map(|key| tenant::Tenants::load(key, &args.tenant_policies, args.merge_strategy))
+        .transpose()?;
+    let tenant_snapshots = tenants.as_ref().map_or(&[][..], |t| t.snapshots());
+    let shadow_snapshot = (!args.shadow_policies.is_empty())
+        .then(|| shadow::load(&args.shadow_policies, args.merge_strategy))
+        .transpose()?;
+    let fallback = configured.then_some(&snapshot);
+    let route = |resource: Option<&otel::Resource>| match &tenants {
+        Some(tenants) => tenants.route(resource, fallback),
+        None => fallback,
+    };
+
+    progress.loaded(started.elapsed());
+    let loaded = Instant::now();
+
     // Read input
-    let input_data = fs::read(&args.input).unwrap_or_else(|e| {
-        eprintln!("failed to read input: {e}");
-        process::exit(1);
-    });
+    let object_input = object_url::is_url(input_path);
+    if let Some(max) = args.max_input_bytes.filter(|_| !object_input) {
+        check_input_size(input_path, max)?;
+    }
+    let input_data = if object_input {
+        // The store checks --max-input-bytes against the object's size.
+        object_url::read(&input_path.to_string_lossy(), args.max_input_bytes)
+            .map(InputData::Heap)
+            .map_err(|e| e.to_string())
+    } else {
+        InputData::open(input_path, args.mmap).map_err(|e| e.to_string())
+    };
+    let input_data = input_data.map_err(|e| Failure(format!("failed to read input: {e}")))?;
+    let (input_data, utf8_replaced) = match args.utf8 {
+        Utf8::Strict => (input_data, false),
+        Utf8::Lossy => input_data.lossy(),
+    };
+    let input_data = match args.format {
+        InputFormat::OtlpJson => input_data,
+        format => InputData::Heap(format.to_otlp(&input_data).map_err(|e| {
+            let name = format.to_possible_value().map(|v| v.get_name().to_string());
+            telemetry::parse_error();
+            Failure(format!(
+                "failed to parse {} input: {e}",
+                name.unwrap_or_default()
+            ))
+        })?),
+    };
 
     let engine = PolicyEngine::new();
 
-    let output = match args.signal.as_str() {
-        "log" => process_logs(&engine, &snapshot, &input_data).await,
-        "metric" => process_metrics(&engine, &snapshot, &input_data).await,
-        "trace" => process_traces(&engine, &snapshot, &input_data).await,
-        other => {
-            eprintln!("unknown signal: {other}");
-            process::exit(1);
+    let default_signal = args
+        .signal
+        .as_deref()
+        .map(|signal| {
+            SIGNALS
+                .iter()
+                .find(|&&s| s == signal)
+                .copied()
+                .ok_or_else(|| Failure(format!("unknown signal: {signal}")))
+        })
+        .transpose()?;
+    let lines = args
+        .input_lines
+        .then(|| split_lines(&input_data, default_signal))
+        .transpose()?;
+    let signals: Vec<&str> = match (&lines, args.signal.as_deref()) {
+        (Some(lines), _) => {
+            let mut signals: Vec<&str> = lines.iter().map(|line| line.signal).collect();
+            signals.sort_unstable();
+            signals.dedup();
+            signals
         }
+        (None, Some(signal)) => {
+            if args.format.signal().is_some_and(|format| format != signal) {
+                return Err(Failure(format!(
+                    "--signal {signal} can't read --format input, which is always another signal"
+                )));
+            }
+            vec![signal]
+        }
+        (None, None) => vec![detect_signal(&input_data).ok_or_else(|| {
+            Failure("could not detect signal from input; pass --signal".to_string())
+        })?],
     };
+    // Tracing the engine wants every record to reach it.
+    let fast_path = !args.no_fast_path && args.trace_engine.is_none();
+    let mut setups = BTreeMap::new();
+    for &signal in &signals {
+        let uncovered = !covers_signal(&snapshot, signal)
+            && !tenant_snapshots.iter().any(|s| covers_signal(s, signal));
+        if uncovered {
+            if args.strict_signals {
+                return Err(Failure(format!(
+                    "no loaded policy applies to {signal} input (--strict-signals)"
+                )));
+            }
+            let skipping = if fast_path {
+                "; skipping evaluation"
+            } else {
+                ""
+            };
+            warnings.warn(
+                "W001",
+                format!(
+                    "no loaded policy applies to {signal} input; every record will pass through unmatched{skipping}"
+                ),
+            );
+        }
+        let setup = SignalSetup {
+            process: processor(signal)?,
+            skip_engine: uncovered && fast_path,
+            suppressed_fields: Arc::new(suppressed_fields(&args.suppress_field, signal)?),
+        };
+        setups.insert(signal, setup);
+    }
+
+    if args
+        .timing_histogram_bounds
+        .windows(2)
+        .any(|pair| pair[0] >= pair[1])
+    {
+        return Err(Failure(
+            "--timing-histogram-bounds must be strictly increasing".to_string(),
+        ));
+    }
+    if args
+        .time_from
+        .zip(args.time_to)
+        .is_some_and(|(from, to)| from >= to)
+    {
+        return Err(Failure("--time-from must be before --time-to".to_string()));
+    }
+    let mut opts = ProcessOptions {
+        timed: telemetry::enabled() || args.timing_histogram.is_some(),
+        strict_input: args.strict_input,
+        check_input: args.check_input,
+        parallel_envelopes: args.parallel == Some(Parallel::Envelopes),
+        max_records: args.max_records_hard,
+        sample_rate: args.sample_input,
+        sample_seed: args.seed,
+        rate_limit_trace: args.rate_limit_trace,
+        record_diffs: args.record_diffs,
+        metric_granularity: args.metric_granularity.into(),
+        envelope_retention: args.envelope_retention.into(),
+        resource_mutation: args.resource_mutation.into(),
+        dedupe_envelopes: args.dedupe_envelopes,
+        dedupe_ignored_keys: args.dedupe_ignore_key.clone().into(),
+        sharding: args.output_shard_records.map(|records| Sharding {
+            output: output_path.clone(),
+            records,
+        }),
+        skip_engine: false,
+        reuse_scope_decisions: fast_path,
+        suppressed_fields: Arc::default(),
+        semconv_aliases,
+        severity_table,
+        strip_attributes: args.strip_attributes.clone(),
+        stamp: if args.stamp_resource {
+            stamp::attributes(
+                stamp::policy_hash(std::iter::once(&snapshot).chain(tenant_snapshots)),
+                &args.stamp_attribute,
+            )
+        } else {
+            Vec::new()
+        },
+        annotate_decisions: args.annotate_decisions.then(|| {
+            Arc::new(runner_rs::DecisionKeys {
+                decision: args.decision_key.clone(),
+                policy: args.decision_policy_key.clone(),
+            })
+        }),
+        validate_tracestate: args.validate_tracestate.then_some(args.tracestate_invalid),
+        assert: args.assert,
+        window: (args.time_from.is_some() || args.time_to.is_some()).then_some(window::Window {
+            from: args.time_from,
+            to: args.time_to,
+            missing: args.time_missing,
+        }),
+        synthesize_temporality: args.synthesize_temporality,
+        utf8_replaced,
+        engine_trace: args
+            .trace_engine
+            .as_deref()
+            .map(|path| trace_engine::Writer::create(path).map(RefCell::new))
+            .transpose()?,
+        selector_stats: args.selector_stats.is_some().then(Arc::default),
+        profile: args.profile_attributes.is_some().then(RefCell::default),
+        line: None,
+    };
+    if !args.suppress_field.is_empty() {
+        warnings.warn(
+            "W005",
+            format!(
+                "--suppress-field hides {} from matchers; decisions may differ from a normal run",
+                args.suppress_field.join(", ")
+            ),
+        );
+    }
+    if opts.parallel_envelopes
+        && (has_rate_limits(&snapshot)
+            || tenant_snapshots.iter().any(has_rate_limits)
+            || shadow_snapshot.as_ref().is_some_and(has_rate_limits))
+    {
+        warnings.warn(
+            "W003",
+            "rate-limited policies present, evaluating envelopes sequentially".to_string(),
+        );
+        opts.parallel_envelopes = false;
+    }
 
     // Write output
-    fs::write(&args.output, &output).unwrap_or_else(|e| {
-        eprintln!("failed to write output: {e}");
-        process::exit(1);
+    let mut processed = None;
+    let mut by_signal: BTreeMap<&str, Processed> = BTreeMap::new();
+    // One entry, or one per line under --input-lines.
+    let mut annotations = Vec::new();
+    write_output(output_path, |out| {
+        let Some(ref lines) = lines else {
+            let setup = &setups[&signals[0]];
+            opts.apply(setup);
+            let mut all = (setup.process)(
+                &engine,
+                &route,
+                shadow_snapshot.as_ref(),
+                &input_data,
+                &opts,
+                &mut warnings,
+                out,
+            )?;
+            annotations.push(std::mem::take(&mut all.annotations));
+            processed = Some(all);
+            return Ok(());
+        };
+        for (i, line) in lines.iter().enumerate() {
+            // Run-wide warnings go out with the first line.
+            let mut line_warnings = if i == 0 {
+                std::mem::take(&mut warnings)
+            } else {
+                Warnings::default()
+            };
+            if let Some(overrode) = line.overrode {
+                line_warnings.warn(
+                    "W007",
+                    format!(
+                        "line {}: its signal {} overrides --signal {overrode}",
+                        line.number, line.signal
+                    ),
+                );
+            }
+            let setup = &setups[&line.signal];
+            opts.apply(setup);
+            opts.line = Some((line.signal, line.number));
+            let mut line_processed = (setup.process)(
+                &engine,
+                &route,
+                shadow_snapshot.as_ref(),
+                &line.payload,
+                &opts,
+                &mut line_warnings,
+                out,
+            )?;
+            writeln!(out).map_err(|e| Failure(format!("failed to write output: {e}")))?;
+            annotations.push(std::mem::take(&mut line_processed.annotations));
+            warnings.absorb(line_warnings);
+            by_signal
+                .entry(line.signal)
+                .or_default()
+                .absorb(line_processed);
+        }
+        Ok(())
+    })?;
+    if let Some(writer) = opts.engine_trace.take() {
+        writer.into_inner().finish()?;
+    }
+    progress.evaluated(loaded.elapsed());
+    let mut signal_totals = BTreeMap::new();
+    let processed = processed.unwrap_or_else(|| {
+        let mut all = Processed::default();
+        for (signal, line_processed) in by_signal {
+            signal_totals.insert(signal, Totals::new(&line_processed));
+            all.absorb(line_processed);
+        }
+        all
+    });
+    let signal = match signals[..] {
+        [signal] => signal,
+        _ => "mixed",
+    };
+    let totals = Totals::new(&processed);
+    progress.counts = Some(status::Counts {
+        records: totals.records,
+        decisions: totals.decisions,
+        policy_errors: policy_errors(&snapshot),
+        warnings: warnings.entries().len(),
     });
+    if let Some(ref path) = args.annotations {
+        write_output(path, |out| {
+            for entry in &annotations {
+                write_json(entry, out, "annotations")?;
+                if lines.is_some() {
+                    writeln!(out).map_err(|e| Failure(format!("failed to write output: {e}")))?;
+                }
+            }
+            Ok(())
+        })?;
+    }
+    if args.drops_sidecar {
+        let dropped = drops::dropped(signal, &processed.results);
+        write_output(&drops::sidecar_path(output_path), |out| {
+            write_json(&dropped, out, "drops")
+        })?;
+    }
+    if let Some(unrouted) = tenants.as_ref().map(|t| t.unrouted()).filter(|&n| n > 0) {
+        warn!(
+            "{unrouted} resource envelope(s) had no tenant policy set and passed through unevaluated"
+        );
+    }
 
-    if let Some(ref hp) = http_provider {
+    match remote {
         // Trigger a sync to report stats back to the server
-        if let Err(e) = hp.load().await {
-            eprintln!("failed to sync stats: {e}");
+        Some(Remote::Http(ref hp)) => {
+            if let Err(e) = hp.load().await {
+                warn!("failed to sync stats: {e}");
+            }
         }
-    } else if let Some(ref gp) = grpc_provider {
-        // Trigger a sync to report stats back to the server
-        if let Err(e) = gp.load().await {
-            eprintln!("failed to sync stats: {e}");
+        Some(Remote::Grpc(ref gp)) => {
+            if let Err(e) = gp.load().await {
+                warn!("failed to sync stats: {e}");
+            }
+        }
+        None => {
+            if let Some(ref stats_path) = args.stats {
+                write_stats(stats_path, &registry)?;
+            }
+        }
+    }
+    telemetry::flush();
+
+    let mut summary = Summary::new(
+        signal,
+        &processed,
+        &snapshot,
+        shadow_snapshot.as_ref(),
+        started.elapsed(),
+    );
+    summary.signals = signal_totals;
+    if !args.quiet {
+        summary.print();
+    }
+    if let Some(ref path) = args.summary_json {
+        write_summary(path, &summary)?;
+    }
+    if let Some(ref path) = args.timing_histogram {
+        timing::write(
+            path,
+            &args.timing_histogram_bounds,
+            &processed.results,
+            signal,
+            input_path,
+            started_at,
+        )?;
+    }
+    if let (Some(path), Some(profile)) = (&args.profile_attributes, &opts.profile) {
+        profile.borrow().write(path)?;
+    }
+    if let (Some(path), Some(stats)) = (&args.selector_stats, &opts.selector_stats) {
+        write_selector_stats(path, stats)?;
+    }
+    if let Some(ref path) = args.manifest {
+        let mut policies = args.policies.clone();
+        policies.extend(args.shadow_policies.iter().cloned());
+        policies.extend(
+            args.tenant_policies
+                .iter()
+                .filter_map(|spec| spec.split_once('=').map(|(_, path)| PathBuf::from(path))),
+        );
+        manifest::write(path, &matches, &policies, input_path, output_path)?;
+    }
+    let expectations = &processed.expectations;
+    if args.assert {
+        if !args.quiet {
+            eprintln!(
+                "assert: {} of {} expectations held",
+                expectations.checked - expectations.failed,
+                expectations.checked
+            );
+        }
+        if expectations.failed > 0 {
+            return Err(Failure(format!(
+                "{} record(s) failed their expectation (--assert)",
+                expectations.failed
+            )));
         }
-    } else if let Some(ref stats_path) = args.stats {
-        write_stats(stats_path, &registry);
+    }
+    if args.deny_warnings && !warnings.is_empty() {
+        return Err(Failure(format!(
+            "{} warning(s) raised (--deny-warnings)",
+            warnings.entries().len()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn policy_file(dir: &Path, file: &str, policies: &[(&str, &str)]) -> String {
+        let policies: Vec<_> = policies
+            .iter()
+            .map(|(id, name)| {
+                json!({
+                    "id": id,
+                    "name": name,
+                    "log": { "match": [{ "log_field": "body", "exact": id }], "keep": "none" },
+                })
+            })
+            .collect();
+        let path = dir.join(file);
+        fs::write(&path, json!({ "policies": policies }).to_string()).unwrap();
+        path.display().to_string()
+    }
+
+    async fn load(flags: &[&str]) -> status::Result<Vec<(String, String)>> {
+        let cli =
+            Cli::try_parse_from(std::iter::once("runner-rs").chain(flags.iter().copied())).unwrap();
+        let providers = load_providers(&cli.args).await?;
+        assert!(providers.configured);
+        let snapshot = providers.registry.snapshot();
+        Ok(snapshot
+            .iter()
+            .map(|e| (e.policy.id().to_string(), e.policy.name().to_string()))
+            .collect())
+    }
+
+    fn loaded(policies: &[(&str, &str)]) -> Vec<(String, String)> {
+        policies
+            .iter()
+            .map(|(id, name)| (id.to_string(), name.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn strict_sources_conflict_naming_both() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = policy_file(dir.path(), "a.json", &[("shared", "a"), ("only-a", "a")]);
+        let b = policy_file(dir.path(), "b.json", &[("shared", "b")]);
+        let Err(Failure(message)) = load(&["--policies", &a, "--policies", &b]).await else {
+            panic!("a shared id should conflict under the strict strategy");
+        };
+        assert!(
+            message.starts_with("failed to load policies: "),
+            "{message}"
+        );
+        assert!(
+            message.contains(&format!("defined by both {a} and {b}")),
+            "{message}"
+        );
+    }
+
+    #[tokio::test]
+    async fn strict_sources_merge_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = policy_file(dir.path(), "a.json", &[("x", "a"), ("y", "a")]);
+        let b = policy_file(dir.path(), "b.json", &[("z", "b")]);
+        let policies = load(&["--policies", &b, "--policies", &a]).await;
+        assert_eq!(
+            policies.ok(),
+            Some(loaded(&[("z", "b"), ("x", "a"), ("y", "a")]))
+        );
+    }
+
+    #[tokio::test]
+    async fn override_sources_let_the_later_one_win_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = policy_file(
+            dir.path(),
+            "a.json",
+            &[("x", "a"), ("shared", "a"), ("y", "a")],
+        );
+        let b = policy_file(dir.path(), "b.json", &[("shared", "b"), ("z", "b")]);
+        let flags = [
+            "--merge-strategy",
+            "override",
+            "--policies",
+            &a,
+            "--policies",
+            &b,
+        ];
+        assert_eq!(
+            load(&flags).await.ok(),
+            Some(loaded(&[
+                ("x", "a"),
+                ("shared", "b"),
+                ("y", "a"),
+                ("z", "b")
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn policy_filters_apply_after_merging() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = policy_file(dir.path(), "a.json", &[("drop-a", "a"), ("keep-a", "a")]);
+        let b = policy_file(dir.path(), "b.json", &[("drop-a", "b"), ("drop-b", "b")]);
+        let flags = [
+            "--merge-strategy",
+            "override",
+            "--policies",
+            &a,
+            "--policies",
+            &b,
+            "--only-policy",
+            "drop-*",
+            "--exclude-policy",
+            "*-b",
+        ];
+        assert_eq!(load(&flags).await.ok(), Some(loaded(&[("drop-a", "b")])));
+    }
+
+    #[test]
+    fn output_name_follows_the_input() {
+        let input = Path::new("data/input.json");
+        assert_eq!(output_name(input), "input.transformed.json");
+        let bare = Path::new("export");
+        assert_eq!(output_name(bare), "export.transformed.json");
+    }
+
+    #[test]
+    fn output_file_resolves_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = Path::new("in/input.json");
+        let file = dir.path().join("out.json");
+        assert_eq!(output_file(&file, input, false).ok(), Some(file));
+        assert_eq!(
+            output_file(dir.path(), input, false).ok(),
+            Some(dir.path().join("input.transformed.json"))
+        );
+
+        let missing = dir.path().join("missing/");
+        let Err(Failure(message)) = output_file(&missing, input, false) else {
+            panic!("a missing output directory needs --create-output-dir");
+        };
+        assert!(
+            message.ends_with("does not exist (pass --create-output-dir)"),
+            "{message}"
+        );
+        assert_eq!(
+            output_file(&missing, input, true).ok(),
+            Some(missing.join("input.transformed.json"))
+        );
+        assert!(missing.is_dir());
+    }
+
+    #[test]
+    fn max_input_bytes_rejects_a_larger_input_naming_its_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("input.json");
+        let records: Vec<_> = (0..2_000)
+            .map(|i| json!({ "body": { "stringValue": format!("record {i}") } }))
+            .collect();
+        let input = json!({ "resourceLogs": [{ "scopeLogs": [{ "logRecords": records }] }] });
+        fs::write(&path, input.to_string()).unwrap();
+        let size = fs::metadata(&path).unwrap().len();
+
+        assert!(check_input_size(&path, size).is_ok());
+        let Err(Failure(message)) = check_input_size(&path, size - 1) else {
+            panic!("a {size}-byte input should exceed a limit of {}", size - 1);
+        };
+        assert_eq!(
+            message,
+            format!(
+                "input is {size} bytes; the limit is {} (--max-input-bytes)",
+                size - 1
+            )
+        );
+    }
+
+    #[test]
+    fn max_records_hard_rejects_one_record_too_many() {
+        const MAX: usize = 100;
+        let logs = |count: usize| -> otel::LogsData {
+            let records: Vec<_> = (0..count)
+                .map(|i| json!({ "body": { "stringValue": format!("record {i}") } }))
+                .collect();
+            let scope_logs: Vec<_> = records
+                .chunks(7)
+                .map(|records| json!({ "logRecords": records }))
+                .collect();
+            serde_json::from_value(json!({
+                "resourceLogs": [
+                    { "scopeLogs": scope_logs[..scope_logs.len() / 2] },
+                    { "scopeLogs": scope_logs[scope_logs.len() / 2..] },
+                ],
+            }))
+            .unwrap()
+        };
+        let granularity = runner_rs::MetricGranularity::Metric;
+
+        assert_eq!(
+            record_count(&logs(MAX), granularity, Some(MAX)).ok(),
+            Some(MAX)
+        );
+        assert_eq!(
+            record_count(&logs(MAX + 1), granularity, None).ok(),
+            Some(MAX + 1)
+        );
+        let Err(Failure(message)) = record_count(&logs(MAX + 1), granularity, Some(MAX)) else {
+            panic!("{} records should exceed --max-records-hard {MAX}", MAX + 1);
+        };
+        assert_eq!(
+            message,
+            "input has 101 records; the limit is 100 (--max-records-hard)"
+        );
+    }
+
+    #[test]
+    fn max_records_hard_counts_datapoints_at_datapoint_granularity() {
+        let metrics: otel::MetricsData = serde_json::from_value(json!({
+            "resourceMetrics": [{ "scopeMetrics": [{ "metrics": [{
+                "name": "requests",
+                "gauge": { "dataPoints": [{ "asInt": "1" }, { "asInt": "2" }, { "asInt": "3" }] },
+            }] }] }],
+        }))
+        .unwrap();
+        assert!(record_count(&metrics, runner_rs::MetricGranularity::Metric, Some(1)).is_ok());
+        let Err(Failure(message)) =
+            record_count(&metrics, runner_rs::MetricGranularity::Datapoint, Some(2))
+        else {
+            panic!("3 datapoints should exceed --max-records-hard 2");
+        };
+        assert_eq!(
+            message,
+            "input has 3 records; the limit is 2 (--max-records-hard)"
+        );
+    }
+
+    #[test]
+    fn check_input_violations_warn_or_reject() {
+        let logs: otel::LogsData = serde_json::from_value(json!({
+            "resourceLogs": [{ "scopeLogs": [{ "logRecords": [{ "traceId": "abcd" }] }] }],
+        }))
+        .unwrap();
+        let violations = check::check(&logs);
+        assert_eq!(violations.len(), 2);
+
+        let mut warnings = Warnings::default();
+        assert!(report_violations(&violations, CheckMode::Warn, &mut warnings).is_ok());
+        let reported: Vec<_> = warnings
+            .entries()
+            .iter()
+            .map(|w| (w.code, w.record_id.as_deref().unwrap_or_default()))
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                ("W004", "resourceLogs[0].scopeLogs[0].logRecords[0].traceId"),
+                (
+                    "W004",
+                    "resourceLogs[0].scopeLogs[0].logRecords[0].observedTimeUnixNano"
+                ),
+            ]
+        );
+
+        let mut warnings = Warnings::default();
+        let Err(Failure(message)) =
+            report_violations(&violations, CheckMode::Strict, &mut warnings)
+        else {
+            panic!("--check-input=strict should reject the input");
+        };
+        assert_eq!(
+            message,
+            "input rejected: 2 violation(s) (--check-input=strict)"
+        );
+        assert!(warnings.is_empty());
     }
 }
@@ -0,0 +1,313 @@
+//! `--manifest <path>` records how a run can be reproduced, and
+//! `runner-rs replay --manifest <path>` reproduces it.
+//!
+//! The manifest holds the runner's version and git hash, the effective flags
+//! (after `--config` and `POLICY_RUNNER_*` variables are folded in), and
+//! SHA-256 digests of every policy source, the input and the output. Files
+//! are hashed in fixed-size chunks, so a large input is never held in memory
+//! a second time for hashing.
+//!
+//! Replay runs the current binary with the recorded flags, writing to a
+//! temporary output, and compares its digest with the recorded one.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::ArgMatches;
+use clap::parser::ValueSource;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::status::{self, Failure};
+
+/// Flags that only name where a run writes side outputs. They are left out of
+/// the recorded flags so a replay never overwrites the original run's files.
+const SIDE_OUTPUTS: &[&str] = &[
+    "annotations",
+    "config",
+    "manifest",
+    "stats",
+    "summary_json",
+    "self_telemetry_file",
+    "selector_stats",
+    "status_file",
+];
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    runner: Runner,
+    /// Effective flags as `--name=value`, output included, side outputs not.
+    args: Vec<String>,
+    policies: Vec<FileDigest>,
+    input: FileDigest,
+    output: FileDigest,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Runner {
+    version: String,
+    git_hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FileDigest {
+    path: String,
+    bytes: u64,
+    sha256: String,
+}
+
+fn digest(path: &Path) -> io::Result<FileDigest> {
+    if crate::object_url::is_url(path) {
+        let data =
+            crate::object_url::read(&path.to_string_lossy(), None).map_err(io::Error::other)?;
+        return Ok(FileDigest {
+            path: path.display().to_string(),
+            bytes: data.len() as u64,
+            sha256: hex(&Sha256::digest(&data)),
+        });
+    }
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    let mut bytes = 0;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        bytes += n as u64;
+    }
+    Ok(FileDigest {
+        path: path.display().to_string(),
+        bytes,
+        sha256: hex(&hasher.finalize()),
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Digests for one `--policies` source: the file itself, every `*.json` file
+/// under a directory (in the order `DirProvider` loads them), or the value of
+/// the variable behind `env:`.
+fn policy_digests(source: &Path, out: &mut Vec<FileDigest>) -> io::Result<()> {
+    if let Some(var) = source.to_str().and_then(|s| s.strip_prefix("env:")) {
+        let var = if var.is_empty() {
+            crate::provider::DEFAULT_ENV_VAR
+        } else {
+            var
+        };
+        let value = std::env::var(var).unwrap_or_default();
+        out.push(FileDigest {
+            path: format!("${var}"),
+            bytes: value.len() as u64,
+            sha256: hex(&Sha256::digest(value.as_bytes())),
+        });
+        return Ok(());
+    }
+    if !source.is_dir() {
+        out.push(digest(source)?);
+        return Ok(());
+    }
+    let mut files = Vec::new();
+    collect_json_files(source, &mut files)?;
+    files.sort();
+    for file in files {
+        out.push(digest(&file)?);
+    }
+    Ok(())
+}
+
+fn collect_json_files(dir: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_json_files(&path, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Every flag that didn't come from a built-in default, as `--name=value`.
+fn effective_args(matches: &ArgMatches) -> Vec<String> {
+    let command = <crate::Cli as clap::CommandFactory>::command();
+    let mut args = Vec::new();
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+        if SIDE_OUTPUTS.contains(&id)
+            || !matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        {
+            continue;
+        }
+        // A flag's raw value is clap's implied `true`, which it won't accept back.
+        if !arg.get_action().takes_values() {
+            args.push(format!("--{long}"));
+            continue;
+        }
+        match matches.get_raw(id) {
+            Some(values) if values.len() > 0 => {
+                for value in values {
+                    args.push(format!("--{long}={}", value.to_string_lossy()));
+                }
+            }
+            // A bare flag.
+            _ => args.push(format!("--{long}")),
+        }
+    }
+    args
+}
+
+/// Write the manifest for a finished run.
+pub fn write(
+    path: &Path,
+    matches: &ArgMatches,
+    policies: &[PathBuf],
+    input: &Path,
+    output: &Path,
+) -> status::Result {
+    let fail = |what: &'static str| {
+        move |e: io::Error| Failure(format!("failed to hash {what} for manifest: {e}"))
+    };
+    let mut policy_files = Vec::new();
+    for source in policies {
+        policy_digests(source, &mut policy_files).map_err(fail("policies"))?;
+    }
+    let manifest = Manifest {
+        runner: Runner {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: env!("RUNNER_GIT_HASH").to_string(),
+        },
+        args: effective_args(matches),
+        policies: policy_files,
+        input: digest(input).map_err(fail("input"))?,
+        output: digest(output).map_err(fail("output"))?,
+    };
+    crate::write_output(path, |out| {
+        serde_json::to_writer_pretty(&mut *out, &manifest)
+            .map_err(|e| Failure(format!("failed to serialize manifest: {e}")))
+    })
+}
+
+// ─── Replay ──────────────────────────────────────────────────────────
+
+#[derive(clap::Args)]
+pub struct ReplayArgs {
+    /// A manifest written by `--manifest`.
+    #[arg(long)]
+    manifest: PathBuf,
+}
+
+pub fn run(args: &ReplayArgs) -> status::Result {
+    let data =
+        fs::read(&args.manifest).map_err(|e| Failure(format!("failed to read manifest: {e}")))?;
+    let manifest: Manifest = serde_json::from_slice(&data)
+        .map_err(|e| Failure(format!("failed to parse manifest: {e}")))?;
+
+    let mut drifted = false;
+    if manifest.runner.git_hash != env!("RUNNER_GIT_HASH") {
+        warn!(
+            "runner: recorded {}, replaying with {}",
+            manifest.runner.git_hash,
+            env!("RUNNER_GIT_HASH")
+        );
+    }
+    for recorded in manifest.policies.iter().chain([&manifest.input]) {
+        let current = if let Some(var) = recorded.path.strip_prefix('$') {
+            let value = std::env::var(var).unwrap_or_default();
+            hex(&Sha256::digest(value.as_bytes()))
+        } else {
+            digest(Path::new(&recorded.path)).map_or_else(|e| e.to_string(), |d| d.sha256)
+        };
+        if current != recorded.sha256 {
+            warn!("changed since the recorded run: {}", recorded.path);
+            drifted = true;
+        }
+    }
+
+    let replayed = tempfile::NamedTempFile::new()
+        .map_err(|e| Failure(format!("failed to create replay output: {e}")))?;
+    let mut argv: Vec<OsString> = manifest
+        .args
+        .iter()
+        .filter(|a| !a.starts_with("--output=") && *a != "--quiet")
+        .map(OsString::from)
+        .collect();
+    let mut output_flag = OsString::from("--output=");
+    output_flag.push(replayed.path());
+    argv.push(output_flag);
+    argv.push("--quiet".into());
+
+    let exe = std::env::current_exe()
+        .map_err(|e| Failure(format!("failed to locate runner binary: {e}")))?;
+    // Everything the run used is on the recorded command line, so variables
+    // set since then mustn't add to it.
+    let mut child = process::Command::new(exe);
+    for arg in <crate::Cli as clap::CommandFactory>::command().get_arguments() {
+        if let Some(var) = arg.get_env() {
+            child.env_remove(var);
+        }
+    }
+    let exit = child
+        .args(&argv)
+        .status()
+        .map_err(|e| Failure(format!("failed to run replay: {e}")))?;
+    if !exit.success() {
+        return Err(Failure(format!("replayed run failed: {exit}")));
+    }
+
+    let output = digest(replayed.path())
+        .map_err(|e| Failure(format!("failed to hash replay output: {e}")))?;
+    if output.sha256 == manifest.output.sha256 {
+        println!("output matches: sha256 {}", output.sha256);
+        Ok(())
+    } else {
+        println!(
+            "output differs: recorded sha256 {}, replayed {}",
+            manifest.output.sha256, output.sha256
+        );
+        if drifted {
+            println!("(inputs changed since the recorded run; see above)");
+        }
+        Err(Failure(
+            "replayed output differs from the recorded run".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn recorded_args_parse_back() {
+        let argv = [
+            "runner-rs",
+            "--policies=p.json",
+            "--input=in.json",
+            "--quiet",
+            "--strict-input",
+        ];
+        let matches = crate::Cli::command().get_matches_from(argv);
+        let args = effective_args(&matches);
+        assert!(args.contains(&"--quiet".to_string()), "{args:?}");
+        assert!(args.contains(&"--strict-input".to_string()), "{args:?}");
+        crate::Cli::command()
+            .try_get_matches_from(std::iter::once("runner-rs".to_string()).chain(args))
+            .unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+//! `runner-rs match-test`: check a selector pattern against a sample value.
+//!
+//! The pattern isn't compiled here. It goes into a one-policy snapshot as a
+//! `log_field: body` matcher, and the value is evaluated as the body of a
+//! single log record. The answer comes from the engine itself, with its
+//! regex dialect, anchoring and case folding, so it can't disagree with a
+//! real evaluation.
+
+use std::io::Write;
+
+use policy_rs::{EvaluateResult, FileProvider, PolicyEngine, PolicyRegistry};
+use runner_rs::{EvalOptions, evaluate_logs_data, otel};
+use serde_json::json;
+
+use crate::status::{self, Failure};
+
+const POLICY_ID: &str = "match-test";
+
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+enum Kind {
+    #[default]
+    Regex,
+    Exact,
+    StartsWith,
+    EndsWith,
+    Contains,
+}
+
+impl Kind {
+    /// The matcher's key in a policy document.
+    fn key(self) -> &'static str {
+        match self {
+            Kind::Regex => "regex",
+            Kind::Exact => "exact",
+            Kind::StartsWith => "starts_with",
+            Kind::EndsWith => "ends_with",
+            Kind::Contains => "contains",
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub struct MatchTestArgs {
+    #[arg(long)]
+    pattern: String,
+    #[arg(long)]
+    value: String,
+    /// How `--pattern` is matched, as the policy matcher of the same name.
+    #[arg(long, value_enum, default_value_t)]
+    kind: Kind,
+    #[arg(long)]
+    case_insensitive: bool,
+}
+
+pub fn run(args: &MatchTestArgs) -> status::Result {
+    let mut matcher = json!({ "log_field": "body", "case_insensitive": args.case_insensitive });
+    matcher[args.kind.key()] = json!(args.pattern);
+    let document = json!({
+        "policies": [{
+            "id": POLICY_ID,
+            "log": { "match": [matcher], "keep": "none" },
+        }]
+    });
+
+    // Policy documents are only parsed from files, so stage this one in one
+    // for FileProvider.
+    let staged = tempfile::NamedTempFile::new().and_then(|mut staged| {
+        serde_json::to_writer(&mut staged, &document)?;
+        staged.flush()?;
+        Ok(staged)
+    });
+    let staged = staged.map_err(|e| Failure(format!("failed to stage policy: {e}")))?;
+    let registry = PolicyRegistry::new();
+    registry
+        .subscribe(&FileProvider::new(staged.path()))
+        .map_err(|e| Failure(format!("invalid pattern: {e}")))?;
+    let snapshot = registry.snapshot();
+    if let Some(e) = snapshot.compilation_errors_for(POLICY_ID).first() {
+        return Err(Failure(format!("invalid pattern: {e}")));
+    }
+
+    let mut logs: otel::LogsData = serde_json::from_value(json!({
+        "resourceLogs": [{
+            "scopeLogs": [{
+                "logRecords": [{ "body": { "stringValue": args.value } }]
+            }]
+        }]
+    }))
+    .expect("a one-record payload always parses");
+    let results = evaluate_logs_data(
+        &PolicyEngine::new(),
+        &snapshot,
+        &mut logs,
+        &EvalOptions::default(),
+    )
+    .map_err(|e| Failure(format!("evaluation error: {e}")))?;
+    match results.first().map(|r| &r.result) {
+        Some(EvaluateResult::NoMatch) | None => println!("no match"),
+        Some(_) => println!("match"),
+    }
+    Ok(())
+}
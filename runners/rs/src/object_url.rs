@@ -0,0 +1,101 @@
+//! `s3://` and `gs://` URLs for `--policies` and `--input`, read through the
+//! `object_store` crate when built with the `object-store` feature.
+//!
+//! Credentials come from each store's standard chain: `AWS_*` variables,
+//! then the instance metadata service, for S3; `GOOGLE_*` variables,
+//! application default credentials, then the metadata server, for GCS.
+//! Requests that fail transiently (5xx, throttling, dropped connections) are
+//! retried with exponential backoff, up to ten times within three minutes; a
+//! missing object, or credentials the store refuses, fail at once with an
+//! error saying which.
+//!
+//! The object is read whole into memory, where the parser needs it, before
+//! the run goes on exactly as for a local file.
+
+use std::path::Path;
+
+/// The schemes read as object URLs.
+const SCHEMES: &[&str] = &["s3://", "gs://"];
+
+/// Whether `path` is an object URL rather than a local path.
+pub fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|p| SCHEMES.iter().any(|scheme| p.starts_with(scheme)))
+}
+
+/// The object at `url`, refused unread if it's larger than `limit` bytes.
+/// Blocks the calling thread, from inside the runtime or not.
+#[cfg(feature = "object-store")]
+pub fn read(url: &str, limit: Option<u64>) -> Result<Vec<u8>, String> {
+    // The runner's own runtime is single-threaded and may be the caller, so
+    // the request runs on a runtime of its own.
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| format!("{url}: {e}"))?
+                .block_on(fetch(url, limit))
+        })
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+#[cfg(not(feature = "object-store"))]
+pub fn read(url: &str, _limit: Option<u64>) -> Result<Vec<u8>, String> {
+    Err(format!(
+        "{url}: object URLs need runner-rs built with the object-store feature"
+    ))
+}
+
+#[cfg(feature = "object-store")]
+async fn fetch(url: &str, limit: Option<u64>) -> Result<Vec<u8>, String> {
+    use object_store::aws::AmazonS3Builder;
+    use object_store::gcp::GoogleCloudStorageBuilder;
+    use object_store::{ObjectStore, ObjectStoreScheme, RetryConfig};
+
+    let parsed = url::Url::parse(url).map_err(|e| format!("{url}: {e}"))?;
+    let (scheme, path) = ObjectStoreScheme::parse(&parsed).map_err(|e| format!("{url}: {e}"))?;
+    let store: Box<dyn ObjectStore> = match scheme {
+        ObjectStoreScheme::AmazonS3 => Box::new(
+            AmazonS3Builder::from_env()
+                .with_url(url)
+                .with_retry(RetryConfig::default())
+                .build()
+                .map_err(|e| described(url, e))?,
+        ),
+        ObjectStoreScheme::GoogleCloudStorage => Box::new(
+            GoogleCloudStorageBuilder::from_env()
+                .with_url(url)
+                .with_retry(RetryConfig::default())
+                .build()
+                .map_err(|e| described(url, e))?,
+        ),
+        other => return Err(format!("{url}: unsupported object store {other:?}")),
+    };
+    let object = store.get(&path).await.map_err(|e| described(url, e))?;
+    if let Some(max) = limit.filter(|&max| object.meta.size > max) {
+        return Err(format!(
+            "{url} is {} bytes; the limit is {max}",
+            object.meta.size
+        ));
+    }
+    let bytes = object.bytes().await.map_err(|e| described(url, e))?;
+    Ok(bytes.into())
+}
+
+/// `e` with a plain statement of what went wrong in front, for the errors a
+/// caller can act on.
+#[cfg(feature = "object-store")]
+fn described(url: &str, e: object_store::Error) -> String {
+    use object_store::Error;
+
+    let what = match e {
+        Error::NotFound { .. } => "not found",
+        Error::PermissionDenied { .. } => "forbidden: the credentials may not read it",
+        Error::Unauthenticated { .. } => "forbidden: no valid credentials",
+        _ => return format!("{url}: {e}"),
+    };
+    format!("{url}: {what} ({e})")
+}
@@ -4,9 +4,15 @@
 //! the canonical format for test case input/expected files). Key differences
 //! from the `opentelemetry-proto` crate:
 //! - trace_id/span_id are hex strings
-//! - span kind and status code are string enums (not integers)
+//! - status code is a string enum (not an integer); span kind is usually one
+//!   too, but is accepted in any producer dialect
 //! - timestamps are numbers (not strings)
 //! - severity_number is a string enum
+//!
+//! Every message struct carries a flattened `extra` map so keys from newer
+//! proto versions survive a round-trip untouched instead of being dropped.
+
+use std::borrow::Cow;
 
 use serde::{Deserialize, Serialize};
 
@@ -18,6 +24,8 @@ pub struct Resource {
     pub attributes: Vec<KeyValue>,
     pub dropped_attributes_count: u32,
     pub entity_refs: Vec<serde_json::Value>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -27,6 +35,8 @@ pub struct InstrumentationScope {
     pub version: String,
     pub attributes: Vec<KeyValue>,
     pub dropped_attributes_count: u32,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -61,12 +71,122 @@ pub struct AnyValue {
     pub bytes_decoded: Option<Vec<u8>>,
 }
 
+impl AnyValue {
+    /// The value as text. Everything that shows or compares values as text
+    /// should go through this, so they can't drift apart; goldens depend on
+    /// it, so change it deliberately.
+    ///
+    /// - strings as they are, bytes as their base64 text
+    /// - bools as `true`/`false`, ints in decimal
+    /// - doubles in shortest round-trip form (`1.0`, `0.1`, `1e+300`); `NaN`,
+    ///   `inf` and `-inf` for the non-finite ones
+    /// - arrays as JSON arrays and kvlists as JSON objects with sorted keys,
+    ///   their members rendered by [`canonical_json`](AnyValue::canonical_json)
+    /// - an empty value as the empty string
+    pub fn canonical_string(&self) -> Cow<'_, str> {
+        if let Some(s) = &self.string_value {
+            return Cow::Borrowed(s);
+        }
+        if let Some(b) = &self.bytes_value {
+            return Cow::Borrowed(b);
+        }
+        match self.canonical_json() {
+            serde_json::Value::Null => Cow::Borrowed(""),
+            serde_json::Value::String(s) => Cow::Owned(s),
+            other => Cow::Owned(other.to_string()),
+        }
+    }
+
+    /// The value as plain JSON: strings and bytes as strings, ints and
+    /// finite doubles as numbers, arrays and kvlists as arrays and objects.
+    /// Ints too large for an `i64` and non-finite doubles stay strings.
+    pub fn canonical_json(&self) -> serde_json::Value {
+        use serde_json::Value;
+        if let Some(s) = &self.string_value {
+            return Value::String(s.clone());
+        }
+        if let Some(b) = self.bool_value {
+            return Value::Bool(b);
+        }
+        if let Some(i) = &self.int_value {
+            return match i {
+                Value::String(s) => s.parse::<i64>().map_or_else(|_| i.clone(), Value::from),
+                other => other.clone(),
+            };
+        }
+        if let Some(d) = self.double_value {
+            return serde_json::Number::from_f64(d).map_or_else(
+                || {
+                    let text = if d.is_nan() {
+                        "NaN"
+                    } else if d > 0.0 {
+                        "inf"
+                    } else {
+                        "-inf"
+                    };
+                    Value::String(text.to_string())
+                },
+                Value::Number,
+            );
+        }
+        if let Some(array) = &self.array_value {
+            return Value::Array(
+                members(array)
+                    .map(|v| any_value(v).canonical_json())
+                    .collect(),
+            );
+        }
+        if let Some(kvlist) = &self.kvlist_value {
+            // Sorted even if serde_json's map preserves insertion order.
+            let sorted: std::collections::BTreeMap<_, _> = members(kvlist)
+                .filter_map(|kv| {
+                    let key = kv.get("key")?.as_str()?.to_string();
+                    let value = kv
+                        .get("value")
+                        .map_or(Value::Null, |v| any_value(v).canonical_json());
+                    Some((key, value))
+                })
+                .collect();
+            return Value::Object(sorted.into_iter().collect());
+        }
+        if let Some(b) = &self.bytes_value {
+            return Value::String(b.clone());
+        }
+        Value::Null
+    }
+}
+
+/// The `values` of an `arrayValue` or `kvlistValue`.
+fn members(list: &serde_json::Value) -> impl Iterator<Item = &serde_json::Value> {
+    list.get("values")
+        .and_then(serde_json::Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+/// A nested value; malformed ones render as empty.
+fn any_value(value: &serde_json::Value) -> AnyValue {
+    AnyValue::deserialize(value).unwrap_or_default()
+}
+
+/// A timestamp as JSON encodes it: a number, or a decimal string for 64-bit
+/// values beyond what some producers emit as numbers.
+pub fn unix_nanos(value: &serde_json::Value) -> Option<u64> {
+    match value {
+        serde_json::Value::Number(n) => n.as_u64(),
+        serde_json::Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
 // ─── Logs ────────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LogsData {
     pub resource_logs: Vec<ResourceLogs>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -75,6 +195,8 @@ pub struct ResourceLogs {
     pub resource: Option<Resource>,
     pub scope_logs: Vec<ScopeLogs>,
     pub schema_url: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -83,6 +205,8 @@ pub struct ScopeLogs {
     pub scope: Option<InstrumentationScope>,
     pub log_records: Vec<LogRecord>,
     pub schema_url: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -105,6 +229,8 @@ pub struct LogRecord {
     pub trace_id_bytes: Option<Vec<u8>>,
     #[serde(skip)]
     pub span_id_bytes: Option<Vec<u8>>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 // ─── Metrics ─────────────────────────────────────────────────────────
@@ -113,6 +239,8 @@ pub struct LogRecord {
 #[serde(rename_all = "camelCase")]
 pub struct MetricsData {
     pub resource_metrics: Vec<ResourceMetrics>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -121,6 +249,8 @@ pub struct ResourceMetrics {
     pub resource: Option<Resource>,
     pub scope_metrics: Vec<ScopeMetrics>,
     pub schema_url: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -129,6 +259,8 @@ pub struct ScopeMetrics {
     pub scope: Option<InstrumentationScope>,
     pub metrics: Vec<Metric>,
     pub schema_url: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -141,6 +273,8 @@ pub struct Metric {
     pub metadata: Vec<KeyValue>,
     #[serde(flatten)]
     pub data: Option<MetricData>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -164,6 +298,25 @@ impl MetricData {
         }
     }
 
+    /// Set a sum's or histogram's aggregation temporality to `temporality`,
+    /// as [`aggregation_temporality`](Self::aggregation_temporality) names
+    /// it, in the numeric encoding if the metric used that and by name
+    /// otherwise. Returns whether the metric has a temporality to set.
+    pub fn set_aggregation_temporality(&mut self, temporality: &'static str) -> bool {
+        let at = match self {
+            MetricData::Sum(s) => &mut s.aggregation_temporality,
+            MetricData::Histogram(h) => &mut h.aggregation_temporality,
+            MetricData::ExponentialHistogram(eh) => &mut eh.aggregation_temporality,
+            _ => return false,
+        };
+        *at = match (&*at, temporality) {
+            (serde_json::Value::Number(_), "AGGREGATION_TEMPORALITY_DELTA") => 1.into(),
+            (serde_json::Value::Number(_), "AGGREGATION_TEMPORALITY_CUMULATIVE") => 2.into(),
+            _ => temporality.into(),
+        };
+        true
+    }
+
     pub fn aggregation_temporality(&self) -> Option<&'static str> {
         let at = match self {
             MetricData::Sum(s) => &s.aggregation_temporality,
@@ -211,12 +364,123 @@ impl MetricData {
                 .unwrap_or(&[]),
         }
     }
+
+    /// Each datapoint's attributes, in order. Exponential histogram
+    /// datapoints aren't modelled, so theirs are read out of the raw JSON.
+    pub fn datapoint_attributes(&self) -> Vec<Cow<'_, [KeyValue]>> {
+        fn borrowed<P>(points: &[P], attrs: fn(&P) -> &[KeyValue]) -> Vec<Cow<'_, [KeyValue]>> {
+            points.iter().map(|dp| Cow::Borrowed(attrs(dp))).collect()
+        }
+        match self {
+            MetricData::Gauge(g) => borrowed(&g.data_points, |dp| &dp.attributes),
+            MetricData::Sum(s) => borrowed(&s.data_points, |dp| &dp.attributes),
+            MetricData::Histogram(h) => borrowed(&h.data_points, |dp| &dp.attributes),
+            MetricData::ExponentialHistogram(eh) => eh
+                .data_points
+                .iter()
+                .map(|dp| {
+                    let attrs = dp
+                        .get("attributes")
+                        .and_then(|a| Vec::<KeyValue>::deserialize(a).ok())
+                        .unwrap_or_default();
+                    Cow::Owned(attrs)
+                })
+                .collect(),
+            MetricData::Summary(s) => borrowed(&s.data_points, |dp| &dp.attributes),
+        }
+    }
+
+    /// Each datapoint's `timeUnixNano`, if it has one.
+    pub fn datapoint_times(&self) -> Vec<Option<u64>> {
+        fn times<P>(points: &[P], time: fn(&P) -> &serde_json::Value) -> Vec<Option<u64>> {
+            points.iter().map(|dp| unix_nanos(time(dp))).collect()
+        }
+        match self {
+            MetricData::Gauge(g) => times(&g.data_points, |dp| &dp.time_unix_nano),
+            MetricData::Sum(s) => times(&s.data_points, |dp| &dp.time_unix_nano),
+            MetricData::Histogram(h) => times(&h.data_points, |dp| &dp.time_unix_nano),
+            MetricData::ExponentialHistogram(eh) => eh
+                .data_points
+                .iter()
+                .map(|dp| dp.get("timeUnixNano").and_then(unix_nanos))
+                .collect(),
+            MetricData::Summary(s) => times(&s.data_points, |dp| &dp.time_unix_nano),
+        }
+    }
+
+    /// Apply `update` to datapoint `index`'s attributes, or to every
+    /// datapoint's when `None`. Exponential histogram datapoints' go through
+    /// their raw JSON.
+    pub fn update_datapoint_attributes(
+        &mut self,
+        index: Option<usize>,
+        update: impl Fn(&mut Vec<KeyValue>),
+    ) {
+        fn each<P>(points: &mut [P], index: Option<usize>, f: impl FnMut(&mut P)) {
+            match index {
+                Some(i) => points.get_mut(i).into_iter().for_each(f),
+                None => points.iter_mut().for_each(f),
+            }
+        }
+        match self {
+            MetricData::Gauge(g) => {
+                each(&mut g.data_points, index, |dp| update(&mut dp.attributes))
+            }
+            MetricData::Sum(s) => each(&mut s.data_points, index, |dp| update(&mut dp.attributes)),
+            MetricData::Histogram(h) => {
+                each(&mut h.data_points, index, |dp| update(&mut dp.attributes))
+            }
+            MetricData::ExponentialHistogram(eh) => each(&mut eh.data_points, index, |dp| {
+                let Some(dp) = dp.as_object_mut() else {
+                    return;
+                };
+                let mut attrs = dp
+                    .get("attributes")
+                    .and_then(|a| Vec::<KeyValue>::deserialize(a).ok())
+                    .unwrap_or_default();
+                update(&mut attrs);
+                if let Ok(attrs) = serde_json::to_value(attrs) {
+                    dp.insert("attributes".to_string(), attrs);
+                }
+            }),
+            MetricData::Summary(s) => {
+                each(&mut s.data_points, index, |dp| update(&mut dp.attributes))
+            }
+        }
+    }
+
+    /// Keep the datapoints whose entry in `keep` is true.
+    pub fn retain_datapoints(&mut self, keep: &[bool]) {
+        fn retain<P>(points: &mut Vec<P>, keep: &[bool]) {
+            let mut keep = keep.iter();
+            points.retain(|_| keep.next().copied().unwrap_or(true));
+        }
+        match self {
+            MetricData::Gauge(g) => retain(&mut g.data_points, keep),
+            MetricData::Sum(s) => retain(&mut s.data_points, keep),
+            MetricData::Histogram(h) => retain(&mut h.data_points, keep),
+            MetricData::ExponentialHistogram(eh) => retain(&mut eh.data_points, keep),
+            MetricData::Summary(s) => retain(&mut s.data_points, keep),
+        }
+    }
+
+    pub fn datapoint_count(&self) -> usize {
+        match self {
+            MetricData::Gauge(g) => g.data_points.len(),
+            MetricData::Sum(s) => s.data_points.len(),
+            MetricData::Histogram(h) => h.data_points.len(),
+            MetricData::ExponentialHistogram(eh) => eh.data_points.len(),
+            MetricData::Summary(s) => s.data_points.len(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase", default)]
 pub struct Gauge {
     pub data_points: Vec<NumberDataPoint>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -225,6 +489,8 @@ pub struct Sum {
     pub data_points: Vec<NumberDataPoint>,
     pub aggregation_temporality: serde_json::Value,
     pub is_monotonic: bool,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -232,6 +498,8 @@ pub struct Sum {
 pub struct Histogram {
     pub data_points: Vec<HistogramDataPoint>,
     pub aggregation_temporality: serde_json::Value,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -239,12 +507,16 @@ pub struct Histogram {
 pub struct ExponentialHistogram {
     pub data_points: Vec<serde_json::Value>,
     pub aggregation_temporality: serde_json::Value,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase", default)]
 pub struct Summary {
     pub data_points: Vec<SummaryDataPoint>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -256,9 +528,11 @@ pub struct NumberDataPoint {
     pub exemplars: Vec<serde_json::Value>,
     pub flags: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub as_double: Option<f64>,
+    pub as_double: Option<Double>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub as_int: Option<serde_json::Value>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -268,13 +542,15 @@ pub struct HistogramDataPoint {
     pub start_time_unix_nano: serde_json::Value,
     pub time_unix_nano: serde_json::Value,
     pub count: serde_json::Value,
-    pub sum: Option<f64>,
+    pub sum: Option<Double>,
     pub bucket_counts: Vec<serde_json::Value>,
-    pub explicit_bounds: Vec<f64>,
+    pub explicit_bounds: Vec<Double>,
     pub exemplars: Vec<serde_json::Value>,
     pub flags: u32,
-    pub min: Option<f64>,
-    pub max: Option<f64>,
+    pub min: Option<Double>,
+    pub max: Option<Double>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -284,7 +560,81 @@ pub struct SummaryDataPoint {
     pub start_time_unix_nano: serde_json::Value,
     pub time_unix_nano: serde_json::Value,
     pub count: serde_json::Value,
-    pub sum: Option<f64>,
+    pub sum: Option<Double>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A datapoint double as OTLP JSON writes it: a number, or a string. Proto
+/// JSON spells the values a JSON number can't hold `"NaN"`, `"Infinity"`
+/// and `"-Infinity"`, and some producers quote ordinary values too. Either
+/// form is written back as it was read; a non-finite value built in code is
+/// written as its sentinel string, never as `null`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "DoubleJson", into = "DoubleJson")]
+pub struct Double {
+    pub value: f64,
+    /// The string the value was read from, if it was quoted.
+    pub text: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum DoubleJson {
+    Number(f64),
+    Text(String),
+}
+
+impl TryFrom<DoubleJson> for Double {
+    type Error = String;
+
+    fn try_from(json: DoubleJson) -> Result<Self, String> {
+        let text = match json {
+            DoubleJson::Number(value) => return Ok(value.into()),
+            DoubleJson::Text(text) => text,
+        };
+        let value = match text.as_str() {
+            "NaN" => f64::NAN,
+            "Infinity" => f64::INFINITY,
+            "-Infinity" => f64::NEG_INFINITY,
+            number => number
+                .parse()
+                .map_err(|_| format!("invalid double {text:?}"))?,
+        };
+        Ok(Double {
+            value,
+            text: Some(text),
+        })
+    }
+}
+
+impl From<Double> for DoubleJson {
+    fn from(double: Double) -> Self {
+        match double.text {
+            Some(text) => DoubleJson::Text(text),
+            None if double.value.is_finite() => DoubleJson::Number(double.value),
+            None => DoubleJson::Text(double.to_string()),
+        }
+    }
+}
+
+impl From<f64> for Double {
+    fn from(value: f64) -> Self {
+        Double { value, text: None }
+    }
+}
+
+/// The value as matchers see it: `NaN`, `Infinity` or `-Infinity` when it
+/// isn't finite, and the shortest round-tripping decimal otherwise.
+impl std::fmt::Display for Double {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value {
+            v if v.is_nan() => f.write_str("NaN"),
+            v if v == f64::INFINITY => f.write_str("Infinity"),
+            v if v == f64::NEG_INFINITY => f.write_str("-Infinity"),
+            v => write!(f, "{v}"),
+        }
+    }
 }
 
 // ─── Traces ──────────────────────────────────────────────────────────
@@ -293,6 +643,8 @@ pub struct SummaryDataPoint {
 #[serde(rename_all = "camelCase")]
 pub struct TracesData {
     pub resource_spans: Vec<ResourceSpans>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -301,6 +653,8 @@ pub struct ResourceSpans {
     pub resource: Option<Resource>,
     pub scope_spans: Vec<ScopeSpans>,
     pub schema_url: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -309,6 +663,8 @@ pub struct ScopeSpans {
     pub scope: Option<InstrumentationScope>,
     pub spans: Vec<Span>,
     pub schema_url: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -320,7 +676,10 @@ pub struct Span {
     pub parent_span_id: String,
     pub flags: u32,
     pub name: String,
-    pub kind: String,
+    /// Kept as raw JSON so whichever encoding the producer used (enum name,
+    /// bare name, or number) is re-emitted as-is; matching goes through
+    /// `eval::normalize_span_kind`.
+    pub kind: serde_json::Value,
     pub start_time_unix_nano: serde_json::Value,
     pub end_time_unix_nano: serde_json::Value,
     pub attributes: Vec<KeyValue>,
@@ -338,6 +697,8 @@ pub struct Span {
     pub span_id_bytes: Option<Vec<u8>>,
     #[serde(skip)]
     pub parent_span_id_bytes: Option<Vec<u8>>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -345,6 +706,43 @@ pub struct Span {
 pub struct Status {
     pub message: String,
     pub code: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+// ─── Profiles ────────────────────────────────────────────────────────
+//
+// policy_rs has no profile selectors yet, so only the envelope is modelled:
+// profiles are matched on resource/scope attributes and schema URLs, and the
+// profile bodies themselves (samples, locations, the shared dictionary) are
+// carried as raw JSON.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfilesData {
+    pub resource_profiles: Vec<ResourceProfiles>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ResourceProfiles {
+    pub resource: Option<Resource>,
+    pub scope_profiles: Vec<ScopeProfiles>,
+    pub schema_url: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScopeProfiles {
+    pub scope: Option<InstrumentationScope>,
+    pub profiles: Vec<serde_json::Value>,
+    pub schema_url: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 // ─── Byte decoding for typed/hex matchers ────────────────────────────
@@ -408,3 +806,636 @@ impl Span {
         prepare_attributes(&mut self.attributes);
     }
 }
+
+// ─── Unknown fields ──────────────────────────────────────────────────
+//
+// `--strict-input` rejects payloads carrying keys the structs above don't
+// model. Paths use the JSON (camelCase) names so a reported key can be found
+// in the input file directly, e.g. `resourceLogs[0].scopeLogs[1].logRecords[3].foo`.
+
+/// Record key holding an inline expectation for `--assert`. It is never an
+/// unknown field on a log record, metric or span.
+pub const EXPECT_KEY: &str = "expect";
+
+fn push_unknown(
+    out: &mut Vec<String>,
+    path: &str,
+    extra: &serde_json::Map<String, serde_json::Value>,
+) {
+    for key in extra.keys() {
+        if path.is_empty() {
+            out.push(key.clone());
+        } else {
+            out.push(format!("{path}.{key}"));
+        }
+    }
+}
+
+/// [`push_unknown`] for a record, which may carry [`EXPECT_KEY`].
+fn push_unknown_record(
+    out: &mut Vec<String>,
+    path: &str,
+    extra: &serde_json::Map<String, serde_json::Value>,
+) {
+    for key in extra.keys().filter(|&key| key != EXPECT_KEY) {
+        out.push(format!("{path}.{key}"));
+    }
+}
+
+fn envelope_unknown(
+    out: &mut Vec<String>,
+    path: &str,
+    resource: Option<&Resource>,
+    scope: Option<&InstrumentationScope>,
+) {
+    if let Some(r) = resource {
+        push_unknown(out, &format!("{path}.resource"), &r.extra);
+    }
+    if let Some(s) = scope {
+        push_unknown(out, &format!("{path}.scope"), &s.extra);
+    }
+}
+
+impl LogsData {
+    /// JSON paths of every key in the payload that isn't modelled above.
+    pub fn unknown_fields(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        push_unknown(&mut out, "", &self.extra);
+        for (i, rl) in self.resource_logs.iter().enumerate() {
+            let rl_path = format!("resourceLogs[{i}]");
+            push_unknown(&mut out, &rl_path, &rl.extra);
+            envelope_unknown(&mut out, &rl_path, rl.resource.as_ref(), None);
+            for (j, sl) in rl.scope_logs.iter().enumerate() {
+                let sl_path = format!("{rl_path}.scopeLogs[{j}]");
+                push_unknown(&mut out, &sl_path, &sl.extra);
+                envelope_unknown(&mut out, &sl_path, None, sl.scope.as_ref());
+                for (k, rec) in sl.log_records.iter().enumerate() {
+                    push_unknown_record(
+                        &mut out,
+                        &format!("{sl_path}.logRecords[{k}]"),
+                        &rec.extra,
+                    );
+                }
+            }
+        }
+        out
+    }
+}
+
+impl MetricsData {
+    /// JSON paths of every key in the payload that isn't modelled above.
+    pub fn unknown_fields(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        push_unknown(&mut out, "", &self.extra);
+        for (i, rm) in self.resource_metrics.iter().enumerate() {
+            let rm_path = format!("resourceMetrics[{i}]");
+            push_unknown(&mut out, &rm_path, &rm.extra);
+            envelope_unknown(&mut out, &rm_path, rm.resource.as_ref(), None);
+            for (j, sm) in rm.scope_metrics.iter().enumerate() {
+                let sm_path = format!("{rm_path}.scopeMetrics[{j}]");
+                push_unknown(&mut out, &sm_path, &sm.extra);
+                envelope_unknown(&mut out, &sm_path, None, sm.scope.as_ref());
+                for (k, m) in sm.metrics.iter().enumerate() {
+                    let m_path = format!("{sm_path}.metrics[{k}]");
+                    push_unknown_record(&mut out, &m_path, &m.extra);
+                    if let Some(data) = &m.data {
+                        data.unknown_fields(&mut out, &m_path);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+impl MetricData {
+    fn unknown_fields(&self, out: &mut Vec<String>, metric_path: &str) {
+        match self {
+            MetricData::Gauge(g) => {
+                let path = format!("{metric_path}.gauge");
+                push_unknown(out, &path, &g.extra);
+                for (i, dp) in g.data_points.iter().enumerate() {
+                    push_unknown(out, &format!("{path}.dataPoints[{i}]"), &dp.extra);
+                }
+            }
+            MetricData::Sum(s) => {
+                let path = format!("{metric_path}.sum");
+                push_unknown(out, &path, &s.extra);
+                for (i, dp) in s.data_points.iter().enumerate() {
+                    push_unknown(out, &format!("{path}.dataPoints[{i}]"), &dp.extra);
+                }
+            }
+            MetricData::Histogram(h) => {
+                let path = format!("{metric_path}.histogram");
+                push_unknown(out, &path, &h.extra);
+                for (i, dp) in h.data_points.iter().enumerate() {
+                    push_unknown(out, &format!("{path}.dataPoints[{i}]"), &dp.extra);
+                }
+            }
+            // Exponential histogram datapoints are kept as raw JSON, so only
+            // the container itself can carry unmodelled keys.
+            MetricData::ExponentialHistogram(eh) => {
+                push_unknown(
+                    out,
+                    &format!("{metric_path}.exponentialHistogram"),
+                    &eh.extra,
+                );
+            }
+            MetricData::Summary(s) => {
+                let path = format!("{metric_path}.summary");
+                push_unknown(out, &path, &s.extra);
+                for (i, dp) in s.data_points.iter().enumerate() {
+                    push_unknown(out, &format!("{path}.dataPoints[{i}]"), &dp.extra);
+                }
+            }
+        }
+    }
+}
+
+impl TracesData {
+    /// JSON paths of every key in the payload that isn't modelled above.
+    pub fn unknown_fields(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        push_unknown(&mut out, "", &self.extra);
+        for (i, rs) in self.resource_spans.iter().enumerate() {
+            let rs_path = format!("resourceSpans[{i}]");
+            push_unknown(&mut out, &rs_path, &rs.extra);
+            envelope_unknown(&mut out, &rs_path, rs.resource.as_ref(), None);
+            for (j, ss) in rs.scope_spans.iter().enumerate() {
+                let ss_path = format!("{rs_path}.scopeSpans[{j}]");
+                push_unknown(&mut out, &ss_path, &ss.extra);
+                envelope_unknown(&mut out, &ss_path, None, ss.scope.as_ref());
+                for (k, span) in ss.spans.iter().enumerate() {
+                    let span_path = format!("{ss_path}.spans[{k}]");
+                    push_unknown_record(&mut out, &span_path, &span.extra);
+                    if let Some(status) = &span.status {
+                        push_unknown(&mut out, &format!("{span_path}.status"), &status.extra);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+impl ProfilesData {
+    /// JSON paths of every key in the payload that isn't modelled above.
+    /// Profile bodies are raw JSON, so only envelope keys are checked.
+    pub fn unknown_fields(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        push_unknown(&mut out, "", &self.extra);
+        for (i, rp) in self.resource_profiles.iter().enumerate() {
+            let rp_path = format!("resourceProfiles[{i}]");
+            push_unknown(&mut out, &rp_path, &rp.extra);
+            envelope_unknown(&mut out, &rp_path, rp.resource.as_ref(), None);
+            for (j, sp) in rp.scope_profiles.iter().enumerate() {
+                let sp_path = format!("{rp_path}.scopeProfiles[{j}]");
+                push_unknown(&mut out, &sp_path, &sp.extra);
+                envelope_unknown(&mut out, &sp_path, None, sp.scope.as_ref());
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    /// Parse `payload` as `T` and write it back, asserting nothing was lost
+    /// or added. Fixtures spell out every modelled field, since absent ones
+    /// are written back with their defaults.
+    fn round_trip<T: Serialize + DeserializeOwned>(payload: Value) -> T {
+        let parsed: T = serde_json::from_value(payload.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), payload);
+        parsed
+    }
+
+    fn resource() -> Value {
+        json!({
+            "attributes": [{ "key": "service.name", "value": { "stringValue": "api" } }],
+            "droppedAttributesCount": 0,
+            "entityRefs": [],
+            "futureResourceField": { "nested": [1, 2] },
+        })
+    }
+
+    fn scope() -> Value {
+        json!({
+            "name": "lib",
+            "version": "1.0",
+            "attributes": [],
+            "droppedAttributesCount": 0,
+            "futureScopeField": true,
+        })
+    }
+
+    #[test]
+    fn logs_keep_unknown_fields_at_every_level() {
+        let logs: LogsData = round_trip(json!({
+            "resourceLogs": [{
+                "resource": resource(),
+                "scopeLogs": [{
+                    "scope": scope(),
+                    "logRecords": [{
+                        "timeUnixNano": 1,
+                        "observedTimeUnixNano": "2",
+                        "severityNumber": "SEVERITY_NUMBER_INFO",
+                        "severityText": "INFO",
+                        "body": { "stringValue": "hello" },
+                        "attributes": [],
+                        "droppedAttributesCount": 0,
+                        "flags": 0,
+                        "traceId": "",
+                        "spanId": "",
+                        "eventName": "",
+                        "futureRecordField": "x",
+                    }],
+                    "schemaUrl": "",
+                    "futureScopeLogsField": 3,
+                }],
+                "schemaUrl": "",
+                "futureResourceLogsField": null,
+            }],
+            "futureTopLevelField": {},
+        }));
+        assert_eq!(
+            logs.unknown_fields(),
+            [
+                "futureTopLevelField",
+                "resourceLogs[0].futureResourceLogsField",
+                "resourceLogs[0].resource.futureResourceField",
+                "resourceLogs[0].scopeLogs[0].futureScopeLogsField",
+                "resourceLogs[0].scopeLogs[0].scope.futureScopeField",
+                "resourceLogs[0].scopeLogs[0].logRecords[0].futureRecordField",
+            ]
+        );
+    }
+
+    #[test]
+    fn traces_keep_unknown_fields_at_every_level() {
+        let traces: TracesData = round_trip(json!({
+            "resourceSpans": [{
+                "resource": resource(),
+                "scopeSpans": [{
+                    "scope": scope(),
+                    "spans": [{
+                        "traceId": "5b8efff798038103d269b633813fc60c",
+                        "spanId": "eee19b7ec3c1b174",
+                        "traceState": "",
+                        "parentSpanId": "",
+                        "flags": 0,
+                        "name": "GET /",
+                        "kind": 2,
+                        "startTimeUnixNano": 1,
+                        "endTimeUnixNano": 2,
+                        "attributes": [],
+                        "droppedAttributesCount": 0,
+                        "events": [],
+                        "droppedEventsCount": 0,
+                        "links": [],
+                        "droppedLinksCount": 0,
+                        "status": { "message": "", "code": "STATUS_CODE_OK", "futureStatusField": 1 },
+                        "futureSpanField": [],
+                    }],
+                    "schemaUrl": "",
+                    "futureScopeSpansField": "y",
+                }],
+                "schemaUrl": "",
+                "futureResourceSpansField": 0.5,
+            }],
+            "futureTopLevelField": "z",
+        }));
+        assert_eq!(
+            traces.unknown_fields(),
+            [
+                "futureTopLevelField",
+                "resourceSpans[0].futureResourceSpansField",
+                "resourceSpans[0].resource.futureResourceField",
+                "resourceSpans[0].scopeSpans[0].futureScopeSpansField",
+                "resourceSpans[0].scopeSpans[0].scope.futureScopeField",
+                "resourceSpans[0].scopeSpans[0].spans[0].futureSpanField",
+                "resourceSpans[0].scopeSpans[0].spans[0].status.futureStatusField",
+            ]
+        );
+    }
+
+    #[test]
+    fn metrics_keep_unknown_fields_at_every_level() {
+        let metrics: MetricsData = round_trip(json!({
+            "resourceMetrics": [{
+                "resource": resource(),
+                "scopeMetrics": [{
+                    "scope": scope(),
+                    "metrics": [
+                        {
+                            "name": "requests",
+                            "description": "",
+                            "unit": "1",
+                            "metadata": [],
+                            "gauge": {
+                                "dataPoints": [{
+                                    "attributes": [],
+                                    "startTimeUnixNano": 0,
+                                    "timeUnixNano": 1,
+                                    "exemplars": [],
+                                    "flags": 0,
+                                    "asDouble": 1.5,
+                                    "futurePointField": "p",
+                                }],
+                                "futureGaugeField": 1,
+                            },
+                            "futureMetricField": "m",
+                        },
+                        {
+                            "name": "latency",
+                            "description": "",
+                            "unit": "ms",
+                            "metadata": [],
+                            "histogram": {
+                                "dataPoints": [{
+                                    "attributes": [],
+                                    "startTimeUnixNano": 0,
+                                    "timeUnixNano": 1,
+                                    "count": 2,
+                                    "sum": 3.0,
+                                    "bucketCounts": [1, 1],
+                                    "explicitBounds": [1.0],
+                                    "exemplars": [],
+                                    "flags": 0,
+                                    "min": null,
+                                    "max": "Infinity",
+                                    "futurePointField": {},
+                                }],
+                                "aggregationTemporality": 2,
+                                "futureHistogramField": [],
+                            },
+                        },
+                        {
+                            "name": "quantiles",
+                            "description": "",
+                            "unit": "",
+                            "metadata": [],
+                            "summary": {
+                                "dataPoints": [{
+                                    "attributes": [],
+                                    "startTimeUnixNano": 0,
+                                    "timeUnixNano": 1,
+                                    "count": 1,
+                                    "sum": 1.0,
+                                    "quantileValues": [{ "quantile": 0.5, "value": 1.0 }],
+                                }],
+                            },
+                            "futureMetricField": false,
+                        },
+                    ],
+                    "schemaUrl": "",
+                    "futureScopeMetricsField": 4,
+                }],
+                "schemaUrl": "",
+                "futureResourceMetricsField": "r",
+            }],
+            "futureTopLevelField": [],
+        }));
+
+        // `data` and `extra` are both flattened into the metric object; each
+        // must take only its own keys.
+        let gauge = &metrics.resource_metrics[0].scope_metrics[0].metrics[0];
+        assert!(matches!(gauge.data, Some(MetricData::Gauge(_))));
+        assert_eq!(
+            gauge.extra.keys().collect::<Vec<_>>(),
+            ["futureMetricField"]
+        );
+
+        assert_eq!(
+            metrics.unknown_fields(),
+            [
+                "futureTopLevelField",
+                "resourceMetrics[0].futureResourceMetricsField",
+                "resourceMetrics[0].resource.futureResourceField",
+                "resourceMetrics[0].scopeMetrics[0].futureScopeMetricsField",
+                "resourceMetrics[0].scopeMetrics[0].scope.futureScopeField",
+                "resourceMetrics[0].scopeMetrics[0].metrics[0].futureMetricField",
+                "resourceMetrics[0].scopeMetrics[0].metrics[0].gauge.futureGaugeField",
+                "resourceMetrics[0].scopeMetrics[0].metrics[0].gauge.dataPoints[0].futurePointField",
+                "resourceMetrics[0].scopeMetrics[0].metrics[1].histogram.futureHistogramField",
+                "resourceMetrics[0].scopeMetrics[0].metrics[1].histogram.dataPoints[0].futurePointField",
+                "resourceMetrics[0].scopeMetrics[0].metrics[2].futureMetricField",
+                "resourceMetrics[0].scopeMetrics[0].metrics[2].summary.dataPoints[0].quantileValues",
+            ]
+        );
+    }
+
+    #[test]
+    fn metric_without_data_keeps_unknown_fields() {
+        let metric: Metric = round_trip(json!({
+            "name": "empty",
+            "description": "",
+            "unit": "",
+            "metadata": [],
+            "futureMetricField": 1,
+        }));
+        assert!(metric.data.is_none());
+        assert_eq!(
+            metric.extra.keys().collect::<Vec<_>>(),
+            ["futureMetricField"]
+        );
+    }
+
+    #[test]
+    fn profiles_keep_unknown_fields_at_every_level() {
+        let profiles: ProfilesData = round_trip(json!({
+            "resourceProfiles": [{
+                "resource": resource(),
+                "scopeProfiles": [{
+                    "scope": scope(),
+                    "profiles": [{ "sample": [], "futureProfileField": 1 }],
+                    "schemaUrl": "",
+                    "futureScopeProfilesField": 5,
+                }],
+                "schemaUrl": "",
+                "futureResourceProfilesField": "q",
+            }],
+            "dictionary": { "stringTable": [""] },
+        }));
+        assert_eq!(
+            profiles.unknown_fields(),
+            [
+                "dictionary",
+                "resourceProfiles[0].futureResourceProfilesField",
+                "resourceProfiles[0].resource.futureResourceField",
+                "resourceProfiles[0].scopeProfiles[0].futureScopeProfilesField",
+                "resourceProfiles[0].scopeProfiles[0].scope.futureScopeField",
+            ]
+        );
+    }
+
+    /// `value`'s canonical text and JSON.
+    fn canonical(value: Value) -> (String, Value) {
+        let value = any_value(&value);
+        (
+            value.canonical_string().into_owned(),
+            value.canonical_json(),
+        )
+    }
+
+    fn double(d: f64) -> String {
+        let value = AnyValue {
+            double_value: Some(d),
+            ..AnyValue::default()
+        };
+        value.canonical_string().into_owned()
+    }
+
+    #[test]
+    fn canonical_strings_and_bytes_are_as_written() {
+        assert_eq!(
+            canonical(json!({ "stringValue": "a \"b\"" })),
+            ("a \"b\"".to_string(), json!("a \"b\""))
+        );
+        assert_eq!(
+            canonical(json!({ "stringValue": "" })),
+            (String::new(), json!(""))
+        );
+        assert_eq!(
+            canonical(json!({ "bytesValue": "3q2+7w==" })),
+            ("3q2+7w==".to_string(), json!("3q2+7w=="))
+        );
+    }
+
+    #[test]
+    fn canonical_bools() {
+        assert_eq!(
+            canonical(json!({ "boolValue": true })),
+            ("true".to_string(), json!(true))
+        );
+        assert_eq!(
+            canonical(json!({ "boolValue": false })),
+            ("false".to_string(), json!(false))
+        );
+    }
+
+    #[test]
+    fn canonical_ints_in_decimal() {
+        assert_eq!(
+            canonical(json!({ "intValue": "42" })),
+            ("42".to_string(), json!(42))
+        );
+        assert_eq!(
+            canonical(json!({ "intValue": -7 })),
+            ("-7".to_string(), json!(-7))
+        );
+        assert_eq!(
+            canonical(json!({ "intValue": "-9223372036854775808" })),
+            ("-9223372036854775808".to_string(), json!(i64::MIN))
+        );
+        assert_eq!(
+            canonical(json!({ "intValue": "9223372036854775807" })),
+            ("9223372036854775807".to_string(), json!(i64::MAX))
+        );
+        // Past i64, the text is kept as written.
+        assert_eq!(
+            canonical(json!({ "intValue": "9223372036854775808" })),
+            (
+                "9223372036854775808".to_string(),
+                json!("9223372036854775808")
+            )
+        );
+    }
+
+    #[test]
+    fn canonical_doubles_in_shortest_round_trip_form() {
+        for (d, text) in [
+            (1.0, "1.0"),
+            (-2.5, "-2.5"),
+            (0.0, "0.0"),
+            (-0.0, "-0.0"),
+            (0.1, "0.1"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1e300, "1e+300"),
+            (1e-7, "1e-7"),
+            (1e16, "1e+16"),
+            (123456.789, "123456.789"),
+            (f64::MAX, "1.7976931348623157e+308"),
+            (f64::MIN_POSITIVE, "2.2250738585072014e-308"),
+            (5e-324, "5e-324"),
+            (f64::NAN, "NaN"),
+            (f64::INFINITY, "inf"),
+            (f64::NEG_INFINITY, "-inf"),
+        ] {
+            assert_eq!(double(d), text, "{d:e}");
+            if d.is_finite() {
+                assert_eq!(text.parse::<f64>().unwrap(), d, "{text} round-trips");
+            }
+        }
+        assert_eq!(
+            canonical(json!({ "doubleValue": 0.5 })),
+            ("0.5".to_string(), json!(0.5))
+        );
+    }
+
+    #[test]
+    fn canonical_arrays_render_each_member() {
+        let array = json!({ "arrayValue": { "values": [
+            { "intValue": "1" },
+            { "stringValue": "a" },
+            { "boolValue": true },
+            { "doubleValue": 1.5 },
+            { "bytesValue": "AA==" },
+            { "arrayValue": { "values": [{ "intValue": 2 }] } },
+            {},
+        ] } });
+        assert_eq!(
+            canonical(array),
+            (
+                r#"[1,"a",true,1.5,"AA==",[2],null]"#.to_string(),
+                json!([1, "a", true, 1.5, "AA==", [2], null])
+            )
+        );
+        assert_eq!(
+            canonical(json!({ "arrayValue": {} })),
+            ("[]".to_string(), json!([]))
+        );
+    }
+
+    #[test]
+    fn canonical_kvlists_sort_keys_at_every_depth() {
+        let kvlist = json!({ "kvlistValue": { "values": [
+            { "key": "b", "value": { "kvlistValue": { "values": [
+                { "key": "z", "value": { "intValue": "1" } },
+                { "key": "y", "value": { "arrayValue": { "values": [
+                    { "kvlistValue": { "values": [
+                        { "key": "q", "value": { "boolValue": false } },
+                        { "key": "p", "value": { "doubleValue": 2.0 } },
+                    ] } },
+                ] } } },
+            ] } } },
+            { "key": "a", "value": { "stringValue": "x" } },
+            { "key": "c" },
+        ] } });
+        let (text, json) = canonical(kvlist);
+        assert_eq!(
+            text,
+            r#"{"a":"x","b":{"y":[{"p":2.0,"q":false}],"z":1},"c":null}"#
+        );
+        assert_eq!(
+            json,
+            json!({ "a": "x", "b": { "y": [{ "p": 2.0, "q": false }], "z": 1 }, "c": null })
+        );
+    }
+
+    #[test]
+    fn canonical_empty_and_malformed_values_are_empty() {
+        assert_eq!(canonical(json!({})), (String::new(), Value::Null));
+        // A malformed member renders as empty rather than failing the rest.
+        assert_eq!(
+            canonical(
+                json!({ "arrayValue": { "values": [{ "boolValue": "yes" }, { "intValue": 3 }] } })
+            ),
+            ("[null,3]".to_string(), json!([null, 3]))
+        );
+    }
+}
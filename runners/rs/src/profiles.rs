@@ -0,0 +1,114 @@
+//! `--profile <name>`: a named bundle of run-mode flags, so a workflow's
+//! invocation lives in one place instead of each person's shell history.
+//!
+//! Profiles are tables of the config file's `profiles` table, keyed and
+//! valued as its top-level flags are:
+//!
+//! ```toml
+//! [profiles.nightly]
+//! sample-input = 0.1
+//! seed = 7
+//! exclude-policy = ["debug-*"]
+//! ```
+//!
+//! Two are built in, and a config profile of the same name replaces one:
+//! - `conformance`, for golden runs: strict input, every record through the
+//!   engine, decision detail (`record_diffs` and `rate_limit_traces`) in the
+//!   output, and a fixed `--seed`
+//! - `replay`, for production captures: the input memory-mapped, invalid
+//!   UTF-8 replaced rather than refused, and retried exports evaluated once
+//!
+//! A profile's flags rank below the command line and `POLICY_RUNNER_*`
+//! variables and above the rest of the config file; a flag is also left
+//! out when it conflicts with one that outranks it. `runner-rs profiles`
+//! lists them all.
+
+use std::path::PathBuf;
+
+use crate::config;
+use crate::status::{self, Failure};
+
+const BUILTIN: &[(&str, &str)] = &[
+    (
+        "conformance",
+        r#"
+        strict-input = true
+        no-fast-path = true
+        record-diffs = true
+        rate-limit-trace = true
+        seed = 0
+        "#,
+    ),
+    (
+        "replay",
+        r#"
+        mmap = true
+        utf8 = "lossy"
+        dedupe-envelopes = true
+        "#,
+    ),
+];
+
+#[derive(clap::Args)]
+pub struct ProfilesArgs {
+    /// Config file whose `profiles` table to list alongside the built-in
+    /// ones.
+    #[arg(long, env = "POLICY_RUNNER_CONFIG", value_name = "PATH")]
+    config: Option<PathBuf>,
+}
+
+/// A profile and where it was defined.
+pub struct Profile {
+    pub name: String,
+    /// `built-in`, or the config file's path.
+    pub source: String,
+    pub flags: toml::Table,
+}
+
+/// Every profile `config` and the built-ins define, by name; `source` names
+/// the config file.
+pub fn all(config: &toml::Table, source: &str) -> Result<Vec<Profile>, String> {
+    let mut profiles: Vec<Profile> = BUILTIN
+        .iter()
+        .map(|(name, flags)| Profile {
+            name: name.to_string(),
+            source: "built-in".to_string(),
+            flags: flags.parse().expect("built-in profiles are valid TOML"),
+        })
+        .collect();
+    let Some(defined) = config.get("profiles") else {
+        return Ok(profiles);
+    };
+    let defined = defined
+        .as_table()
+        .ok_or("profiles must be a table of profiles")?;
+    for (name, flags) in defined {
+        let flags = flags
+            .as_table()
+            .ok_or_else(|| format!("profile {name} must be a table of flags"))?;
+        profiles.retain(|p| p.name != *name);
+        profiles.push(Profile {
+            name: name.clone(),
+            source: source.to_string(),
+            flags: flags.clone(),
+        });
+    }
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(profiles)
+}
+
+/// `runner-rs profiles`: each profile's name and source, then its flags.
+pub fn run(args: &ProfilesArgs) -> status::Result {
+    let (table, source) = match &args.config {
+        Some(path) => (config::read(path)?, path.display().to_string()),
+        None => (toml::Table::new(), String::new()),
+    };
+    let profiles = all(&table, &source).map_err(Failure)?;
+    for profile in profiles {
+        println!("{} ({})", profile.name, profile.source);
+        for (key, value) in &profile.flags {
+            println!("  {key} = {value}");
+        }
+    }
+    Ok(())
+}
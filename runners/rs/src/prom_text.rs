@@ -0,0 +1,539 @@
+//! `--format prom-text`: a Prometheus scrape dump, in the text exposition
+//! format or OpenMetrics, read as one OTLP metrics payload so metric
+//! policies can be tried against it.
+//!
+//! Each family becomes one metric named after it, with its `# HELP` as the
+//! description and its OpenMetrics `# UNIT` as the unit. Each distinct label
+//! set becomes one datapoint carrying the labels as attributes:
+//!
+//! - `counter` becomes a cumulative, monotonic sum, read from the family's
+//!   own samples or its `_total` ones
+//! - `histogram` becomes a cumulative histogram: `_bucket` samples' `le`
+//!   labels fold into its explicit bounds and bucket counts, and `_sum` and
+//!   `_count` into its sum and count
+//! - `summary` becomes a summary whose `quantile` labels fold into its
+//!   quantile values
+//! - `gauge`, every other type, and samples with no `# TYPE` become a gauge
+//!
+//! A `_created` sample sets its datapoint's start time. Sample timestamps
+//! set its time; they're read as milliseconds, or as seconds in OpenMetrics
+//! input (which ends in `# EOF`). OpenMetrics exemplars
+//! (`# {trace_id="…"} 0.5 1700000000.5`) become exemplars of their sample's
+//! datapoint, with `trace_id` and `span_id` labels as its ids and the other
+//! labels as filtered attributes.
+//!
+//! Every metric lands in one resource and scope, neither with attributes.
+
+use std::collections::HashMap;
+
+use runner_rs::otel::{self, Double};
+use serde_json::{Value, json};
+
+use crate::temporality::Temporality;
+
+/// The payload `text` describes, or the first line it can't read.
+pub fn parse(text: &str) -> Result<otel::MetricsData, String> {
+    let open_metrics = text.lines().rev().find(|l| !l.trim().is_empty()) == Some("# EOF");
+    let mut families = Families::default();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let read = if let Some(comment) = line.strip_prefix('#') {
+            if comment.trim() == "EOF" {
+                break;
+            }
+            families.comment(comment);
+            Ok(())
+        } else if line.is_empty() {
+            Ok(())
+        } else {
+            Sample::parse(line, open_metrics).and_then(|sample| families.add(sample))
+        };
+        read.map_err(|e| format!("line {}: {e}", i + 1))?;
+    }
+    let metrics = families.list.into_iter().map(Family::metric).collect();
+    Ok(otel::MetricsData {
+        resource_metrics: vec![otel::ResourceMetrics {
+            scope_metrics: vec![otel::ScopeMetrics {
+                metrics,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        extra: serde_json::Map::new(),
+    })
+}
+
+// ─── Families ────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Counter,
+    Gauge,
+    Histogram,
+    Summary,
+}
+
+impl Kind {
+    fn from_type(name: &str) -> Kind {
+        match name {
+            "counter" => Kind::Counter,
+            "histogram" => Kind::Histogram,
+            "summary" => Kind::Summary,
+            _ => Kind::Gauge,
+        }
+    }
+
+    /// The sample name suffixes that belong to a family of this kind.
+    fn suffixes(self) -> &'static [&'static str] {
+        match self {
+            Kind::Counter => &["_total", "_created"],
+            Kind::Gauge => &[],
+            Kind::Histogram => &["_bucket", "_sum", "_count", "_created"],
+            Kind::Summary => &["_sum", "_count", "_created"],
+        }
+    }
+}
+
+#[derive(Default)]
+struct Families {
+    list: Vec<Family>,
+    by_name: HashMap<String, usize>,
+}
+
+impl Families {
+    fn named(&mut self, name: &str) -> &mut Family {
+        let index = *self.by_name.entry(name.to_string()).or_insert_with(|| {
+            self.list.push(Family::new(name));
+            self.list.len() - 1
+        });
+        &mut self.list[index]
+    }
+
+    /// A `# HELP`, `# TYPE` or `# UNIT` line; other comments say nothing.
+    fn comment(&mut self, comment: &str) {
+        let mut words = comment.trim_start().splitn(3, ' ');
+        let (Some(keyword), Some(name)) = (words.next(), words.next()) else {
+            return;
+        };
+        let rest = words.next().unwrap_or_default();
+        match keyword {
+            "HELP" => self.named(name).help = unescape(rest, false),
+            "TYPE" => self.named(name).kind = Kind::from_type(rest.trim()),
+            "UNIT" => self.named(name).unit = rest.trim().to_string(),
+            _ => {}
+        }
+    }
+
+    /// The family `name` belongs to, and the suffix it was found by.
+    fn find(&self, name: &str) -> Option<(usize, &'static str)> {
+        if let Some(&index) = self.by_name.get(name) {
+            return Some((index, ""));
+        }
+        self.list.iter().enumerate().find_map(|(index, family)| {
+            let suffix = name.strip_prefix(family.name.as_str())?;
+            family
+                .kind
+                .suffixes()
+                .iter()
+                .find(|&&s| s == suffix)
+                .map(|&s| (index, s))
+        })
+    }
+
+    fn add(&mut self, sample: Sample) -> Result<(), String> {
+        let (index, suffix) = match self.find(&sample.name) {
+            Some(found) => found,
+            None => {
+                self.named(&sample.name);
+                (self.list.len() - 1, "")
+            }
+        };
+        self.list[index].add(suffix, sample)
+    }
+}
+
+struct Family {
+    name: String,
+    kind: Kind,
+    help: String,
+    unit: String,
+    series: Vec<Series>,
+    /// Index into `series` by sorted label set.
+    by_labels: HashMap<Vec<(String, String)>, usize>,
+}
+
+#[derive(Default)]
+struct Series {
+    labels: Vec<(String, String)>,
+    value: Option<f64>,
+    start: Option<u64>,
+    time: Option<u64>,
+    /// `(le, cumulative count)` of each `_bucket` sample.
+    buckets: Vec<(f64, f64)>,
+    /// `(quantile, value)` of each quantile sample.
+    quantiles: Vec<(f64, f64)>,
+    sum: Option<f64>,
+    count: Option<f64>,
+    exemplars: Vec<Value>,
+}
+
+impl Family {
+    fn new(name: &str) -> Family {
+        Family {
+            name: name.to_string(),
+            kind: Kind::Gauge,
+            help: String::new(),
+            unit: String::new(),
+            series: Vec::new(),
+            by_labels: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, suffix: &str, mut sample: Sample) -> Result<(), String> {
+        let folded = match self.kind {
+            Kind::Histogram if suffix == "_bucket" => Some("le"),
+            Kind::Summary if suffix.is_empty() => Some("quantile"),
+            _ => None,
+        };
+        let bound = folded
+            .map(|label| {
+                let at = sample.labels.iter().position(|(name, _)| name == label);
+                let (_, text) = sample
+                    .labels
+                    .remove(at.ok_or_else(|| format!("{} has no {label} label", sample.name))?);
+                number(&text).ok_or_else(|| format!("invalid {label} {text:?}"))
+            })
+            .transpose()?;
+        let mut key = sample.labels.clone();
+        key.sort_unstable();
+        let index = *self.by_labels.entry(key).or_insert_with(|| {
+            self.series.push(Series {
+                labels: sample.labels,
+                ..Series::default()
+            });
+            self.series.len() - 1
+        });
+        let series = &mut self.series[index];
+        if suffix == "_created" {
+            let start = scaled(&sample.text, 9)
+                .ok_or_else(|| format!("invalid creation time {:?}", sample.text))?;
+            series.start = Some(start);
+            return Ok(());
+        }
+        match (self.kind, suffix, bound) {
+            (Kind::Histogram, "_bucket", Some(le)) => series.buckets.push((le, sample.value)),
+            (Kind::Summary, "", Some(quantile)) => series.quantiles.push((quantile, sample.value)),
+            (_, "_sum", _) => series.sum = Some(sample.value),
+            (_, "_count", _) => series.count = Some(sample.value),
+            (Kind::Counter | Kind::Gauge, _, _) => series.value = Some(sample.value),
+            _ => {
+                return Err(format!(
+                    "{} is not a sample of a {}",
+                    sample.name, self.name
+                ));
+            }
+        }
+        series.time = sample.time.or(series.time);
+        series.exemplars.extend(sample.exemplar);
+        Ok(())
+    }
+
+    fn metric(self) -> otel::Metric {
+        let kind = self.kind;
+        let series = self.series.into_iter();
+        let data = match kind {
+            Kind::Gauge => otel::MetricData::Gauge(otel::Gauge {
+                data_points: series.map(Series::number_point).collect(),
+                ..Default::default()
+            }),
+            Kind::Counter => otel::MetricData::Sum(otel::Sum {
+                data_points: series.map(Series::number_point).collect(),
+                aggregation_temporality: Temporality::Cumulative.name().into(),
+                is_monotonic: true,
+                ..Default::default()
+            }),
+            Kind::Histogram => otel::MetricData::Histogram(otel::Histogram {
+                data_points: series.map(Series::histogram_point).collect(),
+                aggregation_temporality: Temporality::Cumulative.name().into(),
+                ..Default::default()
+            }),
+            Kind::Summary => otel::MetricData::Summary(otel::Summary {
+                data_points: series.map(Series::summary_point).collect(),
+                ..Default::default()
+            }),
+        };
+        otel::Metric {
+            name: self.name,
+            description: self.help,
+            unit: self.unit,
+            data: Some(data),
+            ..Default::default()
+        }
+    }
+}
+
+impl Series {
+    fn attributes(&self) -> Vec<otel::KeyValue> {
+        attributes(&self.labels)
+    }
+
+    fn number_point(self) -> otel::NumberDataPoint {
+        otel::NumberDataPoint {
+            attributes: self.attributes(),
+            start_time_unix_nano: self.start.unwrap_or_default().into(),
+            time_unix_nano: self.time.unwrap_or_default().into(),
+            exemplars: self.exemplars,
+            as_double: self.value.map(Double::from),
+            ..Default::default()
+        }
+    }
+
+    fn histogram_point(mut self) -> otel::HistogramDataPoint {
+        self.buckets.sort_by(|a, b| a.0.total_cmp(&b.0));
+        // Prometheus buckets count everything at or below their bound; OTLP
+        // buckets count what falls between theirs and the previous one.
+        let mut counts = Vec::with_capacity(self.buckets.len() + 1);
+        let mut below = 0.0;
+        for &(_, cumulative) in &self.buckets {
+            counts.push(Value::from(count(cumulative - below)));
+            below = cumulative;
+        }
+        let total = self.count.unwrap_or(below);
+        let mut bounds: Vec<Double> = self.buckets.iter().map(|&(le, _)| le.into()).collect();
+        if bounds.pop_if(|le| le.value == f64::INFINITY).is_none() {
+            counts.push(Value::from(count(total - below)));
+        }
+        otel::HistogramDataPoint {
+            attributes: self.attributes(),
+            start_time_unix_nano: self.start.unwrap_or_default().into(),
+            time_unix_nano: self.time.unwrap_or_default().into(),
+            count: count(total).into(),
+            sum: self.sum.map(Double::from),
+            bucket_counts: counts,
+            explicit_bounds: bounds,
+            exemplars: self.exemplars,
+            ..Default::default()
+        }
+    }
+
+    fn summary_point(mut self) -> otel::SummaryDataPoint {
+        self.quantiles.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let quantiles: Vec<Value> = self
+            .quantiles
+            .iter()
+            .map(|&(quantile, value)| json!({"quantile": quantile, "value": Double::from(value)}))
+            .collect();
+        let mut extra = serde_json::Map::new();
+        if !quantiles.is_empty() {
+            extra.insert("quantileValues".to_string(), quantiles.into());
+        }
+        otel::SummaryDataPoint {
+            attributes: self.attributes(),
+            start_time_unix_nano: self.start.unwrap_or_default().into(),
+            time_unix_nano: self.time.unwrap_or_default().into(),
+            count: count(self.count.unwrap_or_default()).into(),
+            sum: self.sum.map(Double::from),
+            extra,
+        }
+    }
+}
+
+fn attributes(labels: &[(String, String)]) -> Vec<otel::KeyValue> {
+    labels
+        .iter()
+        .map(|(name, value)| otel::KeyValue {
+            key: name.clone(),
+            value: Some(otel::AnyValue {
+                string_value: Some(value.clone()),
+                ..Default::default()
+            }),
+        })
+        .collect()
+}
+
+/// A sample count as OTLP's unsigned integer.
+fn count(value: f64) -> u64 {
+    // `as` saturates, and takes NaN to 0.
+    value.max(0.0) as u64
+}
+
+// ─── Samples ─────────────────────────────────────────────────────────
+
+struct Sample {
+    name: String,
+    labels: Vec<(String, String)>,
+    value: f64,
+    /// `value` as written.
+    text: String,
+    /// Nanoseconds since the epoch.
+    time: Option<u64>,
+    exemplar: Option<Value>,
+}
+
+impl Sample {
+    /// `name{labels} value [timestamp] [# {labels} value [timestamp]]`.
+    fn parse(line: &str, open_metrics: bool) -> Result<Sample, String> {
+        let mut rest = line;
+        let name = take_name(&mut rest);
+        if name.is_empty() {
+            return Err(format!("expected a metric name in {line:?}"));
+        }
+        let labels = take_labels(&mut rest)?;
+        let (sample, exemplar) = match rest.split_once(" # ") {
+            Some((sample, exemplar)) => (sample, Some(exemplar)),
+            None => (rest, None),
+        };
+        let mut fields = sample.split_whitespace();
+        let text = fields.next().ok_or("missing sample value")?;
+        let value = number(text).ok_or_else(|| format!("invalid sample value {text:?}"))?;
+        let time = fields
+            .next()
+            .map(|text| {
+                let nanos = if open_metrics {
+                    scaled(text, 9)
+                } else {
+                    scaled(text, 6)
+                };
+                nanos.ok_or_else(|| format!("invalid timestamp {text:?}"))
+            })
+            .transpose()?;
+        if let Some(extra) = fields.next() {
+            return Err(format!("unexpected {extra:?} after the timestamp"));
+        }
+        Ok(Sample {
+            name: name.to_string(),
+            labels,
+            value,
+            text: text.to_string(),
+            time,
+            exemplar: exemplar.map(parse_exemplar).transpose()?,
+        })
+    }
+}
+
+/// An OpenMetrics exemplar, `{labels} value [timestamp]`, as an OTLP one.
+fn parse_exemplar(text: &str) -> Result<Value, String> {
+    let mut rest = text.trim_start();
+    let labels = take_labels(&mut rest)?;
+    let mut fields = rest.split_whitespace();
+    let text = fields.next().ok_or("missing exemplar value")?;
+    let value = number(text).ok_or_else(|| format!("invalid exemplar value {text:?}"))?;
+    let mut exemplar = serde_json::Map::new();
+    if let Some(text) = fields.next() {
+        let nanos = scaled(text, 9).ok_or_else(|| format!("invalid timestamp {text:?}"))?;
+        exemplar.insert("timeUnixNano".to_string(), nanos.into());
+    }
+    exemplar.insert("asDouble".to_string(), json!(Double::from(value)));
+    let (ids, filtered): (Vec<_>, Vec<_>) = labels
+        .into_iter()
+        .partition(|(name, _)| name == "trace_id" || name == "span_id");
+    for (name, id) in ids {
+        let key = if name == "trace_id" {
+            "traceId"
+        } else {
+            "spanId"
+        };
+        exemplar.insert(key.to_string(), id.into());
+    }
+    if !filtered.is_empty() {
+        exemplar.insert(
+            "filteredAttributes".to_string(),
+            json!(attributes(&filtered)),
+        );
+    }
+    Ok(exemplar.into())
+}
+
+/// The metric or label name at the start of `rest`, taken off it.
+fn take_name<'a>(rest: &mut &'a str) -> &'a str {
+    let end = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == ':'))
+        .unwrap_or(rest.len());
+    let (name, after) = rest.split_at(end);
+    *rest = after;
+    name
+}
+
+/// The `{name="value",...}` label set at the start of `rest`, if any, taken
+/// off it.
+fn take_labels(rest: &mut &str) -> Result<Vec<(String, String)>, String> {
+    let mut labels = Vec::new();
+    let Some(mut after) = rest.strip_prefix('{') else {
+        return Ok(labels);
+    };
+    loop {
+        after = after.trim_start();
+        if let Some(end) = after.strip_prefix('}') {
+            *rest = end;
+            return Ok(labels);
+        }
+        let name = take_name(&mut after);
+        if name.is_empty() {
+            return Err(format!("expected a label name at {after:?}"));
+        }
+        after = after
+            .trim_start()
+            .strip_prefix('=')
+            .ok_or_else(|| format!("expected = after label {name}"))?
+            .trim_start();
+        let value = take_quoted(&mut after)
+            .ok_or_else(|| format!("expected a quoted value for label {name}"))?;
+        labels.push((name.to_string(), value));
+        after = after.trim_start();
+        after = after.strip_prefix(',').unwrap_or(after);
+    }
+}
+
+/// The escaped, double-quoted string at the start of `rest`, taken off it.
+fn take_quoted(rest: &mut &str) -> Option<String> {
+    let body = rest.strip_prefix('"')?;
+    let mut escaped = false;
+    let end = body.char_indices().find_map(|(i, c)| {
+        let closes = c == '"' && !escaped;
+        escaped = c == '\\' && !escaped;
+        closes.then_some(i)
+    })?;
+    *rest = &body[end + 1..];
+    Some(unescape(&body[..end], true))
+}
+
+/// `text` with `\\` and `\n` escapes resolved, and `\"` too in label values.
+fn unescape(text: &str, quotes: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('\\') => out.push('\\'),
+            Some('"') if quotes => out.push('"'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// A sample value: a float, `+Inf`, `-Inf` or `NaN`.
+fn number(text: &str) -> Option<f64> {
+    text.parse().ok()
+}
+
+/// A non-negative decimal `text` times 10^`digits`, exactly, so that
+/// millisecond and second timestamps keep every digit as nanoseconds.
+fn scaled(text: &str, digits: usize) -> Option<u64> {
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) {
+        return None;
+    }
+    let fraction = &fraction[..fraction.len().min(digits)];
+    let padded = format!("{whole}{fraction:0<digits$}");
+    padded.parse().ok()
+}
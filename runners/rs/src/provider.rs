@@ -0,0 +1,848 @@
+//! Policy providers layered on top of the ones `policy_rs` ships.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use base64::Engine as _;
+use flate2::read::GzDecoder;
+use policy_rs::{
+    FileProvider, Policy, PolicyCallback, PolicyError, PolicyProvider, StatsCollector,
+};
+
+use crate::object_url;
+
+/// The provider `--policies` uses for `path`: [`EnvProvider`] for `env:` or
+/// `env:<VAR>`, [`ObjectProvider`] for an `s3://` or `gs://` URL,
+/// [`DirProvider`] for a directory, `FileProvider` otherwise.
+pub fn for_path(path: &Path) -> Box<dyn PolicyProvider> {
+    if let Some(var) = path.to_str().and_then(|p| p.strip_prefix("env:")) {
+        let var = if var.is_empty() { DEFAULT_ENV_VAR } else { var };
+        Box::new(EnvProvider::new(var))
+    } else if object_url::is_url(path) {
+        Box::new(ObjectProvider::new(path.to_string_lossy()))
+    } else if path.is_dir() {
+        Box::new(DirProvider::new(path))
+    } else {
+        Box::new(FileProvider::new(path))
+    }
+}
+
+// ─── Directory ───────────────────────────────────────────────────────
+
+/// Loads every `*.json` policy file under a directory tree as one policy set.
+///
+/// Files are read in lexicographic path order, so policies keep a stable
+/// order across runs. Each file has the same shape `FileProvider` reads. A
+/// policy id defined in two files is an error naming both paths, and so is a
+/// directory with no policy files at all.
+pub struct DirProvider {
+    dir: PathBuf,
+}
+
+impl DirProvider {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        DirProvider {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn load(&self) -> Result<Vec<Policy>, PolicyError> {
+        let mut files = Vec::new();
+        collect_json_files(&self.dir, &mut files)?;
+        if files.is_empty() {
+            return Err(PolicyError::ParseError {
+                path: self.dir.clone(),
+                message: "no *.json policy files in directory".to_string(),
+            });
+        }
+        files.sort();
+
+        let mut defined_in: HashMap<String, PathBuf> = HashMap::new();
+        let mut policies = Vec::new();
+        for path in files {
+            for policy in FileProvider::new(&path).load()? {
+                if let Some(first) = defined_in.get(policy.id()) {
+                    return Err(PolicyError::InvalidPolicy {
+                        policy_id: policy.id().to_string(),
+                        reason: format!(
+                            "defined in both {} and {}",
+                            first.display(),
+                            path.display()
+                        ),
+                    });
+                }
+                defined_in.insert(policy.id().to_string(), path.clone());
+                policies.push(policy);
+            }
+        }
+        Ok(policies)
+    }
+}
+
+fn collect_json_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), PolicyError> {
+    let entries = fs::read_dir(dir).map_err(|e| PolicyError::FileRead {
+        path: dir.to_path_buf(),
+        source: e,
+    })?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| PolicyError::FileRead {
+                path: dir.to_path_buf(),
+                source: e,
+            })?
+            .path();
+        if path.is_dir() {
+            collect_json_files(&path, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+impl PolicyProvider for DirProvider {
+    fn subscribe(&self, callback: PolicyCallback) -> Result<(), PolicyError> {
+        callback(self.load()?);
+        Ok(())
+    }
+}
+
+// ─── Environment ─────────────────────────────────────────────────────
+
+pub const DEFAULT_ENV_VAR: &str = "POLICY_RUNNER_POLICIES";
+
+/// Largest policy document [`EnvProvider`] accepts once decoded.
+const MAX_ENV_POLICY_BYTES: u64 = 4 << 20;
+
+/// Loads policies from an environment variable holding either the policy JSON
+/// itself or a base64-encoded gzip of it; a leading `{` picks raw JSON.
+pub struct EnvProvider {
+    var: String,
+}
+
+impl EnvProvider {
+    pub fn new(var: impl Into<String>) -> Self {
+        EnvProvider { var: var.into() }
+    }
+
+    pub fn load(&self) -> Result<Vec<Policy>, PolicyError> {
+        let path = PathBuf::from(format!("${}", self.var));
+        let parse_error = |message: String| PolicyError::ParseError {
+            path: path.clone(),
+            message,
+        };
+        let raw = std::env::var(&self.var).map_err(|e| parse_error(e.to_string()))?;
+        let document = decode_env_policies(raw.trim()).map_err(parse_error)?;
+        load_document(&document)
+    }
+}
+
+/// The policies in `document`, a policy file's contents.
+fn load_document(document: &[u8]) -> Result<Vec<Policy>, PolicyError> {
+    // Policy documents are only parsed from files, so stage the document in
+    // one for FileProvider.
+    let mut staged = tempfile::NamedTempFile::new().map_err(|e| PolicyError::FileRead {
+        path: std::env::temp_dir(),
+        source: e,
+    })?;
+    staged
+        .write_all(document)
+        .map_err(|e| PolicyError::FileRead {
+            path: staged.path().to_path_buf(),
+            source: e,
+        })?;
+    FileProvider::new(staged.path()).load()
+}
+
+fn decode_env_policies(value: &str) -> Result<Vec<u8>, String> {
+    let too_large = |size: u64| {
+        format!("decoded policy document is {size} bytes; the limit is {MAX_ENV_POLICY_BYTES}")
+    };
+    if value.starts_with('{') {
+        let size = value.len() as u64;
+        if size > MAX_ENV_POLICY_BYTES {
+            return Err(too_large(size));
+        }
+        return Ok(value.as_bytes().to_vec());
+    }
+    let gzipped = base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .map_err(|e| format!("neither policy JSON nor base64: {e}"))?;
+    let mut document = Vec::new();
+    GzDecoder::new(gzipped.as_slice())
+        .take(MAX_ENV_POLICY_BYTES + 1)
+        .read_to_end(&mut document)
+        .map_err(|e| format!("base64 value is not gzip: {e}"))?;
+    if document.len() as u64 > MAX_ENV_POLICY_BYTES {
+        // The gzip trailer records the uncompressed size (mod 2^32), which
+        // names the real size without inflating the rest of it.
+        let size = gzipped
+            .last_chunk::<4>()
+            .map_or(document.len() as u64, |trailer| {
+                u64::from(u32::from_le_bytes(*trailer))
+            });
+        return Err(too_large(size));
+    }
+    Ok(document)
+}
+
+impl PolicyProvider for EnvProvider {
+    fn subscribe(&self, callback: PolicyCallback) -> Result<(), PolicyError> {
+        callback(self.load()?);
+        Ok(())
+    }
+}
+
+// ─── Object storage ──────────────────────────────────────────────────
+
+/// Loads a policy file from an `s3://` or `gs://` URL; see [`object_url`].
+pub struct ObjectProvider {
+    url: String,
+}
+
+impl ObjectProvider {
+    pub fn new(url: impl Into<String>) -> Self {
+        ObjectProvider { url: url.into() }
+    }
+
+    pub fn load(&self) -> Result<Vec<Policy>, PolicyError> {
+        let document = object_url::read(&self.url, None).map_err(|e| PolicyError::FileRead {
+            path: PathBuf::from(&self.url),
+            source: std::io::Error::other(e),
+        })?;
+        load_document(&document)
+    }
+}
+
+impl PolicyProvider for ObjectProvider {
+    fn subscribe(&self, callback: PolicyCallback) -> Result<(), PolicyError> {
+        callback(self.load()?);
+        Ok(())
+    }
+}
+
+// ─── Chaining ────────────────────────────────────────────────────────
+
+/// What [`ChainProvider`] does when two sources define the same policy id.
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MergeStrategy {
+    /// Fail naming both sources.
+    #[default]
+    Strict,
+    /// The later source's policy replaces the earlier one in place.
+    Override,
+}
+
+/// Several `--policies` sources merged into one policy set, in the order they
+/// were given on the command line; later sources take precedence under
+/// [`MergeStrategy::Override`].
+///
+/// Nothing is published until every source has reported once. A later update
+/// from any source re-merges against the others' latest sets; one that
+/// introduces a conflict is reported and dropped, keeping the previous set.
+pub struct ChainProvider {
+    sources: Vec<(String, Box<dyn PolicyProvider>)>,
+    strategy: MergeStrategy,
+    state: Arc<Mutex<ChainState>>,
+}
+
+#[derive(Default)]
+struct ChainState {
+    latest: Vec<Option<Vec<Policy>>>,
+    /// Policy id to the index of the source its effective definition came from.
+    origins: HashMap<String, usize>,
+    subscribed: bool,
+    conflict: Option<PolicyError>,
+}
+
+impl ChainProvider {
+    pub fn new(paths: &[PathBuf], strategy: MergeStrategy) -> Self {
+        let sources = paths
+            .iter()
+            .map(|p| (p.display().to_string(), for_path(p)))
+            .collect();
+        Self::with_sources(sources, strategy)
+    }
+
+    /// A chain over already-built providers, each named for error messages
+    /// and [`source_of`](Self::source_of).
+    fn with_sources(
+        sources: Vec<(String, Box<dyn PolicyProvider>)>,
+        strategy: MergeStrategy,
+    ) -> Self {
+        ChainProvider {
+            sources,
+            strategy,
+            state: Arc::default(),
+        }
+    }
+
+    /// The source (as given on the command line) the effective definition of
+    /// policy `id` came from.
+    pub fn source_of(&self, id: &str) -> Option<&str> {
+        let state = self.state.lock().unwrap();
+        state.origins.get(id).map(|&i| self.sources[i].0.as_str())
+    }
+}
+
+impl PolicyProvider for ChainProvider {
+    fn subscribe(&self, callback: PolicyCallback) -> Result<(), PolicyError> {
+        let names: Arc<Vec<String>> =
+            Arc::new(self.sources.iter().map(|(name, _)| name.clone()).collect());
+        self.state.lock().unwrap().latest = vec![None; self.sources.len()];
+        for (i, (_, source)) in self.sources.iter().enumerate() {
+            let state = Arc::clone(&self.state);
+            let names = Arc::clone(&names);
+            let callback = Arc::clone(&callback);
+            let strategy = self.strategy;
+            source.subscribe(Arc::new(move |policies: Vec<Policy>| {
+                let mut state = state.lock().unwrap();
+                state.latest[i] = Some(policies);
+                if state.latest.iter().any(Option::is_none) {
+                    return;
+                }
+                match merge(&state.latest, &names, strategy) {
+                    Ok((merged, origins)) => {
+                        state.origins = origins;
+                        drop(state);
+                        callback(merged);
+                    }
+                    Err(e) if state.subscribed => {
+                        tracing::warn!("ignoring policy update from {}: {e}", names[i]);
+                    }
+                    Err(e) => state.conflict = Some(e),
+                }
+            }))?;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.subscribed = true;
+        state.conflict.take().map_or(Ok(()), Err)
+    }
+
+    fn set_stats_collector(&self, collector: StatsCollector) {
+        for (_, source) in &self.sources {
+            source.set_stats_collector(collector.clone());
+        }
+    }
+}
+
+type Merged = (Vec<Policy>, HashMap<String, usize>);
+
+fn merge(
+    sets: &[Option<Vec<Policy>>],
+    names: &[String],
+    strategy: MergeStrategy,
+) -> Result<Merged, PolicyError> {
+    let mut merged: Vec<Policy> = Vec::new();
+    let mut origins: HashMap<String, usize> = HashMap::new();
+    let mut position: HashMap<String, usize> = HashMap::new();
+    for (source, set) in sets.iter().enumerate() {
+        for policy in set.iter().flatten() {
+            let id = policy.id();
+            match position.get(id) {
+                Some(_) if strategy == MergeStrategy::Strict => {
+                    return Err(PolicyError::InvalidPolicy {
+                        policy_id: id.to_string(),
+                        reason: format!(
+                            "defined by both {} and {}",
+                            names[origins[id]], names[source]
+                        ),
+                    });
+                }
+                Some(&at) => merged[at] = policy.clone(),
+                None => {
+                    position.insert(id.to_string(), merged.len());
+                    merged.push(policy.clone());
+                }
+            }
+            origins.insert(id.to_string(), source);
+        }
+    }
+    Ok((merged, origins))
+}
+
+// ─── Filtering ───────────────────────────────────────────────────────
+
+/// Passes through only the policies whose ids match `only` (when non-empty)
+/// and none of `exclude`, so excluded policies never reach the snapshot.
+/// Patterns are globs where `*` matches any run of characters and `?` any
+/// single one. Order among the surviving policies is preserved.
+pub struct FilteredProvider<'a> {
+    inner: &'a dyn PolicyProvider,
+    only: Arc<Vec<String>>,
+    exclude: Arc<Vec<String>>,
+}
+
+impl<'a> FilteredProvider<'a> {
+    pub fn new(inner: &'a dyn PolicyProvider, only: Vec<String>, exclude: Vec<String>) -> Self {
+        FilteredProvider {
+            inner,
+            only: Arc::new(only),
+            exclude: Arc::new(exclude),
+        }
+    }
+}
+
+impl PolicyProvider for FilteredProvider<'_> {
+    fn subscribe(&self, callback: PolicyCallback) -> Result<(), PolicyError> {
+        let only = Arc::clone(&self.only);
+        let exclude = Arc::clone(&self.exclude);
+        self.inner.subscribe(Arc::new(move |policies: Vec<Policy>| {
+            callback(
+                policies
+                    .into_iter()
+                    .filter(|p| {
+                        let id = p.id();
+                        (only.is_empty() || only.iter().any(|g| glob_match(g, id)))
+                            && !exclude.iter().any(|g| glob_match(g, id))
+                    })
+                    .collect(),
+            )
+        }))
+    }
+
+    fn set_stats_collector(&self, collector: StatsCollector) {
+        self.inner.set_stats_collector(collector);
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` stands for any run of
+/// characters and `?` for any one.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let (p, t): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    // Greedy `*` with a single backtrack point, enough for plain globs.
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn policy(id: &str) -> Value {
+        json!({
+            "id": id,
+            "name": id,
+            "log": { "match": [{ "log_field": "body", "exact": id }], "keep": "none" },
+        })
+    }
+
+    fn write_policies(path: &Path, ids: &[&str]) {
+        let policies: Vec<Value> = ids.iter().map(|id| policy(id)).collect();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, json!({ "policies": policies }).to_string()).unwrap();
+    }
+
+    fn ids(policies: &[Policy]) -> Vec<&str> {
+        policies.iter().map(Policy::id).collect()
+    }
+
+    #[test]
+    fn dir_loads_nested_files_in_path_order() {
+        let dir = tempfile::tempdir().unwrap();
+        write_policies(&dir.path().join("b.json"), &["b1", "b2"]);
+        write_policies(&dir.path().join("a/z.json"), &["a1"]);
+        fs::write(dir.path().join("notes.txt"), "not a policy").unwrap();
+        let policies = DirProvider::new(dir.path()).load().unwrap();
+        assert_eq!(ids(&policies), ["a1", "b1", "b2"]);
+    }
+
+    #[test]
+    fn dir_id_collision_names_both_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("a.json");
+        let second = dir.path().join("nested/b.json");
+        write_policies(&first, &["shared", "only-a"]);
+        write_policies(&second, &["shared"]);
+        let e = DirProvider::new(dir.path()).load().unwrap_err();
+        let PolicyError::InvalidPolicy { policy_id, reason } = e else {
+            panic!("unexpected error: {e}");
+        };
+        assert_eq!(policy_id, "shared");
+        assert_eq!(
+            reason,
+            format!(
+                "defined in both {} and {}",
+                first.display(),
+                second.display()
+            )
+        );
+    }
+
+    #[test]
+    fn dir_without_policy_files_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("empty")).unwrap();
+        fs::write(dir.path().join("README.md"), "").unwrap();
+        let e = DirProvider::new(dir.path()).load().unwrap_err();
+        assert!(
+            matches!(&e, PolicyError::ParseError { path, message }
+                if path == dir.path() && message == "no *.json policy files in directory"),
+            "unexpected error: {e}"
+        );
+    }
+
+    #[test]
+    fn glob_matches_literals_stars_and_question_marks() {
+        let cases = [
+            ("drop-debug", "drop-debug", true),
+            ("drop-debug", "drop-debugs", false),
+            ("*", "anything", true),
+            ("*", "", true),
+            ("drop-*", "drop-debug", true),
+            ("drop-*", "drop-", true),
+            ("drop-*", "keep-debug", false),
+            ("*-debug", "drop-debug", true),
+            ("d?op", "drop", true),
+            ("d?op", "dop", false),
+            ("?", "", false),
+            ("", "", true),
+            ("", "drop", false),
+            // The first `-` tried for `*-` is the wrong one.
+            ("*-debug", "drop-all-debug", true),
+            ("a*b*c", "aXbYbZc", true),
+            ("a*b*c", "aXbYbZ", false),
+            ("**", "drop", true),
+            ("*?", "", false),
+            ("dröp-*", "dröp-ü", true),
+        ];
+        for (pattern, text, matches) in cases {
+            assert_eq!(
+                glob_match(pattern, text),
+                matches,
+                "{pattern:?} vs {text:?}"
+            );
+        }
+    }
+
+    fn filtered(file: &Path, only: &[&str], exclude: &[&str]) -> policy_rs::PolicySnapshot {
+        let inner = FileProvider::new(file);
+        let to_vec = |globs: &[&str]| globs.iter().map(|g| g.to_string()).collect();
+        let registry = policy_rs::PolicyRegistry::new();
+        registry
+            .subscribe(&FilteredProvider::new(
+                &inner,
+                to_vec(only),
+                to_vec(exclude),
+            ))
+            .unwrap();
+        registry.snapshot()
+    }
+
+    #[test]
+    fn filter_keeps_matching_ids_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("policies.json");
+        write_policies(&file, &["drop-a", "keep-a", "drop-b", "keep-b"]);
+        let snapshot = filtered(&file, &["drop-*", "keep-a"], &["*-b"]);
+        let ids: Vec<&str> = snapshot.iter().map(|e| e.policy.id()).collect();
+        assert_eq!(ids, ["drop-a", "keep-a"]);
+    }
+
+    #[test]
+    fn excluding_the_winning_policy_changes_the_decision() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("policies.json");
+        let keep = json!({
+            "id": "keep-health",
+            "name": "keep-health",
+            "log": { "match": [{ "log_field": "body", "exact": "health" }], "keep": "all" },
+        });
+        let drop = policy("health");
+        fs::write(&file, json!({ "policies": [keep, drop] }).to_string()).unwrap();
+
+        let record = json!({ "body": { "stringValue": "health" } });
+        let decide = |exclude: &[&str]| {
+            let snapshot = filtered(&file, &[], exclude);
+            let evaluated = runner_rs::evaluate_value(
+                &policy_rs::PolicyEngine::new(),
+                &snapshot,
+                runner_rs::Signal::Log,
+                &record,
+                &runner_rs::EvalOptions::default(),
+            )
+            .unwrap();
+            (
+                runner_rs::decision_name(&evaluated.result.result),
+                runner_rs::decision_policy(&evaluated.result.result).map(str::to_string),
+            )
+        };
+        assert_eq!(decide(&[]), ("drop", Some("health".to_string())));
+        assert_eq!(
+            decide(&["heal*"]),
+            ("keep", Some("keep-health".to_string()))
+        );
+        assert_eq!(decide(&["*health"]), ("no_match", None));
+    }
+
+    fn gzip_base64(document: &[u8]) -> String {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(document).unwrap();
+        base64::engine::general_purpose::STANDARD.encode(encoder.finish().unwrap())
+    }
+
+    /// Load policies from `var` set to `value`. Each test uses its own
+    /// variable, since tests run in parallel in one process.
+    fn load_env(var: &str, value: &str) -> Result<Vec<Policy>, PolicyError> {
+        // SAFETY: no other test reads or writes `var`.
+        unsafe { std::env::set_var(var, value) };
+        let loaded = EnvProvider::new(var).load();
+        unsafe { std::env::remove_var(var) };
+        loaded
+    }
+
+    fn env_error(var: &str, value: &str) -> String {
+        match load_env(var, value) {
+            Err(PolicyError::ParseError { path, message }) => {
+                assert_eq!(path, PathBuf::from(format!("${var}")));
+                message
+            }
+            other => panic!("unexpected result: {:?}", other.map(|p| ids(&p).join(","))),
+        }
+    }
+
+    #[test]
+    fn env_loads_raw_json() {
+        let document = json!({ "policies": [policy("a"), policy("b")] }).to_string();
+        let policies = load_env("RUNNER_RS_TEST_ENV_RAW", &format!("  {document}\n")).unwrap();
+        assert_eq!(ids(&policies), ["a", "b"]);
+    }
+
+    #[test]
+    fn env_loads_base64_gzip() {
+        let document = json!({ "policies": [policy("gz")] }).to_string();
+        let value = gzip_base64(document.as_bytes());
+        let policies = load_env("RUNNER_RS_TEST_ENV_GZIP", &value).unwrap();
+        assert_eq!(ids(&policies), ["gz"]);
+    }
+
+    #[test]
+    fn env_rejects_invalid_base64() {
+        let message = env_error("RUNNER_RS_TEST_ENV_BAD_BASE64", "not base64!");
+        assert!(
+            message.starts_with("neither policy JSON nor base64: "),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn env_rejects_base64_that_is_not_gzip() {
+        let value = base64::engine::general_purpose::STANDARD.encode("plain text");
+        let message = env_error("RUNNER_RS_TEST_ENV_NOT_GZIP", &value);
+        assert!(
+            message.starts_with("base64 value is not gzip: "),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn env_oversized_documents_name_their_decoded_size() {
+        let size = MAX_ENV_POLICY_BYTES as usize + 1000;
+        // Padded inside the object, since the value is trimmed.
+        let mut document = br#"{"policies":[]"#.to_vec();
+        document.resize(size - 1, b' ');
+        document.push(b'}');
+        let expected =
+            format!("decoded policy document is {size} bytes; the limit is {MAX_ENV_POLICY_BYTES}");
+
+        let raw = String::from_utf8(document.clone()).unwrap();
+        assert_eq!(env_error("RUNNER_RS_TEST_ENV_BIG_RAW", &raw), expected);
+        let gzipped = gzip_base64(&document);
+        assert_eq!(env_error("RUNNER_RS_TEST_ENV_BIG_GZIP", &gzipped), expected);
+    }
+
+    fn named(id: &str, name: &str) -> Policy {
+        Policy::new(policy_rs::proto::tero::policy::v1::Policy {
+            id: id.to_string(),
+            name: name.to_string(),
+            ..Default::default()
+        })
+    }
+
+    fn names(sets: usize) -> Vec<String> {
+        (0..sets).map(|i| format!("source-{i}")).collect()
+    }
+
+    #[test]
+    fn strict_merge_conflict_names_both_sources() {
+        let sets = [
+            Some(vec![named("a", "first"), named("shared", "first")]),
+            Some(vec![named("b", "second")]),
+            Some(vec![named("shared", "third")]),
+        ];
+        let Err(PolicyError::InvalidPolicy { policy_id, reason }) =
+            merge(&sets, &names(3), MergeStrategy::Strict)
+        else {
+            panic!("a duplicate id should conflict");
+        };
+        assert_eq!(policy_id, "shared");
+        assert_eq!(reason, "defined by both source-0 and source-2");
+    }
+
+    #[test]
+    fn strict_merge_keeps_source_order() {
+        let sets = [
+            Some(vec![named("b", "first"), named("a", "first")]),
+            Some(vec![named("c", "second")]),
+        ];
+        let (merged, origins) = merge(&sets, &names(2), MergeStrategy::Strict).unwrap();
+        assert_eq!(ids(&merged), ["b", "a", "c"]);
+        assert_eq!(
+            origins,
+            HashMap::from([("a".into(), 0), ("b".into(), 0), ("c".into(), 1)])
+        );
+    }
+
+    #[test]
+    fn override_merge_replaces_in_place_and_records_the_winner() {
+        let sets = [
+            Some(vec![
+                named("a", "first"),
+                named("shared", "first"),
+                named("b", "first"),
+            ]),
+            Some(vec![named("shared", "second"), named("c", "second")]),
+            Some(vec![named("shared", "third")]),
+        ];
+        let (merged, origins) = merge(&sets, &names(3), MergeStrategy::Override).unwrap();
+        let got: Vec<(&str, &str)> = merged.iter().map(|p| (p.id(), p.name())).collect();
+        assert_eq!(
+            got,
+            [
+                ("a", "first"),
+                ("shared", "third"),
+                ("b", "first"),
+                ("c", "second")
+            ]
+        );
+        assert_eq!(origins["shared"], 2);
+        assert_eq!(origins["a"], 0);
+        assert_eq!(origins["c"], 1);
+    }
+
+    /// A provider whose policies the test pushes, like a watched file.
+    #[derive(Clone, Default)]
+    struct Pushed(Arc<Mutex<(Vec<Policy>, Option<PolicyCallback>)>>);
+
+    impl Pushed {
+        fn push(&self, policies: Vec<Policy>) {
+            let callback = {
+                let mut state = self.0.lock().unwrap();
+                state.0 = policies.clone();
+                state.1.clone()
+            };
+            if let Some(callback) = callback {
+                callback(policies);
+            }
+        }
+    }
+
+    impl PolicyProvider for Pushed {
+        fn subscribe(&self, callback: PolicyCallback) -> Result<(), PolicyError> {
+            let policies = {
+                let mut state = self.0.lock().unwrap();
+                state.1 = Some(Arc::clone(&callback));
+                state.0.clone()
+            };
+            callback(policies);
+            Ok(())
+        }
+    }
+
+    fn chain(strategy: MergeStrategy, sources: &[&Pushed]) -> ChainProvider {
+        let sources = sources
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                (
+                    format!("source-{i}"),
+                    Box::new((*s).clone()) as Box<dyn PolicyProvider>,
+                )
+            })
+            .collect();
+        ChainProvider::with_sources(sources, strategy)
+    }
+
+    #[test]
+    fn chain_fails_to_subscribe_on_an_initial_conflict() {
+        let (first, second) = (Pushed::default(), Pushed::default());
+        first.push(vec![named("shared", "first")]);
+        second.push(vec![named("shared", "second")]);
+        let registry = policy_rs::PolicyRegistry::new();
+        let e = registry
+            .subscribe(&chain(MergeStrategy::Strict, &[&first, &second]))
+            .unwrap_err();
+        assert!(
+            e.to_string()
+                .contains("defined by both source-0 and source-1"),
+            "{e}"
+        );
+    }
+
+    #[test]
+    fn conflicting_update_after_subscribing_keeps_the_previous_set() {
+        let (first, second) = (Pushed::default(), Pushed::default());
+        first.push(vec![named("a", "first")]);
+        second.push(vec![named("b", "second")]);
+        let chain = chain(MergeStrategy::Strict, &[&first, &second]);
+        let published = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&published);
+        chain
+            .subscribe(Arc::new(move |policies: Vec<Policy>| {
+                sink.lock().unwrap().push(ids(&policies).join(","));
+            }))
+            .unwrap();
+        assert_eq!(*published.lock().unwrap(), ["a,b"]);
+
+        second.push(vec![named("a", "second")]);
+        assert_eq!(*published.lock().unwrap(), ["a,b"]);
+        assert_eq!(chain.source_of("a"), Some("source-0"));
+        assert_eq!(chain.source_of("b"), Some("source-1"));
+
+        second.push(vec![named("c", "second")]);
+        assert_eq!(*published.lock().unwrap(), ["a,b", "a,c"]);
+        assert_eq!(chain.source_of("c"), Some("source-1"));
+    }
+
+    #[test]
+    fn override_update_after_subscribing_moves_the_origin() {
+        let (first, second) = (Pushed::default(), Pushed::default());
+        first.push(vec![named("a", "first"), named("b", "first")]);
+        second.push(Vec::new());
+        let chain = chain(MergeStrategy::Override, &[&first, &second]);
+        let published = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&published);
+        chain
+            .subscribe(Arc::new(move |policies: Vec<Policy>| {
+                let names: Vec<&str> = policies.iter().map(Policy::name).collect();
+                sink.lock().unwrap().push(names.join(","));
+            }))
+            .unwrap();
+        assert_eq!(chain.source_of("a"), Some("source-0"));
+
+        second.push(vec![named("a", "second")]);
+        assert_eq!(*published.lock().unwrap(), ["first,first", "second,first"]);
+        assert_eq!(chain.source_of("a"), Some("source-1"));
+    }
+}
@@ -0,0 +1,348 @@
+//! Field selectors as text, in the vocabulary of policy matchers, for
+//! messages and debugging flags that need to name a field.
+//!
+//! A selector renders as the matcher key that selects it, then what the key
+//! names:
+//! - fixed fields as `key:field`, e.g. `log_field:severity_text`,
+//!   `trace_field:parent_span_id`
+//! - attributes as `key` followed by each path segment as a bracketed JSON
+//!   string, e.g. `span_attribute["http.route"]`,
+//!   `resource_attribute["k8s"]["pod"]`
+//! - selectors with nothing to name as the bare key, e.g. `span_kind`,
+//!   `metric_type`
+//!
+//! [`parse`] reads back exactly what [`render`] writes.
+
+use policy_rs::proto::tero::policy::v1::{LogField, MetricField, TraceField};
+use policy_rs::{LogFieldSelector, MetricFieldSelector, TraceFieldSelector};
+
+/// A selector type with a text form.
+pub trait Selector: Sized {
+    fn render(&self) -> String;
+    fn parse(text: &str) -> Result<Self, String>;
+}
+
+pub fn render<S: Selector>(selector: &S) -> String {
+    selector.render()
+}
+
+pub fn parse<S: Selector>(text: &str) -> Result<S, String> {
+    S::parse(text)
+}
+
+/// `key["a"]["b"]` for the attribute path `[a, b]`.
+fn attribute(key: &str, path: &[String]) -> String {
+    let mut out = key.to_string();
+    for segment in path {
+        out.push('[');
+        out.push_str(&serde_json::Value::from(segment.as_str()).to_string());
+        out.push(']');
+    }
+    out
+}
+
+/// `key:field`, from a proto field name such as `LOG_FIELD_BODY`.
+fn field(key: &str, name: &str, prefix: &str) -> String {
+    let field = name.strip_prefix(prefix).unwrap_or(name);
+    format!("{key}:{}", field.to_ascii_lowercase())
+}
+
+/// `text` split at its key: into the key and the rest, which is empty, the
+/// `:field` suffix or the bracketed attribute path.
+fn split_key(text: &str) -> (&str, &str) {
+    let end = text.find([':', '[']).unwrap_or(text.len());
+    text.split_at(end)
+}
+
+/// The attribute path in `rest`, a run of bracketed JSON strings.
+fn parse_path(mut rest: &str) -> Result<Vec<String>, String> {
+    let mut path = Vec::new();
+    while let Some(inner) = rest.strip_prefix('[') {
+        let mut segments = serde_json::Deserializer::from_str(inner).into_iter::<String>();
+        let segment = match segments.next() {
+            Some(Ok(segment)) => segment,
+            _ => return Err(format!("expected a quoted attribute key at {rest:?}")),
+        };
+        rest = inner[segments.byte_offset()..]
+            .strip_prefix(']')
+            .ok_or_else(|| format!("unclosed attribute key at {rest:?}"))?;
+        path.push(segment);
+    }
+    if !rest.is_empty() {
+        return Err(format!("unexpected {rest:?} after the attribute path"));
+    }
+    if path.is_empty() {
+        return Err("an attribute selector needs at least one [\"key\"]".to_string());
+    }
+    Ok(path)
+}
+
+/// The proto field named by the `:field` suffix in `rest`.
+fn parse_field<F>(
+    rest: &str,
+    prefix: &str,
+    from_str_name: fn(&str) -> Option<F>,
+) -> Result<F, String> {
+    let name = rest
+        .strip_prefix(':')
+        .ok_or_else(|| "expected :<field>".to_string())?;
+    from_str_name(&format!("{prefix}{}", name.to_ascii_uppercase()))
+        .ok_or_else(|| format!("unknown field {name:?}"))
+}
+
+/// Fails unless a key that names nothing has nothing after it.
+fn bare(key: &str, rest: &str) -> Result<(), String> {
+    if rest.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{key} takes no field or attribute, got {rest:?}"))
+    }
+}
+
+impl Selector for LogFieldSelector {
+    fn render(&self) -> String {
+        match self {
+            LogFieldSelector::Simple(f) => field("log_field", f.as_str_name(), "LOG_FIELD_"),
+            LogFieldSelector::LogAttribute(p) => attribute("log_attribute", p),
+            LogFieldSelector::ResourceAttribute(p) => attribute("resource_attribute", p),
+            LogFieldSelector::ScopeAttribute(p) => attribute("scope_attribute", p),
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let (key, rest) = split_key(text);
+        match key {
+            "log_field" => parse_field(rest, "LOG_FIELD_", LogField::from_str_name)
+                .map(LogFieldSelector::Simple),
+            "log_attribute" => parse_path(rest).map(LogFieldSelector::LogAttribute),
+            "resource_attribute" => parse_path(rest).map(LogFieldSelector::ResourceAttribute),
+            "scope_attribute" => parse_path(rest).map(LogFieldSelector::ScopeAttribute),
+            other => Err(format!("unknown log selector {other:?}")),
+        }
+    }
+}
+
+impl Selector for MetricFieldSelector {
+    fn render(&self) -> String {
+        match self {
+            MetricFieldSelector::Simple(f) => {
+                field("metric_field", f.as_str_name(), "METRIC_FIELD_")
+            }
+            MetricFieldSelector::DatapointAttribute(p) => attribute("datapoint_attribute", p),
+            MetricFieldSelector::ResourceAttribute(p) => attribute("resource_attribute", p),
+            MetricFieldSelector::ScopeAttribute(p) => attribute("scope_attribute", p),
+            MetricFieldSelector::Type => "metric_type".to_string(),
+            MetricFieldSelector::Temporality => "aggregation_temporality".to_string(),
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let (key, rest) = split_key(text);
+        match key {
+            "metric_field" => parse_field(rest, "METRIC_FIELD_", MetricField::from_str_name)
+                .map(MetricFieldSelector::Simple),
+            "datapoint_attribute" => parse_path(rest).map(MetricFieldSelector::DatapointAttribute),
+            "resource_attribute" => parse_path(rest).map(MetricFieldSelector::ResourceAttribute),
+            "scope_attribute" => parse_path(rest).map(MetricFieldSelector::ScopeAttribute),
+            "metric_type" => bare(key, rest).map(|()| MetricFieldSelector::Type),
+            "aggregation_temporality" => bare(key, rest).map(|()| MetricFieldSelector::Temporality),
+            other => Err(format!("unknown metric selector {other:?}")),
+        }
+    }
+}
+
+impl Selector for TraceFieldSelector {
+    fn render(&self) -> String {
+        match self {
+            TraceFieldSelector::Simple(f) => field("trace_field", f.as_str_name(), "TRACE_FIELD_"),
+            TraceFieldSelector::SpanAttribute(p) => attribute("span_attribute", p),
+            TraceFieldSelector::ResourceAttribute(p) => attribute("resource_attribute", p),
+            TraceFieldSelector::ScopeAttribute(p) => attribute("scope_attribute", p),
+            TraceFieldSelector::EventAttribute(p) => attribute("event_attribute", p),
+            TraceFieldSelector::SpanKind => "span_kind".to_string(),
+            TraceFieldSelector::SpanStatus => "span_status".to_string(),
+            TraceFieldSelector::EventName => "event_name".to_string(),
+            TraceFieldSelector::LinkTraceId => "link_trace_id".to_string(),
+            TraceFieldSelector::SamplingThreshold => "sampling_threshold".to_string(),
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let (key, rest) = split_key(text);
+        match key {
+            "trace_field" => parse_field(rest, "TRACE_FIELD_", TraceField::from_str_name)
+                .map(TraceFieldSelector::Simple),
+            "span_attribute" => parse_path(rest).map(TraceFieldSelector::SpanAttribute),
+            "resource_attribute" => parse_path(rest).map(TraceFieldSelector::ResourceAttribute),
+            "scope_attribute" => parse_path(rest).map(TraceFieldSelector::ScopeAttribute),
+            "event_attribute" => parse_path(rest).map(TraceFieldSelector::EventAttribute),
+            "span_kind" => bare(key, rest).map(|()| TraceFieldSelector::SpanKind),
+            "span_status" => bare(key, rest).map(|()| TraceFieldSelector::SpanStatus),
+            "event_name" => bare(key, rest).map(|()| TraceFieldSelector::EventName),
+            "link_trace_id" => bare(key, rest).map(|()| TraceFieldSelector::LinkTraceId),
+            "sampling_threshold" => bare(key, rest).map(|()| TraceFieldSelector::SamplingThreshold),
+            other => Err(format!("unknown trace selector {other:?}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Attribute paths with the characters the text form has to escape.
+    fn paths() -> Vec<Vec<String>> {
+        [
+            &["service.name"][..],
+            &["k8s", "pod"],
+            &["a\"b]", "[x]", ":y"],
+            &[""],
+            &["ü ñ\\"],
+        ]
+        .iter()
+        .map(|path| path.iter().map(|s| s.to_string()).collect())
+        .collect()
+    }
+
+    /// Every proto field a `Simple` selector can hold.
+    fn fields<F: TryFrom<i32>>() -> Vec<F> {
+        (0..=32).filter_map(|i| F::try_from(i).ok()).collect()
+    }
+
+    fn assert_round_trips<S: Selector + PartialEq + std::fmt::Debug>(selectors: Vec<S>) {
+        for selector in selectors {
+            let text = render(&selector);
+            let parsed: S = parse(&text).unwrap_or_else(|e| panic!("{text}: {e}"));
+            assert_eq!(parsed, selector, "{text}");
+            assert_eq!(render(&parsed), text);
+        }
+    }
+
+    #[test]
+    fn log_selectors_round_trip() {
+        let mut selectors: Vec<LogFieldSelector> = fields::<LogField>()
+            .into_iter()
+            .map(LogFieldSelector::Simple)
+            .collect();
+        for path in paths() {
+            selectors.push(LogFieldSelector::LogAttribute(path.clone()));
+            selectors.push(LogFieldSelector::ResourceAttribute(path.clone()));
+            selectors.push(LogFieldSelector::ScopeAttribute(path));
+        }
+        assert_round_trips(selectors);
+    }
+
+    #[test]
+    fn metric_selectors_round_trip() {
+        let mut selectors: Vec<MetricFieldSelector> = fields::<MetricField>()
+            .into_iter()
+            .map(MetricFieldSelector::Simple)
+            .collect();
+        for path in paths() {
+            selectors.push(MetricFieldSelector::DatapointAttribute(path.clone()));
+            selectors.push(MetricFieldSelector::ResourceAttribute(path.clone()));
+            selectors.push(MetricFieldSelector::ScopeAttribute(path));
+        }
+        selectors.push(MetricFieldSelector::Type);
+        selectors.push(MetricFieldSelector::Temporality);
+        assert_round_trips(selectors);
+    }
+
+    #[test]
+    fn trace_selectors_round_trip() {
+        let mut selectors: Vec<TraceFieldSelector> = fields::<TraceField>()
+            .into_iter()
+            .map(TraceFieldSelector::Simple)
+            .collect();
+        for path in paths() {
+            selectors.push(TraceFieldSelector::SpanAttribute(path.clone()));
+            selectors.push(TraceFieldSelector::ResourceAttribute(path.clone()));
+            selectors.push(TraceFieldSelector::ScopeAttribute(path.clone()));
+            selectors.push(TraceFieldSelector::EventAttribute(path));
+        }
+        selectors.extend([
+            TraceFieldSelector::SpanKind,
+            TraceFieldSelector::SpanStatus,
+            TraceFieldSelector::EventName,
+            TraceFieldSelector::LinkTraceId,
+            TraceFieldSelector::SamplingThreshold,
+        ]);
+        assert_round_trips(selectors);
+    }
+
+    #[test]
+    fn renders_in_matcher_vocabulary() {
+        assert_eq!(
+            render(&LogFieldSelector::Simple(LogField::SeverityText)),
+            "log_field:severity_text"
+        );
+        assert_eq!(
+            render(&TraceFieldSelector::Simple(TraceField::ParentSpanId)),
+            "trace_field:parent_span_id"
+        );
+        assert_eq!(
+            render(&LogFieldSelector::ResourceAttribute(vec![
+                "k8s".to_string(),
+                "pod".to_string()
+            ])),
+            r#"resource_attribute["k8s"]["pod"]"#
+        );
+        assert_eq!(render(&MetricFieldSelector::Type), "metric_type");
+        assert_eq!(render(&TraceFieldSelector::SpanKind), "span_kind");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let log = |text: &str| parse::<LogFieldSelector>(text).unwrap_err();
+        let metric = |text: &str| parse::<MetricFieldSelector>(text).unwrap_err();
+        let trace = |text: &str| parse::<TraceFieldSelector>(text).unwrap_err();
+
+        assert_eq!(log("body"), r#"unknown log selector "body""#);
+        assert_eq!(log(""), r#"unknown log selector """#);
+        // Keys of another signal are unknown.
+        assert_eq!(log("span_kind"), r#"unknown log selector "span_kind""#);
+        assert_eq!(
+            metric(r#"log_attribute["a"]"#),
+            r#"unknown metric selector "log_attribute""#
+        );
+        assert_eq!(
+            trace("metric_type"),
+            r#"unknown trace selector "metric_type""#
+        );
+
+        assert_eq!(log("log_field"), "expected :<field>");
+        assert_eq!(log("log_field[\"body\"]"), "expected :<field>");
+        assert_eq!(log("log_field:nope"), r#"unknown field "nope""#);
+        assert_eq!(metric("metric_field:"), r#"unknown field """#);
+
+        assert_eq!(
+            log("log_attribute"),
+            r#"an attribute selector needs at least one ["key"]"#
+        );
+        assert_eq!(
+            log("log_attribute[a]"),
+            r#"expected a quoted attribute key at "[a]""#
+        );
+        assert_eq!(
+            log(r#"log_attribute["a""#),
+            r#"unclosed attribute key at "[\"a\"""#
+        );
+        assert_eq!(
+            log(r#"log_attribute["a"]x"#),
+            r#"unexpected "x" after the attribute path"#
+        );
+        assert_eq!(
+            log(r#"log_attribute:a"#),
+            r#"unexpected ":a" after the attribute path"#
+        );
+
+        assert_eq!(
+            trace("span_kind:server"),
+            r#"span_kind takes no field or attribute, got ":server""#
+        );
+        assert_eq!(
+            metric(r#"metric_type["a"]"#),
+            r#"metric_type takes no field or attribute, got "[\"a\"]""#
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! How often the engine looks up each selector, for finding where evaluation
+//! time goes.
+//!
+//! [`Traced`](crate::engine_trace::Traced) counts every lookup it passes on
+//! to the record context into a shared [`Stats`]: how many times each
+//! selector was requested through `get_field`, `field_exists` or
+//! `get_typed_value`, and how many of those found the field. With the
+//! `selector-timing` feature it also sums the time spent in `get_field`, the
+//! lookup that feeds the engine's pattern scans. Writes aren't counted.
+//!
+//! Selectors are keyed by their [`selector_display`](crate::selector_display)
+//! rendering, so one selector used by several policies counts once per
+//! lookup. Records the engine never sees, such as those skipped by
+//! [`EvalOptions::skip_engine`](crate::EvalOptions::skip_engine) or given a
+//! reused scope decision, add nothing.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::selector_display::Selector;
+
+/// The lookups of one selector.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Counts {
+    pub requests: u64,
+    /// Lookups that found the field.
+    pub some: u64,
+    /// Lookups that found nothing.
+    pub none: u64,
+    /// Time spent in `get_field`, with the `selector-timing` feature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub get_field_nanos: Option<u64>,
+}
+
+/// Lookup counts by rendered selector, shared by every record evaluated.
+#[derive(Debug, Default)]
+pub struct Stats(Mutex<BTreeMap<String, Counts>>);
+
+impl Stats {
+    /// Count one lookup of `field`, `found` or not, that took `elapsed` when
+    /// timed.
+    pub fn record(&self, field: &impl Selector, found: bool, elapsed: Option<Duration>) {
+        let mut counts = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let counts = counts.entry(field.render()).or_default();
+        counts.requests += 1;
+        if found {
+            counts.some += 1;
+        } else {
+            counts.none += 1;
+        }
+        if let Some(elapsed) = elapsed {
+            let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+            let total = counts.get_field_nanos.get_or_insert(0);
+            *total = total.saturating_add(nanos);
+        }
+    }
+
+    /// Every selector looked up so far, with its counts.
+    pub fn snapshot(&self) -> BTreeMap<String, Counts> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Whether this build times `get_field`.
+pub const TIMED: bool = cfg!(feature = "selector-timing");
+
+/// A started `get_field` timing, which measures nothing unless
+/// [`TIMED`].
+pub struct Timer(#[cfg(feature = "selector-timing")] std::time::Instant);
+
+impl Timer {
+    pub fn start() -> Timer {
+        Timer(
+            #[cfg(feature = "selector-timing")]
+            std::time::Instant::now(),
+        )
+    }
+
+    pub fn elapsed(&self) -> Option<Duration> {
+        #[cfg(feature = "selector-timing")]
+        return Some(self.0.elapsed());
+        #[cfg(not(feature = "selector-timing"))]
+        None
+    }
+}
@@ -0,0 +1,275 @@
+//! Semantic-convention attribute aliases for matching records written under
+//! older schemas.
+//!
+//! The alias table is JSON keyed by schema version, each mapping the old
+//! attribute names records under that version carry to the names current
+//! policies use:
+//!
+//! ```json
+//! { "1.20.0": { "http.method": "http.request.method" } }
+//! ```
+//!
+//! With a table in [`EvalOptions::semconv_aliases`](crate::EvalOptions), a
+//! selector for `http.request.method` that finds no such attribute falls back
+//! to `http.method` on records whose schema URL ends in `/1.20.0`, and the
+//! record's result lists the [`AliasUse`]. Aliases apply to matching only;
+//! transforms always address the attribute names as written.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use serde::Serialize;
+
+/// Old attribute names by schema version and current name.
+#[derive(Debug, Default)]
+pub struct Aliases {
+    by_version: HashMap<String, HashMap<String, Vec<String>>>,
+}
+
+impl Aliases {
+    /// Parse an alias table (see the module docs).
+    pub fn from_json(text: &str) -> Result<Aliases, serde_json::Error> {
+        let table: HashMap<String, HashMap<String, String>> = serde_json::from_str(text)?;
+        let mut aliases = Aliases::default();
+        for (version, renames) in table {
+            let by_new = aliases.by_version.entry(version).or_default();
+            for (old, new) in renames {
+                by_new.entry(new).or_default().push(old);
+            }
+        }
+        for by_new in aliases.by_version.values_mut() {
+            for olds in by_new.values_mut() {
+                olds.sort();
+            }
+        }
+        Ok(aliases)
+    }
+
+    /// The old names `key` may appear under in records with `schema_url`.
+    fn get(&self, schema_url: &str, key: &str) -> &[String] {
+        let version = schema_url.rsplit('/').next().unwrap_or("");
+        self.by_version
+            .get(version)
+            .and_then(|by_new| by_new.get(key))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+/// An attribute a selector found under an old name.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AliasUse {
+    /// The name the selector asked for.
+    pub key: String,
+    /// The name the record had it under.
+    pub alias: String,
+    pub schema_url: String,
+}
+
+/// An alias table in force while records are evaluated, noting the aliases
+/// each record matched by.
+#[derive(Debug)]
+pub struct Aliasing {
+    aliases: Arc<Aliases>,
+    used: Mutex<Vec<AliasUse>>,
+}
+
+impl Aliasing {
+    pub fn new(aliases: Arc<Aliases>) -> Self {
+        Aliasing {
+            aliases,
+            used: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The first of `key`'s aliases under `schema_url` that `lookup` finds,
+    /// noted once per record.
+    pub(crate) fn find<T>(
+        &self,
+        schema_url: &str,
+        key: &str,
+        lookup: impl Fn(&str) -> Option<T>,
+    ) -> Option<T> {
+        self.aliases.get(schema_url, key).iter().find_map(|old| {
+            let found = lookup(old)?;
+            let used = AliasUse {
+                key: key.to_string(),
+                alias: old.clone(),
+                schema_url: schema_url.to_string(),
+            };
+            let mut log = self.used.lock().unwrap_or_else(PoisonError::into_inner);
+            if !log.contains(&used) {
+                log.push(used);
+            }
+            Some(found)
+        })
+    }
+
+    /// Take the aliases noted so far.
+    pub fn take(&self) -> Vec<AliasUse> {
+        std::mem::take(&mut *self.used.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// The schema governing a record's own attributes: its scope's, else its
+/// resource's.
+pub(crate) fn record_schema<'a>(
+    scope_schema_url: &'a str,
+    resource_schema_url: &'a str,
+) -> &'a str {
+    if scope_schema_url.is_empty() {
+        resource_schema_url
+    } else {
+        scope_schema_url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use policy_rs::{FileProvider, PolicyEngine, PolicyRegistry};
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{EvalOptions, decision_name, evaluate_logs_data, otel};
+
+    const TABLE: &str = r#"{
+        "1.20.0": { "http.method": "http.request.method", "http.verb": "http.request.method" },
+        "1.21.0": { "net.peer.name": "server.address" }
+    }"#;
+
+    /// Drops GET requests by their 1.26 name, removing the attribute from
+    /// whatever else it matches.
+    fn policies() -> Value {
+        json!({ "policies": [
+            {
+                "id": "drop-get",
+                "name": "drop-get",
+                "log": {
+                    "match": [{ "log_attribute": "http.request.method", "exact": "GET" }],
+                    "keep": "none",
+                },
+            },
+            {
+                "id": "strip-post",
+                "name": "strip-post",
+                "log": {
+                    "match": [{ "log_attribute": "http.request.method", "exact": "POST" }],
+                    "keep": "all",
+                    "transform": { "remove": [{ "log_attribute": "http.request.method" }] },
+                },
+            },
+        ] })
+    }
+
+    /// Evaluate one log with `key=method` under `schema_url`, returning its
+    /// decision, the aliases it matched by, and the record if it survived.
+    fn evaluate(
+        aliases: Option<&str>,
+        schema_url: &str,
+        key: &str,
+        method: &str,
+    ) -> (&'static str, Option<Vec<AliasUse>>, Option<otel::LogRecord>) {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), policies().to_string()).unwrap();
+        let registry = PolicyRegistry::new();
+        registry.subscribe(&FileProvider::new(file.path())).unwrap();
+        let mut logs: otel::LogsData = serde_json::from_value(json!({
+            "resourceLogs": [{
+                "schemaUrl": schema_url,
+                "scopeLogs": [{ "logRecords": [{
+                    "attributes": [{ "key": key, "value": { "stringValue": method } }],
+                }] }],
+            }],
+        }))
+        .unwrap();
+        let opts = EvalOptions {
+            semconv_aliases: aliases.map(|text| Arc::new(Aliases::from_json(text).unwrap())),
+            ..EvalOptions::default()
+        };
+        let results =
+            evaluate_logs_data(&PolicyEngine::new(), &registry.snapshot(), &mut logs, &opts)
+                .unwrap();
+        let record = logs
+            .resource_logs
+            .pop()
+            .and_then(|rl| rl.scope_logs.into_iter().next())
+            .and_then(|sl| sl.log_records.into_iter().next());
+        let [result] = &results[..] else {
+            panic!("expected one result, got {results:?}");
+        };
+        (
+            decision_name(&result.result),
+            result.aliases.clone(),
+            record,
+        )
+    }
+
+    fn used(alias: &str, schema_url: &str) -> AliasUse {
+        AliasUse {
+            key: "http.request.method".to_string(),
+            alias: alias.to_string(),
+            schema_url: schema_url.to_string(),
+        }
+    }
+
+    #[test]
+    fn aliases_are_looked_up_by_schema_version() {
+        let aliases = Aliases::from_json(TABLE).unwrap();
+        let url = "https://opentelemetry.io/schemas/1.20.0";
+        assert_eq!(
+            aliases.get(url, "http.request.method"),
+            ["http.method", "http.verb"]
+        );
+        assert_eq!(aliases.get(url, "server.address"), [] as [String; 0]);
+        assert_eq!(
+            aliases.get("https://opentelemetry.io/schemas/1.21.0", "server.address"),
+            ["net.peer.name"]
+        );
+        assert_eq!(aliases.get("", "http.request.method"), [] as [String; 0]);
+    }
+
+    #[test]
+    fn a_1_20_record_matches_a_1_26_policy_by_alias() {
+        let url = "https://opentelemetry.io/schemas/1.20.0";
+        let (decision, aliases, record) = evaluate(Some(TABLE), url, "http.method", "GET");
+        assert_eq!(decision, "drop");
+        assert_eq!(aliases, Some(vec![used("http.method", url)]));
+        assert!(record.is_none());
+    }
+
+    #[test]
+    fn aliasing_is_off_without_a_table() {
+        let url = "https://opentelemetry.io/schemas/1.20.0";
+        let (decision, aliases, _) = evaluate(None, url, "http.method", "GET");
+        assert_eq!(decision, "no_match");
+        assert_eq!(aliases, None);
+    }
+
+    #[test]
+    fn aliases_apply_only_under_their_schema() {
+        let url = "https://opentelemetry.io/schemas/1.26.0";
+        let (decision, aliases, _) = evaluate(Some(TABLE), url, "http.method", "GET");
+        assert_eq!(decision, "no_match");
+        assert_eq!(aliases, Some(Vec::new()));
+    }
+
+    #[test]
+    fn current_names_match_without_an_alias() {
+        let url = "https://opentelemetry.io/schemas/1.20.0";
+        let (decision, aliases, _) = evaluate(Some(TABLE), url, "http.request.method", "GET");
+        assert_eq!(decision, "drop");
+        assert_eq!(aliases, Some(Vec::new()));
+    }
+
+    #[test]
+    fn transforms_address_the_names_as_written() {
+        let url = "https://opentelemetry.io/schemas/1.20.0";
+        let (decision, aliases, record) = evaluate(Some(TABLE), url, "http.method", "POST");
+        assert_eq!(decision, "keep");
+        assert_eq!(aliases, Some(vec![used("http.method", url)]));
+        let attributes = record.unwrap().attributes;
+        assert_eq!(
+            attributes.iter().map(|kv| &kv.key[..]).collect::<Vec<_>>(),
+            ["http.method"]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Severity-text normalization, so one equality policy catches every SDK's
+//! spelling of a level.
+//!
+//! With a [`Table`] in [`EvalOptions::severity_table`], log matchers on
+//! `severity_text` see the table's canonical spelling (`WARN` for `Warning`,
+//! `warn` or `W`) instead of the record's. Lookups ignore ASCII case. Texts
+//! the table doesn't know are matched as written and listed in
+//! [`RecordResult::unknown_severity_text`]. Serialization and transforms
+//! always see the record's own text.
+//!
+//! [`EvalOptions::severity_table`]: crate::EvalOptions::severity_table
+//! [`RecordResult::unknown_severity_text`]: crate::RecordResult::unknown_severity_text
+//!
+//! The built-in table can be extended or overridden from TOML, keyed by the
+//! text as producers write it:
+//!
+//! ```toml
+//! notice = "INFO"
+//! W = "WARNING"
+//! ```
+
+use std::collections::HashMap;
+
+/// Producer spellings of each OpenTelemetry severity text.
+const BUILTIN: &[(&str, &[&str])] = &[
+    ("TRACE", &["trace", "trc", "t", "finest", "verbose"]),
+    ("DEBUG", &["debug", "dbg", "d", "fine", "finer"]),
+    ("INFO", &["info", "inf", "i", "information"]),
+    ("WARN", &["warn", "warning", "wrn", "w"]),
+    ("ERROR", &["error", "err", "e", "severe"]),
+    ("FATAL", &["fatal", "ftl", "f", "critical", "crit"]),
+];
+
+/// Canonical severity texts by lowercased producer spelling.
+#[derive(Debug)]
+pub struct Table {
+    canonical: HashMap<String, String>,
+}
+
+impl Table {
+    pub fn builtin() -> Table {
+        let canonical = BUILTIN
+            .iter()
+            .flat_map(|(canonical, spellings)| {
+                spellings
+                    .iter()
+                    .map(|s| (s.to_string(), canonical.to_string()))
+            })
+            .collect();
+        Table { canonical }
+    }
+
+    /// The built-in table with the entries of a TOML table (see the module
+    /// docs) added over it.
+    pub fn with_overrides(text: &str) -> Result<Table, toml::de::Error> {
+        let overrides: HashMap<String, String> = toml::from_str(text)?;
+        let mut table = Table::builtin();
+        for (spelling, canonical) in overrides {
+            table
+                .canonical
+                .insert(spelling.to_ascii_lowercase(), canonical);
+        }
+        Ok(table)
+    }
+
+    /// `text` as matchers should see it.
+    pub fn normalize<'a>(&'a self, text: &'a str) -> &'a str {
+        self.canonical
+            .get(&text.to_ascii_lowercase())
+            .map_or(text, String::as_str)
+    }
+
+    /// Whether `text` has a canonical spelling in the table.
+    pub fn knows(&self, text: &str) -> bool {
+        self.canonical.contains_key(&text.to_ascii_lowercase())
+    }
+}
+
+/// `text` as matchers should see it under `table`, if there is one.
+pub(crate) fn normalize<'a>(table: Option<&'a Table>, text: &'a str) -> &'a str {
+    table.map_or(text, |table| table.normalize(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use policy_rs::{FileProvider, PolicyEngine, PolicyRegistry};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{EvalOptions, decision_name, evaluate_logs_data, otel};
+
+    /// Evaluate one log per text against a policy dropping `WARN`, returning
+    /// each record's decision and unknown severity text.
+    fn evaluate(table: Option<Table>, texts: &[&str]) -> Vec<(&'static str, Option<String>)> {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let policies = json!({ "policies": [{
+            "id": "drop-warn",
+            "name": "drop-warn",
+            "log": {
+                "match": [{ "log_field": "severity_text", "exact": "WARN" }],
+                "keep": "none",
+            },
+        }] });
+        std::fs::write(file.path(), policies.to_string()).unwrap();
+        let registry = PolicyRegistry::new();
+        registry.subscribe(&FileProvider::new(file.path())).unwrap();
+        let records: Vec<_> = texts.iter().map(|t| json!({ "severityText": t })).collect();
+        let mut logs: otel::LogsData = serde_json::from_value(json!({
+            "resourceLogs": [{ "scopeLogs": [{ "logRecords": records }] }],
+        }))
+        .unwrap();
+        let opts = EvalOptions {
+            severity_table: table.map(Arc::new),
+            ..EvalOptions::default()
+        };
+        evaluate_logs_data(&PolicyEngine::new(), &registry.snapshot(), &mut logs, &opts)
+            .unwrap()
+            .into_iter()
+            .map(|r| (decision_name(&r.result), r.unknown_severity_text))
+            .collect()
+    }
+
+    #[test]
+    fn builtin_maps_every_spelling_ignoring_case() {
+        let table = Table::builtin();
+        for (canonical, spellings) in BUILTIN {
+            for spelling in *spellings {
+                assert_eq!(table.normalize(spelling), *canonical);
+                assert_eq!(table.normalize(&spelling.to_ascii_uppercase()), *canonical);
+            }
+        }
+        assert_eq!(table.normalize("Warning"), "WARN");
+        assert_eq!(table.normalize("CRIT"), "FATAL");
+    }
+
+    #[test]
+    fn builtin_leaves_unknown_texts_as_written() {
+        let table = Table::builtin();
+        assert_eq!(table.normalize("Notice"), "Notice");
+        assert_eq!(table.normalize(""), "");
+        assert!(!table.knows("Notice"));
+        assert!(table.knows("wArN"));
+    }
+
+    #[test]
+    fn overrides_add_and_replace_entries() {
+        let table = Table::with_overrides("notice = \"INFO\"\nW = \"WARNING\"\n").unwrap();
+        assert_eq!(table.normalize("NOTICE"), "INFO");
+        assert_eq!(table.normalize("w"), "WARNING");
+        // Entries the file doesn't name keep their built-in spelling.
+        assert_eq!(table.normalize("warn"), "WARN");
+        assert_eq!(table.normalize("err"), "ERROR");
+    }
+
+    #[test]
+    fn overrides_reject_bad_toml() {
+        assert!(Table::with_overrides("notice = ").is_err());
+        assert!(Table::with_overrides("notice = 3").is_err());
+    }
+
+    #[test]
+    fn matchers_see_the_canonical_text_and_results_list_unknown_ones() {
+        let found = evaluate(Some(Table::builtin()), &["Warning", "notice", ""]);
+        assert_eq!(
+            found,
+            [
+                ("drop", None),
+                ("no_match", Some("notice".to_string())),
+                ("no_match", None),
+            ]
+        );
+        // Each call reports only its own records.
+        assert_eq!(evaluate(Some(Table::builtin()), &["w"]), [("drop", None)]);
+    }
+
+    #[test]
+    fn texts_match_as_written_without_a_table() {
+        assert_eq!(
+            evaluate(None, &["Warning", "notice"]),
+            [("no_match", None), ("no_match", None)]
+        );
+    }
+
+    #[test]
+    fn normalize_is_off_without_a_table() {
+        assert_eq!(normalize(None, "warning"), "warning");
+        assert_eq!(normalize(Some(&Table::builtin()), "warning"), "WARN");
+    }
+}
@@ -0,0 +1,125 @@
+//! `--shadow-policies`: evaluate candidate policies against every record
+//! without letting them decide anything.
+//!
+//! Shadow policies get a registry and engine of their own and run over a
+//! separate copy of the input, so their transforms and rate-limit buckets
+//! never touch the real evaluation. Each record one of them matches is listed
+//! in the annotations' `shadow` section beside the decision the real policies
+//! made, and the summary totals, per shadow policy, what it matched and what
+//! it would have dropped that the real policies kept.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use policy_rs::{PolicyEngine, PolicyRegistry, PolicySnapshot};
+use runner_rs::{EvalOptions, RecordResult, SignalData, decision_name, evaluate_signal, keeps};
+use serde::Serialize;
+
+use crate::provider::{ChainProvider, MergeStrategy};
+use crate::status::{self, Failure};
+
+/// Load the shadow set from `paths`, merged as `--policies` sources are.
+pub fn load(paths: &[PathBuf], merge_strategy: MergeStrategy) -> status::Result<PolicySnapshot> {
+    let registry = PolicyRegistry::new();
+    if let Err(e) = registry.subscribe(&ChainProvider::new(paths, merge_strategy)) {
+        return Err(Failure(format!("failed to load shadow policies: {e}")));
+    }
+    Ok(registry.snapshot())
+}
+
+/// Evaluate `data`, an unevaluated copy of the input, against the shadow set.
+pub fn evaluate<D: SignalData>(
+    snapshot: &PolicySnapshot,
+    mut data: D,
+    opts: &EvalOptions,
+) -> status::Result<Vec<RecordResult>> {
+    evaluate_signal(&PolicyEngine::new(), snapshot, &mut data, opts)
+        .map_err(|e| Failure(format!("shadow evaluation error: {e}")))
+}
+
+/// A record a shadow policy matched.
+#[derive(Serialize)]
+pub struct ShadowRecord {
+    resource: usize,
+    scope: usize,
+    record: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    datapoint: Option<usize>,
+    policy_id: String,
+    /// The shadow policy's decision, by [`decision_name`].
+    decision: &'static str,
+    /// Whether the record would have survived that decision.
+    kept: bool,
+    /// The real decision; absent when the record's envelope went
+    /// unevaluated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    actual: Option<&'static str>,
+    /// Whether the record survived the real evaluation.
+    #[serde(skip)]
+    actually_kept: bool,
+}
+
+/// The shadow matches in `shadow`, each beside its record's result in
+/// `actual`.
+pub fn records(shadow: &[RecordResult], actual: &[RecordResult]) -> Vec<ShadowRecord> {
+    let actual: HashMap<_, _> = actual
+        .iter()
+        .map(|r| ((r.resource, r.scope, r.record, r.datapoint), &r.result))
+        .collect();
+    shadow
+        .iter()
+        .filter_map(|r| {
+            let policy_id = runner_rs::decision_policy(&r.result)?;
+            let real = actual.get(&(r.resource, r.scope, r.record, r.datapoint));
+            Some(ShadowRecord {
+                resource: r.resource,
+                scope: r.scope,
+                record: r.record,
+                datapoint: r.datapoint,
+                policy_id: policy_id.to_string(),
+                decision: decision_name(&r.result),
+                kept: keeps(&r.result),
+                actual: real.map(|result| decision_name(result)),
+                actually_kept: real.is_none_or(|result| keeps(result)),
+            })
+        })
+        .collect()
+}
+
+/// What one shadow policy would have done over the run.
+#[derive(Default, Serialize)]
+pub struct Impact {
+    /// Records it matched.
+    matched: usize,
+    /// Matched records its decision would have dropped.
+    would_drop: usize,
+    /// Of those, the records the real policies kept.
+    newly_dropped: usize,
+}
+
+/// [`Impact`] per shadow policy, including those that matched nothing.
+pub fn impact(snapshot: &PolicySnapshot, records: &[ShadowRecord]) -> BTreeMap<String, Impact> {
+    let mut impact: BTreeMap<String, Impact> = snapshot
+        .iter()
+        .map(|e| (e.policy.id().to_string(), Impact::default()))
+        .collect();
+    for r in records {
+        let policy = impact.entry(r.policy_id.clone()).or_default();
+        policy.matched += 1;
+        if !r.kept {
+            policy.would_drop += 1;
+            policy.newly_dropped += usize::from(r.actually_kept);
+        }
+    }
+    impact
+}
+
+/// Print `impact` under the end-of-run summary.
+pub fn print(impact: &BTreeMap<String, Impact>) {
+    for (id, policy) in impact {
+        eprintln!(
+            "  shadow {id}: matched {}, would drop {} ({} kept by the real policies)",
+            policy.matched, policy.would_drop, policy.newly_dropped
+        );
+    }
+}
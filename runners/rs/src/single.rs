@@ -0,0 +1,413 @@
+//! Evaluating one record, given as JSON, outside any payload: the entry
+//! point for embedders that hold records one at a time.
+//!
+//! The record is wrapped in a payload of its own, with an empty resource
+//! and scope, and filtered like any other, so decisions and transforms are
+//! exactly those of the `evaluate_*_data` functions.
+
+use policy_rs::{EvaluateResult, PolicyEngine, PolicyError, PolicySnapshot};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::filter::{EvalOptions, MetricGranularity, RecordResult, SignalData, evaluate_signal};
+use crate::otel;
+
+/// The kind of record [`evaluate_value`] reads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    /// An OTLP `LogRecord`.
+    Log,
+    /// An OTLP `Metric`, with its datapoints.
+    Metric,
+    /// An OTLP `Span`.
+    Trace,
+    /// An OTLP `Profile`, kept as given: any JSON object.
+    Profile,
+}
+
+/// One record's evaluation.
+#[derive(Clone, Debug)]
+pub struct ValueResult {
+    /// The decision, located at index 0 throughout. With
+    /// [`EvalOptions::trace_engine`] set, its `requests` are the lookups
+    /// that led to it.
+    pub result: RecordResult,
+    /// The record as transformed, or `None` if the decision dropped it.
+    pub record: Option<serde_json::Value>,
+}
+
+/// Why [`evaluate_value`] couldn't evaluate a record.
+#[derive(Debug)]
+pub enum ValueError {
+    /// The value isn't a record of the signal.
+    Parse(serde_json::Error),
+    /// The engine failed.
+    Policy(PolicyError),
+}
+
+impl std::fmt::Display for ValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueError::Parse(e) => write!(f, "invalid record: {e}"),
+            ValueError::Policy(e) => write!(f, "evaluation error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ValueError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ValueError::Parse(e) => Some(e),
+            ValueError::Policy(e) => Some(e),
+        }
+    }
+}
+
+impl From<PolicyError> for ValueError {
+    fn from(e: PolicyError) -> Self {
+        ValueError::Policy(e)
+    }
+}
+
+/// Evaluate `record`, one `signal` record in OTLP JSON form, against
+/// `snapshot`, applying any transforms to the record if it survives.
+///
+/// `opts` apply as to a payload, except that nothing is sampled away, and a
+/// metric is always decided as a whole. Rate limits draw on `engine`'s
+/// buckets, so pass the same engine for every record of a stream.
+///
+/// ```
+/// use policy_rs::{FileProvider, PolicyEngine, PolicyRegistry};
+/// use runner_rs::{EvalOptions, Signal, decision_name, evaluate_value};
+/// use serde_json::json;
+///
+/// let registry = PolicyRegistry::new();
+/// registry.subscribe(&FileProvider::new("../../testcases/logs_exact_drop/policies.json"))?;
+/// let snapshot = registry.snapshot();
+/// let engine = PolicyEngine::new();
+/// let opts = EvalOptions::default();
+///
+/// let log = json!({"severityText": "INFO", "body": {"stringValue": "health check"}});
+/// let evaluated = evaluate_value(&engine, &snapshot, Signal::Log, &log, &opts)?;
+/// assert_eq!(decision_name(&evaluated.result.result), "drop");
+/// assert_eq!(evaluated.record, None);
+///
+/// // No policy applies to spans, so this one comes back as it was.
+/// let span = json!({"name": "GET /health", "traceId": "0102030405060708090a0b0c0d0e0f10"});
+/// let evaluated = evaluate_value(&engine, &snapshot, Signal::Trace, &span, &opts)?;
+/// assert_eq!(decision_name(&evaluated.result.result), "no_match");
+/// assert_eq!(evaluated.record.unwrap()["name"], "GET /health");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn evaluate_value(
+    engine: &PolicyEngine,
+    snapshot: &PolicySnapshot,
+    signal: Signal,
+    record: &serde_json::Value,
+    opts: &EvalOptions,
+) -> Result<ValueResult, ValueError> {
+    let opts = EvalOptions {
+        sample_rate: None,
+        metric_granularity: MetricGranularity::Metric,
+        ..opts.clone()
+    };
+    match signal {
+        Signal::Log => {
+            let mut data = otel::LogsData {
+                resource_logs: vec![otel::ResourceLogs {
+                    scope_logs: vec![otel::ScopeLogs {
+                        log_records: vec![parse(record)?],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                extra: serde_json::Map::new(),
+            };
+            evaluate(engine, snapshot, &mut data, &opts, |data| {
+                let rl = data.resource_logs.pop()?;
+                rl.scope_logs.into_iter().next()?.log_records.pop()
+            })
+        }
+        Signal::Metric => {
+            let mut data = otel::MetricsData {
+                resource_metrics: vec![otel::ResourceMetrics {
+                    scope_metrics: vec![otel::ScopeMetrics {
+                        metrics: vec![parse(record)?],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                extra: serde_json::Map::new(),
+            };
+            evaluate(engine, snapshot, &mut data, &opts, |data| {
+                let rm = data.resource_metrics.pop()?;
+                rm.scope_metrics.into_iter().next()?.metrics.pop()
+            })
+        }
+        Signal::Trace => {
+            let mut data = otel::TracesData {
+                resource_spans: vec![otel::ResourceSpans {
+                    scope_spans: vec![otel::ScopeSpans {
+                        spans: vec![parse(record)?],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                extra: serde_json::Map::new(),
+            };
+            evaluate(engine, snapshot, &mut data, &opts, |data| {
+                let rs = data.resource_spans.pop()?;
+                rs.scope_spans.into_iter().next()?.spans.pop()
+            })
+        }
+        Signal::Profile => {
+            let mut data = otel::ProfilesData {
+                resource_profiles: vec![otel::ResourceProfiles {
+                    scope_profiles: vec![otel::ScopeProfiles {
+                        profiles: vec![serde_json::Value::Object(parse(record)?)],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                extra: serde_json::Map::new(),
+            };
+            evaluate(engine, snapshot, &mut data, &opts, |data| {
+                let rp = data.resource_profiles.pop()?;
+                rp.scope_profiles.into_iter().next()?.profiles.pop()
+            })
+        }
+    }
+}
+
+fn parse<T: DeserializeOwned>(record: &serde_json::Value) -> Result<T, ValueError> {
+    T::deserialize(record).map_err(ValueError::Parse)
+}
+
+/// Filter the one-record `data`, then `take` what survived of the record.
+fn evaluate<D: SignalData, T: Serialize>(
+    engine: &PolicyEngine,
+    snapshot: &PolicySnapshot,
+    data: &mut D,
+    opts: &EvalOptions,
+    take: impl FnOnce(&mut D) -> Option<T>,
+) -> Result<ValueResult, ValueError> {
+    let results = evaluate_signal(engine, snapshot, data, opts)?;
+    // Nothing is sampled away, so the record always has its result.
+    let result = results.into_iter().next().unwrap_or(RecordResult {
+        resource: 0,
+        scope: 0,
+        record: 0,
+        datapoint: None,
+        result: EvaluateResult::NoMatch,
+        elapsed: None,
+        changes: None,
+        requests: None,
+        aliases: None,
+        unknown_severity_text: None,
+        copy_of: None,
+    });
+    let record = take(data)
+        .map(|record| serde_json::to_value(record).map_err(ValueError::Parse))
+        .transpose()?;
+    Ok(ValueResult { result, record })
+}
+
+#[cfg(test)]
+mod tests {
+    use policy_rs::{FileProvider, PolicyRegistry};
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::filter::decision_name;
+
+    fn snapshot(policies: Value) -> PolicySnapshot {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), json!({ "policies": policies }).to_string()).unwrap();
+        let registry = PolicyRegistry::new();
+        registry.subscribe(&FileProvider::new(file.path())).unwrap();
+        registry.snapshot()
+    }
+
+    /// Evaluate `record` and return its decision's name and what's left of it.
+    fn evaluate(
+        snapshot: &PolicySnapshot,
+        signal: Signal,
+        record: Value,
+    ) -> (&'static str, Option<Value>) {
+        let evaluated = evaluate_value(
+            &PolicyEngine::new(),
+            snapshot,
+            signal,
+            &record,
+            &EvalOptions::default(),
+        )
+        .unwrap();
+        (decision_name(&evaluated.result.result), evaluated.record)
+    }
+
+    #[test]
+    fn logs_are_kept_dropped_and_transformed() {
+        let snapshot = snapshot(json!([
+            {
+                "id": "drop-health",
+                "name": "drop-health",
+                "log": {
+                    "match": [{ "log_field": "body", "exact": "health check" }],
+                    "keep": "none",
+                },
+            },
+            {
+                "id": "tag-errors",
+                "name": "tag-errors",
+                "log": {
+                    "match": [{ "log_field": "severity_text", "exact": "ERROR" }],
+                    "keep": "all",
+                    "transform": { "add": [
+                        { "log_attribute": "triaged", "value": "no", "upsert": true },
+                    ] },
+                },
+            },
+        ]));
+        let log = |severity: &str, body: &str| json!({ "severityText": severity, "body": { "stringValue": body } });
+
+        let (decision, record) = evaluate(&snapshot, Signal::Log, log("INFO", "health check"));
+        assert_eq!((decision, record), ("drop", None));
+
+        let (decision, record) = evaluate(&snapshot, Signal::Log, log("INFO", "user login"));
+        assert_eq!(decision, "no_match");
+        assert_eq!(
+            record.unwrap()["body"],
+            json!({ "stringValue": "user login" })
+        );
+
+        let (decision, record) = evaluate(&snapshot, Signal::Log, log("ERROR", "timeout"));
+        assert_eq!(decision, "keep");
+        let record = record.unwrap();
+        assert_eq!(record["body"], json!({ "stringValue": "timeout" }));
+        assert_eq!(
+            record["attributes"],
+            json!([{ "key": "triaged", "value": { "stringValue": "no" } }])
+        );
+    }
+
+    #[test]
+    fn metrics_are_kept_and_dropped_whole() {
+        let snapshot = snapshot(json!([
+            {
+                "id": "drop-debug",
+                "name": "drop-debug",
+                "metric": {
+                    "match": [{ "metric_field": "name", "starts_with": "debug." }],
+                    "keep": false,
+                },
+            },
+            {
+                "id": "keep-http",
+                "name": "keep-http",
+                "metric": {
+                    "match": [{ "metric_field": "name", "starts_with": "http." }],
+                    "keep": true,
+                },
+            },
+        ]));
+        let metric = |name: &str| {
+            json!({
+                "name": name,
+                "gauge": { "dataPoints": [{ "asDouble": 1.0 }, { "asDouble": 2.0 }] },
+            })
+        };
+
+        let (decision, record) = evaluate(&snapshot, Signal::Metric, metric("debug.queue"));
+        assert_eq!((decision, record), ("drop", None));
+
+        // Metric policies have no transforms: a kept metric comes back with
+        // all its datapoints, as given.
+        let (decision, record) = evaluate(&snapshot, Signal::Metric, metric("http.requests"));
+        assert_eq!(decision, "keep");
+        let record = record.unwrap();
+        assert_eq!(record["name"], "http.requests");
+        assert_eq!(record["gauge"]["dataPoints"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn spans_are_sampled_and_their_tracestate_written() {
+        let snapshot = snapshot(json!([{
+            "id": "sample-half",
+            "name": "sample-half",
+            "trace": {
+                "match": [{ "trace_field": "TRACE_FIELD_NAME", "exact": "GET /users" }],
+                "keep": { "percentage": 50.0 },
+            },
+        }]));
+        // The trace id's last 14 hex digits are its randomness, kept at 50%
+        // from 0x80000000000000 up.
+        let span = |trace_id: &str| json!({ "name": "GET /users", "traceId": trace_id, "spanId": "0101010101010101" });
+
+        let (decision, record) = evaluate(
+            &snapshot,
+            Signal::Trace,
+            span("aabbccdd000000000000000000000000"),
+        );
+        assert_eq!((decision, record), ("sample", None));
+
+        let (decision, record) = evaluate(
+            &snapshot,
+            Signal::Trace,
+            span("aabbccdd000000000180000000000000"),
+        );
+        assert_eq!(decision, "sample");
+        let record = record.unwrap();
+        assert_eq!(record["name"], "GET /users");
+        assert_eq!(record["traceState"], "ot=th:8");
+    }
+
+    #[test]
+    fn profiles_are_decided_by_log_policies_and_never_transformed() {
+        let policy = |keep: &str| {
+            json!([{
+                "id": "profiles",
+                "name": "profiles",
+                "log": {
+                    "match": [{ "resource_attribute": "service.name", "exists": false }],
+                    "keep": keep,
+                    "transform": { "add": [
+                        { "log_attribute": "triaged", "value": "no", "upsert": true },
+                    ] },
+                },
+            }])
+        };
+        let profile = json!({ "sample": [{ "value": [1] }], "futureProfileField": 1 });
+
+        let dropping = snapshot(policy("none"));
+        let (decision, record) = evaluate(&dropping, Signal::Profile, profile.clone());
+        assert_eq!((decision, record), ("drop", None));
+
+        let keeping = snapshot(policy("all"));
+        let (decision, record) = evaluate(&keeping, Signal::Profile, profile.clone());
+        assert_eq!((decision, record), ("keep", Some(profile)));
+    }
+
+    #[test]
+    fn every_signal_rejects_a_value_that_isnt_its_record() {
+        let snapshot = snapshot(json!([]));
+        let cases = [
+            (Signal::Log, json!({ "attributes": "not a list" })),
+            (Signal::Metric, json!({ "name": 42 })),
+            (Signal::Trace, json!({ "name": ["GET /users"] })),
+            (Signal::Profile, json!("not an object")),
+        ];
+        for (signal, record) in cases {
+            let evaluated = evaluate_value(
+                &PolicyEngine::new(),
+                &snapshot,
+                signal,
+                &record,
+                &EvalOptions::default(),
+            );
+            assert!(
+                matches!(evaluated, Err(ValueError::Parse(_))),
+                "{signal:?} accepted {record}"
+            );
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! `--stamp-resource`: attributes naming the policy revision and runner that
+//! produced an output, upserted onto every output envelope's resource for
+//! downstream systems to read.
+//!
+//! By default the stamp is `tero.policy.hash`, the SHA-256 of the loaded
+//! policies' canonical proto JSON (as `export-snapshot` writes them), and
+//! `tero.policy.runner_version`. The hash covers the main policy set, then
+//! each `--tenant-policies` set, in snapshot order, so it changes with any
+//! policy a run could apply. `--stamp-attribute` adds more attributes or
+//! replaces a default's value.
+//!
+//! Stamping comes last, after evaluation and `--strip-attributes`, so no
+//! policy can observe or remove the stamp in the run that adds it. An
+//! attribute already carrying a stamp key is overwritten, leaving one per
+//! envelope.
+
+use policy_rs::PolicySnapshot;
+use runner_rs::{eval, otel};
+use sha2::{Digest, Sha256};
+
+pub const HASH_KEY: &str = "tero.policy.hash";
+pub const VERSION_KEY: &str = "tero.policy.runner_version";
+
+pub fn parse_attribute(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("{s:?} is not KEY=VALUE")),
+    }
+}
+
+/// The SHA-256 of every policy in `snapshots`, in order, as lowercase hex.
+pub fn policy_hash<'a>(snapshots: impl IntoIterator<Item = &'a PolicySnapshot>) -> String {
+    let mut hasher = Sha256::new();
+    for snapshot in snapshots {
+        for entry in snapshot.iter() {
+            // Proto JSON of a plain message can't fail to serialize.
+            hasher.update(serde_json::to_vec(entry.policy.proto()).unwrap_or_default());
+            hasher.update(b"\n");
+        }
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// The default stamp with `extra` applied over it, in order.
+pub fn attributes(policy_hash: String, extra: &[(String, String)]) -> Vec<(String, String)> {
+    let mut attributes = vec![
+        (HASH_KEY.to_string(), policy_hash),
+        (
+            VERSION_KEY.to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+        ),
+    ];
+    for (key, value) in extra {
+        match attributes.iter_mut().find(|(k, _)| k == key) {
+            Some(existing) => existing.1 = value.clone(),
+            None => attributes.push((key.clone(), value.clone())),
+        }
+    }
+    attributes
+}
+
+fn stamp_resource(resource: &mut Option<otel::Resource>, attributes: &[(String, String)]) {
+    let resource = resource.get_or_insert_with(otel::Resource::default);
+    for (key, value) in attributes {
+        // Input may already repeat a stamp key; only ours may remain.
+        let mut seen = false;
+        resource.attributes.retain(|kv| {
+            let repeat = seen && kv.key == *key;
+            seen |= kv.key == *key;
+            !repeat
+        });
+        eval::set_attr(&mut resource.attributes, key, value);
+    }
+}
+
+/// Payloads whose envelopes can be stamped.
+pub trait Stamped {
+    /// Upsert `attributes` onto every envelope's resource.
+    fn stamp(&mut self, attributes: &[(String, String)]);
+}
+
+impl Stamped for otel::LogsData {
+    fn stamp(&mut self, attributes: &[(String, String)]) {
+        for rl in &mut self.resource_logs {
+            stamp_resource(&mut rl.resource, attributes);
+        }
+    }
+}
+
+impl Stamped for otel::MetricsData {
+    fn stamp(&mut self, attributes: &[(String, String)]) {
+        for rm in &mut self.resource_metrics {
+            stamp_resource(&mut rm.resource, attributes);
+        }
+    }
+}
+
+impl Stamped for otel::TracesData {
+    fn stamp(&mut self, attributes: &[(String, String)]) {
+        for rs in &mut self.resource_spans {
+            stamp_resource(&mut rs.resource, attributes);
+        }
+    }
+}
+
+impl Stamped for otel::ProfilesData {
+    fn stamp(&mut self, attributes: &[(String, String)]) {
+        for rp in &mut self.resource_profiles {
+            stamp_resource(&mut rp.resource, attributes);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! `--status-file <path>`: a small JSON document written as the process
+//! ends, however it ends, so an orchestrator can tell what a run did without
+//! scraping stderr:
+//!
+//! ```json
+//! {"exit_code":1,"error":"failed to read input: No such file or directory (os error 2)",
+//!  "durations":{"load_ms":3.1,"total_ms":3.4},"outputs":[]}
+//! ```
+//!
+//! `counts` is present once records were evaluated, `error` once the run
+//! failed, and each of `durations` once its phase finished. `outputs` lists
+//! every file the run wrote, in order, the status file aside; a failed run
+//! leaves none of its unfinished writes behind (see [`atomic_file`]).
+//!
+//! For the file to be written on every path, a failure returns to `main` as
+//! a [`Failure`] instead of exiting where it happens; `main` is the one
+//! place the process exits, `--help` and `--version` aside. A command line
+//! clap rejects fails with clap's exit code, and the file is still written
+//! if the command line names one.
+//!
+//! [`atomic_file`]: crate::atomic_file
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Serialize;
+use tracing::error;
+
+use crate::atomic_file;
+
+/// Why a run failed, which ends it with exit code 1, or with
+/// [`Progress::failure_code`] when that is set. The message is logged once,
+/// by `main`.
+pub struct Failure(pub String);
+
+pub type Result<T = ()> = std::result::Result<T, Failure>;
+
+/// Record counts of a run that got as far as evaluating.
+#[derive(Serialize)]
+pub struct Counts {
+    pub records: usize,
+    /// Records per decision kind, keyed by [`runner_rs::decision_name`].
+    pub decisions: BTreeMap<&'static str, usize>,
+    /// Policies that failed to compile and so never matched.
+    pub policy_errors: usize,
+    pub warnings: usize,
+}
+
+#[derive(Default, Serialize)]
+struct Durations {
+    /// Loading policies.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    load_ms: Option<f64>,
+    /// Reading the input, evaluating it and writing the output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    evaluate_ms: Option<f64>,
+    total_ms: f64,
+}
+
+/// How far a run got, kept as it goes for the status file.
+#[derive(Default)]
+pub struct Progress {
+    /// `--status-file`, once the arguments are parsed.
+    pub path: Option<PathBuf>,
+    pub counts: Option<Counts>,
+    /// The exit code of a failed run, when it isn't 1: clap's, for a
+    /// command line it rejected.
+    pub failure_code: Option<i32>,
+    durations: Durations,
+}
+
+impl Progress {
+    /// The policies finished loading `elapsed` into the run.
+    pub fn loaded(&mut self, elapsed: Duration) {
+        self.durations.load_ms = Some(ms(elapsed));
+    }
+
+    /// The output was written `elapsed` after the policies loaded.
+    pub fn evaluated(&mut self, elapsed: Duration) {
+        self.durations.evaluate_ms = Some(ms(elapsed));
+    }
+
+    /// Write the status file, if there is one, for a run that took
+    /// `elapsed` and ended in `outcome`. Returns the exit code, which is 1
+    /// if the file couldn't be written.
+    pub fn finish(mut self, outcome: &Result, elapsed: Duration) -> i32 {
+        let exit_code = match outcome {
+            Ok(()) => 0,
+            Err(_) => self.failure_code.unwrap_or(1),
+        };
+        let Some(path) = self.path.take() else {
+            return exit_code;
+        };
+        self.durations.total_ms = ms(elapsed);
+        let status = Status {
+            exit_code,
+            error: outcome.as_ref().err().map(|failure| &failure.0[..]),
+            counts: self.counts,
+            durations: self.durations,
+            outputs: atomic_file::written(),
+        };
+        let written = serde_json::to_string(&status)
+            .map_err(|e| e.to_string())
+            .and_then(|data| atomic_file::write(&path, data).map_err(|e| e.to_string()));
+        match written {
+            Ok(()) => exit_code,
+            Err(e) => {
+                error!("failed to write status file: {e}");
+                1
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Status<'a> {
+    exit_code: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    counts: Option<Counts>,
+    durations: Durations,
+    outputs: Vec<PathBuf>,
+}
+
+fn ms(elapsed: Duration) -> f64 {
+    elapsed.as_secs_f64() * 1000.0
+}
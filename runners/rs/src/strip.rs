@@ -0,0 +1,189 @@
+//! `--strip-attributes`: attributes removed from every resource, scope and
+//! record right after parsing, so that neither policies nor the output ever
+//! see them, for runs over privacy-sensitive captures.
+//!
+//! Keys match a glob as policy ids match `--only-policy`: `*` for any run of
+//! characters, `?` for any one. Only top-level keys are matched, not those
+//! inside kvlist values. Record attributes are log records', spans' and
+//! their events' and links', and metrics' datapoints' and metadata;
+//! profile bodies are raw JSON and keep theirs. Each stripped attribute adds
+//! one to its holder's `droppedAttributesCount`, where it has one, as OTLP
+//! counts attributes discarded on the way.
+//!
+//! The output is stripped again after evaluation, so an attribute a
+//! transform adds or renames into a stripped key doesn't reach it either.
+//! The summary counts both as `stripped_attributes`.
+
+use runner_rs::otel::{self, KeyValue};
+use serde_json::Value;
+
+use crate::provider::glob_match;
+
+fn stripped(globs: &[String], key: &str) -> bool {
+    globs.iter().any(|glob| glob_match(glob, key))
+}
+
+/// Remove the matching attributes, returning how many went.
+fn strip(attributes: &mut Vec<KeyValue>, globs: &[String]) -> usize {
+    let before = attributes.len();
+    attributes.retain(|kv| !stripped(globs, &kv.key));
+    before - attributes.len()
+}
+
+/// [`strip`], counting the attributes in `dropped`.
+fn strip_counted(attributes: &mut Vec<KeyValue>, dropped: &mut u32, globs: &[String]) -> usize {
+    let removed = strip(attributes, globs);
+    *dropped = dropped.saturating_add(u32::try_from(removed).unwrap_or(u32::MAX));
+    removed
+}
+
+/// [`strip`] on an object held as raw JSON, counting the attributes in its
+/// `droppedAttributesCount` if `counted`.
+fn strip_json(value: &mut Value, counted: bool, globs: &[String]) -> usize {
+    let Some(object) = value.as_object_mut() else {
+        return 0;
+    };
+    let Some(Value::Array(attributes)) = object.get_mut("attributes") else {
+        return 0;
+    };
+    let before = attributes.len();
+    attributes.retain(|kv| {
+        !kv.get("key")
+            .and_then(Value::as_str)
+            .is_some_and(|key| stripped(globs, key))
+    });
+    let removed = before - attributes.len();
+    if counted && removed > 0 {
+        let dropped = object
+            .get("droppedAttributesCount")
+            .and_then(Value::as_u64)
+            .unwrap_or(0);
+        object.insert(
+            "droppedAttributesCount".to_string(),
+            (dropped + removed as u64).into(),
+        );
+    }
+    removed
+}
+
+fn strip_resource(resource: Option<&mut otel::Resource>, globs: &[String]) -> usize {
+    resource.map_or(0, |r| {
+        strip_counted(&mut r.attributes, &mut r.dropped_attributes_count, globs)
+    })
+}
+
+fn strip_scope(scope: Option<&mut otel::InstrumentationScope>, globs: &[String]) -> usize {
+    scope.map_or(0, |s| {
+        strip_counted(&mut s.attributes, &mut s.dropped_attributes_count, globs)
+    })
+}
+
+/// Payloads whose attributes can be stripped.
+pub trait Stripped {
+    /// Remove every attribute whose key matches one of `globs`, returning
+    /// how many went.
+    fn strip_attributes(&mut self, globs: &[String]) -> usize;
+}
+
+impl Stripped for otel::LogsData {
+    fn strip_attributes(&mut self, globs: &[String]) -> usize {
+        let mut removed = 0;
+        for rl in &mut self.resource_logs {
+            removed += strip_resource(rl.resource.as_mut(), globs);
+            for sl in &mut rl.scope_logs {
+                removed += strip_scope(sl.scope.as_mut(), globs);
+                for rec in &mut sl.log_records {
+                    removed += strip_counted(
+                        &mut rec.attributes,
+                        &mut rec.dropped_attributes_count,
+                        globs,
+                    );
+                }
+            }
+        }
+        removed
+    }
+}
+
+impl Stripped for otel::MetricsData {
+    fn strip_attributes(&mut self, globs: &[String]) -> usize {
+        let mut removed = 0;
+        for rm in &mut self.resource_metrics {
+            removed += strip_resource(rm.resource.as_mut(), globs);
+            for sm in &mut rm.scope_metrics {
+                removed += strip_scope(sm.scope.as_mut(), globs);
+                for m in &mut sm.metrics {
+                    removed += strip(&mut m.metadata, globs);
+                    removed += match &mut m.data {
+                        Some(otel::MetricData::Gauge(g)) => g
+                            .data_points
+                            .iter_mut()
+                            .map(|dp| strip(&mut dp.attributes, globs))
+                            .sum(),
+                        Some(otel::MetricData::Sum(s)) => s
+                            .data_points
+                            .iter_mut()
+                            .map(|dp| strip(&mut dp.attributes, globs))
+                            .sum(),
+                        Some(otel::MetricData::Histogram(h)) => h
+                            .data_points
+                            .iter_mut()
+                            .map(|dp| strip(&mut dp.attributes, globs))
+                            .sum(),
+                        Some(otel::MetricData::ExponentialHistogram(eh)) => eh
+                            .data_points
+                            .iter_mut()
+                            .map(|dp| strip_json(dp, false, globs))
+                            .sum(),
+                        Some(otel::MetricData::Summary(s)) => s
+                            .data_points
+                            .iter_mut()
+                            .map(|dp| strip(&mut dp.attributes, globs))
+                            .sum(),
+                        None => 0,
+                    };
+                }
+            }
+        }
+        removed
+    }
+}
+
+impl Stripped for otel::TracesData {
+    fn strip_attributes(&mut self, globs: &[String]) -> usize {
+        let mut removed = 0;
+        for rs in &mut self.resource_spans {
+            removed += strip_resource(rs.resource.as_mut(), globs);
+            for ss in &mut rs.scope_spans {
+                removed += strip_scope(ss.scope.as_mut(), globs);
+                for span in &mut ss.spans {
+                    removed += strip_counted(
+                        &mut span.attributes,
+                        &mut span.dropped_attributes_count,
+                        globs,
+                    );
+                    removed += span
+                        .events
+                        .iter_mut()
+                        .chain(&mut span.links)
+                        .map(|v| strip_json(v, true, globs))
+                        .sum::<usize>();
+                }
+            }
+        }
+        removed
+    }
+}
+
+impl Stripped for otel::ProfilesData {
+    fn strip_attributes(&mut self, globs: &[String]) -> usize {
+        let mut removed = 0;
+        for rp in &mut self.resource_profiles {
+            removed += strip_resource(rp.resource.as_mut(), globs);
+            for sp in &mut rp.scope_profiles {
+                removed += strip_scope(sp.scope.as_mut(), globs);
+            }
+        }
+        removed
+    }
+}
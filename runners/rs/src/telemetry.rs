@@ -0,0 +1,170 @@
+//! Self-telemetry: counters about the runner's own work, dumped as JSON with
+//! `--self-telemetry-file`.
+//!
+//! Counters are process-wide atomics so evaluation threads update them without
+//! coordination. Nothing is timed or counted until [`enable`] is called.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering::Relaxed};
+use std::time::Duration;
+
+use policy_rs::{EvaluateResult, PolicySnapshot};
+use serde::Serialize;
+
+/// Upper bounds (inclusive, microseconds) of the evaluation latency buckets;
+/// the last bucket counts everything slower.
+const LATENCY_BOUNDS_US: [u64; 7] = [1, 5, 10, 50, 100, 500, 1000];
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+static POLICY_HASH: Mutex<String> = Mutex::new(String::new());
+
+static RECORDS: AtomicU64 = AtomicU64::new(0);
+static NO_MATCH: AtomicU64 = AtomicU64::new(0);
+static KEEP: AtomicU64 = AtomicU64::new(0);
+static DROP: AtomicU64 = AtomicU64::new(0);
+static SAMPLE_KEPT: AtomicU64 = AtomicU64::new(0);
+static SAMPLE_DROPPED: AtomicU64 = AtomicU64::new(0);
+static RATE_ALLOWED: AtomicU64 = AtomicU64::new(0);
+static RATE_LIMITED: AtomicU64 = AtomicU64::new(0);
+static POLICY_LOADS: AtomicU64 = AtomicU64::new(0);
+static PARSE_ERRORS: AtomicU64 = AtomicU64::new(0);
+static LATENCY: [AtomicU64; LATENCY_BOUNDS_US.len() + 1] =
+    [const { AtomicU64::new(0) }; LATENCY_BOUNDS_US.len() + 1];
+
+#[derive(Serialize)]
+struct Report {
+    resource: Resource,
+    records_evaluated: u64,
+    decisions: Decisions,
+    evaluation_latency_us: Histogram,
+    policy_loads: u64,
+    parse_errors: u64,
+}
+
+#[derive(Serialize)]
+struct Resource {
+    #[serde(rename = "service.name")]
+    service_name: &'static str,
+    #[serde(rename = "service.instance.id")]
+    instance_id: String,
+    #[serde(rename = "policy.hash")]
+    policy_hash: String,
+}
+
+#[derive(Serialize)]
+struct Decisions {
+    no_match: u64,
+    keep: u64,
+    drop: u64,
+    sample_kept: u64,
+    sample_dropped: u64,
+    rate_allowed: u64,
+    rate_limited: u64,
+}
+
+#[derive(Serialize)]
+struct Histogram {
+    bounds: &'static [u64],
+    counts: Vec<u64>,
+}
+
+/// Start counting; [`flush`] writes the report to `path`.
+pub fn enable(path: &Path) {
+    *PATH.lock().unwrap() = Some(path.to_path_buf());
+    ENABLED.store(true, Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Relaxed)
+}
+
+/// Count one evaluated record; `elapsed` is only `None` when telemetry is off.
+pub fn record(result: &EvaluateResult, elapsed: Option<Duration>) {
+    // Records are also timed for `--timing-histogram` alone.
+    let Some(elapsed) = elapsed.filter(|_| enabled()) else {
+        return;
+    };
+    RECORDS.fetch_add(1, Relaxed);
+    let decision = match result {
+        EvaluateResult::NoMatch => &NO_MATCH,
+        EvaluateResult::Keep { .. } => &KEEP,
+        EvaluateResult::Drop { .. } => &DROP,
+        EvaluateResult::Sample { keep: true, .. } => &SAMPLE_KEPT,
+        EvaluateResult::Sample { keep: false, .. } => &SAMPLE_DROPPED,
+        EvaluateResult::RateLimit { allowed: true, .. } => &RATE_ALLOWED,
+        EvaluateResult::RateLimit { allowed: false, .. } => &RATE_LIMITED,
+    };
+    decision.fetch_add(1, Relaxed);
+    LATENCY[bucket(elapsed)].fetch_add(1, Relaxed);
+}
+
+fn bucket(elapsed: Duration) -> usize {
+    let us = elapsed.as_micros();
+    LATENCY_BOUNDS_US
+        .iter()
+        .position(|&bound| us <= u128::from(bound))
+        .unwrap_or(LATENCY_BOUNDS_US.len())
+}
+
+/// Count a policy load and remember a hash of what was loaded, so reports
+/// from runs with different policies can be told apart.
+pub fn policies_loaded(snapshot: &PolicySnapshot) {
+    if !ENABLED.load(Relaxed) {
+        return;
+    }
+    POLICY_LOADS.fetch_add(1, Relaxed);
+    // FNV-1a over each policy's canonical JSON: stable across builds, unlike
+    // the std hasher.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for entry in snapshot.iter() {
+        let json = serde_json::to_vec(entry.policy.proto()).unwrap_or_default();
+        for byte in json {
+            hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    *POLICY_HASH.lock().unwrap() = format!("{hash:016x}");
+}
+
+pub fn parse_error() {
+    PARSE_ERRORS.fetch_add(1, Relaxed);
+}
+
+/// Write the report if telemetry is enabled. Called on the way out, including
+/// from fatal parse errors so those still get reported.
+pub fn flush() {
+    let Some(path) = PATH.lock().unwrap().clone() else {
+        return;
+    };
+    let report = Report {
+        resource: Resource {
+            service_name: "runner-rs",
+            instance_id: std::process::id().to_string(),
+            policy_hash: POLICY_HASH.lock().unwrap().clone(),
+        },
+        records_evaluated: RECORDS.load(Relaxed),
+        decisions: Decisions {
+            no_match: NO_MATCH.load(Relaxed),
+            keep: KEEP.load(Relaxed),
+            drop: DROP.load(Relaxed),
+            sample_kept: SAMPLE_KEPT.load(Relaxed),
+            sample_dropped: SAMPLE_DROPPED.load(Relaxed),
+            rate_allowed: RATE_ALLOWED.load(Relaxed),
+            rate_limited: RATE_LIMITED.load(Relaxed),
+        },
+        evaluation_latency_us: Histogram {
+            bounds: &LATENCY_BOUNDS_US,
+            counts: LATENCY.iter().map(|c| c.load(Relaxed)).collect(),
+        },
+        policy_loads: POLICY_LOADS.load(Relaxed),
+        parse_errors: PARSE_ERRORS.load(Relaxed),
+    };
+    let written = serde_json::to_string(&report)
+        .map_err(io::Error::from)
+        .and_then(|data| crate::atomic_file::write(&path, data));
+    if let Err(e) = written {
+        tracing::error!("failed to write self-telemetry: {e}");
+    }
+}
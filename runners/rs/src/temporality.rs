@@ -0,0 +1,66 @@
+//! `--synthesize-temporality`: rewrite every sum's and histogram's
+//! aggregation temporality before evaluation, so one fixture exercises
+//! policies keyed on temporality both ways.
+//!
+//! The rewrite keeps each metric's encoding: a numeric temporality stays
+//! numeric, anything else, absent included, is written by name. It shows in
+//! the output, and each payload it touched raises `W006`.
+
+use runner_rs::otel;
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Temporality {
+    Delta,
+    Cumulative,
+}
+
+impl Temporality {
+    /// The name [`otel::MetricData::aggregation_temporality`] gives it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Temporality::Delta => "AGGREGATION_TEMPORALITY_DELTA",
+            Temporality::Cumulative => "AGGREGATION_TEMPORALITY_CUMULATIVE",
+        }
+    }
+}
+
+/// Payloads whose metrics' temporality can be rewritten.
+pub trait Synthesize {
+    /// Set every metric with a temporality to `temporality`, returning how
+    /// many there were.
+    fn synthesize_temporality(&mut self, temporality: Temporality) -> usize;
+}
+
+impl Synthesize for otel::MetricsData {
+    fn synthesize_temporality(&mut self, temporality: Temporality) -> usize {
+        let mut rewritten = 0;
+        for data in self
+            .resource_metrics
+            .iter_mut()
+            .flat_map(|rm| &mut rm.scope_metrics)
+            .flat_map(|sm| &mut sm.metrics)
+            .filter_map(|m| m.data.as_mut())
+        {
+            rewritten += usize::from(data.set_aggregation_temporality(temporality.name()));
+        }
+        rewritten
+    }
+}
+
+impl Synthesize for otel::LogsData {
+    fn synthesize_temporality(&mut self, _: Temporality) -> usize {
+        0
+    }
+}
+
+impl Synthesize for otel::TracesData {
+    fn synthesize_temporality(&mut self, _: Temporality) -> usize {
+        0
+    }
+}
+
+impl Synthesize for otel::ProfilesData {
+    fn synthesize_temporality(&mut self, _: Temporality) -> usize {
+        0
+    }
+}
@@ -0,0 +1,110 @@
+//! `--tenant-key` / `--tenant-policies`: evaluate each resource envelope
+//! against the policy set of the tenant named by one of its resource
+//! attributes.
+//!
+//! Every distinct policy path is loaded into its own registry once, however
+//! many tenants name it. An envelope whose tenant has no set of its own, or
+//! that carries no tenant at all, falls back to the main policy source
+//! (`--policies`, `--server` or `--grpc`) when there is one; otherwise it is
+//! unrouted and passes through unevaluated.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use policy_rs::{PolicyRegistry, PolicySnapshot};
+use runner_rs::otel;
+
+use crate::provider::{ChainProvider, MergeStrategy};
+use crate::status::{self, Failure};
+
+pub struct Tenants {
+    key: String,
+    /// One per distinct policy path.
+    snapshots: Vec<PolicySnapshot>,
+    /// Tenant name to index into `snapshots`.
+    routes: HashMap<String, usize>,
+    unrouted: AtomicUsize,
+}
+
+impl Tenants {
+    /// Load the sets named by `specs`, each `tenant=path`.
+    pub fn load(
+        key: &str,
+        specs: &[String],
+        merge_strategy: MergeStrategy,
+    ) -> status::Result<Tenants> {
+        let mut by_path: HashMap<PathBuf, usize> = HashMap::new();
+        let mut snapshots = Vec::new();
+        let mut routes = HashMap::new();
+        for spec in specs {
+            let Some((tenant, path)) = spec.split_once('=') else {
+                return Err(Failure(format!(
+                    "--tenant-policies entry {spec:?} is not tenant=path"
+                )));
+            };
+            let path = PathBuf::from(path);
+            let index = match by_path.entry(path.clone()) {
+                Entry::Occupied(entry) => *entry.get(),
+                Entry::Vacant(entry) => {
+                    let registry = PolicyRegistry::new();
+                    let provider = ChainProvider::new(std::slice::from_ref(&path), merge_strategy);
+                    if let Err(e) = registry.subscribe(&provider) {
+                        return Err(Failure(format!(
+                            "failed to load policies for tenant {tenant}: {e}"
+                        )));
+                    }
+                    let snapshot = registry.snapshot();
+                    for entry in snapshot.iter() {
+                        entry.stats.reset_all();
+                    }
+                    snapshots.push(snapshot);
+                    *entry.insert(snapshots.len() - 1)
+                }
+            };
+            if routes.insert(tenant.to_string(), index).is_some() {
+                return Err(Failure(format!(
+                    "tenant {tenant} is given more than one policy set"
+                )));
+            }
+        }
+        Ok(Tenants {
+            key: key.to_string(),
+            snapshots,
+            routes,
+            unrouted: AtomicUsize::new(0),
+        })
+    }
+
+    /// Every tenant set, for checks that apply across all of them.
+    pub fn snapshots(&self) -> &[PolicySnapshot] {
+        &self.snapshots
+    }
+
+    /// The set for `resource`'s tenant, else `fallback`. Envelopes that get
+    /// neither are counted in [`unrouted`](Self::unrouted).
+    pub fn route<'a>(
+        &'a self,
+        resource: Option<&otel::Resource>,
+        fallback: Option<&'a PolicySnapshot>,
+    ) -> Option<&'a PolicySnapshot> {
+        let tenant = resource
+            .and_then(|r| r.attributes.iter().find(|kv| kv.key == self.key))
+            .and_then(|kv| kv.value.as_ref())
+            .and_then(|v| v.string_value.as_deref());
+        let snapshot = tenant
+            .and_then(|t| self.routes.get(t))
+            .map(|&i| &self.snapshots[i])
+            .or(fallback);
+        if snapshot.is_none() {
+            self.unrouted.fetch_add(1, Ordering::Relaxed);
+        }
+        snapshot
+    }
+
+    /// Envelopes passed through without a policy set so far.
+    pub fn unrouted(&self) -> usize {
+        self.unrouted.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,124 @@
+//! `--timing-histogram`: the evaluation latency of every record, as an OTLP
+//! metrics JSON document holding one explicit-bucket histogram.
+//!
+//! The document is built from the [`otel`] metric types, so it is also a
+//! check that they serialize as valid OTLP. Latencies are in microseconds;
+//! bucket `i` counts those in `(bounds[i-1], bounds[i]]` and the last counts
+//! everything slower, as OTLP defines them. The resource names the run: the
+//! runner, its process, the signal and the input file.
+
+use std::path::Path;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use runner_rs::RecordResult;
+use runner_rs::otel::{self, AnyValue, KeyValue};
+
+use crate::status::{self, Failure};
+
+/// `--timing-histogram-bounds` unless given.
+pub const DEFAULT_BOUNDS_US: [f64; 7] = [1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0];
+
+/// OTLP's `AGGREGATION_TEMPORALITY_DELTA`: the histogram covers this run
+/// alone.
+const DELTA: u64 = 1;
+
+pub fn parse_bound(s: &str) -> Result<f64, String> {
+    let bound: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if bound.is_finite() && bound >= 0.0 {
+        Ok(bound)
+    } else {
+        Err(format!("{bound} is not a latency in microseconds"))
+    }
+}
+
+fn attribute(key: &str, value: String) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            string_value: Some(value),
+            ..AnyValue::default()
+        }),
+    }
+}
+
+/// Unix nanoseconds, as OTLP JSON writes a `fixed64`.
+fn nanos(time: SystemTime) -> serde_json::Value {
+    let nanos = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    nanos.to_string().into()
+}
+
+/// Write the latencies of `results`, evaluated between `started` and now,
+/// bucketed by `bounds`.
+pub fn write(
+    path: &Path,
+    bounds: &[f64],
+    results: &[RecordResult],
+    signal: &str,
+    input: &Path,
+    started: SystemTime,
+) -> status::Result {
+    let latencies: Vec<f64> = results
+        .iter()
+        .filter_map(|r| r.elapsed)
+        .map(|elapsed| elapsed.as_secs_f64() * 1e6)
+        .collect();
+    let mut counts = vec![0u64; bounds.len() + 1];
+    for &us in &latencies {
+        counts[bounds.partition_point(|&bound| bound < us)] += 1;
+    }
+    let point = otel::HistogramDataPoint {
+        start_time_unix_nano: nanos(started),
+        time_unix_nano: nanos(SystemTime::now()),
+        count: latencies.len().to_string().into(),
+        sum: Some(latencies.iter().sum::<f64>().into()),
+        bucket_counts: counts.iter().map(|c| c.to_string().into()).collect(),
+        explicit_bounds: bounds.iter().map(|&bound| bound.into()).collect(),
+        min: latencies.iter().copied().reduce(f64::min).map(Into::into),
+        max: latencies.iter().copied().reduce(f64::max).map(Into::into),
+        ..otel::HistogramDataPoint::default()
+    };
+    let metric = otel::Metric {
+        name: "policy_runner.evaluation.duration".to_string(),
+        description: "Time to evaluate one record against the policies".to_string(),
+        unit: "us".to_string(),
+        data: Some(otel::MetricData::Histogram(otel::Histogram {
+            data_points: vec![point],
+            aggregation_temporality: DELTA.into(),
+            ..otel::Histogram::default()
+        })),
+        ..otel::Metric::default()
+    };
+    let document = otel::MetricsData {
+        resource_metrics: vec![otel::ResourceMetrics {
+            resource: Some(otel::Resource {
+                attributes: vec![
+                    attribute("service.name", "runner-rs".to_string()),
+                    attribute("service.version", env!("CARGO_PKG_VERSION").to_string()),
+                    attribute("service.instance.id", process::id().to_string()),
+                    attribute("policy_runner.signal", signal.to_string()),
+                    attribute("policy_runner.input", input.display().to_string()),
+                ],
+                ..otel::Resource::default()
+            }),
+            scope_metrics: vec![otel::ScopeMetrics {
+                scope: Some(otel::InstrumentationScope {
+                    name: "runner-rs".to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                    ..otel::InstrumentationScope::default()
+                }),
+                metrics: vec![metric],
+                ..otel::ScopeMetrics::default()
+            }],
+            ..otel::ResourceMetrics::default()
+        }],
+        extra: serde_json::Map::new(),
+    };
+    let data = serde_json::to_string_pretty(&document)
+        .map_err(|e| Failure(format!("failed to serialize timing histogram: {e}")))?;
+    crate::atomic_file::write(path, data)
+        .map_err(|e| Failure(format!("failed to write timing histogram: {e}")))
+}
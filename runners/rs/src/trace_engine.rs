@@ -0,0 +1,182 @@
+//! `--trace-engine`: every request the engine made of each record, as JSON
+//! lines, and `runner-rs compare-engine-trace`, which lines two such traces
+//! up record by record and reports where they first differ.
+//!
+//! Each line is one evaluated record: its id (its JSON path in the input,
+//! prefixed by `<line>:` under `--input-lines`), its decision and its
+//! requests in order (see [`runner_rs::engine_trace`]):
+//!
+//! ```json
+//! {"id":"resourceLogs[0].scopeLogs[0].logRecords[1]","decision":"drop","requests":[["get_field","log_field:severity_text","DEBUG"]]}
+//! ```
+//!
+//! A record `--dedupe-envelopes` gave an earlier envelope's decision carries
+//! `"deduplicated":true` and no requests, and only its decision is compared.
+//!
+//! Traces from runs on different `policy_rs` versions, over the same input
+//! and policies, should differ only where the engine's behaviour changed.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use runner_rs::RecordResult;
+use runner_rs::engine_trace::Request;
+use serde::{Deserialize, Serialize};
+
+use crate::atomic_file;
+use crate::status::{self, Failure};
+
+#[derive(Serialize, Deserialize)]
+struct Entry<'a> {
+    id: String,
+    decision: Cow<'a, str>,
+    requests: Cow<'a, [Request]>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    deduplicated: bool,
+}
+
+/// The `--trace-engine` file being written, in place only once
+/// [`finish`](Writer::finish)ed.
+pub struct Writer {
+    out: atomic_file::Staged,
+}
+
+impl Writer {
+    pub fn create(path: &Path) -> status::Result<Writer> {
+        let out = atomic_file::Staged::create(path)
+            .map_err(|e| Failure(format!("failed to create engine trace: {e}")))?;
+        Ok(Writer { out })
+    }
+
+    /// Append the traced `results` of one `signal` (a
+    /// [`SignalData::NAME`](runner_rs::SignalData::NAME)) payload, from
+    /// `--input-lines` line `line` if given.
+    pub fn write(
+        &mut self,
+        signal: &str,
+        line: Option<usize>,
+        results: &[RecordResult],
+    ) -> status::Result {
+        let (resources, scopes, records) = match signal {
+            "logs" => ("resourceLogs", "scopeLogs", "logRecords"),
+            "metrics" => ("resourceMetrics", "scopeMetrics", "metrics"),
+            "traces" => ("resourceSpans", "scopeSpans", "spans"),
+            _ => ("resourceProfiles", "scopeProfiles", "profiles"),
+        };
+        let prefix = line.map_or(String::new(), |n| format!("{n}:"));
+        for r in results {
+            let mut id = format!(
+                "{prefix}{resources}[{}].{scopes}[{}].{records}[{}]",
+                r.resource, r.scope, r.record
+            );
+            if let Some(datapoint) = r.datapoint {
+                id.push_str(&format!(".dataPoints[{datapoint}]"));
+            }
+            let entry = Entry {
+                id,
+                decision: Cow::Borrowed(runner_rs::decision_name(&r.result)),
+                requests: Cow::Borrowed(r.requests.as_deref().unwrap_or_default()),
+                deduplicated: r.copy_of.is_some(),
+            };
+            let written = serde_json::to_writer(&mut self.out, &entry)
+                .map_err(std::io::Error::from)
+                .and_then(|()| writeln!(self.out));
+            if let Err(e) = written {
+                return Err(Failure(format!("failed to write engine trace: {e}")));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> status::Result {
+        self.out
+            .commit()
+            .map_err(|e| Failure(format!("failed to write engine trace: {e}")))
+    }
+}
+
+// ─── compare-engine-trace ────────────────────────────────────────────
+
+#[derive(clap::Args)]
+pub struct CompareArgs {
+    /// The baseline trace.
+    a: PathBuf,
+    /// The trace to compare against it.
+    b: PathBuf,
+}
+
+fn read(path: &Path) -> status::Result<Vec<Entry<'static>>> {
+    let file = fs::File::open(path)
+        .map_err(|e| Failure(format!("failed to read {}: {e}", path.display())))?;
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().is_ok_and(|l| !l.trim().is_empty()))
+        .map(|(i, line)| {
+            line.map_err(|e| e.to_string())
+                .and_then(|l| serde_json::from_str(&l).map_err(|e| e.to_string()))
+                .map_err(|e| Failure(format!("{}:{}: {e}", path.display(), i + 1)))
+        })
+        .collect()
+}
+
+/// Where `b`'s entry first differs from `a`'s, if it does.
+fn divergence(a: &Entry<'_>, b: &Entry<'_>) -> Option<String> {
+    let (x, y) = (&a.requests, &b.requests);
+    let first = x
+        .iter()
+        .zip(y.iter())
+        .position(|(x, y)| x != y)
+        .or_else(|| (x.len() != y.len()).then(|| x.len().min(y.len())));
+    let shown = |r: Option<&Request>| {
+        r.map_or("nothing".to_string(), |Request(call, field, answer)| {
+            format!("{call} {field} -> {answer}")
+        })
+    };
+    if let Some(i) = first.filter(|_| !a.deduplicated && !b.deduplicated) {
+        return Some(format!(
+            "request {}:\n  a: {}\n  b: {}",
+            i + 1,
+            shown(x.get(i)),
+            shown(y.get(i))
+        ));
+    }
+    (a.decision != b.decision).then(|| {
+        format!(
+            "same requests, different decision:\n  a: {}\n  b: {}",
+            a.decision, b.decision
+        )
+    })
+}
+
+pub fn run(args: &CompareArgs) -> status::Result {
+    let a = read(&args.a)?;
+    let b = read(&args.b)?;
+    let mut b_by_id: HashMap<&str, &Entry<'_>> = b.iter().map(|e| (e.id.as_str(), e)).collect();
+    let mut differing = 0;
+    for entry in &a {
+        let message = match b_by_id.remove(entry.id.as_str()) {
+            Some(other) => divergence(entry, other),
+            None => Some("only in a".to_string()),
+        };
+        if let Some(message) = message {
+            differing += 1;
+            println!("{}: {message}", entry.id);
+        }
+    }
+    for entry in b.iter().filter(|e| b_by_id.contains_key(e.id.as_str())) {
+        differing += 1;
+        println!("{}: only in b", entry.id);
+    }
+    let total = a.len() + b_by_id.len();
+    println!("{differing} of {total} records differ");
+    if differing > 0 {
+        return Err(Failure(format!(
+            "the traces differ in {differing} record(s)"
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! W3C Trace Context `tracestate` validation.
+//!
+//! A tracestate is a comma-separated list of at most 32 `key=value`
+//! members, no key appearing twice. A key is a lowercase letter followed by
+//! up to 255 lowercase letters, digits, `_`, `-`, `*` or `/`, or the
+//! multi-tenant `tenant@system`: a lowercase letter or digit and up to 240
+//! more such characters, then a lowercase letter and up to 13 more. A value
+//! is 1 to 256 printable ASCII characters other than `,` and `=`, and
+//! doesn't end in a space. Blank members, and spaces and tabs around
+//! members, are allowed.
+
+/// Most members a tracestate may carry.
+pub const MAX_MEMBERS: usize = 32;
+
+/// What's wrong with one member.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    /// Short rule name, e.g. `tracestate-key`.
+    pub rule: &'static str,
+    pub message: String,
+}
+
+/// A tracestate's members, split into those that pass and the problems of
+/// those that don't.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Validated<'a> {
+    /// The members that pass, trimmed, in order.
+    pub members: Vec<&'a str>,
+    /// One per member that doesn't, in order.
+    pub problems: Vec<Problem>,
+}
+
+impl Validated<'_> {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// The tracestate with only the members that pass.
+    pub fn repaired(&self) -> String {
+        self.members.join(",")
+    }
+}
+
+/// Check each member of `tracestate`. Past the member limit, the rightmost
+/// are the ones that fail, as the spec has them dropped first.
+///
+/// ```
+/// use runner_rs::tracestate::validate;
+///
+/// assert!(validate("ot=th:8, congo=t61rcWkgMzE,,rojo@vendor=00f067aa0ba902b7").is_valid());
+///
+/// let checked = validate("ot=th:8,Congo=1,rojo=,rojo=2,rojo=3");
+/// let rules: Vec<_> = checked.problems.iter().map(|p| p.rule).collect();
+/// assert_eq!(rules, ["tracestate-key", "tracestate-value", "tracestate-duplicate"]);
+/// assert_eq!(checked.repaired(), "ot=th:8,rojo=2");
+///
+/// let long = (0..33).map(|i| format!("k{i}=v")).collect::<Vec<_>>().join(",");
+/// assert_eq!(validate(&long).problems[0].rule, "tracestate-members");
+/// ```
+pub fn validate(tracestate: &str) -> Validated<'_> {
+    let mut validated = Validated::default();
+    let mut keys: Vec<&str> = Vec::new();
+    let members = tracestate
+        .split(',')
+        .map(|m| m.trim_matches([' ', '\t']))
+        .filter(|m| !m.is_empty());
+    for member in members {
+        let problem = match member.split_once('=') {
+            None => Some(("tracestate-member", format!("{member:?} is not key=value"))),
+            Some((key, _)) if !valid_key(key) => {
+                Some(("tracestate-key", format!("{key:?} is not a valid key")))
+            }
+            Some((key, value)) if !valid_value(value) => Some((
+                "tracestate-value",
+                format!("{value:?} is not a valid value for {key:?}"),
+            )),
+            Some((key, _)) if keys.contains(&key) => {
+                Some(("tracestate-duplicate", format!("{key:?} appears twice")))
+            }
+            Some((key, _)) if keys.len() == MAX_MEMBERS => Some((
+                "tracestate-members",
+                format!("{key:?} is past the {MAX_MEMBERS}-member limit"),
+            )),
+            Some((key, _)) => {
+                keys.push(key);
+                None
+            }
+        };
+        match problem {
+            Some((rule, message)) => validated.problems.push(Problem { rule, message }),
+            None => validated.members.push(member),
+        }
+    }
+    validated
+}
+
+fn valid_key(key: &str) -> bool {
+    match key.split_once('@') {
+        None => name(key, |b| b.is_ascii_lowercase(), 256),
+        Some((tenant, system)) => {
+            name(
+                tenant,
+                |b| b.is_ascii_lowercase() || b.is_ascii_digit(),
+                241,
+            ) && name(system, |b| b.is_ascii_lowercase(), 14)
+        }
+    }
+}
+
+/// `part` of a key: up to `max` characters, the first passing `first`.
+fn name(part: &str, first: impl Fn(u8) -> bool, max: usize) -> bool {
+    let rest = |b: u8| {
+        b.is_ascii_lowercase() || b.is_ascii_digit() || matches!(b, b'_' | b'-' | b'*' | b'/')
+    };
+    part.len() <= max && part.bytes().next().is_some_and(first) && part.bytes().all(rest)
+}
+
+fn valid_value(value: &str) -> bool {
+    (1..=256).contains(&value.len())
+        && value
+            .bytes()
+            .all(|b| (0x20..=0x7e).contains(&b) && b != b',' && b != b'=')
+        && !value.ends_with(' ')
+}
@@ -0,0 +1,108 @@
+//! `--utf8 lossy`: input that isn't valid UTF-8, such as binary payloads
+//! logged raw, read with each invalid sequence replaced by U+FFFD instead of
+//! refused.
+//!
+//! Records are matched as they read after replacement. Those whose content
+//! may have been altered are listed by JSON path in the annotations'
+//! `utf8_replaced` and counted in the summary: every record carrying U+FFFD
+//! in itself, its scope or its resource. A U+FFFD already in the input is
+//! indistinguishable from a replaced one, so such records are listed too.
+
+use std::borrow::Cow;
+
+use runner_rs::otel;
+use serde::Serialize;
+
+/// Replace invalid sequences in `bytes`, copying only if there are any.
+pub fn replace(bytes: &[u8]) -> Option<Vec<u8>> {
+    match String::from_utf8_lossy(bytes) {
+        Cow::Borrowed(_) => None,
+        Cow::Owned(text) => Some(text.into_bytes()),
+    }
+}
+
+fn carries_replacement<T: Serialize>(value: &T) -> bool {
+    serde_json::to_string(value).is_ok_and(|json| json.contains(char::REPLACEMENT_CHARACTER))
+}
+
+/// Payloads whose records can be checked for replaced content.
+pub trait Replaced {
+    /// JSON paths of the records that carry U+FFFD.
+    fn replaced_records(&self) -> Vec<String>;
+}
+
+impl Replaced for otel::LogsData {
+    fn replaced_records(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        for (i, rl) in self.resource_logs.iter().enumerate() {
+            let resource = carries_replacement(&rl.resource);
+            for (j, sl) in rl.scope_logs.iter().enumerate() {
+                let scope = resource || carries_replacement(&sl.scope);
+                for (k, rec) in sl.log_records.iter().enumerate() {
+                    if scope || carries_replacement(rec) {
+                        out.push(format!("resourceLogs[{i}].scopeLogs[{j}].logRecords[{k}]"));
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+impl Replaced for otel::MetricsData {
+    fn replaced_records(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        for (i, rm) in self.resource_metrics.iter().enumerate() {
+            let resource = carries_replacement(&rm.resource);
+            for (j, sm) in rm.scope_metrics.iter().enumerate() {
+                let scope = resource || carries_replacement(&sm.scope);
+                for (k, m) in sm.metrics.iter().enumerate() {
+                    if scope || carries_replacement(m) {
+                        out.push(format!(
+                            "resourceMetrics[{i}].scopeMetrics[{j}].metrics[{k}]"
+                        ));
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+impl Replaced for otel::TracesData {
+    fn replaced_records(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        for (i, rs) in self.resource_spans.iter().enumerate() {
+            let resource = carries_replacement(&rs.resource);
+            for (j, ss) in rs.scope_spans.iter().enumerate() {
+                let scope = resource || carries_replacement(&ss.scope);
+                for (k, span) in ss.spans.iter().enumerate() {
+                    if scope || carries_replacement(span) {
+                        out.push(format!("resourceSpans[{i}].scopeSpans[{j}].spans[{k}]"));
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+impl Replaced for otel::ProfilesData {
+    fn replaced_records(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        for (i, rp) in self.resource_profiles.iter().enumerate() {
+            let resource = carries_replacement(&rp.resource);
+            for (j, sp) in rp.scope_profiles.iter().enumerate() {
+                let scope = resource || carries_replacement(&sp.scope);
+                for (k, profile) in sp.profiles.iter().enumerate() {
+                    if scope || carries_replacement(profile) {
+                        out.push(format!(
+                            "resourceProfiles[{i}].scopeProfiles[{j}].profiles[{k}]"
+                        ));
+                    }
+                }
+            }
+        }
+        out
+    }
+}
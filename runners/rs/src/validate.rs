@@ -0,0 +1,86 @@
+//! `--validate-tracestate`: every surviving span's `traceState` checked
+//! against the W3C rules once evaluation and every transform are done, so
+//! the output carries none a downstream gateway would reject.
+//!
+//! Under `--tracestate-invalid repair`, the default, a span keeps the
+//! members that pass; under `error` it is removed from the output, along
+//! with any scope or resource it leaves empty. Either way it raises `W008`,
+//! located by its path in the output before anything was removed. Decisions
+//! and stats still count it as its policy decided.
+
+use runner_rs::{otel, tracestate};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Invalid {
+    #[default]
+    Repair,
+    Error,
+}
+
+/// A span whose tracestate failed validation.
+pub struct Failed {
+    /// JSON path of its `traceState` in the output.
+    pub path: String,
+    pub problems: Vec<tracestate::Problem>,
+}
+
+/// Payloads whose spans' tracestates can be validated.
+pub trait Validated {
+    /// Repair or remove each span with an invalid tracestate, as `invalid`
+    /// says, returning them in order.
+    fn validate_tracestate(&mut self, invalid: Invalid) -> Vec<Failed>;
+}
+
+impl Validated for otel::TracesData {
+    fn validate_tracestate(&mut self, invalid: Invalid) -> Vec<Failed> {
+        let mut failed = Vec::new();
+        for (i, rs) in self.resource_spans.iter_mut().enumerate() {
+            for (j, ss) in rs.scope_spans.iter_mut().enumerate() {
+                let mut k = 0;
+                ss.spans.retain_mut(|span| {
+                    k += 1;
+                    let checked = tracestate::validate(&span.trace_state);
+                    if checked.is_valid() {
+                        return true;
+                    }
+                    let repaired = checked.repaired();
+                    failed.push(Failed {
+                        path: format!(
+                            "resourceSpans[{i}].scopeSpans[{j}].spans[{}].traceState",
+                            k - 1
+                        ),
+                        problems: checked.problems,
+                    });
+                    match invalid {
+                        Invalid::Repair => {
+                            span.trace_state = repaired;
+                            true
+                        }
+                        Invalid::Error => false,
+                    }
+                });
+            }
+            rs.scope_spans.retain(|ss| !ss.spans.is_empty());
+        }
+        self.resource_spans.retain(|rs| !rs.scope_spans.is_empty());
+        failed
+    }
+}
+
+impl Validated for otel::LogsData {
+    fn validate_tracestate(&mut self, _: Invalid) -> Vec<Failed> {
+        Vec::new()
+    }
+}
+
+impl Validated for otel::MetricsData {
+    fn validate_tracestate(&mut self, _: Invalid) -> Vec<Failed> {
+        Vec::new()
+    }
+}
+
+impl Validated for otel::ProfilesData {
+    fn validate_tracestate(&mut self, _: Invalid) -> Vec<Failed> {
+        Vec::new()
+    }
+}
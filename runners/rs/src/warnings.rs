@@ -0,0 +1,73 @@
+//! Non-fatal warnings, written to stderr as they happen and to the
+//! `--annotations` file's `warnings` array so conformance runs can check
+//! them.
+//!
+//! Each warning carries a stable code; `message` is for people and may
+//! change:
+//! - `W001` no loaded policy applies to the input's signal; unless
+//!   `--no-fast-path` is set, its records are decided `no_match` without
+//!   calling the engine
+//! - `W002` `--only-policy`/`--exclude-policy` narrowed the policy set
+//! - `W003` rate-limited policies forced sequential evaluation
+//! - `W004` an input value failed a `--check-input` rule
+//! - `W005` `--suppress-field` hid fields from matchers
+//! - `W006` `--synthesize-temporality` rewrote metrics' aggregation
+//!   temporality
+//! - `W007` an `--input-lines` line's own `signal` field overrode
+//!   `--signal`
+//! - `W008` `--validate-tracestate` found an invalid tracestate in the
+//!   output, and repaired it or removed its span
+//!
+//! `--deny-warnings` fails the run, once the output is written, if any were
+//! raised.
+
+use serde::Serialize;
+use tracing::warn;
+
+#[derive(Serialize)]
+pub struct Warning {
+    pub code: &'static str,
+    pub message: String,
+    /// JSON path of the record or field the warning is about.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_id: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Warnings {
+    entries: Vec<Warning>,
+}
+
+impl Warnings {
+    pub fn warn(&mut self, code: &'static str, message: String) {
+        warn!("{message}");
+        self.entries.push(Warning {
+            code,
+            message,
+            record_id: None,
+        });
+    }
+
+    /// A warning about one record or field, located by its JSON path.
+    pub fn warn_record(&mut self, code: &'static str, record_id: String, message: String) {
+        warn!("{record_id}: {message}");
+        self.entries.push(Warning {
+            code,
+            message,
+            record_id: Some(record_id),
+        });
+    }
+
+    /// Append `other`'s warnings, already logged, to these.
+    pub fn absorb(&mut self, other: Warnings) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn entries(&self) -> &[Warning] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
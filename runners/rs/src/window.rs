@@ -0,0 +1,202 @@
+//! `--time-from`/`--time-to`: only the records inside a time window, for
+//! incident analysis.
+//!
+//! Log records are placed by `timeUnixNano`, falling back to
+//! `observedTimeUnixNano`; spans by `startTimeUnixNano`; metric datapoints
+//! by `timeUnixNano`, a metric going once none of its datapoints are left.
+//! Profiles aren't filtered. The window includes `--time-from` and excludes
+//! `--time-to`. A zero or absent timestamp is kept or removed as
+//! `--time-missing` says.
+//!
+//! Records outside the window are removed right after parsing, so to
+//! evaluation, `--assert` and the output's record indexes the input is the
+//! windowed one. The summary counts them as `outside_window`, in datapoints
+//! for metrics.
+
+use runner_rs::otel;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Missing {
+    #[default]
+    Include,
+    Exclude,
+}
+
+/// The window, in Unix nanoseconds.
+pub struct Window {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub missing: Missing,
+}
+
+impl Window {
+    fn admits(&self, nanos: Option<u64>) -> bool {
+        match nanos.filter(|&n| n != 0) {
+            Some(n) => self.from.is_none_or(|from| n >= from) && self.to.is_none_or(|to| n < to),
+            None => self.missing == Missing::Include,
+        }
+    }
+}
+
+/// Parse an RFC 3339 timestamp, such as `2024-05-01T12:00:00Z` or
+/// `2024-05-01T14:00:00.250+02:00`, into Unix nanoseconds.
+pub fn parse_rfc3339(s: &str) -> Result<u64, String> {
+    let invalid = || format!("{s:?} is not an RFC 3339 timestamp");
+    let field = |range: std::ops::Range<usize>| -> Result<i64, String> {
+        s.get(range)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(invalid)
+    };
+    let bytes = s.as_bytes();
+    let separators = [(4, b'-'), (7, b'-'), (13, b':'), (16, b':')];
+    if bytes.len() < 20
+        || separators.iter().any(|&(i, b)| bytes[i] != b)
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+    {
+        return Err(invalid());
+    }
+    let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+    let (hour, minute, second) = (field(11..13)?, field(14..16)?, field(17..19)?);
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return Err(invalid());
+    }
+
+    let mut rest = &s[19..];
+    let mut fraction = 0;
+    if let Some(digits) = rest.strip_prefix('.') {
+        let len = digits.bytes().take_while(u8::is_ascii_digit).count();
+        if len == 0 {
+            return Err(invalid());
+        }
+        // Nanosecond precision; finer digits are dropped.
+        let nanos = &digits[..len.min(9)];
+        fraction =
+            nanos.parse::<i64>().map_err(|_| invalid())? * 10_i64.pow(9 - nanos.len() as u32);
+        rest = &digits[len..];
+    }
+    let offset = match rest.as_bytes() {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), _, _, b':', _, _] => {
+            let at = s.len() - 5;
+            let (hours, minutes) = (field(at..at + 2)?, field(at + 3..at + 5)?);
+            if hours > 23 || minutes > 59 {
+                return Err(invalid());
+            }
+            let offset = hours * 3600 + minutes * 60;
+            if *sign == b'-' { -offset } else { offset }
+        }
+        _ => return Err(invalid()),
+    };
+
+    let seconds =
+        days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    u64::try_from(seconds)
+        .ok()
+        .and_then(|seconds| seconds.checked_mul(1_000_000_000))
+        .and_then(|nanos| nanos.checked_add(fraction as u64))
+        .ok_or_else(|| format!("{s:?} is outside the range of Unix nanoseconds"))
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to the given proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Payloads whose records can be filtered by time.
+pub trait Windowed {
+    /// Remove the records outside `window`, returning how many went.
+    fn retain_window(&mut self, window: &Window) -> usize;
+}
+
+impl Windowed for otel::LogsData {
+    fn retain_window(&mut self, window: &Window) -> usize {
+        let mut removed = 0;
+        for sl in self
+            .resource_logs
+            .iter_mut()
+            .flat_map(|rl| &mut rl.scope_logs)
+        {
+            let before = sl.log_records.len();
+            sl.log_records.retain(|rec| {
+                let time = otel::unix_nanos(&rec.time_unix_nano)
+                    .filter(|&n| n != 0)
+                    .or_else(|| otel::unix_nanos(&rec.observed_time_unix_nano));
+                window.admits(time)
+            });
+            removed += before - sl.log_records.len();
+        }
+        removed
+    }
+}
+
+impl Windowed for otel::MetricsData {
+    fn retain_window(&mut self, window: &Window) -> usize {
+        let mut removed = 0;
+        for sm in self
+            .resource_metrics
+            .iter_mut()
+            .flat_map(|rm| &mut rm.scope_metrics)
+        {
+            sm.metrics.retain_mut(|m| {
+                let Some(data) = m.data.as_mut() else {
+                    return true;
+                };
+                let keep: Vec<bool> = data
+                    .datapoint_times()
+                    .into_iter()
+                    .map(|time| window.admits(time))
+                    .collect();
+                let kept = keep.iter().filter(|&&k| k).count();
+                removed += keep.len() - kept;
+                data.retain_datapoints(&keep);
+                kept > 0 || keep.is_empty()
+            });
+        }
+        removed
+    }
+}
+
+impl Windowed for otel::TracesData {
+    fn retain_window(&mut self, window: &Window) -> usize {
+        let mut removed = 0;
+        for ss in self
+            .resource_spans
+            .iter_mut()
+            .flat_map(|rs| &mut rs.scope_spans)
+        {
+            let before = ss.spans.len();
+            ss.spans
+                .retain(|span| window.admits(otel::unix_nanos(&span.start_time_unix_nano)));
+            removed += before - ss.spans.len();
+        }
+        removed
+    }
+}
+
+impl Windowed for otel::ProfilesData {
+    fn retain_window(&mut self, _: &Window) -> usize {
+        0
+    }
+}
@@ -0,0 +1,263 @@
+//! `--format zipkin-json`: a Zipkin v2 JSON dump, as `/api/v2/spans`
+//! accepts it or `/api/v2/traces` returns it, read as one OTLP traces
+//! payload so trace policies can be tried against it.
+//!
+//! Each span maps onto an OTLP one:
+//!
+//! - `traceId`, `id` and `parentId` become the span's ids; 64-bit trace ids
+//!   are zero-padded to 128 bits
+//! - `kind` becomes the span kind, and a span without one is internal
+//! - `timestamp` and `duration`, in microseconds, become the start and end
+//!   times
+//! - `localEndpoint.serviceName` becomes the `service.name` of the span's
+//!   resource, one resource per service; the local endpoint's address and
+//!   port become `network.local.*` attributes
+//! - `remoteEndpoint` becomes `peer.service` and `network.peer.*` attributes
+//! - `tags` become string attributes, except `otel.scope.name` and
+//!   `otel.scope.version`, which name the span's scope, and
+//!   `otel.status_code` and `otel.status_description`, which set its status
+//! - without `otel.status_code`, an `error` tag sets an error status with
+//!   the tag's value as the message
+//! - `annotations` become events named by their value
+//!
+//! A `shared` span is the server half of an RPC whose client half carries
+//! the same id. Both halves are kept, ids untouched; the server half is
+//! marked with a `zipkin.shared` attribute so policies and the output can
+//! tell them apart.
+
+use std::collections::BTreeMap;
+
+use runner_rs::eval::set_attr;
+use runner_rs::otel;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ZipkinSpan {
+    trace_id: String,
+    id: String,
+    #[serde(default)]
+    parent_id: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(default)]
+    timestamp: Option<u64>,
+    #[serde(default)]
+    duration: Option<u64>,
+    #[serde(default)]
+    local_endpoint: Option<Endpoint>,
+    #[serde(default)]
+    remote_endpoint: Option<Endpoint>,
+    #[serde(default)]
+    annotations: Vec<Annotation>,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    #[serde(default)]
+    shared: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Endpoint {
+    #[serde(default)]
+    service_name: Option<String>,
+    #[serde(default)]
+    ipv4: Option<String>,
+    #[serde(default)]
+    ipv6: Option<String>,
+    #[serde(default)]
+    port: Option<u16>,
+}
+
+#[derive(Deserialize)]
+struct Annotation {
+    timestamp: u64,
+    value: String,
+}
+
+/// The payload `input` describes: a JSON array of spans, or of traces that
+/// are arrays of spans.
+pub fn parse(input: &[u8]) -> Result<otel::TracesData, String> {
+    let dump: Vec<Value> = serde_json::from_slice(input).map_err(|e| e.to_string())?;
+    let spans = dump.into_iter().flat_map(|item| match item {
+        Value::Array(trace) => trace,
+        span => vec![span],
+    });
+    let mut resources: Vec<otel::ResourceSpans> = Vec::new();
+    for (i, span) in spans.enumerate() {
+        let span: ZipkinSpan =
+            serde_json::from_value(span).map_err(|e| format!("span {}: {e}", i + 1))?;
+        let service = span
+            .local_endpoint
+            .as_ref()
+            .and_then(|e| e.service_name.clone());
+        let (scope, span) = convert(span).map_err(|e| format!("span {}: {e}", i + 1))?;
+        let at = match resources.iter().position(|rs| service_of(rs) == service) {
+            Some(at) => at,
+            None => {
+                resources.push(resource_spans(service));
+                resources.len() - 1
+            }
+        };
+        let scopes = &mut resources[at].scope_spans;
+        let at = match scopes.iter().position(|ss| scope_of(ss) == scope) {
+            Some(at) => at,
+            None => {
+                scopes.push(otel::ScopeSpans {
+                    scope: Some(otel::InstrumentationScope {
+                        name: scope.0,
+                        version: scope.1,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                });
+                scopes.len() - 1
+            }
+        };
+        scopes[at].spans.push(span);
+    }
+    Ok(otel::TracesData {
+        resource_spans: resources,
+        extra: serde_json::Map::new(),
+    })
+}
+
+fn resource_spans(service: Option<String>) -> otel::ResourceSpans {
+    let mut attributes = Vec::new();
+    if let Some(service) = service {
+        set_attr(&mut attributes, "service.name", &service);
+    }
+    otel::ResourceSpans {
+        resource: Some(otel::Resource {
+            attributes,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn service_of(rs: &otel::ResourceSpans) -> Option<String> {
+    rs.resource
+        .iter()
+        .flat_map(|r| &r.attributes)
+        .find(|kv| kv.key == "service.name")
+        .and_then(|kv| kv.value.as_ref()?.string_value.clone())
+}
+
+fn scope_of(ss: &otel::ScopeSpans) -> (String, String) {
+    ss.scope
+        .as_ref()
+        .map(|s| (s.name.clone(), s.version.clone()))
+        .unwrap_or_default()
+}
+
+/// `span` as an OTLP span, with the name and version of its scope.
+fn convert(mut span: ZipkinSpan) -> Result<((String, String), otel::Span), String> {
+    let scope = (
+        span.tags.remove("otel.scope.name").unwrap_or_default(),
+        span.tags.remove("otel.scope.version").unwrap_or_default(),
+    );
+    let status = status(&mut span.tags);
+    let kind = match span.kind.as_deref() {
+        None => "SPAN_KIND_INTERNAL",
+        Some("CLIENT") => "SPAN_KIND_CLIENT",
+        Some("SERVER") => "SPAN_KIND_SERVER",
+        Some("PRODUCER") => "SPAN_KIND_PRODUCER",
+        Some("CONSUMER") => "SPAN_KIND_CONSUMER",
+        Some(other) => return Err(format!("unknown kind {other:?}")),
+    };
+    let mut attributes = Vec::new();
+    for (key, value) in &span.tags {
+        set_attr(&mut attributes, key, value);
+    }
+    if let Some(endpoint) = &span.local_endpoint {
+        endpoint_attributes(&mut attributes, endpoint, "network.local");
+    }
+    if let Some(endpoint) = &span.remote_endpoint {
+        if let Some(service) = &endpoint.service_name {
+            set_attr(&mut attributes, "peer.service", service);
+        }
+        endpoint_attributes(&mut attributes, endpoint, "network.peer");
+    }
+    if span.shared {
+        attributes.push(otel::KeyValue {
+            key: "zipkin.shared".to_string(),
+            value: Some(otel::AnyValue {
+                bool_value: Some(true),
+                ..Default::default()
+            }),
+        });
+    }
+    let start = span.timestamp.map(|us| us.saturating_mul(1000));
+    let end = start
+        .zip(span.duration)
+        .map(|(start, us)| start.saturating_add(us.saturating_mul(1000)));
+    let events = span
+        .annotations
+        .iter()
+        .map(|a| json!({"timeUnixNano": a.timestamp.saturating_mul(1000), "name": a.value}))
+        .collect();
+    let converted = otel::Span {
+        trace_id: hex_id(&span.trace_id, 32).ok_or("traceId is not 16 or 32 hex digits")?,
+        span_id: hex_id(&span.id, 16).ok_or("id is not 16 hex digits")?,
+        parent_span_id: match &span.parent_id {
+            Some(id) => hex_id(id, 16).ok_or("parentId is not 16 hex digits")?,
+            None => String::new(),
+        },
+        name: span.name.unwrap_or_default(),
+        kind: kind.into(),
+        start_time_unix_nano: start.unwrap_or_default().into(),
+        end_time_unix_nano: end.or(start).unwrap_or_default().into(),
+        attributes,
+        events,
+        status,
+        ..Default::default()
+    };
+    Ok((scope, converted))
+}
+
+/// The status `tags` set, taking the tags that set it.
+fn status(tags: &mut BTreeMap<String, String>) -> Option<otel::Status> {
+    let description = tags.remove("otel.status_description");
+    if let Some(code) = tags.remove("otel.status_code") {
+        return Some(otel::Status {
+            message: description.unwrap_or_default(),
+            code: format!("STATUS_CODE_{}", code.to_ascii_uppercase()),
+            ..Default::default()
+        });
+    }
+    let message = tags.remove("error")?;
+    Some(otel::Status {
+        message,
+        code: "STATUS_CODE_ERROR".to_string(),
+        ..Default::default()
+    })
+}
+
+fn endpoint_attributes(attributes: &mut Vec<otel::KeyValue>, endpoint: &Endpoint, prefix: &str) {
+    if let Some(address) = endpoint.ipv4.as_ref().or(endpoint.ipv6.as_ref()) {
+        set_attr(attributes, &format!("{prefix}.address"), address);
+    }
+    if let Some(port) = endpoint.port {
+        attributes.push(otel::KeyValue {
+            key: format!("{prefix}.port"),
+            value: Some(otel::AnyValue {
+                int_value: Some(port.into()),
+                ..Default::default()
+            }),
+        });
+    }
+}
+
+/// `id` as lowercase hex of exactly `digits` digits, zero-padded on the
+/// left: Zipkin drops leading zeros, and carries 64-bit trace ids.
+fn hex_id(id: &str, digits: usize) -> Option<String> {
+    let valid = !id.is_empty()
+        && id.len() <= digits
+        && (digits != 32 || id.len() <= 16 || id.len() == 32)
+        && id.bytes().all(|b| b.is_ascii_hexdigit());
+    valid.then(|| format!("{:0>digits$}", id.to_ascii_lowercase()))
+}